@@ -0,0 +1,23 @@
+// Golden image fixture.
+use std::collections::HashMap;
+
+pub struct Counter {
+    counts: HashMap<String, u32>,
+}
+
+pub fn count(words: &str) -> Counter {
+    let mut counts = HashMap::new();
+
+    for word in words.split(' ') {
+        *counts.entry(word.to_string()).or_insert(0) += 1;
+    }
+
+    Counter { counts }
+}
+
+fn main() {
+    let counter = count("a b a");
+    println!("{:?}", counter.counts.get("a"));
+    let c = 'x';
+    let total = 42 + 7;
+}
@@ -1,13 +1,12 @@
 #version 460
 
-layout(location = 0) out vec3 frag_color;
+layout(location = 0) out vec4 frag_color;
 layout(location = 1) out vec2 frag_texture_coords;
 
-layout(location = 0) in vec2 texture_coords;
-
-layout(push_constant) uniform InstanceData {
-  vec2 position;
-} instance;
+layout(location = 0) in vec2 position;
+layout(location = 1) in uint glyph;
+layout(location = 2) in vec4 color;
+layout(location = 3) in float width;
 
 vec2[4] vertices = {{-1.0, -1.0}, {1.0, -1.0}, {-1.0, 1.0}, {1.0, 1.0}};
 
@@ -19,12 +18,16 @@ layout(set = 0, binding = 0) uniform UniformGlobalObject {
   float x_ratio;
 } ugo;
 
+layout(std430, set = 0, binding = 1) readonly buffer GlyphCoords {
+  vec2 coords[];
+} glyphs;
+
 void main() {
-  vec2 p = vertices[gl_VertexIndex].xy + instance.position;
+  vec2 p = vertices[gl_VertexIndex].xy * vec2(width, 1.0) + position;
   vec2 vertex_position = ugo.scale * vec2(p.x * ugo.ratio * ugo.x_ratio, p.y) + vec2(ugo.x_offset, ugo.y_offset);
 
   gl_Position = vec4(vertex_position, 0.0, 1.0);
 
-  frag_color = vec3(1.0);
-  frag_texture_coords = texture_coords;
+  frag_color = color;
+  frag_texture_coords = glyphs.coords[glyph * 4 + gl_VertexIndex];
 }
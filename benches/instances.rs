@@ -0,0 +1,29 @@
+use engine::renderer::{self, vulkan};
+use engine::editor::buffer;
+
+// Times the CPU side of a frame: collecting the visible text, breaking it
+// into runs and writing the glyph instances the text draw reads. Recording
+// the command buffers needs a device and isn't measured.
+const COLUMNS: u32 = 240;
+const ROWS: u32 = 70;
+const ITERATIONS: u32 = 1000;
+
+fn main() {
+    let mut buffer = buffer::buffer_from_file("src/renderer/vulkan.rs").expect("run from the repository root");
+    let mut unique_chars = buffer::unique_chars_from_text(COLUMNS, ROWS, &buffer.text, &mut buffer.syntax, &buffer.mode_line);
+    let glyph_indices = unique_chars.positions.keys().enumerate().map(|(i, c)| (*c, i as u32)).collect();
+    let empty = vulkan::GlyphInstance { position: [0.0; 2], glyph: 0, color: [0.0; 4], width: 1.0 };
+    let mut instances = vec![empty; 256 * 256];
+
+    let start = std::time::Instant::now();
+    let mut count = 0;
+
+    for _ in 0..ITERATIONS {
+        unique_chars = buffer::unique_chars_from_text(COLUMNS, ROWS, &buffer.text, &mut buffer.syntax, &buffer.mode_line);
+        let runs = renderer::text_runs(&unique_chars, [0, 0]);
+        count = vulkan::text_instances(&mut instances, &glyph_indices, &runs);
+    }
+
+    let elapsed = start.elapsed() / ITERATIONS;
+    println!("{}x{} cells, {} instances in 1 draw: {:?} per frame", COLUMNS, ROWS, count, elapsed);
+}
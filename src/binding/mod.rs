@@ -1,3 +1,5 @@
 pub mod vulkan;
 pub mod wayland;
 pub mod dl;
+pub mod xkb;
+pub mod termios;
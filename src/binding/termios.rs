@@ -0,0 +1,49 @@
+#![allow(non_camel_case_types)]
+
+// The parts of <termios.h>, <sys/ioctl.h>, <poll.h> and <unistd.h> the
+// terminal frontend needs, with the Linux layouts.
+
+pub const STDIN_FILENO: i32 = 0;
+pub const STDOUT_FILENO: i32 = 1;
+pub const TCSAFLUSH: i32 = 2;
+pub const TIOCGWINSZ: u64 = 0x5413;
+pub const POLLIN: i16 = 1;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct termios {
+    pub c_iflag: u32,
+    pub c_oflag: u32,
+    pub c_cflag: u32,
+    pub c_lflag: u32,
+    pub c_line: u8,
+    pub c_cc: [u8; 32],
+    pub c_ispeed: u32,
+    pub c_ospeed: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct winsize {
+    pub ws_row: u16,
+    pub ws_col: u16,
+    pub ws_xpixel: u16,
+    pub ws_ypixel: u16,
+}
+
+#[repr(C)]
+pub struct pollfd {
+    pub fd: i32,
+    pub events: i16,
+    pub revents: i16,
+}
+
+extern "C" {
+    pub fn tcgetattr(fd: i32, termios: *mut termios) -> i32;
+    pub fn tcsetattr(fd: i32, optional_actions: i32, termios: *const termios) -> i32;
+    pub fn cfmakeraw(termios: *mut termios);
+    pub fn ioctl(fd: i32, request: u64, ...) -> i32;
+    pub fn poll(fds: *mut pollfd, nfds: u64, timeout: i32) -> i32;
+    pub fn read(fd: i32, buf: *mut std::ffi::c_void, count: usize) -> isize;
+    pub fn isatty(fd: i32) -> i32;
+}
@@ -112,10 +112,12 @@ pub const SHARING_MODE_CONCURRENT: u32 = 1;
 pub const BUFFER_USAGE_TRANSFER_SRC_BIT: u32 = 1;
 pub const BUFFER_USAGE_UNIFORM_BUFFER_BIT: u32 = 16;
 pub const BUFFER_USAGE_VERTEX_BUFFER_BIT: u32 = 128;
+pub const BUFFER_USAGE_STORAGE_BUFFER_BIT: u32 = 32;
 
 pub const PRESENT_MODE_FIFO_KHR: u32 = 2;
 
 pub const DESCRIPTOR_TYPE_UNIFORM_BUFFER: u32 = 6;
+pub const DESCRIPTOR_TYPE_STORAGE_BUFFER: u32 = 7;
 pub const DEPENDENCY_BY_REGION_BIT: u32 = 1;
 
 pub const BLEND_FACTOR_ZERO: u32 = 0;
@@ -136,6 +138,7 @@ pub const PRIMITIVE_TOPOLOGY_TRIANGLE_LIST: u32 = 3;
 pub const COLOR_SPACE_SRGB_NONLINEAR_KHR: u32 = 0;
 pub const FEATURE_DEPTH_STENCIL_ATTACHMENT_BIT: u32 = 512;
 pub const VERTEX_INPUT_RATE: u32 = 0;
+pub const VERTEX_INPUT_RATE_INSTANCE: u32 = 1;
 
 pub const ATTACHMENT_LOAD_OP_CLEAR: u32 = 1;
 pub const ATTACHMENT_LOAD_OP_DONT_CARE: u32 = 2;
@@ -164,6 +167,8 @@ pub const R8G8B8A8_SRGB: u32 = 43;
 pub const R32G32B32_SFLOAT: u32 = 106;
 pub const R32G32_SFLOAT: u32 = 103;
 pub const R32_SFLOAT: u32 = 100;
+pub const R32_UINT: u32 = 98;
+pub const R32G32B32A32_SFLOAT: u32 = 109;
 
 pub const D32_SFLOAT: u32 = 126;
 pub const D24_UNORM_S8_UINT: u32 = 129;
@@ -8,6 +8,29 @@ pub const WL_SEAT_RELEASE: u32 = 3;
 pub const WL_MARSHAL_FLAG_DESTROY: u32 = 1;
 pub const WL_SEAT_CAPABILITY_KEYBOARD: u32 = 2;
 pub const WL_SEAT_GET_KEYBOARD: u32 = 1;
+pub const WL_SEAT_CAPABILITY_POINTER: u32 = 1;
+pub const WL_SEAT_GET_POINTER: u32 = 0;
+pub const WL_POINTER_RELEASE: u32 = 1;
+pub const WL_POINTER_BUTTON_STATE_PRESSED: u32 = 1;
+pub const WL_POINTER_FRAME_SINCE_VERSION: u32 = 5;
+pub const WL_DATA_DEVICE_MANAGER_CREATE_DATA_SOURCE: u32 = 0;
+pub const WL_DATA_DEVICE_MANAGER_GET_DATA_DEVICE: u32 = 1;
+pub const WL_DATA_SOURCE_OFFER: u32 = 0;
+pub const WL_DATA_SOURCE_DESTROY: u32 = 1;
+pub const WL_DATA_DEVICE_SET_SELECTION: u32 = 1;
+pub const WL_DATA_DEVICE_RELEASE: u32 = 2;
+pub const WL_DATA_OFFER_RECEIVE: u32 = 1;
+pub const WL_DATA_OFFER_DESTROY: u32 = 2;
+
+pub const ZWP_PRIMARY_SELECTION_DEVICE_MANAGER_V1_CREATE_SOURCE: u32 = 0;
+pub const ZWP_PRIMARY_SELECTION_DEVICE_MANAGER_V1_GET_DEVICE: u32 = 1;
+pub const ZWP_PRIMARY_SELECTION_DEVICE_MANAGER_V1_DESTROY: u32 = 2;
+pub const ZWP_PRIMARY_SELECTION_DEVICE_V1_SET_SELECTION: u32 = 0;
+pub const ZWP_PRIMARY_SELECTION_DEVICE_V1_DESTROY: u32 = 1;
+pub const ZWP_PRIMARY_SELECTION_OFFER_V1_RECEIVE: u32 = 0;
+pub const ZWP_PRIMARY_SELECTION_OFFER_V1_DESTROY: u32 = 1;
+pub const ZWP_PRIMARY_SELECTION_SOURCE_V1_OFFER: u32 = 0;
+pub const ZWP_PRIMARY_SELECTION_SOURCE_V1_DESTROY: u32 = 1;
 
 pub const XDG_WM_BASE_GET_XDG_SURFACE: u32 = 2;
 pub const XDG_WM_BASE_PONG: u32 = 3;
@@ -69,6 +92,42 @@ pub struct wl_keyboard {
     _unused: [u8; 0],
 }
 #[repr(C)]
+pub struct wl_pointer {
+    _unused: [u8; 0],
+}
+#[repr(C)]
+pub struct wl_data_device_manager {
+    _unused: [u8; 0],
+}
+#[repr(C)]
+pub struct wl_data_device {
+    _unused: [u8; 0],
+}
+#[repr(C)]
+pub struct wl_data_offer {
+    _unused: [u8; 0],
+}
+#[repr(C)]
+pub struct wl_data_source {
+    _unused: [u8; 0],
+}
+#[repr(C)]
+pub struct zwp_primary_selection_device_manager_v1 {
+    _unused: [u8; 0],
+}
+#[repr(C)]
+pub struct zwp_primary_selection_device_v1 {
+    _unused: [u8; 0],
+}
+#[repr(C)]
+pub struct zwp_primary_selection_offer_v1 {
+    _unused: [u8; 0],
+}
+#[repr(C)]
+pub struct zwp_primary_selection_source_v1 {
+    _unused: [u8; 0],
+}
+#[repr(C)]
 pub struct wl_registry_listener {
     pub global: ::std::option::Option<
         unsafe extern "C" fn(
@@ -163,6 +222,235 @@ pub struct wl_keyboard_listener {
     >,
 }
 #[repr(C)]
+pub struct wl_pointer_listener {
+    pub enter: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_pointer: *mut wl_pointer,
+            serial: u32,
+            surface: *mut wl_surface,
+            surface_x: i32,
+            surface_y: i32,
+        ),
+    >,
+    pub leave: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_pointer: *mut wl_pointer,
+            serial: u32,
+            surface: *mut wl_surface,
+        ),
+    >,
+    pub motion: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_pointer: *mut wl_pointer,
+            time: u32,
+            surface_x: i32,
+            surface_y: i32,
+        ),
+    >,
+    pub button: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_pointer: *mut wl_pointer,
+            serial: u32,
+            time: u32,
+            button: u32,
+            state: u32,
+        ),
+    >,
+    pub axis: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_pointer: *mut wl_pointer,
+            time: u32,
+            axis: u32,
+            value: i32,
+        ),
+    >,
+    pub frame: ::std::option::Option<
+        unsafe extern "C" fn(data: *mut ::std::os::raw::c_void, wl_pointer: *mut wl_pointer),
+    >,
+    pub axis_source: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_pointer: *mut wl_pointer,
+            axis_source: u32,
+        ),
+    >,
+    pub axis_stop: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_pointer: *mut wl_pointer,
+            time: u32,
+            axis: u32,
+        ),
+    >,
+    pub axis_discrete: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_pointer: *mut wl_pointer,
+            axis: u32,
+            discrete: i32,
+        ),
+    >,
+}
+#[repr(C)]
+pub struct wl_data_offer_listener {
+    pub offer: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_data_offer: *mut wl_data_offer,
+            mime_type: *const ::std::os::raw::c_char,
+        ),
+    >,
+    pub source_actions: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_data_offer: *mut wl_data_offer,
+            source_actions: u32,
+        ),
+    >,
+    pub action: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_data_offer: *mut wl_data_offer,
+            dnd_action: u32,
+        ),
+    >,
+}
+#[repr(C)]
+pub struct wl_data_source_listener {
+    pub target: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_data_source: *mut wl_data_source,
+            mime_type: *const ::std::os::raw::c_char,
+        ),
+    >,
+    pub send: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_data_source: *mut wl_data_source,
+            mime_type: *const ::std::os::raw::c_char,
+            fd: i32,
+        ),
+    >,
+    pub cancelled: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_data_source: *mut wl_data_source,
+        ),
+    >,
+    pub dnd_drop_performed: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_data_source: *mut wl_data_source,
+        ),
+    >,
+    pub dnd_finished: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_data_source: *mut wl_data_source,
+        ),
+    >,
+    pub action: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_data_source: *mut wl_data_source,
+            dnd_action: u32,
+        ),
+    >,
+}
+#[repr(C)]
+pub struct wl_data_device_listener {
+    pub data_offer: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_data_device: *mut wl_data_device,
+            id: *mut wl_data_offer,
+        ),
+    >,
+    pub enter: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_data_device: *mut wl_data_device,
+            serial: u32,
+            surface: *mut wl_surface,
+            x: i32,
+            y: i32,
+            id: *mut wl_data_offer,
+        ),
+    >,
+    pub leave: ::std::option::Option<
+        unsafe extern "C" fn(data: *mut ::std::os::raw::c_void, wl_data_device: *mut wl_data_device),
+    >,
+    pub motion: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_data_device: *mut wl_data_device,
+            time: u32,
+            x: i32,
+            y: i32,
+        ),
+    >,
+    pub drop: ::std::option::Option<
+        unsafe extern "C" fn(data: *mut ::std::os::raw::c_void, wl_data_device: *mut wl_data_device),
+    >,
+    pub selection: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            wl_data_device: *mut wl_data_device,
+            id: *mut wl_data_offer,
+        ),
+    >,
+}
+#[repr(C)]
+pub struct zwp_primary_selection_offer_v1_listener {
+    pub offer: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            zwp_primary_selection_offer_v1: *mut zwp_primary_selection_offer_v1,
+            mime_type: *const ::std::os::raw::c_char,
+        ),
+    >,
+}
+#[repr(C)]
+pub struct zwp_primary_selection_source_v1_listener {
+    pub send: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            zwp_primary_selection_source_v1: *mut zwp_primary_selection_source_v1,
+            mime_type: *const ::std::os::raw::c_char,
+            fd: i32,
+        ),
+    >,
+    pub cancelled: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            zwp_primary_selection_source_v1: *mut zwp_primary_selection_source_v1,
+        ),
+    >,
+}
+#[repr(C)]
+pub struct zwp_primary_selection_device_v1_listener {
+    pub data_offer: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            zwp_primary_selection_device_v1: *mut zwp_primary_selection_device_v1,
+            offer: *mut zwp_primary_selection_offer_v1,
+        ),
+    >,
+    pub selection: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            zwp_primary_selection_device_v1: *mut zwp_primary_selection_device_v1,
+            id: *mut zwp_primary_selection_offer_v1,
+        ),
+    >,
+}
+#[repr(C)]
 pub struct xdg_surface {
     _unused: [u8; 0],
 }
@@ -240,6 +528,21 @@ extern "C" {
 extern "C" {
     pub static wl_keyboard_interface: wl_interface;
 }
+extern "C" {
+    pub static wl_pointer_interface: wl_interface;
+}
+extern "C" {
+    pub static wl_data_device_manager_interface: wl_interface;
+}
+extern "C" {
+    pub static wl_data_device_interface: wl_interface;
+}
+extern "C" {
+    pub static wl_data_source_interface: wl_interface;
+}
+extern "C" {
+    pub static wl_data_offer_interface: wl_interface;
+}
 extern "C" {
     pub static xdg_wm_base_interface: wl_interface;
 }
@@ -262,6 +565,9 @@ extern "C" {
 extern "C" {
     pub fn wl_display_connect(name: *const ::std::os::raw::c_char) -> *mut wl_display;
 }
+extern "C" {
+    pub fn wl_display_flush(display: *mut wl_display) -> ::std::os::raw::c_int;
+}
 extern "C" {
     pub fn wl_display_roundtrip(display: *mut wl_display) -> ::std::os::raw::c_int;
 }
@@ -282,3 +588,97 @@ extern "C" {
         data: *mut ::std::os::raw::c_void,
     ) -> ::std::os::raw::c_int;
 }
+
+// The primary selection protocol isn't part of libwayland, so its interface
+// tables are written out here the way wayland-scanner would generate them.
+macro_rules! message {
+    ($name:expr, $signature:expr, $types:expr) => {
+        wl_message {
+            name: $name.as_ptr(),
+            signature: $signature.as_ptr(),
+            types: (std::ptr::addr_of_mut!(zwp_primary_selection_types) as *mut *const wl_interface).wrapping_add($types),
+        }
+    };
+}
+
+static mut zwp_primary_selection_types: [*const wl_interface; 8] = [
+    std::ptr::null(),
+    std::ptr::null(),
+    std::ptr::addr_of!(zwp_primary_selection_source_v1_interface),
+    std::ptr::addr_of!(zwp_primary_selection_device_v1_interface),
+    std::ptr::addr_of!(wl_seat_interface),
+    std::ptr::addr_of!(zwp_primary_selection_source_v1_interface),
+    std::ptr::null(),
+    std::ptr::addr_of!(zwp_primary_selection_offer_v1_interface),
+];
+
+static mut zwp_primary_selection_device_manager_v1_requests: [wl_message; 3] = [
+    message!(c"create_source", c"n", 2),
+    message!(c"get_device", c"no", 3),
+    message!(c"destroy", c"", 0),
+];
+
+static mut zwp_primary_selection_device_v1_requests: [wl_message; 2] = [
+    message!(c"set_selection", c"?ou", 5),
+    message!(c"destroy", c"", 0),
+];
+
+static mut zwp_primary_selection_device_v1_events: [wl_message; 2] = [
+    message!(c"data_offer", c"n", 7),
+    message!(c"selection", c"?o", 7),
+];
+
+static mut zwp_primary_selection_offer_v1_requests: [wl_message; 2] = [
+    message!(c"receive", c"sh", 0),
+    message!(c"destroy", c"", 0),
+];
+
+static mut zwp_primary_selection_offer_v1_events: [wl_message; 1] = [
+    message!(c"offer", c"s", 0),
+];
+
+static mut zwp_primary_selection_source_v1_requests: [wl_message; 2] = [
+    message!(c"offer", c"s", 0),
+    message!(c"destroy", c"", 0),
+];
+
+static mut zwp_primary_selection_source_v1_events: [wl_message; 2] = [
+    message!(c"send", c"sh", 0),
+    message!(c"cancelled", c"", 0),
+];
+
+pub static mut zwp_primary_selection_device_manager_v1_interface: wl_interface = wl_interface {
+    name: c"zwp_primary_selection_device_manager_v1".as_ptr(),
+    version: 1,
+    method_count: 3,
+    methods: std::ptr::addr_of!(zwp_primary_selection_device_manager_v1_requests) as *const wl_message,
+    event_count: 0,
+    events: std::ptr::null(),
+};
+
+pub static mut zwp_primary_selection_device_v1_interface: wl_interface = wl_interface {
+    name: c"zwp_primary_selection_device_v1".as_ptr(),
+    version: 1,
+    method_count: 2,
+    methods: std::ptr::addr_of!(zwp_primary_selection_device_v1_requests) as *const wl_message,
+    event_count: 2,
+    events: std::ptr::addr_of!(zwp_primary_selection_device_v1_events) as *const wl_message,
+};
+
+pub static mut zwp_primary_selection_offer_v1_interface: wl_interface = wl_interface {
+    name: c"zwp_primary_selection_offer_v1".as_ptr(),
+    version: 1,
+    method_count: 2,
+    methods: std::ptr::addr_of!(zwp_primary_selection_offer_v1_requests) as *const wl_message,
+    event_count: 1,
+    events: std::ptr::addr_of!(zwp_primary_selection_offer_v1_events) as *const wl_message,
+};
+
+pub static mut zwp_primary_selection_source_v1_interface: wl_interface = wl_interface {
+    name: c"zwp_primary_selection_source_v1".as_ptr(),
+    version: 1,
+    method_count: 2,
+    methods: std::ptr::addr_of!(zwp_primary_selection_source_v1_requests) as *const wl_message,
+    event_count: 2,
+    events: std::ptr::addr_of!(zwp_primary_selection_source_v1_events) as *const wl_message,
+};
@@ -0,0 +1,86 @@
+#![allow(non_camel_case_types, non_snake_case, non_upper_case_globals, unused_imports)]
+
+use std::ffi::c_void as void;
+
+pub const XKB_CONTEXT_NO_FLAGS: i32 = 0;
+pub const XKB_KEYMAP_FORMAT_TEXT_V1: i32 = 1;
+pub const XKB_KEYMAP_COMPILE_NO_FLAGS: i32 = 0;
+pub const XKB_KEY_NoSymbol: u32 = 0;
+
+#[repr(C)]
+pub struct xkb_context {
+    _unused: [u8; 0],
+}
+
+#[repr(C)]
+pub struct xkb_keymap {
+    _unused: [u8; 0],
+}
+
+#[repr(C)]
+pub struct xkb_state {
+    _unused: [u8; 0],
+}
+
+pub type xkb_context_new = unsafe extern "C" fn(
+    flags: i32,
+) -> *mut xkb_context;
+pub type PFN_xkb_context_new = Option<xkb_context_new>;
+pub type xkb_context_unref = unsafe extern "C" fn(
+    context: *mut xkb_context,
+);
+pub type PFN_xkb_context_unref = Option<xkb_context_unref>;
+pub type xkb_keymap_new_from_buffer = unsafe extern "C" fn(
+    context: *mut xkb_context,
+    buffer: *const std::ffi::c_char,
+    length: usize,
+    format: i32,
+    flags: i32,
+) -> *mut xkb_keymap;
+pub type PFN_xkb_keymap_new_from_buffer = Option<xkb_keymap_new_from_buffer>;
+pub type xkb_keymap_unref = unsafe extern "C" fn(
+    keymap: *mut xkb_keymap,
+);
+pub type PFN_xkb_keymap_unref = Option<xkb_keymap_unref>;
+pub type xkb_keymap_key_get_syms_by_level = unsafe extern "C" fn(
+    keymap: *mut xkb_keymap,
+    key: u32,
+    layout: u32,
+    level: u32,
+    syms_out: *mut *const u32,
+) -> i32;
+pub type PFN_xkb_keymap_key_get_syms_by_level = Option<xkb_keymap_key_get_syms_by_level>;
+pub type xkb_state_new = unsafe extern "C" fn(
+    keymap: *mut xkb_keymap,
+) -> *mut xkb_state;
+pub type PFN_xkb_state_new = Option<xkb_state_new>;
+pub type xkb_state_unref = unsafe extern "C" fn(
+    state: *mut xkb_state,
+);
+pub type PFN_xkb_state_unref = Option<xkb_state_unref>;
+pub type xkb_state_update_mask = unsafe extern "C" fn(
+    state: *mut xkb_state,
+    depressed_mods: u32,
+    latched_mods: u32,
+    locked_mods: u32,
+    depressed_layout: u32,
+    latched_layout: u32,
+    locked_layout: u32,
+) -> i32;
+pub type PFN_xkb_state_update_mask = Option<xkb_state_update_mask>;
+pub type xkb_state_key_get_one_sym = unsafe extern "C" fn(
+    state: *mut xkb_state,
+    key: u32,
+) -> u32;
+pub type PFN_xkb_state_key_get_one_sym = Option<xkb_state_key_get_one_sym>;
+pub type xkb_state_key_get_utf8 = unsafe extern "C" fn(
+    state: *mut xkb_state,
+    key: u32,
+    buffer: *mut std::ffi::c_char,
+    size: usize,
+) -> i32;
+pub type PFN_xkb_state_key_get_utf8 = Option<xkb_state_key_get_utf8>;
+pub type xkb_keysym_to_utf32 = unsafe extern "C" fn(
+    keysym: u32,
+) -> u32;
+pub type PFN_xkb_keysym_to_utf32 = Option<xkb_keysym_to_utf32>;
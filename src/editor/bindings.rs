@@ -0,0 +1,259 @@
+use super::key::{self, Key};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Press {
+    Char(char),
+    Key(Key),
+}
+
+// One key together with the modifiers held for it. Shift is folded into the
+// character for keys that type text, so it is only set for `Press::Key`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Chord {
+    pub press: Press,
+    pub control: bool,
+    pub alt: bool,
+    pub shift: bool,
+}
+
+pub enum Binding {
+    Command(usize),
+    Prefix(Bindings),
+}
+
+// Maps chords to commands, given as indices into the command table the
+// config was parsed against, or to nested maps for prefix keys like C-x.
+pub struct Bindings {
+    pub map: std::collections::HashMap<Chord, Binding>,
+}
+
+pub struct Config {
+    pub edit: Bindings,
+    pub command: Bindings,
+}
+
+#[derive(PartialEq, Debug)]
+pub enum Lookup {
+    Command(usize),
+    Prefix,
+    Undefined,
+}
+
+#[derive(PartialEq, Debug)]
+pub enum ConfigErrorKind {
+    UnknownSection(String),
+    NoSection,
+    MissingCommand,
+    BadChord(String),
+    UnknownCommand(String),
+    NotPrefix(String),
+}
+
+#[derive(PartialEq, Debug)]
+pub struct ConfigError {
+    pub line: usize,
+    pub kind: ConfigErrorKind,
+}
+
+// Removes a binding rather than naming a command.
+pub const UNBIND: &str = "unbind";
+
+pub fn new() -> Config {
+    Config {
+        edit: Bindings { map: std::collections::HashMap::new() },
+        command: Bindings { map: std::collections::HashMap::new() },
+    }
+}
+
+pub fn chord(press: Press) -> Chord {
+    Chord { press, control: false, alt: false, shift: false }
+}
+
+// Reads chords written the Emacs way: any of the `C-`, `M-` and `S-`
+// modifiers followed by a character, `SPC`, `TAB` or a key name like <up>.
+pub fn parse_chord(text: &str) -> Option<Chord> {
+    let mut rest = text;
+    let mut chord = chord(Press::Char(' '));
+
+    while rest.len() > 2 {
+        match rest.get(..2) {
+            Some("C-") => chord.control = true,
+            Some("M-") => chord.alt = true,
+            Some("S-") => chord.shift = true,
+            _ => break,
+        }
+
+        rest = &rest[2..];
+    }
+
+    chord.press = match rest {
+        "SPC" => Press::Char(' '),
+        "TAB" => Press::Char('\t'),
+        _ if rest.len() > 1 && rest.starts_with('<') => Press::Key(key::from_name(rest)?),
+        _ => {
+            let mut chars = rest.chars();
+            let c = chars.next()?;
+            if chars.next().is_some() {
+                return None;
+            }
+
+            Press::Char(c)
+        },
+    };
+
+    if chord.shift && matches!(chord.press, Press::Char(_)) {
+        return None;
+    }
+
+    Some(chord)
+}
+
+pub fn chord_name(chord: &Chord) -> String {
+    let mut name = String::new();
+
+    if chord.control {
+        name.push_str("C-");
+    }
+    if chord.alt {
+        name.push_str("M-");
+    }
+    if chord.shift {
+        name.push_str("S-");
+    }
+
+    match chord.press {
+        Press::Char(' ') => name.push_str("SPC"),
+        Press::Char('\t') => name.push_str("TAB"),
+        Press::Char(c) => name.push(c),
+        Press::Key(k) => name.push_str(&key::name(k)),
+    }
+
+    name
+}
+
+pub fn sequence_name(sequence: &[Chord]) -> String {
+    sequence.iter().map(chord_name).collect::<Vec<String>>().join(" ")
+}
+
+pub fn lookup(bindings: &Bindings, sequence: &[Chord]) -> Lookup {
+    let mut bindings = bindings;
+
+    for (i, chord) in sequence.iter().enumerate() {
+        match bindings.map.get(chord) {
+            Some(Binding::Command(command)) if i + 1 == sequence.len() => return Lookup::Command(*command),
+            Some(Binding::Prefix(next)) => bindings = next,
+            _ => return Lookup::Undefined,
+        }
+    }
+
+    if sequence.is_empty() {
+        Lookup::Undefined
+    } else {
+        Lookup::Prefix
+    }
+}
+
+fn bind(bindings: &mut Bindings, sequence: &[Chord], command: Option<usize>) -> Result<(), ConfigErrorKind> {
+    let mut bindings = bindings;
+
+    for (i, chord) in sequence[..sequence.len() - 1].iter().enumerate() {
+        let binding = bindings.map.entry(*chord).or_insert_with(|| Binding::Prefix(Bindings { map: std::collections::HashMap::new() }));
+
+        bindings = match binding {
+            Binding::Prefix(next) => next,
+            Binding::Command(_) => return Err(ConfigErrorKind::NotPrefix(sequence_name(&sequence[..=i]))),
+        };
+    }
+
+    let last = sequence[sequence.len() - 1];
+    match command {
+        Some(command) => bindings.map.insert(last, Binding::Command(command)),
+        None => bindings.map.remove(&last),
+    };
+
+    Ok(())
+}
+
+fn parse_line(config: &mut Config, section: Option<&str>, line: &str, commands: &[&str]) -> Result<(), ConfigErrorKind> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let (name, keys) = words.split_last().unwrap();
+
+    if keys.is_empty() {
+        return Err(ConfigErrorKind::MissingCommand);
+    }
+
+    let sequence = keys
+        .iter()
+        .map(|word| parse_chord(word).ok_or_else(|| ConfigErrorKind::BadChord(word.to_string())))
+        .collect::<Result<Vec<Chord>, ConfigErrorKind>>()?;
+
+    let command = if *name == UNBIND {
+        None
+    } else {
+        Some(commands.iter().position(|command| command == name).ok_or_else(|| ConfigErrorKind::UnknownCommand(name.to_string()))?)
+    };
+
+    let bindings = match section {
+        Some("edit") => &mut config.edit,
+        Some("command") => &mut config.command,
+        _ => return Err(ConfigErrorKind::NoSection),
+    };
+
+    bind(bindings, &sequence, command)
+}
+
+// Applies a config on top of `config`. Each line binds a key sequence to one
+// of `commands` in the `[edit]` or `[command]` section above it, and lines
+// starting with `#` are comments. Bad lines are skipped and reported, the rest
+// still take effect.
+pub fn parse(config: &mut Config, text: &str, commands: &[&str]) -> Vec<ConfigError> {
+    let mut errors = Vec::new();
+    let mut section = None;
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+
+        let result = if line.is_empty() || line.starts_with('#') {
+            Ok(())
+        } else if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            if name == "edit" || name == "command" {
+                section = Some(name);
+                Ok(())
+            } else {
+                section = None;
+                Err(ConfigErrorKind::UnknownSection(name.to_string()))
+            }
+        } else {
+            parse_line(config, section, line, commands)
+        };
+
+        if let Err(kind) = result {
+            errors.push(ConfigError { line: i + 1, kind });
+        }
+    }
+
+    errors
+}
+
+pub fn error_message(error: &ConfigError) -> String {
+    let message = match &error.kind {
+        ConfigErrorKind::UnknownSection(name) => format!("unknown section [{}]", name),
+        ConfigErrorKind::NoSection => String::from("binding outside of an [edit] or [command] section"),
+        ConfigErrorKind::MissingCommand => String::from("expected a key sequence followed by a command"),
+        ConfigErrorKind::BadChord(chord) => format!("cannot read key {}", chord),
+        ConfigErrorKind::UnknownCommand(name) => format!("unknown command {}", name),
+        ConfigErrorKind::NotPrefix(sequence) => format!("{} is bound to a command, not a prefix", sequence),
+    };
+
+    format!("{}: {}", error.line, message)
+}
+
+// $XDG_CONFIG_HOME/engine/keys.conf, or the same under ~/.config.
+pub fn config_path() -> Option<std::path::PathBuf> {
+    let base = match std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => std::path::PathBuf::from(dir),
+        None => std::path::PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+
+    Some(base.join("engine").join("keys.conf"))
+}
@@ -0,0 +1,1299 @@
+use super::Editor;
+use super::rope;
+use super::history;
+use super::grapheme;
+use super::search;
+use super::replace;
+use super::syntax;
+use super::layout;
+use super::file;
+use super::indent;
+use super::complete;
+
+pub struct UniqueChars {
+    pub positions: std::collections::HashMap<char, Vec<[u8; 3]>>,
+}
+
+// How wide tabs show and what Tab inserts: a tab, or spaces up to the next
+// tab stop.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Indent {
+    pub tab_width: u32,
+    pub tabs: bool,
+}
+
+#[derive(Clone)]
+pub struct ModeLine {
+    pub left: Vec<u8>,
+    pub middle: Vec<u8>,
+    pub right: Vec<u8>,
+}
+
+pub struct Buffer {
+    pub cursors: Vec<Cursor>,
+    pub offset: Offset,
+    pub text: rope::Rope,
+    pub history: history::History,
+    pub syntax: syntax::Highlighter,
+    pub file_name: Option<Vec<u8>>,
+    pub format: file::Format,
+    // The file as last read or written, to notice others writing to it.
+    pub stamp: Option<file::Stamp>,
+    pub indent: Indent,
+    // Edited since it was loaded or last saved.
+    pub modified: bool,
+    // The *Buffers* list, which shows the other buffers rather than a file.
+    pub listing: bool,
+    pub mode_line: ModeLine,
+    pub main_cursor_index: u32,
+}
+
+// A split showing one buffer. The focused window's cursors and offset live in
+// its buffer while it has focus and are copied back here by `update_chars`.
+pub struct Window {
+    pub buffer: usize,
+    pub offset: Offset,
+    pub cursors: Vec<Cursor>,
+    pub main_cursor_index: u32,
+    pub rect: layout::Rect,
+    pub unique_chars: UniqueChars,
+    pub highlights: Vec<[u8; 2]>,
+    pub matches: Vec<[u8; 2]>,
+    pub cursor_cells: Vec<[u32; 2]>,
+}
+
+#[derive(Clone)]
+pub struct Cursor {
+    pub position: Position,
+    pub selection: Option<Position>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Position {
+    pub x: u32,
+    pub y: u32,
+}
+
+#[derive(Clone)]
+pub struct Offset {
+    pub x: u32,
+    pub y: u32,
+}
+
+pub fn empty_buffer() -> Buffer {
+    let mode_line = ModeLine {
+        left: vec![b'm', b'a', b'c', b'o', b'n', b'h', b'a'],
+        middle: Vec::new(),
+        right: Vec::new(),
+    };
+
+    let text = rope::empty();
+    let syntax = syntax::new(syntax::Language::Plain, rope::line_count(&text));
+
+    Buffer {
+        file_name: None,
+        format: file::default_format(),
+        stamp: None,
+        indent: Indent { tab_width: TAB_WIDTH, tabs: false },
+        modified: false,
+        listing: false,
+        offset: Offset {
+            x: 0,
+            y: 0,
+        },
+        main_cursor_index: 0,
+        syntax,
+        text,
+        history: history::empty(),
+        mode_line,
+        cursors: vec![
+            Cursor {
+                selection: None,
+                position: Position {
+                    x: 0,
+                    y: 0,
+                },
+            }
+        ],
+    }
+}
+
+pub fn buffer_from_file(file_path: &str) -> Option<Buffer> {
+    let path = std::path::Path::new(file_path);
+    let content = std::fs::read(path).ok()?;
+    let stamp = file::stamp(path);
+    let (bytes, format) = file::decode(&content);
+
+    let text = rope::from_bytes(&bytes);
+    let syntax = syntax::new(syntax::language_from_file_name(file_path.as_bytes()), rope::line_count(&text));
+    let indent = indent_of(&bytes);
+
+    let mode_line = ModeLine {
+        left: Vec::from(file_path.as_bytes()),
+        middle: Vec::new(),
+        right: mode_line_right(&format, false, 0, 0),
+    };
+
+    Some(Buffer {
+        file_name: Some(Vec::from(file_path.as_bytes())),
+        format,
+        stamp,
+        indent,
+        modified: false,
+        listing: false,
+        offset: Offset {
+            x: 0,
+            y: 0,
+        },
+        main_cursor_index: 0,
+        syntax,
+        text,
+        history: history::empty(),
+        mode_line,
+        cursors: vec![
+            Cursor {
+                selection: None,
+                position: Position {
+                    x: 0,
+                    y: 0,
+                }
+            }
+        ],
+    })
+}
+
+// Files indented with tabs more often than with spaces keep using tabs.
+fn indent_of(text: &[u8]) -> Indent {
+    let mut counts = [0, 0];
+
+    for line in text.split(|b| *b == b'\n') {
+        match line.first() {
+            Some(b'\t') => counts[0] += 1,
+            Some(b' ') => counts[1] += 1,
+            _ => {},
+        }
+    }
+
+    Indent { tab_width: TAB_WIDTH, tabs: counts[0] > counts[1] }
+}
+
+// What the command line runs itself, besides the commands keys are bound to.
+pub const LINE_COMMANDS: [&str; 11] = ["e", "b", "k", "k!", "w", "replace", "query-replace", "encoding", "line-ending", "tab-width", "indent"];
+
+pub fn execute_command(editor: &mut Editor) {
+    let mut command_content_iter = editor.command.iter();
+    let command: Vec<u8> = command_content_iter.by_ref().take_while(|c| **c != b' ').map(|c| *c).collect();
+    let argument: Vec<u8> = command_content_iter.by_ref().skip_while(|c| **c == b' ').map(|c| *c).collect();
+
+    let command_slice: &[u8] = &command;
+
+    match command_slice {
+        &[b'e'] => {
+            if let Some(buffer) = buffer_from_file(&String::from_utf8_lossy(&argument)) {
+                let len = editor.buffers.len();
+                editor.buffers.push(buffer);
+                editor.main_buffer_index = len as u32;
+            } else {
+                set_mode_line_middle(editor, format!("Could not open {}", String::from_utf8_lossy(&argument)).into_bytes());
+            }
+        },
+        &[b'b'] => match editor.buffers.iter().position(|buffer| buffer_name(buffer) == argument) {
+            Some(index) => editor.main_buffer_index = index as u32,
+            None => set_mode_line_middle(editor, format!("No buffer named {}", String::from_utf8_lossy(&argument)).into_bytes()),
+        },
+        &[b'k'] | b"k!" => {
+            let index = if argument.is_empty() {
+                Some(editor.main_buffer_index as usize)
+            } else {
+                editor.buffers.iter().position(|buffer| buffer_name(buffer) == argument)
+            };
+
+            match index {
+                Some(index) => close_buffer(editor, index, command == b"k!"),
+                None => set_mode_line_middle(editor, format!("No buffer named {}", String::from_utf8_lossy(&argument)).into_bytes()),
+            }
+        },
+        &[b'w'] => {
+            if !argument.is_empty() {
+                set_file_name(editor, argument);
+            }
+
+            save_buffer(editor);
+            history::seal(&mut editor.buffers[editor.main_buffer_index as usize].history);
+        }
+        b"replace" => match replace::parse_arguments(&argument) {
+            Ok(replace) => {
+                let input = rope::to_bytes(&editor.buffers[editor.main_buffer_index as usize].text);
+                let edits = replace::replace_all(&replace, &input, 0);
+
+                begin_edit(editor, history::EditKind::Other);
+                apply_edits(editor, &edits);
+                end_edit(editor);
+
+                set_mode_line_middle(editor, replaced_message(edits.len()));
+            },
+            Err(_) => set_mode_line_middle(editor, b"Invalid replace pattern".to_vec()),
+        },
+        b"query-replace" => match replace::parse_arguments(&argument) {
+            Ok(replace) => {
+                begin_edit(editor, history::EditKind::Other);
+                editor.query_replace = Some(replace::query(replace, 0));
+            },
+            Err(_) => set_mode_line_middle(editor, b"Invalid replace pattern".to_vec()),
+        },
+        b"encoding" => match file::parse_encoding(&argument) {
+            Some((encoding, bom)) => {
+                let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+                let format = file::Format { encoding, bom, ..buffer.format };
+
+                match file::encode(&rope::to_bytes(&buffer.text), &format) {
+                    Ok(_) => {
+                        buffer.format = format;
+                        buffer.modified = true;
+                        history::forget_saved(&mut buffer.history);
+                    },
+                    Err(c) => set_mode_line_middle(editor, format!("{} can't hold {:?}", String::from_utf8_lossy(&argument), c).into_bytes()),
+                }
+            },
+            None => set_mode_line_middle(editor, format!("Unknown encoding: {}", String::from_utf8_lossy(&argument)).into_bytes()),
+        },
+        b"line-ending" => match file::parse_line_ending(&argument) {
+            Some(line_ending) => {
+                let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+                buffer.format.line_ending = line_ending;
+                buffer.modified = true;
+                history::forget_saved(&mut buffer.history);
+            },
+            None => set_mode_line_middle(editor, format!("Unknown line ending: {}", String::from_utf8_lossy(&argument)).into_bytes()),
+        },
+        b"tab-width" => match std::str::from_utf8(&argument).ok().and_then(|n| n.parse().ok()).filter(|n| *n > 0) {
+            Some(tab_width) => editor.buffers[editor.main_buffer_index as usize].indent.tab_width = tab_width,
+            None => set_mode_line_middle(editor, format!("Invalid tab width: {}", String::from_utf8_lossy(&argument)).into_bytes()),
+        },
+        b"indent" => match &argument[..] {
+            b"tabs" | b"spaces" => editor.buffers[editor.main_buffer_index as usize].indent.tabs = argument == b"tabs",
+            _ => set_mode_line_middle(editor, format!("Indent with tabs or spaces, not {}", String::from_utf8_lossy(&argument)).into_bytes()),
+        },
+        _ => set_mode_line_middle(editor, format!("Unknown command: {}", String::from_utf8_lossy(&command)).into_bytes()),
+    }
+
+    editor.command.clear();
+}
+
+// What `:b` and `:k` know a buffer by: its file, or the mode line's name for
+// it when it has none.
+pub fn buffer_name(buffer: &Buffer) -> &[u8] {
+    buffer.file_name.as_deref().unwrap_or(&buffer.mode_line.left)
+}
+
+// Names of the buffers whose edits would be lost on quitting.
+pub fn unsaved_buffers(editor: &Editor) -> Vec<Vec<u8>> {
+    editor.buffers.iter().filter(|buffer| buffer.modified && !buffer.listing).map(|buffer| buffer_name(buffer).to_vec()).collect()
+}
+
+// Closes buffer `index` unless it has unsaved edits and `force` isn't set.
+// Windows showing it move to the buffer before it, or the one after for the
+// first, and an empty buffer takes the place of the last one.
+pub fn close_buffer(editor: &mut Editor, index: usize, force: bool) {
+    let buffer = &editor.buffers[index];
+    if buffer.modified && !buffer.listing && !force {
+        let message = format!("{} has unsaved changes; :k! closes it anyway", String::from_utf8_lossy(buffer_name(buffer)));
+        return set_mode_line_middle(editor, message.into_bytes());
+    }
+
+    save_window(editor);
+    editor.buffers.remove(index);
+    if editor.buffers.is_empty() {
+        editor.buffers.push(empty_buffer());
+    }
+
+    let replacement = std::cmp::min(index.saturating_sub(1), editor.buffers.len() - 1);
+    for window in editor.windows.iter_mut() {
+        if window.buffer == index {
+            let buffer = &editor.buffers[replacement];
+            window.buffer = replacement;
+            window.offset = buffer.offset.clone();
+            window.cursors = buffer.cursors.clone();
+            window.main_cursor_index = buffer.main_cursor_index;
+        } else if window.buffer > index {
+            window.buffer -= 1;
+        }
+    }
+
+    load_window(editor);
+
+    if let Some(listing) = editor.buffers.iter().position(|buffer| buffer.listing) {
+        refresh_listing(editor, listing);
+    }
+}
+
+// Writes the buffers into the listing at `index`, one a line in the order
+// they're kept in, so that a line's number is its buffer's index. Unsaved
+// ones are marked with `*`.
+pub fn refresh_listing(editor: &mut Editor, index: usize) {
+    let mut text = Vec::new();
+
+    for (i, buffer) in editor.buffers.iter().enumerate() {
+        if i > 0 {
+            text.push(b'\n');
+        }
+
+        text.extend_from_slice(if buffer.modified && !buffer.listing { b" * " } else { b"   " });
+        text.extend_from_slice(buffer_name(buffer));
+    }
+
+    let listing = &mut editor.buffers[index];
+    listing.text = rope::from_bytes(&text);
+    listing.syntax = syntax::new(syntax::Language::Plain, rope::line_count(&listing.text));
+    listing.history = history::empty();
+
+    let last = rope::line_count(&listing.text) as u32 - 1;
+    for cursor in listing.cursors.iter_mut() {
+        cursor.position = Position { x: 0, y: std::cmp::min(cursor.position.y, last) };
+        cursor.selection = None;
+    }
+}
+
+pub fn listing_buffer() -> Buffer {
+    let mut buffer = empty_buffer();
+    buffer.mode_line.left = b"*Buffers*".to_vec();
+    buffer.listing = true;
+
+    buffer
+}
+
+// Gives the buffer a file to be saved in, which may already exist.
+pub fn set_file_name(editor: &mut Editor, file_name: Vec<u8>) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+
+    buffer.syntax = syntax::new(syntax::language_from_file_name(&file_name), rope::line_count(&buffer.text));
+    buffer.mode_line.left = file_name.clone();
+    buffer.file_name = Some(file_name);
+    buffer.stamp = None;
+    buffer.listing = false;
+}
+
+// Saves the main buffer and says how that went in the mode line. A file
+// that changed on disk is left alone once; saving again overwrites it.
+pub fn save_buffer(editor: &mut Editor) {
+    use std::os::unix::ffi::OsStrExt;
+
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    let Some(file_name) = buffer.file_name.clone() else {
+        set_mode_line_middle(editor, b"No file name; save with :w <name>".to_vec());
+        return;
+    };
+
+    let path = std::path::Path::new(std::ffi::OsStr::from_bytes(&file_name));
+    let name = String::from_utf8_lossy(&file_name);
+
+    let content = match buffer_content(buffer) {
+        Ok(content) => content,
+        Err(c) => {
+            let message = format!("Could not save {}: {} can't hold {:?}", name, file::label(&buffer.format), c);
+            set_mode_line_middle(editor, message.into_bytes());
+            return;
+        },
+    };
+
+    let message = match file::save(path, &content, buffer.stamp) {
+        Ok(stamp) => {
+            buffer.stamp = stamp;
+            buffer.modified = false;
+            history::mark_saved(&mut buffer.history);
+            format!("Wrote {}", name)
+        },
+        Err(file::SaveError::ChangedOnDisk(stamp)) => {
+            buffer.stamp = stamp;
+            format!("{} changed on disk; save again to overwrite", name)
+        },
+        Err(file::SaveError::Io(error)) => format!("Could not save {}: {}", name, error),
+    };
+
+    set_mode_line_middle(editor, message.into_bytes());
+}
+
+pub fn buffer_content(buffer: &Buffer) -> Result<Vec<u8>, char> {
+    file::encode(&rope::to_bytes(&buffer.text), &buffer.format)
+}
+
+pub fn get_this_line_or_max(text: &rope::Rope, i: u32) -> u32 {
+    let len = rope::line_count(text) as u32;
+
+    if len < i {
+        len
+    } else {
+        i
+    }
+}
+
+pub fn check_offset(editor: &mut Editor) -> bool {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    let position = &buffer.cursors[buffer.main_cursor_index as usize].position;
+    let column = cursor_column(buffer, buffer.main_cursor_index as usize);
+    let mut change_flag = false;
+
+    if position.y >= buffer.offset.y + editor.chars_per_coloumn {
+        buffer.offset.y = position.y - editor.chars_per_coloumn + 1;
+        change_flag = true;
+    } else if position.y < buffer.offset.y {
+        buffer.offset.y = position.y;
+        change_flag = true;
+    }
+
+    if column > buffer.offset.x + editor.chars_per_row {
+        buffer.offset.x = column - editor.chars_per_row;
+        change_flag = true;
+    } else if column < buffer.offset.x {
+        buffer.offset.x = column;
+        change_flag = true;
+    }
+
+    change_flag
+}
+
+fn get_position_bytes(x: u32, y: u32) -> Vec<u8> {
+    let line_number_parse = y.to_string();
+    let col_number_parse = x.to_string();
+
+    let mut string = Vec::new();
+    string.extend_from_slice(&[b'l', b':']);
+    string.extend_from_slice(&line_number_parse.into_bytes());
+    string.extend_from_slice(&[b' ', b'c', b':']);
+    string.extend_from_slice(&col_number_parse.into_bytes());
+
+    string
+}
+
+// The file format, where the cursor is, and whether there are unsaved edits.
+fn mode_line_right(format: &file::Format, modified: bool, x: u32, y: u32) -> Vec<u8> {
+    let mut right = file::label(format).into_bytes();
+    right.extend_from_slice(b"  ");
+    right.extend(get_position_bytes(x, y));
+    if modified {
+        right.extend_from_slice(b"  [+]");
+    }
+
+    right
+}
+
+pub fn update_mode_line_right(editor: &mut Editor) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    let column = cursor_column(buffer, buffer.main_cursor_index as usize);
+    let cursor = &buffer.cursors[buffer.main_cursor_index as usize];
+    buffer.mode_line.right = mode_line_right(&buffer.format, buffer.modified, column, cursor.position.y);
+}
+
+pub fn delete_prev_char(editor: &mut Editor, position_index: usize) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    let position = buffer.cursors[position_index].position.clone();
+    let offset = cursor_offset(&buffer.text, &position);
+
+    if position.x == 0 {
+        if position.y > 0 {
+            let y = position.y - 1;
+            let x = rope::line_len(&buffer.text, y as usize) as u32;
+            delete_text(buffer, offset - 1, offset);
+            buffer.cursors[position_index].position = Position { x, y };
+        }
+    } else {
+        let line = rope::line(&buffer.text, position.y as usize);
+        let x = grapheme::prev_boundary(&line, position.x as usize) as u32;
+        delete_text(buffer, offset - (position.x - x) as usize, offset);
+        buffer.cursors[position_index].position.x = x;
+    }
+}
+
+pub fn start_of_line(editor: &mut Editor, position_index: usize) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    let position = &mut buffer.cursors[position_index].position;
+    position.x = 0;
+}
+
+pub fn end_of_line(editor: &mut Editor, position_index: usize) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    let position = &mut buffer.cursors[position_index].position;
+    position.x = rope::line_len(&buffer.text, position.y as usize) as u32;
+}
+
+pub fn next_char(editor: &mut Editor, position_index: usize) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    let position = &mut buffer.cursors[position_index].position;
+    let line = rope::line(&buffer.text, position.y as usize);
+
+    if position.x as usize >= line.len() {
+        if rope::line_count(&buffer.text) > position.y as usize + 1 {
+            position.x = 0;
+            position.y += 1;
+        }
+    } else {
+        position.x = grapheme::next_boundary(&line, position.x as usize) as u32;
+    }
+}
+
+pub fn prev_char(editor: &mut Editor, position_index: usize) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    let position = &mut buffer.cursors[position_index].position;
+    if position.x == 0 {
+        if position.y > 0 {
+            position.y -= 1;
+            position.x = rope::line_len(&buffer.text, position.y as usize) as u32;
+        }
+    } else {
+        let line = rope::line(&buffer.text, position.y as usize);
+        position.x = grapheme::prev_boundary(&line, position.x as usize) as u32;
+    }
+}
+
+pub fn prev_line(editor: &mut Editor, position_index: usize) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    let position = &mut buffer.cursors[position_index].position;
+    if position.y > 0 {
+        let column = grapheme::column_of(&rope::line(&buffer.text, position.y as usize), position.x as usize, buffer.indent.tab_width);
+        position.y -= 1;
+        position.x = grapheme::offset_of(&rope::line(&buffer.text, position.y as usize), column, buffer.indent.tab_width) as u32;
+    }
+}
+
+pub fn next_line(editor: &mut Editor, position_index: usize) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    let position = &mut buffer.cursors[position_index].position;
+
+    if rope::line_count(&buffer.text) > position.y as usize + 1 {
+        let column = grapheme::column_of(&rope::line(&buffer.text, position.y as usize), position.x as usize, buffer.indent.tab_width);
+        position.y += 1;
+        position.x = grapheme::offset_of(&rope::line(&buffer.text, position.y as usize), column, buffer.indent.tab_width) as u32;
+    }
+}
+
+pub fn insert_new_line(editor: &mut Editor, position_index: usize) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    let offset = cursor_offset(&buffer.text, &buffer.cursors[position_index].position);
+    insert_text(buffer, offset, b"\n");
+
+    let y = buffer.cursors[position_index].position.y + 1;
+    let [_, indent] = reindent_line(buffer, y as usize);
+    buffer.cursors[position_index].position = Position { x: indent as u32, y };
+}
+
+// The indentation line `y` should have: that of the nearest non-blank line
+// above, one level deeper if that line opens one, and one level out if this
+// one starts by closing it.
+fn wanted_indent(buffer: &mut Buffer, y: usize) -> Vec<u8> {
+    let Some(above) = (0..y).rev().find(|i| !is_blank(&rope::line(&buffer.text, *i))) else {
+        return Vec::new();
+    };
+
+    let previous = line_indent(&buffer.text, above);
+    let Some(rules) = indent::rules(buffer.syntax.language) else {
+        return previous;
+    };
+
+    syntax::update(&mut buffer.syntax, &buffer.text);
+    let tab_width = buffer.indent.tab_width;
+    let mut columns = indent::columns(&previous, tab_width);
+
+    if indent::opens(&rules, &rope::line(&buffer.text, above), syntax::spans(&buffer.syntax, above)) {
+        columns += tab_width;
+    }
+
+    if indent::closes(&rules, &rope::line(&buffer.text, y)) {
+        columns = columns.saturating_sub(tab_width);
+    }
+
+    indent::make(columns, tab_width, buffer.indent.tabs)
+}
+
+// Replaces the indentation of line `y` with the one it should have. Returns
+// the old and new lengths of the indentation.
+fn reindent_line(buffer: &mut Buffer, y: usize) -> [usize; 2] {
+    let wanted = wanted_indent(buffer, y);
+    let current = line_indent(&buffer.text, y);
+
+    if wanted != current {
+        let start = rope::line_start(&buffer.text, y);
+        delete_text(buffer, start, start + current.len());
+        insert_text(buffer, start, &wanted);
+    }
+
+    [current.len(), wanted.len()]
+}
+
+fn is_blank(line: &[u8]) -> bool {
+    line.iter().all(|b| matches!(b, b' ' | b'\t'))
+}
+
+// Reindents the lines the region touches, or the cursor's line without one.
+// Blank lines lose their whitespace.
+pub fn indent_region(editor: &mut Editor, position_index: usize) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    let position = buffer.cursors[position_index].position.clone();
+
+    let [first, last] = match region(buffer, position_index) {
+        Some([start, end]) => {
+            let [start, end] = [position_of(&buffer.text, start), position_of(&buffer.text, end)];
+            // A region ending at the start of a line leaves that line alone.
+            let last = if end.x == 0 && end.y > start.y { end.y - 1 } else { end.y };
+            [start.y, last]
+        },
+        None => [position.y, position.y],
+    };
+
+    for y in first..=last {
+        let [old, new] = if is_blank(&rope::line(&buffer.text, y as usize)) {
+            let start = rope::line_start(&buffer.text, y as usize);
+            let end = rope::line_end(&buffer.text, y as usize);
+            delete_text(buffer, start, end);
+            [end - start, 0]
+        } else {
+            reindent_line(buffer, y as usize)
+        };
+
+        if y == position.y {
+            let x = position.x as usize;
+            buffer.cursors[position_index].position.x = if x > old { x - old + new } else { new } as u32;
+        }
+    }
+}
+
+const TAB_WIDTH: u32 = 4;
+
+// A tab, or spaces to the next tab stop.
+fn indent_bytes(buffer: &Buffer, column: u32) -> Vec<u8> {
+    if buffer.indent.tabs {
+        vec![b'\t']
+    } else {
+        vec![b' '; (buffer.indent.tab_width - column % buffer.indent.tab_width) as usize]
+    }
+}
+
+pub fn insert_char_at(editor: &mut Editor, position_index: usize) {
+    let c = editor.last_inserted_char;
+    let chars_inserted = if c == '\t' {
+        let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+        let offset = cursor_offset(&buffer.text, &buffer.cursors[position_index].position);
+        let indent = indent_bytes(buffer, cursor_column(buffer, position_index));
+        insert_text(buffer, offset, &indent);
+
+        indent.len()
+    } else {
+        let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+        let position = buffer.cursors[position_index].position.clone();
+        let offset = cursor_offset(&buffer.text, &position);
+        let mut bytes = [0; 4];
+        let encoded = c.encode_utf8(&mut bytes).as_bytes();
+        insert_text(buffer, offset, encoded);
+
+        // A closing bracket typed first on its line moves it a level out.
+        let closing = indent::rules(buffer.syntax.language).is_some_and(|rules| rules.close.contains(&encoded[0]));
+        if closing && is_blank(&rope::line(&buffer.text, position.y as usize)[..position.x as usize]) {
+            let [old, new] = reindent_line(buffer, position.y as usize);
+            buffer.cursors[position_index].position.x = (position.x as usize - old + new) as u32;
+        }
+
+        encoded.len()
+    };
+
+    update_chars(editor);
+    editor.buffers[editor.main_buffer_index as usize].cursors[position_index].position.x += chars_inserted as u32;
+}
+
+pub fn delete_char_at(editor: &mut Editor, position_index: usize) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    let position = &mut buffer.cursors[position_index].position;
+    let offset = cursor_offset(&buffer.text, position);
+
+    let line = rope::line(&buffer.text, position.y as usize);
+
+    if line.len() <= position.x as usize {
+        if rope::line_count(&buffer.text) > position.y as usize + 1 {
+            delete_text(buffer, offset, offset + 1);
+        }
+    } else {
+        let end = grapheme::next_boundary(&line, position.x as usize) - position.x as usize;
+        delete_text(buffer, offset, offset + end);
+    }
+}
+
+pub fn delete_to_line_end(editor: &mut Editor, position_index: usize) -> Vec<u8> {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    let position = &mut buffer.cursors[position_index].position;
+    let offset = cursor_offset(&buffer.text, position);
+    let mut end = rope::line_end(&buffer.text, position.y as usize);
+
+    if offset == end && end < rope::len(&buffer.text) {
+        end += 1;
+    }
+
+    let content = rope::slice(&buffer.text, offset, end);
+    delete_text(buffer, offset, end);
+
+    content
+}
+
+pub fn set_mark(editor: &mut Editor, position_index: usize) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    let cursor = &mut buffer.cursors[position_index];
+    cursor.selection = Some(cursor.position.clone());
+}
+
+pub fn clear_mark(editor: &mut Editor, position_index: usize) {
+    editor.buffers[editor.main_buffer_index as usize].cursors[position_index].selection = None;
+}
+
+pub fn region(buffer: &Buffer, cursor_index: usize) -> Option<[usize; 2]> {
+    let cursor = &buffer.cursors[cursor_index];
+    let mark = cursor_offset(&buffer.text, cursor.selection.as_ref()?);
+    let point = cursor_offset(&buffer.text, &cursor.position);
+
+    Some([std::cmp::min(mark, point), std::cmp::max(mark, point)])
+}
+
+pub fn copy_region(editor: &mut Editor, position_index: usize) -> Option<Vec<u8>> {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    let [start, end] = region(buffer, position_index)?;
+    buffer.cursors[position_index].selection = None;
+
+    Some(rope::slice(&buffer.text, start, end))
+}
+
+pub fn kill_region(editor: &mut Editor, position_index: usize) -> Option<Vec<u8>> {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    let [start, end] = region(buffer, position_index)?;
+
+    let content = rope::slice(&buffer.text, start, end);
+    delete_text(buffer, start, end);
+
+    let cursor = &mut buffer.cursors[position_index];
+    cursor.position = position_of(&buffer.text, start);
+    cursor.selection = None;
+
+    Some(content)
+}
+
+pub fn yank(editor: &mut Editor, position_index: usize, content: &[u8]) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    let offset = cursor_offset(&buffer.text, &buffer.cursors[position_index].position);
+    insert_text(buffer, offset, content);
+    buffer.cursors[position_index].position = position_of(&buffer.text, offset + content.len());
+}
+
+pub fn replace_yank(editor: &mut Editor, position_index: usize, len: usize, content: &[u8]) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    let end = cursor_offset(&buffer.text, &buffer.cursors[position_index].position);
+    let start = end.saturating_sub(len);
+    delete_text(buffer, start, end);
+    insert_text(buffer, start, content);
+    buffer.cursors[position_index].position = position_of(&buffer.text, start + content.len());
+}
+
+pub fn begin_edit(editor: &mut Editor, kind: history::EditKind) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    history::begin(&mut buffer.history, kind, &buffer.cursors);
+}
+
+pub fn end_edit(editor: &mut Editor) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+
+    for cursor in buffer.cursors.iter_mut() {
+        cursor.selection = None;
+    }
+
+    history::commit(&mut buffer.history, &buffer.cursors);
+}
+
+pub fn undo(editor: &mut Editor) -> bool {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+
+    let syntax = &mut buffer.syntax;
+
+    if let Some(cursors) = history::undo_with(&mut buffer.text, &mut buffer.history, |text, offset, content, inserted| mark_edited(syntax, text, offset, content, inserted)) {
+        buffer.cursors = cursors;
+        buffer.modified = !history::is_saved(&buffer.history);
+        true
+    } else {
+        false
+    }
+}
+
+pub fn redo(editor: &mut Editor) -> bool {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+
+    let syntax = &mut buffer.syntax;
+
+    if let Some(cursors) = history::redo_with(&mut buffer.text, &mut buffer.history, |text, offset, content, inserted| mark_edited(syntax, text, offset, content, inserted)) {
+        buffer.cursors = cursors;
+        buffer.modified = !history::is_saved(&buffer.history);
+        true
+    } else {
+        false
+    }
+}
+
+fn command_string(string: &[u8]) -> Vec<u8> {
+    let mut v = Vec::with_capacity(string.len() + 1);
+    v.push(b':');
+    v.extend_from_slice(string);
+
+    v
+}
+
+pub fn window(buffer: &Buffer, buffer_index: usize, rect: layout::Rect) -> Window {
+    Window {
+        buffer: buffer_index,
+        offset: buffer.offset.clone(),
+        cursors: buffer.cursors.clone(),
+        main_cursor_index: buffer.main_cursor_index,
+        rect,
+        unique_chars: UniqueChars {
+            positions: std::collections::HashMap::new(),
+        },
+        highlights: Vec::new(),
+        matches: Vec::new(),
+        cursor_cells: Vec::new(),
+    }
+}
+
+pub fn save_window(editor: &mut Editor) {
+    let buffer = &editor.buffers[editor.main_buffer_index as usize];
+    let window = &mut editor.windows[editor.layout.focus];
+
+    window.buffer = editor.main_buffer_index as usize;
+    window.offset = buffer.offset.clone();
+    window.cursors = buffer.cursors.clone();
+    window.main_cursor_index = buffer.main_cursor_index;
+}
+
+// Gives the buffer of the focused window back that window's cursors and
+// offset, clamped in case the text changed through another window.
+pub fn load_window(editor: &mut Editor) {
+    let window = &editor.windows[editor.layout.focus];
+    let buffer = &mut editor.buffers[window.buffer];
+
+    buffer.offset = window.offset.clone();
+    buffer.cursors = window.cursors.clone();
+    buffer.main_cursor_index = window.main_cursor_index;
+
+    let last = rope::line_count(&buffer.text).saturating_sub(1) as u32;
+    for cursor in buffer.cursors.iter_mut() {
+        for position in std::iter::once(&mut cursor.position).chain(cursor.selection.as_mut()) {
+            position.y = std::cmp::min(position.y, last);
+            position.x = std::cmp::min(position.x, rope::line_len(&buffer.text, position.y as usize) as u32);
+        }
+    }
+
+    editor.main_buffer_index = window.buffer as u32;
+    editor.chars_per_row = window.rect.width;
+    editor.chars_per_coloumn = window.rect.height - 1;
+}
+
+pub fn update_chars(editor: &mut Editor) {
+    save_window(editor);
+
+    for i in 0..editor.windows.len() {
+        if i == editor.layout.focus {
+            update_focused_chars(editor);
+        } else {
+            update_window_chars(editor, i);
+        }
+    }
+}
+
+fn update_focused_chars(editor: &mut Editor) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    let window = &mut editor.windows[editor.layout.focus];
+    let completion = editor.completion.as_ref().filter(|_| editor.command_mode);
+    let box_rows = completion.map_or(0, |completion| (completion.candidates.len() as u32).min(complete::ROWS).min(editor.chars_per_coloumn));
+    let line_max = get_this_line_or_max(&buffer.text, buffer.offset.y + editor.chars_per_coloumn - box_rows);
+
+    syntax::update(&mut buffer.syntax, &buffer.text);
+    clear_chars(&mut window.unique_chars);
+
+    for (i, y) in (buffer.offset.y..line_max).enumerate() {
+        let line = rope::line(&buffer.text, y as usize);
+        push_line_cells(&mut window.unique_chars, &line, syntax::spans(&buffer.syntax, y as usize), [buffer.offset.x, editor.chars_per_row], buffer.indent.tab_width, i as u32);
+    }
+
+    let mode_line_content = if editor.command_mode {
+        command_string(&editor.command)
+    } else {
+        mode_line_string(editor.chars_per_row, &buffer.mode_line)
+    };
+
+    push_cells(&mut window.unique_chars, &mode_line_content, editor.chars_per_coloumn);
+    update_highlights(buffer, &mut window.highlights, editor.chars_per_row, line_max);
+    update_matches(buffer, &mut window.matches, editor.isearch.as_ref(), editor.chars_per_row, line_max);
+
+    if let Some(range) = editor.query_replace.as_ref().and_then(|query| query.current.as_ref()).and_then(|captures| captures[0]) {
+        let cells = range_cells(buffer, range, editor.chars_per_row, line_max);
+        window.matches.extend(cells);
+    }
+
+    window.cursor_cells = (0..buffer.cursors.len()).map(|i| cursor_cell(buffer, i)).collect();
+
+    // The completion box sits on the last rows of text, with the selected
+    // candidate highlighted and kept in view.
+    if let Some(completion) = completion {
+        let top = editor.chars_per_coloumn - box_rows;
+        let first = completion.selected.map_or(0, |i| (i as u32 + 1).saturating_sub(box_rows));
+
+        for (row, candidate) in completion.candidates.iter().skip(first as usize).take(box_rows as usize).enumerate() {
+            push_cells(&mut window.unique_chars, candidate, top + row as u32);
+
+            if completion.selected == Some(first as usize + row) {
+                window.highlights.extend((0..editor.chars_per_row).map(|x| [x as u8, (top + row as u32) as u8]));
+            }
+        }
+    }
+}
+
+fn update_window_chars(editor: &mut Editor, window_index: usize) {
+    let window = &mut editor.windows[window_index];
+    let buffer = &mut editor.buffers[window.buffer];
+    let columns = window.rect.width;
+    let rows = window.rect.height - 1;
+    let line_max = get_this_line_or_max(&buffer.text, window.offset.y + rows);
+
+    syntax::update(&mut buffer.syntax, &buffer.text);
+    clear_chars(&mut window.unique_chars);
+
+    for (i, y) in (window.offset.y..line_max).enumerate() {
+        let line = rope::line(&buffer.text, y as usize);
+        push_line_cells(&mut window.unique_chars, &line, syntax::spans(&buffer.syntax, y as usize), [window.offset.x, columns], buffer.indent.tab_width, i as u32);
+    }
+
+    let position = &window.cursors[window.main_cursor_index as usize].position;
+    let column = if (position.y as usize) < rope::line_count(&buffer.text) {
+        let line = rope::line(&buffer.text, position.y as usize);
+        grapheme::column_of(&line, std::cmp::min(position.x as usize, line.len()), buffer.indent.tab_width)
+    } else {
+        position.x
+    };
+
+    let mode_line = ModeLine {
+        right: mode_line_right(&buffer.format, buffer.modified, column, position.y),
+        ..buffer.mode_line.clone()
+    };
+
+    push_cells(&mut window.unique_chars, &mode_line_string(columns, &mode_line), rows);
+    window.highlights.clear();
+    window.matches.clear();
+    window.cursor_cells.clear();
+}
+
+fn update_matches(buffer: &Buffer, matches: &mut Vec<[u8; 2]>, isearch: Option<&search::Isearch>, chars_per_row: u32, line_max: u32) {
+    matches.clear();
+
+    let isearch = if let Some(isearch) = isearch {
+        isearch
+    } else {
+        return;
+    };
+
+    for y in buffer.offset.y..line_max {
+        let line = rope::line(&buffer.text, y as usize);
+
+        for [start, end] in search::find_all(&line, &isearch.query, isearch.case_fold) {
+            let first = grapheme::column_of(&line, start, buffer.indent.tab_width);
+            let last = grapheme::column_of(&line, end, buffer.indent.tab_width);
+
+            for column in std::cmp::max(first, buffer.offset.x)..std::cmp::min(last, buffer.offset.x + chars_per_row) {
+                matches.push([(column - buffer.offset.x) as u8, (y - buffer.offset.y) as u8]);
+            }
+        }
+    }
+}
+
+pub fn replaced_message(count: usize) -> Vec<u8> {
+    let mut message = b"Replaced ".to_vec();
+    message.extend_from_slice(count.to_string().as_bytes());
+    message.extend_from_slice(if count == 1 { b" occurrence" } else { b" occurrences" });
+
+    message
+}
+
+pub fn apply_edits(editor: &mut Editor, edits: &[([usize; 2], Vec<u8>)]) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    let offsets: Vec<usize> = (0..buffer.cursors.len()).map(|i| cursor_offset(&buffer.text, &buffer.cursors[i].position)).collect();
+
+    for ([start, end], content) in edits.iter().rev() {
+        delete_text(buffer, *start, *end);
+        insert_text(buffer, *start, content);
+    }
+
+    for (i, offset) in offsets.into_iter().enumerate() {
+        let mut new_offset = offset;
+
+        for ([start, end], content) in edits.iter() {
+            if *end <= offset {
+                new_offset = new_offset + content.len() - (end - start);
+            } else if *start < offset {
+                new_offset -= offset - start;
+            }
+        }
+
+        buffer.cursors[i].position = position_of(&buffer.text, new_offset);
+    }
+}
+
+pub fn set_mode_line_middle(editor: &mut Editor, content: Vec<u8>) {
+    editor.buffers[editor.main_buffer_index as usize].mode_line.middle = content;
+}
+
+pub fn cursor_byte_offset(buffer: &Buffer, cursor_index: usize) -> usize {
+    cursor_offset(&buffer.text, &buffer.cursors[cursor_index].position)
+}
+
+pub fn move_to_offset(editor: &mut Editor, position_index: usize, offset: usize) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    buffer.cursors[position_index].position = position_of(&buffer.text, offset);
+}
+
+// Maps a cell of the focused window's text area to the nearest position,
+// snapping to the end of short lines and the last line of the buffer.
+pub fn position_at_cell(buffer: &Buffer, cell: [u32; 2]) -> Position {
+    let last = rope::line_count(&buffer.text).saturating_sub(1) as u32;
+    let y = std::cmp::min(buffer.offset.y + cell[1], last);
+    let line = rope::line(&buffer.text, y as usize);
+
+    Position {
+        x: grapheme::offset_of(&line, buffer.offset.x + cell[0], buffer.indent.tab_width) as u32,
+        y,
+    }
+}
+
+pub fn select_word(editor: &mut Editor, position_index: usize) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    let cursor = &mut buffer.cursors[position_index];
+    let line = rope::line(&buffer.text, cursor.position.y as usize);
+    let [start, end] = grapheme::word_bounds(&line, cursor.position.x as usize);
+
+    cursor.selection = Some(Position { x: start as u32, y: cursor.position.y });
+    cursor.position.x = end as u32;
+}
+
+pub fn select_line(editor: &mut Editor, position_index: usize) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    let cursor = &mut buffer.cursors[position_index];
+    let y = cursor.position.y;
+
+    cursor.selection = Some(Position { x: 0, y });
+    cursor.position = if (y as usize) + 1 < rope::line_count(&buffer.text) {
+        Position { x: 0, y: y + 1 }
+    } else {
+        Position { x: rope::line_len(&buffer.text, y as usize) as u32, y }
+    };
+}
+
+// Scrolls a view by whole cells and drags its cursors along so that they stay
+// visible, which keeps `check_offset` from scrolling straight back.
+pub fn scroll_view(text: &rope::Rope, tab_width: u32, offset: &mut Offset, cursors: &mut [Cursor], scroll: [i32; 2], size: [u32; 2]) {
+    let last = rope::line_count(text).saturating_sub(1) as u32;
+    offset.y = std::cmp::min(offset.y.saturating_add_signed(scroll[0]), last);
+    offset.x = offset.x.saturating_add_signed(scroll[1]);
+
+    let [columns, rows] = size;
+    for cursor in cursors.iter_mut() {
+        let position = &mut cursor.position;
+        let column = grapheme::column_of(&rope::line(text, position.y as usize), position.x as usize, tab_width);
+        let y = std::cmp::min(position.y.clamp(offset.y, offset.y + rows.saturating_sub(1)), last);
+        let x = column.clamp(offset.x, offset.x + columns);
+
+        if y != position.y || x != column {
+            position.x = grapheme::offset_of(&rope::line(text, y as usize), x, tab_width) as u32;
+            position.y = y;
+        }
+    }
+}
+
+fn update_highlights(buffer: &Buffer, highlights: &mut Vec<[u8; 2]>, chars_per_row: u32, line_max: u32) {
+    highlights.clear();
+
+    for i in 0..buffer.cursors.len() {
+        let [start, end] = if let Some(region) = region(buffer, i) {
+            region
+        } else {
+            continue;
+        };
+
+        let cells = range_cells(buffer, [start, end], chars_per_row, line_max);
+        highlights.extend(cells);
+    }
+}
+
+fn range_cells(buffer: &Buffer, range: [usize; 2], chars_per_row: u32, line_max: u32) -> Vec<[u8; 2]> {
+    let start = position_of(&buffer.text, range[0]);
+    let end = position_of(&buffer.text, range[1]);
+    let mut cells = Vec::new();
+
+    for y in std::cmp::max(start.y, buffer.offset.y)..std::cmp::min(end.y + 1, line_max) {
+        let line = rope::line(&buffer.text, y as usize);
+        let tab_width = buffer.indent.tab_width;
+        let first = if y == start.y { grapheme::column_of(&line, start.x as usize, tab_width) } else { 0 };
+        let last = if y == end.y { grapheme::column_of(&line, end.x as usize, tab_width) } else { grapheme::column_of(&line, line.len(), tab_width) + 1 };
+
+        for column in std::cmp::max(first, buffer.offset.x)..std::cmp::min(last, buffer.offset.x + chars_per_row) {
+            cells.push([(column - buffer.offset.x) as u8, (y - buffer.offset.y) as u8]);
+        }
+    }
+
+    cells
+}
+
+fn mode_line_string(chars_per_row: u32, mode_line: &ModeLine) -> Vec<u8> {
+    let width = |content: &[u8]| grapheme::column_of(content, content.len(), TAB_WIDTH) as usize;
+
+    let mut content = Vec::new();
+    content.extend_from_slice(&mode_line.left);
+    let l = (chars_per_row as usize / 2).saturating_sub(width(&mode_line.middle) / 2);
+
+    if l < width(&content) {
+        return content;
+    }
+
+    let white_space = l - width(&content);
+
+    content.extend_from_slice(&vec![b' '; white_space]);
+    content.extend_from_slice(&mode_line.middle);
+
+    let white_space = (chars_per_row as usize).saturating_sub(width(&content) + width(&mode_line.right));
+    content.extend_from_slice(&vec![b' '; white_space]);
+    content.extend_from_slice(&mode_line.right);
+
+    content
+}
+
+pub fn unique_chars_from_text(chars_per_row: u32, chars_per_coloumn: u32, text: &rope::Rope, syntax: &mut syntax::Highlighter, mode_line: &ModeLine) -> UniqueChars {
+    let line_max = get_this_line_or_max(text, chars_per_coloumn);
+    syntax::update(syntax, text);
+
+    let mut unique_chars = UniqueChars {
+        positions: std::collections::HashMap::new(),
+    };
+
+    for i in 0..line_max as usize {
+        let line = rope::line(text, i);
+        push_line_cells(&mut unique_chars, &line, syntax::spans(syntax, i), [0, chars_per_row], TAB_WIDTH, i as u32);
+    }
+
+    let mode_line_content = mode_line_string(chars_per_row, mode_line);
+    push_cells(&mut unique_chars, &mode_line_content, chars_per_coloumn);
+
+    unique_chars
+}
+
+fn clear_chars(unique_chars: &mut UniqueChars) {
+    for positions in unique_chars.positions.values_mut() {
+        positions.clear();
+    }
+}
+
+fn push_cells(unique_chars: &mut UniqueChars, content: &[u8], row: u32) {
+    let mut start = 0;
+    let mut column = 0;
+
+    while start < content.len() {
+        let end = grapheme::next_boundary(content, start);
+
+        for c in grapheme::chars(&content[start..end]) {
+            unique_chars.positions.entry(c).or_default().push([column as u8, row as u8, syntax::Kind::Text as u8]);
+        }
+
+        column += grapheme::width_at(content, start, column, TAB_WIDTH);
+        start = end;
+    }
+}
+
+// `columns` is the first visible column and the number of columns shown.
+// Tabs leave their cells blank.
+fn push_line_cells(unique_chars: &mut UniqueChars, line: &[u8], spans: &[syntax::Span], columns: [u32; 2], tab_width: u32, row: u32) {
+    let [offset, size] = columns;
+    let mut start = grapheme::offset_of(line, offset, tab_width);
+    let mut column = grapheme::column_of(line, start, tab_width);
+
+    while start < line.len() && column < offset + size {
+        let end = grapheme::next_boundary(line, start);
+        let kind = syntax::kind_at(spans, start);
+
+        if column >= offset && line[start] != b'\t' {
+            for c in grapheme::chars(&line[start..end]) {
+                unique_chars.positions.entry(c).or_default().push([(column - offset) as u8, row as u8, kind as u8]);
+            }
+        }
+
+        column += grapheme::width_at(line, start, column, tab_width);
+        start = end;
+    }
+}
+
+pub fn cursor_cell(buffer: &Buffer, cursor_index: usize) -> [u32; 2] {
+    let position = &buffer.cursors[cursor_index].position;
+
+    [cursor_column(buffer, cursor_index).saturating_sub(buffer.offset.x), position.y.saturating_sub(buffer.offset.y)]
+}
+
+pub fn cursor_column(buffer: &Buffer, cursor_index: usize) -> u32 {
+    let position = &buffer.cursors[cursor_index].position;
+
+    if position.y as usize >= rope::line_count(&buffer.text) {
+        return position.x;
+    }
+
+    grapheme::column_of(&rope::line(&buffer.text, position.y as usize), position.x as usize, buffer.indent.tab_width)
+}
+
+fn cursor_offset(text: &rope::Rope, position: &Position) -> usize {
+    rope::line_start(text, position.y as usize) + position.x as usize
+}
+
+fn position_of(text: &rope::Rope, offset: usize) -> Position {
+    let y = rope::line_of_offset(text, offset);
+
+    Position {
+        x: (offset - rope::line_start(text, y)) as u32,
+        y: y as u32,
+    }
+}
+
+// The spaces and tabs a line starts with.
+fn line_indent(text: &rope::Rope, y: usize) -> Vec<u8> {
+    let start = rope::line_start(text, y);
+    let end = rope::line_end(text, y);
+    let mut indent = Vec::new();
+
+    while start + indent.len() < end && matches!(rope::byte_at(text, start + indent.len()), b' ' | b'\t') {
+        indent.push(rope::byte_at(text, start + indent.len()));
+    }
+
+    indent
+}
+
+fn mark_edited(syntax: &mut syntax::Highlighter, text: &rope::Rope, offset: usize, content: &[u8], inserted: bool) {
+    let line = rope::line_of_offset(text, offset);
+    let lines = content.iter().filter(|c| **c == b'\n').count();
+
+    if inserted {
+        syntax::edited(syntax, line, 0, lines);
+    } else {
+        syntax::edited(syntax, line, lines, 0);
+    }
+}
+
+fn insert_text(buffer: &mut Buffer, offset: usize, content: &[u8]) {
+    buffer.modified = true;
+    mark_edited(&mut buffer.syntax, &buffer.text, offset, content, true);
+    rope::insert(&mut buffer.text, offset, content);
+    history::record(&mut buffer.history, history::Change::Insert { offset, content: Vec::from(content) });
+}
+
+fn delete_text(buffer: &mut Buffer, start: usize, end: usize) {
+    if start >= end {
+        return;
+    }
+
+    let content = rope::slice(&buffer.text, start, end);
+    buffer.modified = true;
+    mark_edited(&mut buffer.syntax, &buffer.text, start, &content, false);
+    rope::delete(&mut buffer.text, start, end);
+    history::record(&mut buffer.history, history::Change::Delete { offset: start, content });
+}
@@ -0,0 +1,100 @@
+// Completion of the last word on the command line, with candidates matched
+// fuzzily and shown in a box above it.
+
+// How many candidates the box shows at once.
+pub const ROWS: u32 = 5;
+
+pub struct Completion {
+    // Where in the command the completed word starts.
+    pub start: usize,
+    pub candidates: Vec<Vec<u8>>,
+    pub selected: Option<usize>,
+}
+
+fn is_separator(c: u8) -> bool {
+    matches!(c, b'/' | b'_' | b'-' | b'.' | b' ')
+}
+
+// How well `query` matches `candidate`, or None when its characters don't all
+// appear there in order, ignoring case. Each character takes the first match
+// left, so it's greedy rather than the best alignment. Matches at the start
+// of a word and right after the previous match count up; skipped characters
+// count down.
+pub fn score(query: &[u8], candidate: &[u8]) -> Option<i32> {
+    let mut score = 0;
+    let mut at = 0;
+    let mut previous = None;
+
+    for q in query {
+        let found = at + candidate[at..].iter().position(|c| c.eq_ignore_ascii_case(q))?;
+
+        if found == 0 || is_separator(candidate[found - 1]) {
+            score += 8;
+        }
+
+        if previous == Some(found.wrapping_sub(1)) {
+            score += 5;
+        }
+
+        score -= (found - at) as i32;
+        previous = Some(found);
+        at = found + 1;
+    }
+
+    Some(score)
+}
+
+// The candidates that match, best first, with shorter ones first among equals.
+// Only the part of each candidate after `skip` bytes is matched.
+pub fn rank(query: &[u8], candidates: Vec<Vec<u8>>, skip: usize) -> Vec<Vec<u8>> {
+    let mut scored: Vec<(i32, Vec<u8>)> = candidates.into_iter().filter_map(|candidate| Some((score(query, candidate.get(skip..)?)?, candidate))).collect();
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.len().cmp(&b.1.len())).then(a.1.cmp(&b.1)));
+    scored.dedup_by(|a, b| a.1 == b.1);
+
+    scored.into_iter().map(|(_, candidate)| candidate).collect()
+}
+
+// Paths that can replace `word`: the entries of the directory it names up to
+// its last `/`, ranked against the rest of it. Directories end in `/`, and
+// hidden entries only show once the rest starts with a dot.
+pub fn paths(word: &[u8]) -> Vec<Vec<u8>> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let split = word.iter().rposition(|c| *c == b'/').map_or(0, |i| i + 1);
+    let (directory, name) = word.split_at(split);
+    let path = if directory.is_empty() { std::path::Path::new(".") } else { std::path::Path::new(std::ffi::OsStr::from_bytes(directory)) };
+
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return Vec::new();
+    };
+
+    let mut candidates = Vec::new();
+    for entry in entries.flatten() {
+        let file_name = entry.file_name();
+        if file_name.as_bytes().starts_with(b".") && !name.starts_with(b".") {
+            continue;
+        }
+
+        let mut candidate = directory.to_vec();
+        candidate.extend_from_slice(file_name.as_bytes());
+        if std::fs::metadata(entry.path()).is_ok_and(|metadata| metadata.is_dir()) {
+            candidate.push(b'/');
+        }
+
+        candidates.push(candidate);
+    }
+
+    rank(name, candidates, split)
+}
+
+// Selects the candidate `step` away from the selected one, wrapping around,
+// or the first or last when none is.
+pub fn cycle(completion: &mut Completion, step: isize) {
+    let len = completion.candidates.len() as isize;
+
+    completion.selected = Some(match completion.selected {
+        Some(i) => (i as isize + step).rem_euclid(len) as usize,
+        None if step < 0 => len as usize - 1,
+        None => 0,
+    });
+}
@@ -0,0 +1,279 @@
+// Reading and writing files the way they were found: their encoding, their
+// line endings, whether they end in a newline, their permissions. Buffers
+// always hold UTF-8 with `\n` between lines.
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+    Cr,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    // What a file that isn't UTF-8 is taken to be, since any bytes are valid
+    // Latin-1.
+    Latin1,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Format {
+    pub encoding: Encoding,
+    pub bom: bool,
+    pub line_ending: LineEnding,
+    pub final_newline: bool,
+}
+
+// Enough of a file's metadata to tell whether someone else wrote to it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Stamp {
+    modified: Option<std::time::SystemTime>,
+    len: u64,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    // The file isn't what was read or last written; the stamp is what it is
+    // now.
+    ChangedOnDisk(Option<Stamp>),
+    Io(std::io::Error),
+}
+
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+const UTF16LE_BOM: &[u8] = b"\xff\xfe";
+const UTF16BE_BOM: &[u8] = b"\xfe\xff";
+
+// Names for the command line and the mode line. UTF-16 is written with a
+// byte order mark unless the file came without one.
+pub const ENCODINGS: [(&str, Encoding, bool); 5] = [
+    ("utf-8", Encoding::Utf8, false),
+    ("utf-8-bom", Encoding::Utf8, true),
+    ("utf-16le", Encoding::Utf16Le, true),
+    ("utf-16be", Encoding::Utf16Be, true),
+    ("latin-1", Encoding::Latin1, false),
+];
+
+pub const LINE_ENDINGS: [(&str, LineEnding); 3] = [
+    ("lf", LineEnding::Lf),
+    ("crlf", LineEnding::CrLf),
+    ("cr", LineEnding::Cr),
+];
+
+// New files get UTF-8, Unix line endings and a final newline.
+pub fn default_format() -> Format {
+    Format {
+        encoding: Encoding::Utf8,
+        bom: false,
+        line_ending: LineEnding::Lf,
+        final_newline: true,
+    }
+}
+
+pub fn parse_encoding(name: &[u8]) -> Option<(Encoding, bool)> {
+    ENCODINGS.iter().find(|(n, _, _)| n.as_bytes().eq_ignore_ascii_case(name)).map(|(_, encoding, bom)| (*encoding, *bom))
+}
+
+pub fn parse_line_ending(name: &[u8]) -> Option<LineEnding> {
+    LINE_ENDINGS.iter().find(|(n, _)| n.as_bytes().eq_ignore_ascii_case(name)).map(|(_, line_ending)| *line_ending)
+}
+
+// How the mode line shows the format, like `utf-8 CRLF`.
+pub fn label(format: &Format) -> String {
+    let encoding = match format.encoding {
+        Encoding::Utf8 if format.bom => "utf-8-bom",
+        Encoding::Utf8 => "utf-8",
+        Encoding::Utf16Le => "utf-16le",
+        Encoding::Utf16Be => "utf-16be",
+        Encoding::Latin1 => "latin-1",
+    };
+
+    let line_ending = match format.line_ending {
+        LineEnding::Lf => "LF",
+        LineEnding::CrLf => "CRLF",
+        LineEnding::Cr => "CR",
+    };
+
+    format!("{} {}", encoding, line_ending)
+}
+
+// Splits file content into the text the buffer holds and the format to
+// write it back in. Files with mixed endings go by whichever is most common,
+// and are written back with that one alone.
+pub fn decode(bytes: &[u8]) -> (Vec<u8>, Format) {
+    let (utf8, encoding, bom) = decode_encoding(bytes);
+
+    let mut counts = [0; 3];
+    let mut text = Vec::with_capacity(utf8.len());
+    let mut i = 0;
+
+    while i < utf8.len() {
+        match (utf8[i], utf8.get(i + 1)) {
+            (b'\r', Some(b'\n')) => {
+                counts[1] += 1;
+                text.push(b'\n');
+                i += 1;
+            },
+            (b'\r', _) => {
+                counts[2] += 1;
+                text.push(b'\n');
+            },
+            (b'\n', _) => {
+                counts[0] += 1;
+                text.push(b'\n');
+            },
+            (b, _) => text.push(b),
+        }
+
+        i += 1;
+    }
+
+    let line_ending = match counts {
+        [lf, crlf, cr] if crlf > lf && crlf >= cr => LineEnding::CrLf,
+        [lf, crlf, cr] if cr > lf && cr > crlf => LineEnding::Cr,
+        _ => LineEnding::Lf,
+    };
+
+    let final_newline = text.last() == Some(&b'\n');
+    if final_newline {
+        text.pop();
+    }
+
+    (text, Format { encoding, bom, line_ending, final_newline })
+}
+
+// A byte order mark settles it; otherwise UTF-16 shows as zero bytes in
+// every other place, and anything that isn't valid UTF-8 is Latin-1.
+fn decode_encoding(bytes: &[u8]) -> (Vec<u8>, Encoding, bool) {
+    if let Some(rest) = bytes.strip_prefix(UTF8_BOM) {
+        return (rest.to_vec(), Encoding::Utf8, true);
+    }
+
+    if let Some(rest) = bytes.strip_prefix(UTF16LE_BOM) {
+        return (decode_utf16(rest, u16::from_le_bytes), Encoding::Utf16Le, true);
+    }
+
+    if let Some(rest) = bytes.strip_prefix(UTF16BE_BOM) {
+        return (decode_utf16(rest, u16::from_be_bytes), Encoding::Utf16Be, true);
+    }
+
+    let zeros = |parity: usize| bytes.iter().skip(parity).step_by(2).filter(|b| **b == 0).count();
+    let units = bytes.len() / 2;
+
+    if bytes.len().is_multiple_of(2) && units > 0 {
+        if zeros(0) == 0 && zeros(1) * 2 > units {
+            return (decode_utf16(bytes, u16::from_le_bytes), Encoding::Utf16Le, false);
+        }
+
+        if zeros(1) == 0 && zeros(0) * 2 > units {
+            return (decode_utf16(bytes, u16::from_be_bytes), Encoding::Utf16Be, false);
+        }
+    }
+
+    match std::str::from_utf8(bytes) {
+        Ok(_) => (bytes.to_vec(), Encoding::Utf8, false),
+        Err(_) => (bytes.iter().map(|b| *b as char).collect::<String>().into_bytes(), Encoding::Latin1, false),
+    }
+}
+
+// Unpaired surrogates and an odd last byte become U+FFFD.
+fn decode_utf16(bytes: &[u8], unit: fn([u8; 2]) -> u16) -> Vec<u8> {
+    let units = bytes.chunks(2).map(|pair| if pair.len() == 2 { unit([pair[0], pair[1]]) } else { 0xfffd });
+    char::decode_utf16(units).map(|c| c.unwrap_or('\u{fffd}')).collect::<String>().into_bytes()
+}
+
+// The bytes to write for `text`, or the first character the encoding can't
+// hold.
+pub fn encode(text: &[u8], format: &Format) -> Result<Vec<u8>, char> {
+    let ending: &[u8] = match format.line_ending {
+        LineEnding::Lf => b"\n",
+        LineEnding::CrLf => b"\r\n",
+        LineEnding::Cr => b"\r",
+    };
+
+    let mut utf8 = Vec::with_capacity(text.len() + text.len() / 32);
+    for b in text {
+        if *b == b'\n' {
+            utf8.extend_from_slice(ending);
+        } else {
+            utf8.push(*b);
+        }
+    }
+
+    if format.final_newline {
+        utf8.extend_from_slice(ending);
+    }
+
+    let bom: &[u8] = match format.encoding {
+        _ if !format.bom => b"",
+        Encoding::Utf8 => UTF8_BOM,
+        Encoding::Utf16Le => UTF16LE_BOM,
+        Encoding::Utf16Be => UTF16BE_BOM,
+        Encoding::Latin1 => b"",
+    };
+
+    let mut bytes = bom.to_vec();
+    match format.encoding {
+        Encoding::Utf8 => bytes.extend(utf8),
+        Encoding::Utf16Le => bytes.extend(String::from_utf8_lossy(&utf8).encode_utf16().flat_map(u16::to_le_bytes)),
+        Encoding::Utf16Be => bytes.extend(String::from_utf8_lossy(&utf8).encode_utf16().flat_map(u16::to_be_bytes)),
+        Encoding::Latin1 => {
+            for c in String::from_utf8_lossy(&utf8).chars() {
+                bytes.push(u8::try_from(c as u32).map_err(|_| c)?);
+            }
+        },
+    }
+
+    Ok(bytes)
+}
+
+pub fn stamp(path: &std::path::Path) -> Option<Stamp> {
+    let metadata = std::fs::metadata(path).ok()?;
+
+    Some(Stamp {
+        modified: metadata.modified().ok(),
+        len: metadata.len(),
+    })
+}
+
+// Writes `content` to a temporary file next to `path` and renames it over
+// the original, so a failed save never leaves a truncated file behind. The
+// original's permissions carry over, and a symlink is followed rather than
+// replaced. `expected` is the stamp of the file as last read or written, and
+// the save stops if the file changed since. Returns the new stamp.
+pub fn save(path: &std::path::Path, content: &[u8], expected: Option<Stamp>) -> Result<Option<Stamp>, SaveError> {
+    use std::io::Write;
+
+    let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let current = stamp(&path);
+    if current.is_some() && current != expected {
+        return Err(SaveError::ChangedOnDisk(current));
+    }
+
+    let name = path.file_name().ok_or_else(|| SaveError::Io(std::io::ErrorKind::InvalidInput.into()))?;
+    let mut temporary_name = std::ffi::OsString::from(".");
+    temporary_name.push(name);
+    temporary_name.push(format!(".{}.save", std::process::id()));
+    let temporary = path.with_file_name(temporary_name);
+
+    let written = (|| {
+        let mut file = std::fs::OpenOptions::new().write(true).create_new(true).open(&temporary)?;
+        file.write_all(content)?;
+
+        if let Ok(metadata) = std::fs::metadata(&path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+
+        file.sync_all()?;
+        std::fs::rename(&temporary, &path)
+    })();
+
+    if let Err(error) = written {
+        let _ = std::fs::remove_file(&temporary);
+        return Err(SaveError::Io(error));
+    }
+
+    Ok(stamp(&path))
+}
@@ -0,0 +1,332 @@
+const REPLACEMENT: char = '\u{fffd}';
+const ZERO_WIDTH_JOINER: u32 = 0x200d;
+
+const EXTEND_RANGES: &[(u32, u32)] = &[
+    (0x0300, 0x036f),
+    (0x0483, 0x0489),
+    (0x0591, 0x05bd),
+    (0x05bf, 0x05bf),
+    (0x05c1, 0x05c2),
+    (0x05c4, 0x05c5),
+    (0x05c7, 0x05c7),
+    (0x0610, 0x061a),
+    (0x064b, 0x065f),
+    (0x0670, 0x0670),
+    (0x06d6, 0x06dc),
+    (0x06df, 0x06e4),
+    (0x06e7, 0x06e8),
+    (0x06ea, 0x06ed),
+    (0x0711, 0x0711),
+    (0x0730, 0x074a),
+    (0x0900, 0x0903),
+    (0x093a, 0x094f),
+    (0x0951, 0x0957),
+    (0x0962, 0x0963),
+    (0x0981, 0x0983),
+    (0x09bc, 0x09d7),
+    (0x0e31, 0x0e31),
+    (0x0e34, 0x0e3a),
+    (0x0e47, 0x0e4e),
+    (0x1ab0, 0x1aff),
+    (0x1dc0, 0x1dff),
+    (0x200c, 0x200d),
+    (0x20d0, 0x20ff),
+    (0x302a, 0x302f),
+    (0x3099, 0x309a),
+    (0xfe00, 0xfe0f),
+    (0xfe20, 0xfe2f),
+    (0x1f3fb, 0x1f3ff),
+    (0xe0020, 0xe007f),
+    (0xe0100, 0xe01ef),
+];
+
+// East Asian Wide and Fullwidth, which includes the emoji shown as pictures
+// by default. Terminals and fonts draw these two cells wide.
+const WIDE_RANGES: &[(u32, u32)] = &[
+    (0x1100, 0x115f),
+    (0x231a, 0x231b),
+    (0x2329, 0x232a),
+    (0x23e9, 0x23ec),
+    (0x23f0, 0x23f0),
+    (0x23f3, 0x23f3),
+    (0x25fd, 0x25fe),
+    (0x2614, 0x2615),
+    (0x2648, 0x2653),
+    (0x267f, 0x267f),
+    (0x2693, 0x2693),
+    (0x26a1, 0x26a1),
+    (0x26aa, 0x26ab),
+    (0x26bd, 0x26be),
+    (0x26c4, 0x26c5),
+    (0x26ce, 0x26ce),
+    (0x26d4, 0x26d4),
+    (0x26ea, 0x26ea),
+    (0x26f2, 0x26f3),
+    (0x26f5, 0x26f5),
+    (0x26fa, 0x26fa),
+    (0x26fd, 0x26fd),
+    (0x2705, 0x2705),
+    (0x270a, 0x270b),
+    (0x2728, 0x2728),
+    (0x274c, 0x274c),
+    (0x274e, 0x274e),
+    (0x2753, 0x2755),
+    (0x2757, 0x2757),
+    (0x2795, 0x2797),
+    (0x27b0, 0x27b0),
+    (0x27bf, 0x27bf),
+    (0x2b1b, 0x2b1c),
+    (0x2b50, 0x2b50),
+    (0x2b55, 0x2b55),
+    (0x2e80, 0x303e),
+    (0x3041, 0x33ff),
+    (0x3400, 0x4dbf),
+    (0x4e00, 0x9fff),
+    (0xa000, 0xa4cf),
+    (0xa960, 0xa97f),
+    (0xac00, 0xd7a3),
+    (0xf900, 0xfaff),
+    (0xfe10, 0xfe19),
+    (0xfe30, 0xfe6f),
+    (0xff00, 0xff60),
+    (0xffe0, 0xffe6),
+    (0x16fe0, 0x16fe4),
+    (0x17000, 0x18cff),
+    (0x1b000, 0x1b2ff),
+    (0x1f004, 0x1f004),
+    (0x1f0cf, 0x1f0cf),
+    (0x1f18e, 0x1f18e),
+    (0x1f191, 0x1f19a),
+    (0x1f1e6, 0x1f1ff),
+    (0x1f200, 0x1f251),
+    (0x1f300, 0x1f320),
+    (0x1f32d, 0x1f335),
+    (0x1f337, 0x1f37c),
+    (0x1f37e, 0x1f393),
+    (0x1f3a0, 0x1f3ca),
+    (0x1f3cf, 0x1f3d3),
+    (0x1f3e0, 0x1f3f0),
+    (0x1f3f4, 0x1f3f4),
+    (0x1f3f8, 0x1f43e),
+    (0x1f440, 0x1f440),
+    (0x1f442, 0x1f4fc),
+    (0x1f4ff, 0x1f53d),
+    (0x1f54b, 0x1f54e),
+    (0x1f550, 0x1f567),
+    (0x1f57a, 0x1f57a),
+    (0x1f595, 0x1f596),
+    (0x1f5a4, 0x1f5a4),
+    (0x1f5fb, 0x1f64f),
+    (0x1f680, 0x1f6c5),
+    (0x1f6cc, 0x1f6cc),
+    (0x1f6d0, 0x1f6d2),
+    (0x1f6d5, 0x1f6d7),
+    (0x1f6dc, 0x1f6df),
+    (0x1f6eb, 0x1f6ec),
+    (0x1f6f4, 0x1f6fc),
+    (0x1f7e0, 0x1f7eb),
+    (0x1f7f0, 0x1f7f0),
+    (0x1f90c, 0x1f93a),
+    (0x1f93c, 0x1f945),
+    (0x1f947, 0x1f9ff),
+    (0x1fa70, 0x1faff),
+    (0x20000, 0x2fffd),
+    (0x30000, 0x3fffd),
+];
+
+fn is_extend(c: u32) -> bool {
+    EXTEND_RANGES.iter().any(|(start, end)| c >= *start && c <= *end)
+}
+
+fn is_regional_indicator(c: u32) -> bool {
+    (0x1f1e6..=0x1f1ff).contains(&c)
+}
+
+pub fn decode(bytes: &[u8], at: usize) -> (char, usize) {
+    let first = bytes[at];
+
+    let (len, initial) = if first < 0x80 {
+        return (first as char, 1);
+    } else if first & 0xe0 == 0xc0 {
+        (2, (first & 0x1f) as u32)
+    } else if first & 0xf0 == 0xe0 {
+        (3, (first & 0x0f) as u32)
+    } else if first & 0xf8 == 0xf0 {
+        (4, (first & 0x07) as u32)
+    } else {
+        return (REPLACEMENT, 1);
+    };
+
+    if at + len > bytes.len() {
+        return (REPLACEMENT, 1);
+    }
+
+    let mut code_point = initial;
+    for byte in bytes[at + 1..at + len].iter() {
+        if byte & 0xc0 != 0x80 {
+            return (REPLACEMENT, 1);
+        }
+
+        code_point = (code_point << 6) | (byte & 0x3f) as u32;
+    }
+
+    match char::from_u32(code_point) {
+        Some(c) => (c, len),
+        None => (REPLACEMENT, 1),
+    }
+}
+
+pub fn next_boundary(line: &[u8], at: usize) -> usize {
+    if at >= line.len() {
+        return line.len();
+    }
+
+    let (first, len) = decode(line, at);
+    let mut end = at + len;
+    let mut previous = first as u32;
+    let mut regional_count = if is_regional_indicator(previous) { 1 } else { 0 };
+
+    while end < line.len() {
+        let (c, len) = decode(line, end);
+        let c = c as u32;
+
+        let joins = is_extend(c) || previous == ZERO_WIDTH_JOINER || (is_regional_indicator(c) && regional_count % 2 == 1);
+
+        if !joins {
+            break;
+        }
+
+        if is_regional_indicator(c) {
+            regional_count += 1;
+        }
+
+        previous = c;
+        end += len;
+    }
+
+    end
+}
+
+pub fn prev_boundary(line: &[u8], at: usize) -> usize {
+    let mut start = 0;
+    let mut boundary = 0;
+
+    while start < at {
+        boundary = start;
+        start = next_boundary(line, start);
+    }
+
+    boundary
+}
+
+// How many cells a character is drawn across.
+pub fn char_width(c: char) -> u32 {
+    let c = c as u32;
+
+    if WIDE_RANGES.iter().any(|(start, end)| c >= *start && c <= *end) {
+        2
+    } else {
+        1
+    }
+}
+
+// How many columns the cluster at `at` takes when it starts at `column`: a
+// tab reaches the next multiple of `tab_width`, anything else is as wide as
+// its first character.
+pub fn width_at(line: &[u8], at: usize, column: u32, tab_width: u32) -> u32 {
+    if line[at] == b'\t' {
+        tab_width - column % tab_width
+    } else {
+        char_width(decode(line, at).0)
+    }
+}
+
+pub fn column_of(line: &[u8], at: usize, tab_width: u32) -> u32 {
+    let mut column = 0;
+    let mut start = 0;
+
+    while start < at && start < line.len() {
+        column += width_at(line, start, column, tab_width);
+        start = next_boundary(line, start);
+    }
+
+    column
+}
+
+// The cluster at `column`, or the one covering it when a tab or a wide
+// character spans it.
+pub fn offset_of(line: &[u8], column: u32, tab_width: u32) -> usize {
+    let mut start = 0;
+    let mut current = 0;
+
+    while start < line.len() {
+        current += width_at(line, start, current, tab_width);
+        if current > column {
+            break;
+        }
+
+        start = next_boundary(line, start);
+    }
+
+    start
+}
+
+pub fn chars(cluster: &[u8]) -> Vec<char> {
+    let mut chars = Vec::with_capacity(cluster.len());
+    let mut i = 0;
+
+    while i < cluster.len() {
+        let (c, len) = decode(cluster, i);
+        chars.push(c);
+        i += len;
+    }
+
+    chars
+}
+
+fn class(line: &[u8], at: usize) -> u8 {
+    let (c, _) = decode(line, at);
+
+    if c.is_alphanumeric() || c == '_' {
+        0
+    } else if c.is_whitespace() {
+        1
+    } else {
+        2
+    }
+}
+
+// The run of clusters around `at` that are all word characters, all
+// whitespace or all punctuation, as used by double click selection.
+pub fn word_bounds(line: &[u8], at: usize) -> [usize; 2] {
+    if line.is_empty() {
+        return [0, 0];
+    }
+
+    let at = if at >= line.len() { prev_boundary(line, line.len()) } else { at };
+    let target = class(line, at);
+    let mut start = 0;
+    let mut bounds = [0, 0];
+    let mut inside = false;
+
+    while start < line.len() {
+        let end = next_boundary(line, start);
+
+        if class(line, start) == target {
+            if !inside {
+                bounds[0] = start;
+                inside = true;
+            }
+
+            bounds[1] = end;
+        } else if start > at {
+            break;
+        } else {
+            inside = false;
+        }
+
+        start = end;
+    }
+
+    bounds
+}
@@ -0,0 +1,187 @@
+use super::buffer::Cursor;
+use super::rope;
+
+const MAX_TYPING_GROUP: usize = 20;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum EditKind {
+    Typing,
+    Other,
+}
+
+pub enum Change {
+    Insert { offset: usize, content: Vec<u8> },
+    Delete { offset: usize, content: Vec<u8> },
+}
+
+pub struct Step {
+    kind: EditKind,
+    changes: Vec<Change>,
+    cursors_before: Vec<Cursor>,
+    cursors_after: Vec<Cursor>,
+    typed: usize,
+}
+
+pub struct History {
+    undo_stack: Vec<Step>,
+    redo_stack: Vec<Step>,
+    current: Option<Step>,
+    current_start: usize,
+    sealed: bool,
+    // Undo stack depth where the text matches the file, None once edits have
+    // made that text unreachable.
+    saved: Option<usize>,
+}
+
+pub fn empty() -> History {
+    History {
+        undo_stack: Vec::new(),
+        redo_stack: Vec::new(),
+        current: None,
+        current_start: 0,
+        sealed: true,
+        saved: Some(0),
+    }
+}
+
+fn same_positions(a: &[Cursor], b: &[Cursor]) -> bool {
+    a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| a.position == b.position)
+}
+
+pub fn begin(history: &mut History, kind: EditKind, cursors: &[Cursor]) {
+    if history.current.is_some() {
+        return;
+    }
+
+    let can_merge = match history.undo_stack.last() {
+        Some(step) => !history.sealed && kind == EditKind::Typing && step.kind == EditKind::Typing && step.typed < MAX_TYPING_GROUP && same_positions(&step.cursors_after, cursors),
+        None => false,
+    };
+
+    history.current = if can_merge {
+        let step = history.undo_stack.pop();
+        history.current_start = step.as_ref().map(|s| s.changes.len()).unwrap_or(0);
+        step
+    } else {
+        history.current_start = 0;
+
+        Some(Step {
+            kind,
+            changes: Vec::new(),
+            cursors_before: cursors.to_vec(),
+            cursors_after: Vec::new(),
+            typed: 0,
+        })
+    };
+}
+
+pub fn record(history: &mut History, change: Change) {
+    if let Some(step) = history.current.as_mut() {
+        step.changes.push(change);
+    }
+}
+
+pub fn commit(history: &mut History, cursors: &[Cursor]) {
+    let mut step = if let Some(step) = history.current.take() {
+        step
+    } else {
+        return;
+    };
+
+    if step.changes.len() == history.current_start {
+        if history.current_start > 0 {
+            history.undo_stack.push(step);
+        }
+
+        return;
+    }
+
+    if step.kind == EditKind::Typing {
+        step.typed += 1;
+    }
+
+    // The saved text was among the steps being dropped from the redo stack.
+    if history.saved.is_some_and(|saved| saved > history.undo_stack.len()) {
+        history.saved = None;
+    }
+
+    step.cursors_after = cursors.to_vec();
+    history.undo_stack.push(step);
+    history.redo_stack.clear();
+    history.sealed = false;
+}
+
+pub fn seal(history: &mut History) {
+    history.sealed = true;
+}
+
+// Sealed so that typing after a save starts a step of its own.
+pub fn mark_saved(history: &mut History) {
+    history.saved = Some(history.undo_stack.len());
+    history.sealed = true;
+}
+
+// For changes the steps don't hold, like the file's encoding.
+pub fn forget_saved(history: &mut History) {
+    history.saved = None;
+}
+
+pub fn is_saved(history: &History) -> bool {
+    history.saved == Some(history.undo_stack.len())
+}
+
+pub fn undo(text: &mut rope::Rope, history: &mut History) -> Option<Vec<Cursor>> {
+    undo_with(text, history, |_, _, _, _| {})
+}
+
+pub fn redo(text: &mut rope::Rope, history: &mut History) -> Option<Vec<Cursor>> {
+    redo_with(text, history, |_, _, _, _| {})
+}
+
+// `edited` sees the text before each change is applied, with the offset,
+// the bytes involved and whether they are being inserted.
+pub fn undo_with(text: &mut rope::Rope, history: &mut History, mut edited: impl FnMut(&rope::Rope, usize, &[u8], bool)) -> Option<Vec<Cursor>> {
+    let step = history.undo_stack.pop()?;
+
+    for change in step.changes.iter().rev() {
+        match change {
+            Change::Insert { offset, content } => {
+                edited(text, *offset, content, false);
+                rope::delete(text, *offset, offset + content.len());
+            },
+            Change::Delete { offset, content } => {
+                edited(text, *offset, content, true);
+                rope::insert(text, *offset, content);
+            },
+        }
+    }
+
+    let cursors = step.cursors_before.clone();
+    history.redo_stack.push(step);
+    history.sealed = true;
+
+    Some(cursors)
+}
+
+pub fn redo_with(text: &mut rope::Rope, history: &mut History, mut edited: impl FnMut(&rope::Rope, usize, &[u8], bool)) -> Option<Vec<Cursor>> {
+    let step = history.redo_stack.pop()?;
+
+    for change in step.changes.iter() {
+        match change {
+            Change::Insert { offset, content } => {
+                edited(text, *offset, content, true);
+                rope::insert(text, *offset, content);
+            },
+            Change::Delete { offset, content } => {
+                edited(text, *offset, content, false);
+                rope::delete(text, *offset, offset + content.len());
+            },
+        }
+    }
+
+    let cursors = step.cursors_after.clone();
+    history.undo_stack.push(step);
+    history.sealed = true;
+
+    Some(cursors)
+}
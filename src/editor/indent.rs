@@ -0,0 +1,99 @@
+use super::grapheme;
+use super::syntax::{self, Kind, Language};
+
+// What opens and closes an indentation level in a language. Python-like
+// languages also open one with a line ending in `:`. `quotes` start strings
+// in languages the highlighter has no spans for.
+pub struct Rules {
+    pub open: &'static [u8],
+    pub close: &'static [u8],
+    pub colon: bool,
+    pub line_comment: &'static [u8],
+    pub quotes: &'static [u8],
+}
+
+// Languages without rules keep the indentation of the line above.
+pub fn rules(language: Language) -> Option<Rules> {
+    match language {
+        Language::Rust | Language::C => Some(Rules { open: b"{([", close: b"})]", colon: false, line_comment: b"//", quotes: b"" }),
+        Language::Python => Some(Rules { open: b"{([", close: b"})]", colon: true, line_comment: b"#", quotes: b"\"'" }),
+        Language::Plain | Language::Markdown => None,
+    }
+}
+
+// Whether the lines after `line` go one level deeper: it leaves a bracket
+// open, or ends in a colon. Brackets closed before any opens, as in `} else {`,
+// don't cancel the ones after them, and strings and comments don't count.
+pub fn opens(rules: &Rules, line: &[u8], spans: &[syntax::Span]) -> bool {
+    let mut depth = 0u32;
+    let mut last = None;
+    let mut quote = None;
+    let mut escaped = false;
+
+    for (i, b) in line.iter().enumerate() {
+        match syntax::kind_at(spans, i) {
+            Kind::String => continue,
+            Kind::Comment => break,
+            _ => {},
+        }
+
+        if let Some(open) = quote {
+            if escaped {
+                escaped = false;
+            } else if *b == b'\\' {
+                escaped = true;
+            } else if *b == open {
+                quote = None;
+                last = Some(*b);
+            }
+
+            continue;
+        }
+
+        if rules.quotes.contains(b) {
+            quote = Some(*b);
+            last = Some(*b);
+            continue;
+        }
+
+        if line[i..].starts_with(rules.line_comment) {
+            break;
+        }
+
+        if rules.open.contains(b) {
+            depth += 1;
+        } else if rules.close.contains(b) {
+            depth = depth.saturating_sub(1);
+        }
+
+        if !b.is_ascii_whitespace() {
+            last = Some(*b);
+        }
+    }
+
+    depth > 0 || (rules.colon && last == Some(b':'))
+}
+
+// Whether `line` starts by closing a level, and so sits one level out.
+pub fn closes(rules: &Rules, line: &[u8]) -> bool {
+    line.iter().find(|b| !matches!(b, b' ' | b'\t')).is_some_and(|b| rules.close.contains(b))
+}
+
+pub fn columns(indent: &[u8], tab_width: u32) -> u32 {
+    grapheme::column_of(indent, indent.len(), tab_width)
+}
+
+// Indentation reaching `columns`, in tabs as far as they go when `tabs` is
+// set and in spaces otherwise.
+pub fn make(columns: u32, tab_width: u32, tabs: bool) -> Vec<u8> {
+    let mut indent = Vec::new();
+
+    if tabs {
+        indent.resize((columns / tab_width) as usize, b'\t');
+        indent.resize(indent.len() + (columns % tab_width) as usize, b' ');
+    } else {
+        indent.resize(columns as usize, b' ');
+    }
+
+    indent
+}
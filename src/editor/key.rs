@@ -0,0 +1,55 @@
+// Keys that don't type text. Keys that do arrive as characters instead.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Key {
+    Escape,
+    Enter,
+    Backspace,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Insert,
+    Delete,
+    Function(u8),
+}
+
+pub fn is_motion(key: Key) -> bool {
+    matches!(key, Key::Up | Key::Down | Key::Left | Key::Right | Key::Home | Key::End | Key::PageUp | Key::PageDown)
+}
+
+pub fn name(key: Key) -> String {
+    match key {
+        Key::Escape => String::from("<escape>"),
+        Key::Enter => String::from("<return>"),
+        Key::Backspace => String::from("<backspace>"),
+        Key::Up => String::from("<up>"),
+        Key::Down => String::from("<down>"),
+        Key::Left => String::from("<left>"),
+        Key::Right => String::from("<right>"),
+        Key::Home => String::from("<home>"),
+        Key::End => String::from("<end>"),
+        Key::PageUp => String::from("<prior>"),
+        Key::PageDown => String::from("<next>"),
+        Key::Insert => String::from("<insert>"),
+        Key::Delete => String::from("<delete>"),
+        Key::Function(n) => format!("<f{}>", n),
+    }
+}
+
+pub fn from_name(name: &str) -> Option<Key> {
+    let keys = [
+        Key::Escape, Key::Enter, Key::Backspace, Key::Up, Key::Down, Key::Left, Key::Right,
+        Key::Home, Key::End, Key::PageUp, Key::PageDown, Key::Insert, Key::Delete,
+    ];
+
+    if let Some(k) = keys.into_iter().find(|k| self::name(*k) == name) {
+        return Some(k);
+    }
+
+    let n: u8 = name.strip_prefix("<f")?.strip_suffix('>')?.parse().ok()?;
+    Some(Key::Function(n)).filter(|_| (1..=12).contains(&n))
+}
@@ -0,0 +1,49 @@
+const MAX_ENTRIES: usize = 60;
+
+pub struct KillRing {
+    entries: Vec<Vec<u8>>,
+    index: usize,
+}
+
+pub fn empty() -> KillRing {
+    KillRing {
+        entries: Vec::new(),
+        index: 0,
+    }
+}
+
+pub fn push(ring: &mut KillRing, content: Vec<u8>) {
+    if ring.entries.len() >= MAX_ENTRIES {
+        ring.entries.remove(0);
+    }
+
+    ring.entries.push(content);
+    ring.index = ring.entries.len() - 1;
+}
+
+pub fn append(ring: &mut KillRing, content: &[u8]) {
+    if let Some(last) = ring.entries.last_mut() {
+        last.extend_from_slice(content);
+        ring.index = ring.entries.len() - 1;
+    } else {
+        push(ring, Vec::from(content));
+    }
+}
+
+pub fn current(ring: &KillRing) -> Option<&[u8]> {
+    ring.entries.get(ring.index).map(|entry| entry.as_slice())
+}
+
+pub fn rotate(ring: &mut KillRing) -> Option<&[u8]> {
+    if ring.entries.is_empty() {
+        return None;
+    }
+
+    ring.index = if ring.index == 0 {
+        ring.entries.len() - 1
+    } else {
+        ring.index - 1
+    };
+
+    current(ring)
+}
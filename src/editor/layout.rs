@@ -0,0 +1,133 @@
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Direction {
+    Horizontal,
+    Vertical,
+}
+
+enum Node {
+    Leaf(usize),
+    Split(Direction, Box<Node>, Box<Node>),
+}
+
+pub struct Layout {
+    root: Node,
+    pub focus: usize,
+    count: usize,
+}
+
+pub const MIN_WIDTH: u32 = 4;
+pub const MIN_HEIGHT: u32 = 2;
+
+pub fn single() -> Layout {
+    Layout {
+        root: Node::Leaf(0),
+        focus: 0,
+        count: 1,
+    }
+}
+
+fn split_leaf(node: &mut Node, target: usize, direction: Direction, new: usize) -> bool {
+    match node {
+        Node::Leaf(id) if *id == target => {
+            *node = Node::Split(direction, Box::new(Node::Leaf(target)), Box::new(Node::Leaf(new)));
+            true
+        },
+        Node::Leaf(_) => false,
+        Node::Split(_, first, second) => split_leaf(first, target, direction, new) || split_leaf(second, target, direction, new),
+    }
+}
+
+// Splits the focused window in two. The focus stays on the original window,
+// which keeps the top or left half; the new window's id is returned.
+pub fn split(layout: &mut Layout, direction: Direction) -> usize {
+    let new = layout.count;
+    split_leaf(&mut layout.root, layout.focus, direction, new);
+    layout.count += 1;
+
+    new
+}
+
+fn collect_leaves(node: &Node, leaves: &mut Vec<usize>) {
+    match node {
+        Node::Leaf(id) => leaves.push(*id),
+        Node::Split(_, first, second) => {
+            collect_leaves(first, leaves);
+            collect_leaves(second, leaves);
+        },
+    }
+}
+
+pub fn leaves(layout: &Layout) -> Vec<usize> {
+    let mut leaves = Vec::new();
+    collect_leaves(&layout.root, &mut leaves);
+
+    leaves
+}
+
+pub fn next_focus(layout: &mut Layout) -> usize {
+    let leaves = leaves(layout);
+    let i = leaves.iter().position(|id| *id == layout.focus).unwrap_or(0);
+    layout.focus = leaves[(i + 1) % leaves.len()];
+
+    layout.focus
+}
+
+fn halves(direction: Direction, rect: Rect) -> (Rect, Rect, Option<Rect>) {
+    match direction {
+        Direction::Horizontal => {
+            let top = rect.height.div_ceil(2);
+            (
+                Rect { height: top, ..rect },
+                Rect { y: rect.y + top, height: rect.height - top, ..rect },
+                None,
+            )
+        },
+        Direction::Vertical => {
+            let left = rect.width.saturating_sub(1) / 2;
+            (
+                Rect { width: left, ..rect },
+                Rect { x: rect.x + left + 1, width: rect.width.saturating_sub(left + 1), ..rect },
+                Some(Rect { x: rect.x + left, width: 1, ..rect }),
+            )
+        },
+    }
+}
+
+fn place(node: &Node, rect: Rect, windows: &mut Vec<(usize, Rect)>, separators: &mut Vec<Rect>) {
+    match node {
+        Node::Leaf(id) => windows.push((*id, rect)),
+        Node::Split(direction, first, second) => {
+            let (a, b, separator) = halves(*direction, rect);
+            separators.extend(separator);
+            place(first, a, windows, separators);
+            place(second, b, windows, separators);
+        },
+    }
+}
+
+// Lays the tree out over a grid of `size` cells. Every window rect includes
+// its mode line as the last row; vertical splits leave a one column separator.
+pub fn arrange(layout: &Layout, size: [u32; 2]) -> (Vec<(usize, Rect)>, Vec<Rect>) {
+    let mut windows = Vec::new();
+    let mut separators = Vec::new();
+    let rect = Rect { x: 0, y: 0, width: size[0], height: size[1] };
+
+    place(&layout.root, rect, &mut windows, &mut separators);
+
+    (windows, separators)
+}
+
+pub fn can_split(rect: Rect, direction: Direction) -> bool {
+    match direction {
+        Direction::Horizontal => rect.height >= 2 * MIN_HEIGHT,
+        Direction::Vertical => rect.width > 2 * MIN_WIDTH,
+    }
+}
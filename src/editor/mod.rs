@@ -0,0 +1,1749 @@
+use key::Key;
+use bindings::{Chord, Lookup, Press};
+pub mod buffer;
+pub mod rope;
+pub mod history;
+pub mod grapheme;
+pub mod kill_ring;
+pub mod search;
+pub mod regex;
+pub mod replace;
+pub mod syntax;
+pub mod layout;
+pub mod key;
+pub mod bindings;
+pub mod file;
+pub mod indent;
+pub mod complete;
+
+// Selections shared with other programs, as the regular clipboard and the
+// X11-style primary selection that follows the mouse.
+pub const CLIPBOARD: usize = 0;
+pub const PRIMARY: usize = 1;
+
+pub struct Editor {
+    pub changed: bool,
+    pub buffers: Vec<buffer::Buffer>,
+    pub main_buffer_index: u32,
+    pub windows: Vec<buffer::Window>,
+    pub separators: Vec<layout::Rect>,
+    layout: layout::Layout,
+
+    completion: Option<complete::Completion>,
+
+    command_mode: bool,
+    command: Vec<u8>,
+
+    kill_ring: kill_ring::KillRing,
+    isearch: Option<search::Isearch>,
+    query_replace: Option<replace::QueryReplace>,
+    last_search: Vec<u8>,
+    sequence: Sequence,
+    last_sequence: Sequence,
+
+    chars_per_row: u32,
+    chars_per_coloumn: u32,
+    columns: u32,
+    rows: u32,
+
+    last_inserted_char: char,
+    last_function: Option<fn(&mut Editor)>,
+    shift_held: bool,
+    shift_selection: bool,
+    dragging: bool,
+    primary: Vec<u8>,
+    bindings: bindings::Config,
+    pending_keys: Vec<bindings::Chord>,
+
+    pub requests: Vec<Request>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Sequence {
+    None,
+    Kill,
+    Yank(usize),
+    Complete,
+    Quit,
+}
+
+// A key going down. Keys that type text arrive as the character they type,
+// Shift and the keyboard layout already applied; `shift` still says whether
+// Shift is held, since motions select with it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct KeyEvent {
+    pub press: Press,
+    pub control: bool,
+    pub alt: bool,
+    pub shift: bool,
+}
+
+// Everything a frontend reports to the editor. Pointer positions are cells
+// of the whole text area, counted from its top left corner.
+#[derive(Clone, PartialEq, Debug)]
+pub enum InputEvent {
+    Key(KeyEvent),
+    // A key went up, or one went down that means nothing to the editor.
+    // Either way the last command stops repeating.
+    KeyRelease,
+    Resize { columns: u32, rows: u32 },
+    // The left button went down, `clicks` times in a row on the same cell.
+    Press { cell: [u32; 2], clicks: u32 },
+    // The pointer moved while the left button is down.
+    Drag { cell: [u32; 2] },
+    Release,
+    Scroll { cell: [u32; 2], lines: [i32; 2] },
+    // The middle button, which pastes the primary selection.
+    Paste { cell: [u32; 2] },
+    // Answers `Request::ReadSelection`, with None when the selection is still
+    // the one the editor set last.
+    Selection { selection: usize, content: Option<Vec<u8>> },
+    // The window manager asks to close the window, which quits the way the
+    // quit command does.
+    Close,
+}
+
+// What the editor needs from the frontend, queued in `Editor::requests`.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Request {
+    SetSelection(usize, Vec<u8>),
+    ReadSelection(usize),
+    // Close the editor.
+    Quit,
+}
+
+pub fn new(buffer: buffer::Buffer, columns: u32, rows: u32) -> Editor {
+    let layout = layout::single();
+    let window = buffer::window(&buffer, 0, layout::Rect { x: 0, y: 0, width: columns, height: rows });
+
+    let mut editor = Editor {
+        changed: false,
+        buffers: vec![buffer],
+        main_buffer_index: 0,
+        windows: vec![window],
+        separators: Vec::new(),
+        layout,
+        completion: None,
+        command_mode: false,
+        command: Vec::new(),
+        kill_ring: kill_ring::empty(),
+        isearch: None,
+        query_replace: None,
+        last_search: Vec::new(),
+        sequence: Sequence::None,
+        last_sequence: Sequence::None,
+        chars_per_row: columns,
+        chars_per_coloumn: rows - 1,
+        columns,
+        rows,
+        last_inserted_char: ' ',
+        last_function: None,
+        shift_held: false,
+        shift_selection: false,
+        dragging: false,
+        primary: Vec::new(),
+        bindings: bindings::new(),
+        pending_keys: Vec::new(),
+        requests: Vec::new(),
+    };
+
+    let errors = bindings::parse(&mut editor.bindings, DEFAULT_BINDINGS, &command_names());
+    debug_assert!(errors.is_empty());
+
+    editor
+}
+
+pub fn handle_event(editor: &mut Editor, event: InputEvent) {
+    match event {
+        InputEvent::Key(event) => key(editor, event),
+        InputEvent::KeyRelease => editor.last_function = None,
+        InputEvent::Resize { columns, rows } => {
+            editor.columns = columns;
+            editor.rows = rows;
+            relayout(editor);
+        },
+        InputEvent::Press { cell, clicks } => pointer_press(editor, cell, clicks),
+        InputEvent::Drag { cell } => pointer_drag(editor, cell),
+        InputEvent::Release => pointer_release(editor),
+        InputEvent::Scroll { cell, lines } => pointer_scroll(editor, cell, lines),
+        InputEvent::Paste { cell } => paste_primary(editor, cell),
+        InputEvent::Selection { selection, content } => receive_selection(editor, selection, content),
+        InputEvent::Close => run(editor, quit),
+    }
+}
+
+// Whether a held key has a command to repeat.
+pub fn repeating(editor: &Editor) -> bool {
+    editor.last_function.is_some()
+}
+
+// Runs the command of the held key once more, at the frontend's repeat rate.
+pub fn repeat(editor: &mut Editor) {
+    if let Some(f) = editor.last_function {
+        run(editor, f);
+    }
+}
+
+fn run(editor: &mut Editor, f: fn(&mut Editor)) {
+    editor.last_sequence = std::mem::replace(&mut editor.sequence, Sequence::None);
+    f(editor);
+
+    // The completion box only stays up while it's being stepped through.
+    if editor.sequence != Sequence::Complete && editor.completion.take().is_some() {
+        buffer::update_chars(editor);
+        editor.changed = true;
+    }
+}
+
+fn page_down(editor: &mut Editor) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    let position = &mut buffer.cursors[buffer.main_cursor_index as usize].position;
+    let len = rope::line_count(&buffer.text) as u32;
+    let last_page = len.saturating_sub(editor.chars_per_coloumn);
+
+    // A page down keeps one line of the old page in view, and stops at the
+    // last page; once there it goes to the last line.
+    if buffer.offset.y >= last_page {
+        position.y = len - 1;
+    } else {
+        buffer.offset.y = std::cmp::min(buffer.offset.y + editor.chars_per_coloumn.saturating_sub(1), last_page);
+    }
+
+    if position.y < buffer.offset.y {
+        position.y = buffer.offset.y;
+    }
+
+    position.x = 0;
+    buffer::update_mode_line_right(editor);
+    buffer::update_chars(editor);
+    editor.changed = true;
+}
+
+fn page_up(editor: &mut Editor) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    let position = &mut buffer.cursors[buffer.main_cursor_index as usize].position;
+
+    if buffer.offset.y == 0 {
+        position.y = 0;
+    } else {
+        buffer.offset.y = buffer.offset.y.saturating_sub(editor.chars_per_coloumn.saturating_sub(1));
+    }
+
+    if position.y + 1 > buffer.offset.y + editor.chars_per_coloumn {
+        position.y = (buffer.offset.y + editor.chars_per_coloumn).saturating_sub(1);
+    }
+
+    position.x = 0;
+    buffer::update_mode_line_right(editor);
+    buffer::update_chars(editor);
+    editor.changed = true;
+}
+
+fn prev_line(editor: &mut Editor) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    for i in 0..buffer.cursors.len() {
+        buffer::prev_line(editor, i);
+    }
+
+    buffer::update_mode_line_right(editor);
+    buffer::check_offset(editor);
+    buffer::update_chars(editor);
+
+    editor.changed = true;
+}
+
+fn next_line(editor: &mut Editor) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    for i in 0..buffer.cursors.len() {
+        buffer::next_line(editor, i);
+    }
+
+    buffer::update_mode_line_right(editor);
+    buffer::check_offset(editor);
+    buffer::update_chars(editor);
+
+    editor.changed = true;
+}
+
+fn prev_char(editor: &mut Editor) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    for i in 0..buffer.cursors.len() {
+        buffer::prev_char(editor, i);
+    }
+
+    buffer::update_mode_line_right(editor);
+    buffer::check_offset(editor);
+    buffer::update_chars(editor);
+
+    editor.changed = true;
+}
+
+fn next_char(editor: &mut Editor) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    for i in 0..buffer.cursors.len() {
+        buffer::next_char(editor, i);
+    }
+
+    buffer::update_mode_line_right(editor);
+    buffer::check_offset(editor);
+    buffer::update_chars(editor);
+
+    editor.changed = true;
+}
+
+fn end_of_line(editor: &mut Editor) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    for i in 0..buffer.cursors.len() {
+        buffer::end_of_line(editor, i);
+    }
+
+    buffer::update_mode_line_right(editor);
+    buffer::check_offset(editor);
+    buffer::update_chars(editor);
+
+    editor.changed = true;
+}
+
+fn start_of_line(editor: &mut Editor) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    for i in 0..buffer.cursors.len() {
+        buffer::start_of_line(editor, i);
+    }
+
+    buffer::update_mode_line_right(editor);
+    buffer::check_offset(editor);
+    buffer::update_chars(editor);
+
+    editor.changed = true;
+}
+
+fn delete_char_at(editor: &mut Editor) {
+    buffer::begin_edit(editor, history::EditKind::Other);
+
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    for i in 0..buffer.cursors.len() {
+        buffer::delete_char_at(editor, i);
+    }
+
+    buffer::end_edit(editor);
+
+    buffer::check_offset(editor);
+    buffer::update_chars(editor);
+
+    editor.changed = true;
+}
+
+fn delete_to_line_end(editor: &mut Editor) {
+    buffer::begin_edit(editor, history::EditKind::Other);
+
+    let mut killed = Vec::new();
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    for i in 0..buffer.cursors.len() {
+        killed.extend(buffer::delete_to_line_end(editor, i));
+    }
+
+    buffer::end_edit(editor);
+    kill(editor, killed);
+
+    buffer::check_offset(editor);
+    buffer::update_chars(editor);
+
+    editor.changed = true;
+}
+
+fn insert_char_at_current_position(editor: &mut Editor) {
+    buffer::begin_edit(editor, history::EditKind::Typing);
+
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    for i in 0..buffer.cursors.len() {
+        buffer::insert_char_at(editor, i);
+    }
+
+    buffer::end_edit(editor);
+
+    buffer::update_mode_line_right(editor);
+    buffer::check_offset(editor);
+    buffer::update_chars(editor);
+
+    editor.changed = true;
+}
+
+fn delete_to_command_end(editor: &mut Editor) {
+    if editor.buffers[editor.main_buffer_index as usize].cursors[1].position.x < editor.command.len() as u32 + 1 {
+        editor.command.drain(editor.buffers[editor.main_buffer_index as usize].cursors[1].position.x as usize - 1..);
+        buffer::update_chars(editor);
+        editor.changed = true;
+    }
+}
+
+fn insert_new_line(editor: &mut Editor) {
+    buffer::begin_edit(editor, history::EditKind::Other);
+
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    for i in 0..buffer.cursors.len() {
+        buffer::insert_new_line(editor, i);
+    }
+
+    buffer::end_edit(editor);
+
+    buffer::update_mode_line_right(editor);
+    buffer::check_offset(editor);
+    buffer::update_chars(editor);
+
+    editor.changed = true;
+}
+
+fn indent_region(editor: &mut Editor) {
+    buffer::begin_edit(editor, history::EditKind::Other);
+
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    for i in 0..buffer.cursors.len() {
+        buffer::indent_region(editor, i);
+    }
+
+    buffer::end_edit(editor);
+
+    buffer::update_mode_line_right(editor);
+    buffer::check_offset(editor);
+    buffer::update_chars(editor);
+
+    editor.changed = true;
+}
+
+fn delete_prev_char(editor: &mut Editor) {
+    buffer::begin_edit(editor, history::EditKind::Other);
+
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    for i in 0..buffer.cursors.len() {
+        buffer::delete_prev_char(editor, i);
+    }
+
+    buffer::end_edit(editor);
+
+    buffer::update_mode_line_right(editor);
+    buffer::check_offset(editor);
+    buffer::update_chars(editor);
+
+    editor.changed = true;
+}
+
+fn insert_command_char(editor: &mut Editor) {
+    let c = editor.last_inserted_char;
+    let mut bytes = [0; 4];
+    let encoded = c.encode_utf8(&mut bytes).as_bytes();
+    let at = editor.buffers[editor.main_buffer_index as usize].cursors[1].position.x as usize - 1;
+
+    editor.command.splice(at..at, encoded.iter().copied());
+    editor.buffers[editor.main_buffer_index as usize].cursors[1].position.x += encoded.len() as u32;
+    buffer::update_chars(editor);
+
+    editor.changed = true;
+}
+
+// The word at the end of the command line and what could replace it: the
+// command name itself, or an argument to the commands whose arguments are
+// known.
+fn completions(editor: &Editor) -> (usize, Vec<Vec<u8>>) {
+    let command = &editor.command;
+    let Some(space) = command.iter().position(|c| *c == b' ') else {
+        let names = buffer::LINE_COMMANDS.iter().copied().chain(command_names().into_iter().filter(|name| !name.starts_with("command-")));
+        return (0, complete::rank(command, names.map(|name| name.as_bytes().to_vec()).collect(), 0));
+    };
+
+    let start = space + command[space..].iter().take_while(|c| **c == b' ').count();
+    let word = &command[start..];
+
+    let candidates: Vec<Vec<u8>> = match &command[..space] {
+        b"e" | b"w" => return (start, complete::paths(word)),
+        b"b" | b"k" | b"k!" => editor.buffers.iter().map(|buffer| buffer::buffer_name(buffer).to_vec()).collect(),
+        b"encoding" => file::ENCODINGS.iter().map(|(name, _, _)| name.as_bytes().to_vec()).collect(),
+        b"line-ending" => file::LINE_ENDINGS.iter().map(|(name, _)| name.as_bytes().to_vec()).collect(),
+        b"indent" => vec![b"tabs".to_vec(), b"spaces".to_vec()],
+        _ => Vec::new(),
+    };
+
+    (start, complete::rank(word, candidates, 0))
+}
+
+fn replace_command_word(editor: &mut Editor, start: usize, word: &[u8]) {
+    editor.command.truncate(start);
+    editor.command.extend_from_slice(word);
+    editor.buffers[editor.main_buffer_index as usize].cursors[1].position.x = editor.command.len() as u32 + 1;
+}
+
+// A single candidate is put in straight away; more open the completion box.
+// Returns whether the box is open.
+fn open_completion(editor: &mut Editor) -> bool {
+    let (start, candidates) = completions(editor);
+
+    match candidates.len() {
+        0 => false,
+        1 => {
+            replace_command_word(editor, start, &candidates[0]);
+            false
+        },
+        _ => {
+            editor.completion = Some(complete::Completion { start, candidates, selected: None });
+            true
+        },
+    }
+}
+
+fn step_completion(editor: &mut Editor, step: isize) {
+    if editor.completion.is_some() || open_completion(editor) {
+        let completion = editor.completion.as_mut().unwrap();
+        complete::cycle(completion, step);
+
+        let start = completion.start;
+        let candidate = completion.candidates[completion.selected.unwrap()].clone();
+        replace_command_word(editor, start, &candidate);
+        editor.sequence = Sequence::Complete;
+    }
+
+    buffer::update_chars(editor);
+    editor.changed = true;
+}
+
+fn command_complete(editor: &mut Editor) {
+    if editor.completion.is_some() {
+        return step_completion(editor, 1);
+    }
+
+    if open_completion(editor) {
+        editor.sequence = Sequence::Complete;
+    }
+
+    buffer::update_chars(editor);
+    editor.changed = true;
+}
+
+fn command_next_completion(editor: &mut Editor) {
+    step_completion(editor, 1);
+}
+
+fn command_previous_completion(editor: &mut Editor) {
+    step_completion(editor, -1);
+}
+
+fn delete_prev_command_char(editor: &mut Editor) {
+    let len = editor.command.len();
+    while let Some(c) = editor.command.pop() {
+        if c & 0xc0 != 0x80 {
+            break;
+        }
+    }
+
+    if editor.command.len() < len {
+        editor.buffers[editor.main_buffer_index as usize].cursors[1].position.x -= (len - editor.command.len()) as u32;
+        buffer::update_chars(editor);
+        editor.changed = true;
+    }
+}
+
+fn prev_command_char(editor: &mut Editor) {
+    if editor.buffers[editor.main_buffer_index as usize].cursors[1].position.x > 0 {
+        editor.buffers[editor.main_buffer_index as usize].cursors[1].position.x -= 1;
+        buffer::update_chars(editor);
+        editor.changed = true;
+    };
+}
+
+fn next_command_char(editor: &mut Editor) {
+    if editor.buffers[editor.main_buffer_index as usize].cursors[1].position.x < editor.command.len() as u32 + 1 {
+        editor.buffers[editor.main_buffer_index as usize].cursors[1].position.x += 1;
+        buffer::update_chars(editor);
+        editor.changed = true;
+    }
+}
+
+fn end_of_command_line(editor: &mut Editor) {
+    if editor.buffers[editor.main_buffer_index as usize].cursors[1].position.x < editor.command.len() as u32 + 1 {
+        editor.buffers[editor.main_buffer_index as usize].cursors[1].position.x = editor.command.len() as u32 + 1;
+        buffer::update_chars(editor);
+        editor.changed = true;
+    }
+}
+
+fn start_of_command_line(editor: &mut Editor) {
+    if editor.buffers[editor.main_buffer_index as usize].cursors[1].position.x > 1 {
+        editor.buffers[editor.main_buffer_index as usize].cursors[1].position.x = 1;
+        buffer::update_chars(editor);
+        editor.changed = true;
+    }
+}
+
+fn delete_command_char_at(editor: &mut Editor) {
+    if !editor.command.is_empty() {
+        editor.command.remove(editor.buffers[editor.main_buffer_index as usize].cursors[1].position.x as usize - 1);
+        buffer::update_chars(editor);
+        editor.changed = true;
+    }
+}
+
+fn active_command_mode(editor: &mut Editor) {
+    editor.command_mode = true;
+    editor.buffers[editor.main_buffer_index as usize].cursors.resize(
+        1,
+        buffer::Cursor {
+            selection: None,
+            position: buffer::Position {
+                x: 0,
+                y: 0,
+            }
+        }
+    );
+
+    let offset = editor.buffers[editor.main_buffer_index as usize].offset.y;
+    editor.buffers[editor.main_buffer_index as usize].cursors.push(
+        buffer::Cursor {
+            selection: None,
+            position: buffer::Position {
+                x: 1,
+                y: editor.chars_per_coloumn + offset,
+            }
+        }
+    );
+
+    buffer::update_chars(editor);
+    editor.changed = true;
+}
+
+fn deactive_command_mode(editor: &mut Editor) {
+    editor.command_mode = false;
+    editor.buffers[editor.main_buffer_index as usize].cursors.pop();
+}
+
+fn execute_command(editor: &mut Editor) {
+    // Commands that keys are bound to run by name too, except the ones that
+    // only make sense on the command line.
+    let named = COMMANDS.iter().find(|command| command.name.as_bytes() == editor.command && !command.name.starts_with("command-"));
+    if let Some(command) = named {
+        editor.command.clear();
+        return (command.function)(editor);
+    }
+
+    buffer::execute_command(editor);
+
+    if editor.query_replace.is_some() {
+        query_replace_show(editor);
+        return;
+    }
+
+    buffer::update_mode_line_right(editor);
+    buffer::check_offset(editor);
+    buffer::update_chars(editor);
+    editor.changed = true;
+}
+
+fn query_replace_show(editor: &mut Editor) {
+    let input = rope::to_bytes(&editor.buffers[editor.main_buffer_index as usize].text);
+
+    let query = if let Some(query) = editor.query_replace.as_mut() {
+        query
+    } else {
+        return;
+    };
+
+    if !replace::find_next(query, &input) {
+        return query_replace_finish(editor);
+    }
+
+    let end = query.current.as_ref().unwrap()[0].unwrap()[1];
+    let prompt = replace::prompt(query);
+
+    let index = editor.buffers[editor.main_buffer_index as usize].main_cursor_index as usize;
+    buffer::move_to_offset(editor, index, end);
+    buffer::set_mode_line_middle(editor, prompt);
+
+    buffer::update_mode_line_right(editor);
+    buffer::check_offset(editor);
+    buffer::update_chars(editor);
+
+    editor.changed = true;
+}
+
+fn query_replace_accept(editor: &mut Editor) {
+    let input = rope::to_bytes(&editor.buffers[editor.main_buffer_index as usize].text);
+
+    let edit = if let Some(query) = editor.query_replace.as_mut() {
+        let captures = if let Some(captures) = query.current.as_ref() {
+            captures
+        } else {
+            return;
+        };
+
+        let [start, end] = captures[0].unwrap();
+        let content = replace::replacement(&query.replace, &input, captures);
+
+        query.count += 1;
+        query.next = start + content.len() + if start == end { 1 } else { 0 };
+
+        ([start, end], content)
+    } else {
+        return;
+    };
+
+    buffer::apply_edits(editor, &[edit]);
+    query_replace_show(editor);
+}
+
+fn query_replace_skip(editor: &mut Editor) {
+    if let Some(query) = editor.query_replace.as_mut() {
+        replace::skip(query);
+    }
+
+    query_replace_show(editor);
+}
+
+fn query_replace_all(editor: &mut Editor) {
+    let input = rope::to_bytes(&editor.buffers[editor.main_buffer_index as usize].text);
+
+    let edits = if let Some(query) = editor.query_replace.as_mut() {
+        let from = query.current.as_ref().and_then(|captures| captures[0]).map(|[start, _]| start).unwrap_or(query.next);
+        let edits = replace::replace_all(&query.replace, &input, from);
+        query.count += edits.len();
+
+        edits
+    } else {
+        return;
+    };
+
+    buffer::apply_edits(editor, &edits);
+    query_replace_finish(editor);
+}
+
+fn query_replace_finish(editor: &mut Editor) {
+    let count = if let Some(query) = editor.query_replace.take() {
+        query.count
+    } else {
+        return;
+    };
+
+    buffer::end_edit(editor);
+    buffer::set_mode_line_middle(editor, buffer::replaced_message(count));
+
+    buffer::update_mode_line_right(editor);
+    buffer::check_offset(editor);
+    buffer::update_chars(editor);
+
+    editor.changed = true;
+}
+
+fn query_replace_function(event: &KeyEvent) -> Option<fn(&mut Editor)> {
+    let c = match event.press {
+        Press::Key(Key::Enter | Key::Escape) => return Some(query_replace_finish),
+        Press::Key(Key::Backspace) => return Some(query_replace_skip),
+        Press::Key(_) => return None,
+        Press::Char(c) => c,
+    };
+
+    match c {
+        'g' if event.control => Some(query_replace_finish),
+        _ if event.control || event.alt => None,
+        'y' | ' ' => Some(query_replace_accept),
+        'n' => Some(query_replace_skip),
+        '!' => Some(query_replace_all),
+        'q' => Some(query_replace_finish),
+        _ => None,
+    }
+}
+
+fn kill(editor: &mut Editor, content: Vec<u8>) {
+    if editor.last_sequence == Sequence::Kill {
+        kill_ring::append(&mut editor.kill_ring, &content);
+    } else {
+        kill_ring::push(&mut editor.kill_ring, content);
+    }
+
+    editor.sequence = Sequence::Kill;
+
+    let content = Vec::from(kill_ring::current(&editor.kill_ring).unwrap_or_default());
+    editor.requests.push(Request::SetSelection(CLIPBOARD, content));
+}
+
+fn set_mark(editor: &mut Editor) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    for i in 0..buffer.cursors.len() {
+        buffer::set_mark(editor, i);
+    }
+
+    buffer::update_chars(editor);
+    editor.changed = true;
+}
+
+fn keyboard_quit(editor: &mut Editor) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    for i in 0..buffer.cursors.len() {
+        buffer::clear_mark(editor, i);
+    }
+
+    buffer::update_chars(editor);
+    editor.changed = true;
+}
+
+// Unsaved edits make it ask first; quitting again right away goes ahead.
+fn quit(editor: &mut Editor) {
+    let unsaved = buffer::unsaved_buffers(editor);
+    if unsaved.is_empty() || editor.last_sequence == Sequence::Quit {
+        editor.requests.push(Request::Quit);
+        return;
+    }
+
+    let names: Vec<String> = unsaved.iter().map(|name| String::from_utf8_lossy(name).into_owned()).collect();
+    let message = format!("Unsaved: {}; quit again to discard", names.join(", "));
+    buffer::set_mode_line_middle(editor, message.into_bytes());
+    editor.sequence = Sequence::Quit;
+
+    buffer::update_chars(editor);
+    editor.changed = true;
+}
+
+fn kill_buffer(editor: &mut Editor) {
+    buffer::close_buffer(editor, editor.main_buffer_index as usize, false);
+
+    buffer::update_mode_line_right(editor);
+    buffer::check_offset(editor);
+    buffer::update_chars(editor);
+    editor.changed = true;
+}
+
+// Shows the *Buffers* list, made up to date, with the cursor on the buffer it
+// was called from.
+fn list_buffers(editor: &mut Editor) {
+    let previous = editor.main_buffer_index as usize;
+    let index = match editor.buffers.iter().position(|buffer| buffer.listing) {
+        Some(index) => index,
+        None => {
+            editor.buffers.push(buffer::listing_buffer());
+            editor.buffers.len() - 1
+        },
+    };
+
+    buffer::refresh_listing(editor, index);
+    editor.main_buffer_index = index as u32;
+
+    if previous != index {
+        let listing = &mut editor.buffers[index];
+        listing.cursors = vec![buffer::Cursor { position: buffer::Position { x: 0, y: previous as u32 }, selection: None }];
+        listing.main_cursor_index = 0;
+    }
+
+    buffer::update_mode_line_right(editor);
+    buffer::check_offset(editor);
+    buffer::update_chars(editor);
+    editor.changed = true;
+}
+
+fn listing_line(editor: &Editor) -> usize {
+    let buffer = &editor.buffers[editor.main_buffer_index as usize];
+    buffer.cursors[buffer.main_cursor_index as usize].position.y as usize
+}
+
+fn listing_select(editor: &mut Editor) {
+    let line = listing_line(editor);
+    if line < editor.buffers.len() {
+        editor.main_buffer_index = line as u32;
+    }
+
+    buffer::update_mode_line_right(editor);
+    buffer::check_offset(editor);
+    buffer::update_chars(editor);
+    editor.changed = true;
+}
+
+fn listing_kill(editor: &mut Editor) {
+    let line = listing_line(editor);
+    if line < editor.buffers.len() {
+        buffer::close_buffer(editor, line, false);
+    }
+
+    buffer::update_mode_line_right(editor);
+    buffer::check_offset(editor);
+    buffer::update_chars(editor);
+    editor.changed = true;
+}
+
+fn ignore(_: &mut Editor) {}
+
+// Keys of the *Buffers* list: Enter shows the buffer on the cursor's line, k
+// closes it and g brings the list up to date. Other characters don't type,
+// and everything else works as anywhere.
+fn listing_function(event: &KeyEvent) -> Option<fn(&mut Editor)> {
+    match event.press {
+        Press::Key(Key::Enter) => Some(listing_select),
+        Press::Char(_) if event.control || event.alt => None,
+        Press::Char('k') => Some(listing_kill),
+        Press::Char('g') => Some(list_buffers),
+        Press::Char(_) => Some(ignore),
+        Press::Key(_) => None,
+    }
+}
+
+fn kill_region(editor: &mut Editor) {
+    let buffer = &editor.buffers[editor.main_buffer_index as usize];
+    if buffer::region(buffer, buffer.main_cursor_index as usize).is_none() {
+        return;
+    }
+
+    buffer::begin_edit(editor, history::EditKind::Other);
+
+    let mut killed: Vec<u8> = Vec::new();
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    for i in 0..buffer.cursors.len() {
+        if let Some(content) = buffer::kill_region(editor, i) {
+            if !killed.is_empty() {
+                killed.push(b'\n');
+            }
+
+            killed.extend(content);
+        }
+    }
+
+    buffer::end_edit(editor);
+    kill(editor, killed);
+
+    buffer::update_mode_line_right(editor);
+    buffer::check_offset(editor);
+    buffer::update_chars(editor);
+
+    editor.changed = true;
+}
+
+fn copy_region(editor: &mut Editor) {
+    let buffer = &editor.buffers[editor.main_buffer_index as usize];
+    if buffer::region(buffer, buffer.main_cursor_index as usize).is_none() {
+        return;
+    }
+
+    let mut copied: Vec<u8> = Vec::new();
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    for i in 0..buffer.cursors.len() {
+        if let Some(content) = buffer::copy_region(editor, i) {
+            if !copied.is_empty() {
+                copied.push(b'\n');
+            }
+
+            copied.extend(content);
+        }
+    }
+
+    kill_ring::push(&mut editor.kill_ring, copied.clone());
+    editor.requests.push(Request::SetSelection(CLIPBOARD, copied));
+
+    buffer::update_chars(editor);
+    editor.changed = true;
+}
+
+// Yanking waits for the frontend to say whether another program has put
+// something on the clipboard since our last kill; `yank_current` finishes it.
+fn yank(editor: &mut Editor) {
+    editor.requests.push(Request::ReadSelection(CLIPBOARD));
+}
+
+fn yank_current(editor: &mut Editor, clipboard: Option<Vec<u8>>) {
+    if let Some(content) = clipboard.filter(|content| !content.is_empty()) {
+        if kill_ring::current(&editor.kill_ring) != Some(content.as_slice()) {
+            kill_ring::push(&mut editor.kill_ring, content);
+        }
+    }
+
+    let content = if let Some(content) = kill_ring::current(&editor.kill_ring) {
+        Vec::from(content)
+    } else {
+        return;
+    };
+
+    buffer::begin_edit(editor, history::EditKind::Other);
+
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    for i in 0..buffer.cursors.len() {
+        buffer::yank(editor, i, &content);
+    }
+
+    buffer::end_edit(editor);
+    editor.sequence = Sequence::Yank(content.len());
+
+    buffer::update_mode_line_right(editor);
+    buffer::check_offset(editor);
+    buffer::update_chars(editor);
+
+    editor.changed = true;
+}
+
+fn yank_pop(editor: &mut Editor) {
+    let len = if let Sequence::Yank(len) = editor.last_sequence {
+        len
+    } else {
+        return;
+    };
+
+    let content = if let Some(content) = kill_ring::rotate(&mut editor.kill_ring) {
+        Vec::from(content)
+    } else {
+        return;
+    };
+
+    buffer::begin_edit(editor, history::EditKind::Other);
+
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    for i in 0..buffer.cursors.len() {
+        buffer::replace_yank(editor, i, len, &content);
+    }
+
+    buffer::end_edit(editor);
+    editor.sequence = Sequence::Yank(content.len());
+
+    buffer::update_mode_line_right(editor);
+    buffer::check_offset(editor);
+    buffer::update_chars(editor);
+
+    editor.changed = true;
+}
+
+fn isearch_refresh(editor: &mut Editor) {
+    let (point, prompt) = if let Some(isearch) = editor.isearch.as_ref() {
+        (search::point(isearch), search::prompt(isearch))
+    } else {
+        return;
+    };
+
+    let index = editor.buffers[editor.main_buffer_index as usize].main_cursor_index as usize;
+    buffer::move_to_offset(editor, index, point);
+    buffer::set_mode_line_middle(editor, prompt);
+
+    buffer::update_mode_line_right(editor);
+    buffer::check_offset(editor);
+    buffer::update_chars(editor);
+
+    editor.changed = true;
+}
+
+fn isearch_step(editor: &mut Editor, forward: bool) {
+    let text = &editor.buffers[editor.main_buffer_index as usize].text;
+
+    if let Some(isearch) = editor.isearch.as_mut() {
+        if isearch.query.is_empty() {
+            isearch.forward = forward;
+            isearch.query = editor.last_search.clone();
+            search::update(isearch, text);
+        } else {
+            search::repeat(isearch, text, forward);
+        }
+    } else {
+        let buffer = &editor.buffers[editor.main_buffer_index as usize];
+        let origin = buffer::cursor_byte_offset(buffer, buffer.main_cursor_index as usize);
+        editor.isearch = Some(search::start(origin, forward));
+    }
+
+    isearch_refresh(editor);
+}
+
+fn isearch_forward(editor: &mut Editor) {
+    isearch_step(editor, true);
+}
+
+fn isearch_backward(editor: &mut Editor) {
+    isearch_step(editor, false);
+}
+
+fn isearch_insert_char(editor: &mut Editor) {
+    let text = &editor.buffers[editor.main_buffer_index as usize].text;
+
+    if let Some(isearch) = editor.isearch.as_mut() {
+        let mut bytes = [0; 4];
+        isearch.query.extend_from_slice(editor.last_inserted_char.encode_utf8(&mut bytes).as_bytes());
+        search::update(isearch, text);
+    }
+
+    isearch_refresh(editor);
+}
+
+fn isearch_delete_char(editor: &mut Editor) {
+    let text = &editor.buffers[editor.main_buffer_index as usize].text;
+
+    if let Some(isearch) = editor.isearch.as_mut() {
+        while let Some(c) = isearch.query.pop() {
+            if c & 0xc0 != 0x80 {
+                break;
+            }
+        }
+
+        isearch.current = None;
+        isearch.wrapped = false;
+        search::update(isearch, text);
+    }
+
+    isearch_refresh(editor);
+}
+
+fn isearch_toggle_case_fold(editor: &mut Editor) {
+    let text = &editor.buffers[editor.main_buffer_index as usize].text;
+
+    if let Some(isearch) = editor.isearch.as_mut() {
+        isearch.case_fold = !isearch.case_fold;
+        search::update(isearch, text);
+    }
+
+    isearch_refresh(editor);
+}
+
+fn isearch_exit(editor: &mut Editor) {
+    if let Some(isearch) = editor.isearch.take() {
+        if !isearch.query.is_empty() {
+            editor.last_search = isearch.query;
+        }
+    }
+
+    buffer::set_mode_line_middle(editor, Vec::new());
+    buffer::update_chars(editor);
+    editor.changed = true;
+}
+
+fn isearch_cancel(editor: &mut Editor) {
+    if let Some(isearch) = editor.isearch.as_ref() {
+        let origin = isearch.origin;
+        let index = editor.buffers[editor.main_buffer_index as usize].main_cursor_index as usize;
+        buffer::move_to_offset(editor, index, origin);
+    }
+
+    isearch_exit(editor);
+    buffer::update_mode_line_right(editor);
+    buffer::check_offset(editor);
+    buffer::update_chars(editor);
+}
+
+fn isearch_function(editor: &mut Editor, event: &KeyEvent) -> Option<fn(&mut Editor)> {
+    match event.press {
+        Press::Char(c) => {
+            if event.control {
+                match c {
+                    's' | 'r' => return None,
+                    'g' => return Some(isearch_cancel),
+                    _ => {},
+                }
+            } else if event.alt {
+                if c == 'c' {
+                    return Some(isearch_toggle_case_fold);
+                }
+            } else if c != '\t' {
+                editor.last_inserted_char = c;
+                return Some(isearch_insert_char);
+            }
+        },
+        Press::Key(Key::Backspace) => return Some(isearch_delete_char),
+        Press::Key(Key::Enter | Key::Escape) => return Some(isearch_exit),
+        Press::Key(_) => {},
+    }
+
+    isearch_exit(editor);
+    None
+}
+
+fn relayout(editor: &mut Editor) {
+    buffer::save_window(editor);
+
+    let (windows, separators) = layout::arrange(&editor.layout, [editor.columns, editor.rows]);
+    for (id, rect) in windows {
+        editor.windows[id].rect = rect;
+    }
+
+    editor.separators = separators;
+    buffer::load_window(editor);
+    buffer::check_offset(editor);
+    buffer::update_chars(editor);
+
+    editor.changed = true;
+}
+
+fn split_window(editor: &mut Editor, direction: layout::Direction) {
+    let rect = editor.windows[editor.layout.focus].rect;
+
+    if !layout::can_split(rect, direction) {
+        buffer::set_mode_line_middle(editor, b"Window too small for splitting".to_vec());
+        buffer::update_chars(editor);
+        editor.changed = true;
+        return;
+    }
+
+    buffer::save_window(editor);
+    layout::split(&mut editor.layout, direction);
+
+    let index = editor.main_buffer_index as usize;
+    let window = buffer::window(&editor.buffers[index], index, rect);
+    editor.windows.push(window);
+
+    relayout(editor);
+}
+
+fn split_below(editor: &mut Editor) {
+    split_window(editor, layout::Direction::Horizontal);
+}
+
+fn split_right(editor: &mut Editor) {
+    split_window(editor, layout::Direction::Vertical);
+}
+
+fn other_window(editor: &mut Editor) {
+    buffer::save_window(editor);
+    layout::next_focus(&mut editor.layout);
+    buffer::load_window(editor);
+
+    buffer::update_mode_line_right(editor);
+    buffer::check_offset(editor);
+    buffer::update_chars(editor);
+
+    editor.changed = true;
+}
+
+// Unnamed buffers ask for a name on the command line first.
+fn save_buffer(editor: &mut Editor) {
+    if editor.buffers[editor.main_buffer_index as usize].file_name.is_none() {
+        return prompt_command(editor, b"w ");
+    }
+
+    buffer::save_buffer(editor);
+    history::seal(&mut editor.buffers[editor.main_buffer_index as usize].history);
+    buffer::update_mode_line_right(editor);
+
+    buffer::update_chars(editor);
+    editor.changed = true;
+}
+
+// Opens the command line with `text` already typed.
+fn prompt_command(editor: &mut Editor, text: &[u8]) {
+    active_command_mode(editor);
+    editor.command = text.to_vec();
+    editor.buffers[editor.main_buffer_index as usize].cursors[1].position.x += text.len() as u32;
+
+    buffer::update_chars(editor);
+}
+
+fn execute_command_line(editor: &mut Editor) {
+    deactive_command_mode(editor);
+    execute_command(editor);
+}
+
+fn cancel_command_line(editor: &mut Editor) {
+    deactive_command_mode(editor);
+    editor.command.clear();
+    buffer::update_chars(editor);
+    editor.changed = true;
+}
+
+fn undo(editor: &mut Editor) {
+    if buffer::undo(editor) {
+        buffer::update_mode_line_right(editor);
+        buffer::check_offset(editor);
+        buffer::update_chars(editor);
+
+        editor.changed = true;
+    }
+}
+
+fn redo(editor: &mut Editor) {
+    if buffer::redo(editor) {
+        buffer::update_mode_line_right(editor);
+        buffer::check_offset(editor);
+        buffer::update_chars(editor);
+
+        editor.changed = true;
+    }
+}
+
+// Shift with a motion key drops a mark where the cursors are, unless there
+// is one already, and the same motion without Shift takes it away again.
+fn shift_select(editor: &mut Editor) {
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+
+    if editor.shift_held {
+        if !editor.shift_selection && buffer.cursors.iter().all(|cursor| cursor.selection.is_none()) {
+            for i in 0..buffer.cursors.len() {
+                buffer::set_mark(editor, i);
+            }
+
+            editor.shift_selection = true;
+        }
+    } else if editor.shift_selection {
+        for i in 0..buffer.cursors.len() {
+            buffer::clear_mark(editor, i);
+        }
+
+        editor.shift_selection = false;
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum CommandKind {
+    // Moves the cursors; Shift extends the selection while it does.
+    Motion,
+    // Runs again while the key is held down.
+    Repeat,
+    Once,
+}
+
+struct Command {
+    name: &'static str,
+    function: fn(&mut Editor),
+    kind: CommandKind,
+}
+
+const COMMANDS: [Command; 43] = [
+    Command { name: "previous-line", function: prev_line, kind: CommandKind::Motion },
+    Command { name: "next-line", function: next_line, kind: CommandKind::Motion },
+    Command { name: "backward-char", function: prev_char, kind: CommandKind::Motion },
+    Command { name: "forward-char", function: next_char, kind: CommandKind::Motion },
+    Command { name: "move-beginning-of-line", function: start_of_line, kind: CommandKind::Motion },
+    Command { name: "move-end-of-line", function: end_of_line, kind: CommandKind::Motion },
+    Command { name: "page-down", function: page_down, kind: CommandKind::Motion },
+    Command { name: "page-up", function: page_up, kind: CommandKind::Motion },
+    Command { name: "newline", function: insert_new_line, kind: CommandKind::Repeat },
+    Command { name: "indent-region", function: indent_region, kind: CommandKind::Once },
+    Command { name: "delete-char", function: delete_char_at, kind: CommandKind::Repeat },
+    Command { name: "delete-backward-char", function: delete_prev_char, kind: CommandKind::Repeat },
+    Command { name: "kill-line", function: delete_to_line_end, kind: CommandKind::Repeat },
+    Command { name: "undo", function: undo, kind: CommandKind::Repeat },
+    Command { name: "redo", function: redo, kind: CommandKind::Repeat },
+    Command { name: "set-mark", function: set_mark, kind: CommandKind::Once },
+    Command { name: "keyboard-quit", function: keyboard_quit, kind: CommandKind::Once },
+    Command { name: "kill-region", function: kill_region, kind: CommandKind::Once },
+    Command { name: "copy-region", function: copy_region, kind: CommandKind::Once },
+    Command { name: "yank", function: yank, kind: CommandKind::Repeat },
+    Command { name: "yank-pop", function: yank_pop, kind: CommandKind::Repeat },
+    Command { name: "isearch-forward", function: isearch_forward, kind: CommandKind::Repeat },
+    Command { name: "isearch-backward", function: isearch_backward, kind: CommandKind::Repeat },
+    Command { name: "execute-extended-command", function: active_command_mode, kind: CommandKind::Once },
+    Command { name: "split-window-below", function: split_below, kind: CommandKind::Once },
+    Command { name: "split-window-right", function: split_right, kind: CommandKind::Once },
+    Command { name: "other-window", function: other_window, kind: CommandKind::Once },
+    Command { name: "save-buffer", function: save_buffer, kind: CommandKind::Once },
+    Command { name: "quit", function: quit, kind: CommandKind::Once },
+    Command { name: "kill-buffer", function: kill_buffer, kind: CommandKind::Once },
+    Command { name: "list-buffers", function: list_buffers, kind: CommandKind::Once },
+    Command { name: "command-backward-char", function: prev_command_char, kind: CommandKind::Repeat },
+    Command { name: "command-forward-char", function: next_command_char, kind: CommandKind::Repeat },
+    Command { name: "command-beginning-of-line", function: start_of_command_line, kind: CommandKind::Once },
+    Command { name: "command-end-of-line", function: end_of_command_line, kind: CommandKind::Once },
+    Command { name: "command-delete-char", function: delete_command_char_at, kind: CommandKind::Repeat },
+    Command { name: "command-delete-backward-char", function: delete_prev_command_char, kind: CommandKind::Repeat },
+    Command { name: "command-kill-line", function: delete_to_command_end, kind: CommandKind::Once },
+    Command { name: "command-execute", function: execute_command_line, kind: CommandKind::Once },
+    Command { name: "command-cancel", function: cancel_command_line, kind: CommandKind::Once },
+    Command { name: "command-complete", function: command_complete, kind: CommandKind::Once },
+    Command { name: "command-next-completion", function: command_next_completion, kind: CommandKind::Repeat },
+    Command { name: "command-previous-completion", function: command_previous_completion, kind: CommandKind::Repeat },
+];
+
+// Overridden line by line from $XDG_CONFIG_HOME/engine/keys.conf.
+pub const DEFAULT_BINDINGS: &str = "\
+[edit]
+C-p            previous-line
+<up>           previous-line
+C-n            next-line
+<down>         next-line
+C-b            backward-char
+<left>         backward-char
+C-f            forward-char
+<right>        forward-char
+C-a            move-beginning-of-line
+<home>         move-beginning-of-line
+C-e            move-end-of-line
+<end>          move-end-of-line
+C-v            page-down
+<next>         page-down
+M-v            page-up
+<prior>        page-up
+<return>       newline
+C-M-\\         indent-region
+C-d            delete-char
+<delete>       delete-char
+<backspace>    delete-backward-char
+C-k            kill-line
+C-/            undo
+C-?            redo
+C-SPC          set-mark
+C-g            keyboard-quit
+<escape>       keyboard-quit
+C-w            kill-region
+S-<delete>     kill-region
+M-w            copy-region
+C-<insert>     copy-region
+C-y            yank
+S-<insert>     yank
+M-y            yank-pop
+C-s            isearch-forward
+C-r            isearch-backward
+M-x            execute-extended-command
+C-x 2          split-window-below
+C-x 3          split-window-right
+C-x o          other-window
+C-x C-s        save-buffer
+C-x C-c        quit
+C-x k          kill-buffer
+C-x C-b        list-buffers
+
+[command]
+C-b            command-backward-char
+<left>         command-backward-char
+C-f            command-forward-char
+<right>        command-forward-char
+C-a            command-beginning-of-line
+<home>         command-beginning-of-line
+C-e            command-end-of-line
+<end>          command-end-of-line
+C-d            command-delete-char
+<delete>       command-delete-char
+<backspace>    command-delete-backward-char
+C-k            command-kill-line
+<return>       command-execute
+<escape>       command-cancel
+C-g            command-cancel
+TAB            command-complete
+C-n            command-next-completion
+<down>         command-next-completion
+C-p            command-previous-completion
+<up>           command-previous-completion
+";
+
+pub fn command_names() -> Vec<&'static str> {
+    COMMANDS.iter().map(|command| command.name).collect()
+}
+
+// Reads the user's config over the built-in bindings. Config errors go to
+// stderr with their line numbers, and the first one is also shown in the
+// mode line.
+pub fn load_user_bindings(editor: &mut Editor) {
+    let Some(path) = bindings::config_path() else {
+        return;
+    };
+
+    let Ok(text) = std::fs::read_to_string(&path) else {
+        return;
+    };
+
+    let errors = bindings::parse(&mut editor.bindings, &text, &command_names());
+    for error in errors.iter() {
+        eprintln!("{}:{}", path.display(), bindings::error_message(error));
+    }
+
+    if let Some(error) = errors.first() {
+        let message = format!("{}:{}", path.display(), bindings::error_message(error));
+        buffer::set_mode_line_middle(editor, message.into_bytes());
+    }
+}
+
+fn key_chord(event: &KeyEvent) -> Chord {
+    Chord {
+        press: event.press,
+        control: event.control,
+        alt: event.alt,
+        shift: event.shift && matches!(event.press, Press::Key(_)),
+    }
+}
+
+// Looks the keys pressed so far up in the keymap of the current mode. A
+// shifted sequence that isn't bound falls back to the one without Shift, so
+// that S-<up> and C-S-n select while they move.
+fn lookup_keys(editor: &Editor) -> Lookup {
+    let map = if editor.command_mode {
+        &editor.bindings.command
+    } else {
+        &editor.bindings.edit
+    };
+
+    let found = bindings::lookup(map, &editor.pending_keys);
+    if found != Lookup::Undefined || !editor.shift_held {
+        return found;
+    }
+
+    let mut unshifted = editor.pending_keys.clone();
+    let last = unshifted.last_mut().unwrap();
+
+    match last.press {
+        Press::Key(_) if last.shift => last.shift = false,
+        Press::Char(c) if (last.control || last.alt) && c.is_ascii_uppercase() => last.press = Press::Char(c.to_ascii_lowercase()),
+        _ => return found,
+    }
+
+    bindings::lookup(map, &unshifted)
+}
+
+fn echo_keys(editor: &mut Editor, message: String) {
+    buffer::set_mode_line_middle(editor, message.into_bytes());
+    buffer::update_chars(editor);
+    editor.changed = true;
+}
+
+// Feeds one more chord into the pending key sequence. Returns the function
+// to run for a complete sequence that may repeat while held; other complete
+// sequences have already run.
+fn press_keys(editor: &mut Editor, chord: Chord) -> Option<fn(&mut Editor)> {
+    editor.pending_keys.push(chord);
+
+    match lookup_keys(editor) {
+        Lookup::Prefix => {
+            if !editor.command_mode {
+                echo_keys(editor, format!("{}-", bindings::sequence_name(&editor.pending_keys)));
+            }
+
+            None
+        },
+        Lookup::Command(i) => {
+            if std::mem::take(&mut editor.pending_keys).len() > 1 && !editor.command_mode {
+                echo_keys(editor, String::new());
+            }
+
+            let command = &COMMANDS[i];
+            if command.kind == CommandKind::Motion {
+                shift_select(editor);
+            }
+
+            if command.kind == CommandKind::Once {
+                run(editor, command.function);
+                None
+            } else {
+                Some(command.function)
+            }
+        },
+        Lookup::Undefined => {
+            let sequence = std::mem::take(&mut editor.pending_keys);
+
+            match sequence[..] {
+                [Chord { press: Press::Char(c), control: false, alt: false, .. }] => {
+                    editor.last_inserted_char = c;
+                    if editor.command_mode {
+                        Some(insert_command_char)
+                    } else {
+                        Some(insert_char_at_current_position)
+                    }
+                },
+                _ => {
+                    if !editor.command_mode {
+                        echo_keys(editor, format!("{} is undefined", bindings::sequence_name(&sequence)));
+                    }
+
+                    None
+                },
+            }
+        },
+    }
+}
+
+fn key(editor: &mut Editor, event: KeyEvent) {
+    editor.last_function = None;
+    editor.shift_held = event.shift;
+
+    if editor.query_replace.is_some() {
+        if let Some(f) = query_replace_function(&event) {
+            run(editor, f);
+        }
+
+        return;
+    }
+
+    if editor.buffers[editor.main_buffer_index as usize].listing && !editor.command_mode && editor.pending_keys.is_empty() {
+        if let Some(f) = listing_function(&event) {
+            run(editor, f);
+            return;
+        }
+    }
+
+    if editor.isearch.is_some() {
+        if let Some(f) = isearch_function(editor, &event) {
+            editor.last_function = Some(f);
+            run(editor, f);
+            return;
+        }
+    }
+
+    editor.last_function = press_keys(editor, key_chord(&event));
+
+    if let Some(f) = editor.last_function {
+        run(editor, f)
+    }
+}
+
+fn window_at(editor: &Editor, cell: [u32; 2]) -> Option<usize> {
+    editor.windows.iter().position(|window| {
+        let rect = window.rect;
+        cell[0] >= rect.x && cell[0] < rect.x + rect.width && cell[1] >= rect.y && cell[1] < rect.y + rect.height
+    })
+}
+
+fn focus_window(editor: &mut Editor, id: usize) {
+    if id == editor.layout.focus {
+        return;
+    }
+
+    buffer::save_window(editor);
+    editor.layout.focus = id;
+    buffer::load_window(editor);
+    buffer::update_mode_line_right(editor);
+}
+
+// The text area cell relative to the focused window, clamped to its edges so
+// that dragging outside keeps extending the selection.
+fn focused_cell(editor: &Editor, cell: [u32; 2]) -> [u32; 2] {
+    let rect = editor.windows[editor.layout.focus].rect;
+
+    [
+        std::cmp::min(cell[0].saturating_sub(rect.x), rect.width.saturating_sub(1)),
+        std::cmp::min(cell[1].saturating_sub(rect.y), rect.height.saturating_sub(2)),
+    ]
+}
+
+// Mouse input is ignored while the minibuffer, a search or a replace has the
+// keyboard.
+fn pointer_blocked(editor: &Editor) -> bool {
+    editor.command_mode || editor.isearch.is_some() || editor.query_replace.is_some()
+}
+
+fn pointer_moved(editor: &mut Editor) {
+    buffer::update_mode_line_right(editor);
+    buffer::check_offset(editor);
+    buffer::update_chars(editor);
+
+    editor.changed = true;
+}
+
+fn pointer_scroll(editor: &mut Editor, cell: [u32; 2], scroll: [i32; 2]) {
+    if scroll == [0, 0] {
+        return;
+    }
+
+    let id = window_at(editor, cell).unwrap_or(editor.layout.focus);
+
+    if id == editor.layout.focus {
+        let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+        buffer::scroll_view(&buffer.text, buffer.indent.tab_width, &mut buffer.offset, &mut buffer.cursors, scroll, [editor.chars_per_row, editor.chars_per_coloumn]);
+    } else {
+        let window = &mut editor.windows[id];
+        let size = [window.rect.width, window.rect.height - 1];
+        buffer::scroll_view(&editor.buffers[window.buffer].text, editor.buffers[window.buffer].indent.tab_width, &mut window.offset, &mut window.cursors, scroll, size);
+    }
+
+    pointer_moved(editor);
+}
+
+fn pointer_press(editor: &mut Editor, cell: [u32; 2], clicks: u32) {
+    if pointer_blocked(editor) {
+        return;
+    }
+
+    let id = if let Some(id) = window_at(editor, cell) {
+        id
+    } else {
+        return;
+    };
+
+    focus_window(editor, id);
+
+    let rect = editor.windows[id].rect;
+    if cell[1] + 1 >= rect.y + rect.height {
+        pointer_moved(editor);
+        return;
+    }
+
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    let position = buffer::position_at_cell(buffer, [cell[0] - rect.x, cell[1] - rect.y]);
+
+    let mut cursor = buffer.cursors[buffer.main_cursor_index as usize].clone();
+    cursor.position = position.clone();
+    cursor.selection = Some(position);
+    buffer.cursors = vec![cursor];
+    buffer.main_cursor_index = 0;
+
+    match clicks {
+        2 => buffer::select_word(editor, 0),
+        3 => buffer::select_line(editor, 0),
+        _ => {},
+    }
+
+    editor.dragging = true;
+    pointer_moved(editor);
+}
+
+fn pointer_drag(editor: &mut Editor, cell: [u32; 2]) {
+    if !editor.dragging {
+        return;
+    }
+
+    let cell = focused_cell(editor, cell);
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    let position = buffer::position_at_cell(buffer, cell);
+    let cursor = &mut buffer.cursors[buffer.main_cursor_index as usize];
+
+    if cursor.position != position {
+        cursor.position = position;
+        pointer_moved(editor);
+    }
+}
+
+// Ends a drag, offering what it selected as the primary selection.
+fn pointer_release(editor: &mut Editor) {
+    if !std::mem::take(&mut editor.dragging) {
+        return;
+    }
+
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    let index = buffer.main_cursor_index as usize;
+    let cursor = &mut buffer.cursors[index];
+    if cursor.selection.as_ref() == Some(&cursor.position) {
+        cursor.selection = None;
+    }
+
+    if let Some([start, end]) = buffer::region(buffer, index) {
+        editor.primary = rope::slice(&buffer.text, start, end);
+        editor.requests.push(Request::SetSelection(PRIMARY, editor.primary.clone()));
+    }
+}
+
+// Moves the cursor to the clicked cell and asks for the primary selection,
+// which `receive_selection` inserts there.
+fn paste_primary(editor: &mut Editor, cell: [u32; 2]) {
+    if pointer_blocked(editor) {
+        return;
+    }
+
+    let id = if let Some(id) = window_at(editor, cell) {
+        id
+    } else {
+        return;
+    };
+
+    focus_window(editor, id);
+
+    let rect = editor.windows[id].rect;
+    if cell[1] + 1 >= rect.y + rect.height {
+        pointer_moved(editor);
+        return;
+    }
+
+    let buffer = &mut editor.buffers[editor.main_buffer_index as usize];
+    let position = buffer::position_at_cell(buffer, [cell[0] - rect.x, cell[1] - rect.y]);
+
+    let mut cursor = buffer.cursors[buffer.main_cursor_index as usize].clone();
+    cursor.position = position;
+    cursor.selection = None;
+    buffer.cursors = vec![cursor];
+    buffer.main_cursor_index = 0;
+
+    editor.requests.push(Request::ReadSelection(PRIMARY));
+    pointer_moved(editor);
+}
+
+fn receive_selection(editor: &mut Editor, selection: usize, content: Option<Vec<u8>>) {
+    if selection == CLIPBOARD {
+        return yank_current(editor, content);
+    }
+
+    let content = content.unwrap_or_else(|| editor.primary.clone());
+    if content.is_empty() {
+        return;
+    }
+
+    buffer::begin_edit(editor, history::EditKind::Other);
+    buffer::yank(editor, 0, &content);
+    buffer::end_edit(editor);
+
+    pointer_moved(editor);
+}
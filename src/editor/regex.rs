@@ -0,0 +1,623 @@
+use super::grapheme;
+
+#[derive(Debug, PartialEq)]
+pub enum RegexError {
+    UnbalancedParenthesis,
+    UnterminatedClass,
+    NothingToRepeat,
+    TrailingBackslash,
+    InvalidRepetition,
+}
+
+// Counted repetitions are compiled by copying, so they're kept small.
+const MAX_REPETITION: u32 = 1000;
+
+#[derive(Clone)]
+enum Node {
+    Char(char),
+    Any,
+    Class { ranges: Vec<(char, char)>, negated: bool },
+    LineStart,
+    LineEnd,
+    WordBoundary,
+    Group(Box<Node>, Option<usize>),
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Repeat { node: Box<Node>, min: u32, max: Option<u32>, greedy: bool },
+}
+
+// Matching runs a program compiled from the parsed pattern, backtracking
+// through a stack kept on the heap, so long inputs can't exhaust the call
+// stack however many times a repetition goes around.
+enum Inst {
+    // A `Char`, `Any` or `Class` node.
+    Single(Node),
+    LineStart,
+    LineEnd,
+    WordBoundary,
+    // Stores the position in a slot: group starts and ends, then one per
+    // unbounded repetition for where its current pass began.
+    Save(usize),
+    // Fails when the repetition whose pass began at this slot hasn't moved,
+    // so patterns that can match nothing don't loop forever.
+    Progress(usize),
+    // Goes on at the first, and at the second when that fails.
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+pub struct Regex {
+    program: Vec<Inst>,
+    groups: usize,
+    slots: usize,
+    case_fold: bool,
+}
+
+pub type Captures = Vec<Option<[usize; 2]>>;
+
+struct Parser<'a> {
+    pattern: &'a [u8],
+    at: usize,
+    groups: usize,
+}
+
+fn peek(parser: &Parser) -> Option<char> {
+    if parser.at < parser.pattern.len() {
+        Some(grapheme::decode(parser.pattern, parser.at).0)
+    } else {
+        None
+    }
+}
+
+fn next(parser: &mut Parser) -> Option<char> {
+    if parser.at < parser.pattern.len() {
+        let (c, len) = grapheme::decode(parser.pattern, parser.at);
+        parser.at += len;
+        Some(c)
+    } else {
+        None
+    }
+}
+
+fn class_escape(c: char) -> Option<(Vec<(char, char)>, bool)> {
+    let digits = vec![('0', '9')];
+    let words = vec![('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')];
+    let spaces = vec![(' ', ' '), ('\t', '\t'), ('\n', '\n'), ('\r', '\r'), ('\u{b}', '\u{c}')];
+
+    match c {
+        'd' => Some((digits, false)),
+        'D' => Some((digits, true)),
+        'w' => Some((words, false)),
+        'W' => Some((words, true)),
+        's' => Some((spaces, false)),
+        'S' => Some((spaces, true)),
+        _ => None,
+    }
+}
+
+fn escaped_char(c: char) -> char {
+    match c {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        _ => c,
+    }
+}
+
+fn parse_alternate(parser: &mut Parser) -> Result<Node, RegexError> {
+    let mut branches = vec![parse_concat(parser)?];
+
+    while peek(parser) == Some('|') {
+        next(parser);
+        branches.push(parse_concat(parser)?);
+    }
+
+    if branches.len() == 1 {
+        Ok(branches.pop().unwrap())
+    } else {
+        Ok(Node::Alternate(branches))
+    }
+}
+
+fn parse_concat(parser: &mut Parser) -> Result<Node, RegexError> {
+    let mut nodes = Vec::new();
+
+    while let Some(c) = peek(parser) {
+        if c == '|' || c == ')' {
+            break;
+        }
+
+        let atom = parse_atom(parser)?;
+        nodes.push(parse_repeat(parser, atom)?);
+    }
+
+    Ok(Node::Concat(nodes))
+}
+
+fn parse_number(parser: &mut Parser) -> Option<u32> {
+    let start = parser.at;
+
+    while peek(parser).map(|c| c.is_ascii_digit()).unwrap_or(false) {
+        next(parser);
+    }
+
+    std::str::from_utf8(&parser.pattern[start..parser.at]).ok()?.parse().ok()
+}
+
+// The bounds of a `{m}`, `{m,}` or `{m,n}` at the parser, which is left on
+// its `}`. A `{` that doesn't start one is None and left alone, to be read as
+// itself.
+fn parse_counts(parser: &mut Parser) -> Result<Option<(u32, Option<u32>)>, RegexError> {
+    let start = parser.at;
+    next(parser);
+
+    let min = parse_number(parser);
+    let max = if peek(parser) == Some(',') {
+        next(parser);
+        parse_number(parser)
+    } else {
+        min
+    };
+
+    let Some(min) = min.filter(|_| peek(parser) == Some('}')) else {
+        parser.at = start;
+        return Ok(None);
+    };
+
+    if max.map(|max| max < min).unwrap_or(false) || max.unwrap_or(min) > MAX_REPETITION {
+        return Err(RegexError::InvalidRepetition);
+    }
+
+    Ok(Some((min, max)))
+}
+
+fn parse_repeat(parser: &mut Parser, atom: Node) -> Result<Node, RegexError> {
+    let mut node = atom;
+
+    loop {
+        let (min, max) = match peek(parser) {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => match parse_counts(parser)? {
+                Some(counts) => counts,
+                None => return Ok(node),
+            },
+            _ => return Ok(node),
+        };
+
+        next(parser);
+
+        let greedy = if peek(parser) == Some('?') {
+            next(parser);
+            false
+        } else {
+            true
+        };
+
+        if matches!(node, Node::LineStart | Node::LineEnd | Node::WordBoundary) {
+            return Err(RegexError::NothingToRepeat);
+        }
+
+        node = Node::Repeat { node: Box::new(node), min, max, greedy };
+    }
+}
+
+fn parse_class(parser: &mut Parser) -> Result<Node, RegexError> {
+    let mut ranges = Vec::new();
+    let negated = if peek(parser) == Some('^') {
+        next(parser);
+        true
+    } else {
+        false
+    };
+
+    let mut first = true;
+    loop {
+        let c = next(parser).ok_or(RegexError::UnterminatedClass)?;
+
+        if c == ']' && !first {
+            break;
+        }
+
+        first = false;
+
+        let start = if c == '\\' {
+            let e = next(parser).ok_or(RegexError::TrailingBackslash)?;
+
+            if let Some((escape_ranges, false)) = class_escape(e) {
+                ranges.extend(escape_ranges);
+                continue;
+            }
+
+            escaped_char(e)
+        } else {
+            c
+        };
+
+        let is_range = peek(parser) == Some('-') && parser.pattern.get(parser.at + 1).map(|c| *c != b']').unwrap_or(false);
+
+        if is_range {
+            next(parser);
+            let end = match next(parser).ok_or(RegexError::UnterminatedClass)? {
+                '\\' => escaped_char(next(parser).ok_or(RegexError::TrailingBackslash)?),
+                end => end,
+            };
+
+            ranges.push((start, end));
+        } else {
+            ranges.push((start, start));
+        }
+    }
+
+    Ok(Node::Class { ranges, negated })
+}
+
+fn parse_atom(parser: &mut Parser) -> Result<Node, RegexError> {
+    let c = next(parser).unwrap();
+
+    match c {
+        '.' => Ok(Node::Any),
+        '^' => Ok(Node::LineStart),
+        '$' => Ok(Node::LineEnd),
+        '*' | '+' | '?' => Err(RegexError::NothingToRepeat),
+        '[' => parse_class(parser),
+        '(' => {
+            let index = if parser.pattern[parser.at..].starts_with(b"?:") {
+                parser.at += 2;
+                None
+            } else {
+                parser.groups += 1;
+                Some(parser.groups)
+            };
+
+            let node = parse_alternate(parser)?;
+
+            if next(parser) != Some(')') {
+                return Err(RegexError::UnbalancedParenthesis);
+            }
+
+            Ok(Node::Group(Box::new(node), index))
+        },
+        ')' => Err(RegexError::UnbalancedParenthesis),
+        '\\' => {
+            let e = next(parser).ok_or(RegexError::TrailingBackslash)?;
+
+            if e == 'b' {
+                return Ok(Node::WordBoundary);
+            }
+
+            if let Some((ranges, negated)) = class_escape(e) {
+                return Ok(Node::Class { ranges, negated });
+            }
+
+            Ok(Node::Char(escaped_char(e)))
+        },
+        c => Ok(Node::Char(c)),
+    }
+}
+
+pub fn parse(pattern: &[u8], case_fold: bool) -> Result<Regex, RegexError> {
+    let mut parser = Parser {
+        pattern,
+        at: 0,
+        groups: 0,
+    };
+
+    let root = parse_alternate(&mut parser)?;
+
+    if parser.at < pattern.len() {
+        return Err(RegexError::UnbalancedParenthesis);
+    }
+
+    Ok(compile(&root, parser.groups, case_fold))
+}
+
+pub fn literal(pattern: &[u8], case_fold: bool) -> Regex {
+    compile(&Node::Concat(grapheme::chars(pattern).into_iter().map(Node::Char).collect()), 0, case_fold)
+}
+
+fn compile(root: &Node, groups: usize, case_fold: bool) -> Regex {
+    let mut regex = Regex {
+        program: Vec::new(),
+        groups,
+        slots: 2 * (groups + 1),
+        case_fold,
+    };
+
+    emit(&mut regex, root);
+    regex.program.push(Inst::Match);
+
+    regex
+}
+
+fn emit(regex: &mut Regex, node: &Node) {
+    match node {
+        Node::Char(_) | Node::Any | Node::Class { .. } => regex.program.push(Inst::Single(node.clone())),
+        Node::LineStart => regex.program.push(Inst::LineStart),
+        Node::LineEnd => regex.program.push(Inst::LineEnd),
+        Node::WordBoundary => regex.program.push(Inst::WordBoundary),
+        Node::Group(inner, None) => emit(regex, inner),
+        Node::Group(inner, Some(index)) => {
+            regex.program.push(Inst::Save(2 * index));
+            emit(regex, inner);
+            regex.program.push(Inst::Save(2 * index + 1));
+        },
+        Node::Concat(nodes) => {
+            for node in nodes {
+                emit(regex, node);
+            }
+        },
+        Node::Alternate(branches) => {
+            let mut jumps = Vec::new();
+
+            for (i, branch) in branches.iter().enumerate() {
+                let split = regex.program.len();
+                if i + 1 < branches.len() {
+                    regex.program.push(Inst::Split(split + 1, 0));
+                }
+
+                emit(regex, branch);
+
+                if i + 1 < branches.len() {
+                    jumps.push(regex.program.len());
+                    regex.program.push(Inst::Jump(0));
+                    regex.program[split] = Inst::Split(split + 1, regex.program.len());
+                }
+            }
+
+            let end = regex.program.len();
+            for jump in jumps {
+                regex.program[jump] = Inst::Jump(end);
+            }
+        },
+        Node::Repeat { node, min, max, greedy } => {
+            for _ in 0..*min {
+                emit(regex, node);
+            }
+
+            match max {
+                Some(max) => {
+                    let mut splits = Vec::new();
+
+                    for _ in *min..*max {
+                        splits.push(regex.program.len());
+                        regex.program.push(Inst::Jump(0));
+                        emit(regex, node);
+                    }
+
+                    let end = regex.program.len();
+                    for split in splits {
+                        regex.program[split] = branch(split + 1, end, *greedy);
+                    }
+                },
+                None => {
+                    let split = regex.program.len();
+                    regex.program.push(Inst::Jump(0));
+
+                    // Single characters always move on, so only the rest
+                    // need their passes checked.
+                    let slot = (!matches!(**node, Node::Char(_) | Node::Any | Node::Class { .. })).then(|| {
+                        regex.slots += 1;
+                        regex.slots - 1
+                    });
+
+                    if let Some(slot) = slot {
+                        regex.program.push(Inst::Save(slot));
+                    }
+
+                    emit(regex, node);
+
+                    if let Some(slot) = slot {
+                        regex.program.push(Inst::Progress(slot));
+                    }
+
+                    regex.program.push(Inst::Jump(split));
+                    regex.program[split] = branch(split + 1, regex.program.len(), *greedy);
+                },
+            }
+        },
+    }
+}
+
+// Greedy repetitions try another pass before stopping; lazy ones stop first.
+fn branch(more: usize, stop: usize, greedy: bool) -> Inst {
+    if greedy {
+        Inst::Split(more, stop)
+    } else {
+        Inst::Split(stop, more)
+    }
+}
+
+fn same_char(a: char, b: char, case_fold: bool) -> bool {
+    a == b || (case_fold && a.to_lowercase().eq(b.to_lowercase()))
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn char_before(input: &[u8], at: usize) -> Option<char> {
+    if at == 0 {
+        return None;
+    }
+
+    let mut start = at - 1;
+    while start > 0 && input[start] & 0xc0 == 0x80 {
+        start -= 1;
+    }
+
+    Some(grapheme::decode(input, start).0)
+}
+
+fn char_at(input: &[u8], at: usize) -> Option<(char, usize)> {
+    if at < input.len() {
+        Some(grapheme::decode(input, at))
+    } else {
+        None
+    }
+}
+
+fn single(node: &Node, input: &[u8], at: usize, case_fold: bool) -> Option<usize> {
+    let (c, len) = char_at(input, at)?;
+
+    let matched = match node {
+        Node::Char(expected) => same_char(c, *expected, case_fold),
+        Node::Any => c != '\n',
+        Node::Class { ranges, negated } => {
+            let inside = ranges.iter().any(|(start, end)| {
+                (*start <= c && c <= *end) || (case_fold && c.to_lowercase().chain(c.to_uppercase()).any(|c| *start <= c && c <= *end))
+            });
+
+            inside != *negated
+        },
+        _ => false,
+    };
+
+    if matched {
+        Some(at + len)
+    } else {
+        None
+    }
+}
+
+enum Backtrack {
+    // A thread to resume when the current one fails.
+    Thread { pc: usize, at: usize },
+    // A slot's value from before the current thread stored it.
+    Slot { index: usize, value: Option<usize> },
+}
+
+pub fn match_at(regex: &Regex, input: &[u8], at: usize) -> Option<Captures> {
+    let mut slots: Vec<Option<usize>> = vec![None; regex.slots];
+    let mut stack = vec![Backtrack::Thread { pc: 0, at }];
+
+    while let Some(entry) = stack.pop() {
+        let (mut pc, mut position) = match entry {
+            Backtrack::Thread { pc, at } => (pc, at),
+            Backtrack::Slot { index, value } => {
+                slots[index] = value;
+                continue;
+            },
+        };
+
+        loop {
+            let ok = match &regex.program[pc] {
+                Inst::Single(node) => match single(node, input, position, regex.case_fold) {
+                    Some(end) => {
+                        position = end;
+                        true
+                    },
+                    None => false,
+                },
+                Inst::LineStart => position == 0 || input[position - 1] == b'\n',
+                Inst::LineEnd => position == input.len() || input[position] == b'\n',
+                Inst::WordBoundary => {
+                    let before = char_before(input, position).map(is_word).unwrap_or(false);
+                    let after = char_at(input, position).map(|(c, _)| is_word(c)).unwrap_or(false);
+
+                    before != after
+                },
+                Inst::Save(index) => {
+                    stack.push(Backtrack::Slot { index: *index, value: slots[*index] });
+                    slots[*index] = Some(position);
+                    true
+                },
+                Inst::Progress(index) => slots[*index] != Some(position),
+                Inst::Split(first, second) => {
+                    stack.push(Backtrack::Thread { pc: *second, at: position });
+                    pc = *first;
+                    continue;
+                },
+                Inst::Jump(target) => {
+                    pc = *target;
+                    continue;
+                },
+                Inst::Match => {
+                    let mut captures: Captures = (0..=regex.groups).map(|i| Some([slots[2 * i]?, slots[2 * i + 1]?])).collect();
+                    captures[0] = Some([at, position]);
+                    return Some(captures);
+                },
+            };
+
+            if !ok {
+                break;
+            }
+
+            pc += 1;
+        }
+    }
+
+    None
+}
+
+pub fn find_at(regex: &Regex, input: &[u8], from: usize) -> Option<Captures> {
+    for at in from..=input.len() {
+        if at < input.len() && input[at] & 0xc0 == 0x80 {
+            continue;
+        }
+
+        if let Some(captures) = match_at(regex, input, at) {
+            return Some(captures);
+        }
+    }
+
+    None
+}
+
+pub fn find_all(regex: &Regex, input: &[u8]) -> Vec<Captures> {
+    let mut all = Vec::new();
+    let mut from = 0;
+
+    while from <= input.len() {
+        let captures = if let Some(captures) = find_at(regex, input, from) {
+            captures
+        } else {
+            break;
+        };
+
+        let [start, end] = captures[0].unwrap();
+        from = if end == start {
+            end + char_at(input, end).map(|(_, len)| len).unwrap_or(1)
+        } else {
+            end
+        };
+
+        all.push(captures);
+    }
+
+    all
+}
+
+pub fn expand(template: &[u8], input: &[u8], captures: &Captures) -> Vec<u8> {
+    let mut out = Vec::with_capacity(template.len());
+    let mut i = 0;
+
+    while i < template.len() {
+        let c = template[i];
+
+        if c == b'\\' && i + 1 < template.len() {
+            let e = template[i + 1];
+            i += 2;
+
+            match e {
+                b'0'..=b'9' | b'&' => {
+                    let index = if e == b'&' { 0 } else { (e - b'0') as usize };
+
+                    if let Some(Some([start, end])) = captures.get(index) {
+                        out.extend_from_slice(&input[*start..*end]);
+                    }
+                },
+                b'n' => out.push(b'\n'),
+                b't' => out.push(b'\t'),
+                e => out.push(e),
+            }
+        } else {
+            out.push(c);
+            i += 1;
+        }
+    }
+
+    out
+}
@@ -0,0 +1,132 @@
+use super::regex;
+
+#[derive(Debug, PartialEq)]
+pub enum ReplaceError {
+    MissingDelimiter,
+    UnknownFlag(u8),
+    Regex(regex::RegexError),
+}
+
+pub struct Replace {
+    pub regex: regex::Regex,
+    pub pattern: Vec<u8>,
+    pub replacement: Vec<u8>,
+    pub literal: bool,
+}
+
+pub struct QueryReplace {
+    pub replace: Replace,
+    pub next: usize,
+    pub current: Option<regex::Captures>,
+    pub count: usize,
+}
+
+fn split_delimited(argument: &[u8], delimiter: u8) -> Vec<Vec<u8>> {
+    let mut parts = vec![Vec::new()];
+    let mut i = 0;
+
+    while i < argument.len() {
+        let c = argument[i];
+
+        if c == b'\\' && i + 1 < argument.len() && argument[i + 1] == delimiter {
+            parts.last_mut().unwrap().push(delimiter);
+            i += 2;
+        } else if c == b'\\' && i + 1 < argument.len() {
+            parts.last_mut().unwrap().extend_from_slice(&argument[i..i + 2]);
+            i += 2;
+        } else if c == delimiter {
+            parts.push(Vec::new());
+            i += 1;
+        } else {
+            parts.last_mut().unwrap().push(c);
+            i += 1;
+        }
+    }
+
+    parts
+}
+
+pub fn parse_arguments(argument: &[u8]) -> Result<Replace, ReplaceError> {
+    let delimiter = *argument.first().ok_or(ReplaceError::MissingDelimiter)?;
+    let parts = split_delimited(&argument[1..], delimiter);
+
+    if parts.len() < 2 || parts.len() > 3 {
+        return Err(ReplaceError::MissingDelimiter);
+    }
+
+    let mut literal = false;
+    let mut case_fold = false;
+
+    for flag in parts.get(2).map(|flags| flags.as_slice()).unwrap_or(&[]) {
+        match flag {
+            b'l' => literal = true,
+            b'i' => case_fold = true,
+            flag => return Err(ReplaceError::UnknownFlag(*flag)),
+        }
+    }
+
+    let regex = if literal {
+        regex::literal(&parts[0], case_fold)
+    } else {
+        regex::parse(&parts[0], case_fold).map_err(ReplaceError::Regex)?
+    };
+
+    Ok(Replace {
+        regex,
+        pattern: parts[0].clone(),
+        replacement: parts[1].clone(),
+        literal,
+    })
+}
+
+pub fn replacement(replace: &Replace, input: &[u8], captures: &regex::Captures) -> Vec<u8> {
+    if replace.literal {
+        replace.replacement.clone()
+    } else {
+        regex::expand(&replace.replacement, input, captures)
+    }
+}
+
+pub fn replace_all(replace: &Replace, input: &[u8], from: usize) -> Vec<([usize; 2], Vec<u8>)> {
+    regex::find_all(&replace.regex, input)
+        .into_iter()
+        .filter(|captures| captures[0].unwrap()[0] >= from)
+        .map(|captures| (captures[0].unwrap(), replacement(replace, input, &captures)))
+        .collect()
+}
+
+pub fn query(replace: Replace, from: usize) -> QueryReplace {
+    QueryReplace {
+        replace,
+        next: from,
+        current: None,
+        count: 0,
+    }
+}
+
+pub fn find_next(query: &mut QueryReplace, input: &[u8]) -> bool {
+    query.current = if query.next <= input.len() {
+        regex::find_at(&query.replace.regex, input, query.next)
+    } else {
+        None
+    };
+
+    query.current.is_some()
+}
+
+pub fn skip(query: &mut QueryReplace) {
+    if let Some([start, end]) = query.current.as_ref().and_then(|captures| captures[0]) {
+        query.next = if start == end { end + 1 } else { end };
+    }
+}
+
+pub fn prompt(query: &QueryReplace) -> Vec<u8> {
+    let mut prompt = Vec::new();
+    prompt.extend_from_slice(b"Query replacing ");
+    prompt.extend_from_slice(&query.replace.pattern);
+    prompt.extend_from_slice(b" with ");
+    prompt.extend_from_slice(&query.replace.replacement);
+    prompt.extend_from_slice(b" (y/n/!/q)");
+
+    prompt
+}
@@ -0,0 +1,388 @@
+const MAX_LEAF: usize = 1024;
+
+enum Node {
+    Leaf(Vec<u8>),
+    Branch {
+        left: Box<Node>,
+        right: Box<Node>,
+        len: usize,
+        lines: usize,
+        height: u32,
+    },
+}
+
+pub struct Rope {
+    root: Node,
+}
+
+fn node_len(node: &Node) -> usize {
+    match node {
+        Node::Leaf(content) => content.len(),
+        Node::Branch { len, .. } => *len,
+    }
+}
+
+fn node_lines(node: &Node) -> usize {
+    match node {
+        Node::Leaf(content) => content.iter().filter(|c| **c == b'\n').count(),
+        Node::Branch { lines, .. } => *lines,
+    }
+}
+
+fn node_height(node: &Node) -> u32 {
+    match node {
+        Node::Leaf(_) => 0,
+        Node::Branch { height, .. } => *height,
+    }
+}
+
+fn branch(left: Node, right: Node) -> Node {
+    Node::Branch {
+        len: node_len(&left) + node_len(&right),
+        lines: node_lines(&left) + node_lines(&right),
+        height: std::cmp::max(node_height(&left), node_height(&right)) + 1,
+        left: Box::new(left),
+        right: Box::new(right),
+    }
+}
+
+fn rotate_left(node: Node) -> Node {
+    match node {
+        Node::Branch { left, right, .. } => match *right {
+            Node::Branch { left: right_left, right: right_right, .. } => branch(branch(*left, *right_left), *right_right),
+            right => branch(*left, right),
+        },
+        leaf => leaf,
+    }
+}
+
+fn rotate_right(node: Node) -> Node {
+    match node {
+        Node::Branch { left, right, .. } => match *left {
+            Node::Branch { left: left_left, right: left_right, .. } => branch(*left_left, branch(*left_right, *right)),
+            left => branch(left, *right),
+        },
+        leaf => leaf,
+    }
+}
+
+fn balance(node: Node) -> Node {
+    let (left_height, right_height) = match &node {
+        Node::Branch { left, right, .. } => (node_height(left), node_height(right)),
+        Node::Leaf(_) => return node,
+    };
+
+    if left_height > right_height + 1 {
+        let node = match node {
+            Node::Branch { left, right, .. } => {
+                let left = match *left {
+                    Node::Branch { left: ll, right: lr, .. } if node_height(&lr) > node_height(&ll) => rotate_left(branch(*ll, *lr)),
+                    left => left,
+                };
+
+                branch(left, *right)
+            },
+            leaf => leaf,
+        };
+
+        rotate_right(node)
+    } else if right_height > left_height + 1 {
+        let node = match node {
+            Node::Branch { left, right, .. } => {
+                let right = match *right {
+                    Node::Branch { left: rl, right: rr, .. } if node_height(&rl) > node_height(&rr) => rotate_right(branch(*rl, *rr)),
+                    right => right,
+                };
+
+                branch(*left, right)
+            },
+            leaf => leaf,
+        };
+
+        rotate_left(node)
+    } else {
+        node
+    }
+}
+
+fn join(left: Node, right: Node) -> Node {
+    if node_len(&left) == 0 {
+        return right;
+    }
+
+    if node_len(&right) == 0 {
+        return left;
+    }
+
+    let left_height = node_height(&left);
+    let right_height = node_height(&right);
+
+    if left_height > right_height + 1 {
+        match left {
+            Node::Branch { left: ll, right: lr, .. } => balance(branch(*ll, join(*lr, right))),
+            leaf => branch(leaf, right),
+        }
+    } else if right_height > left_height + 1 {
+        match right {
+            Node::Branch { left: rl, right: rr, .. } => balance(branch(join(left, *rl), *rr)),
+            leaf => branch(left, leaf),
+        }
+    } else {
+        match (left, right) {
+            (Node::Leaf(mut l), Node::Leaf(r)) if l.len() + r.len() <= MAX_LEAF => {
+                l.extend_from_slice(&r);
+                Node::Leaf(l)
+            },
+            (l, r) => branch(l, r),
+        }
+    }
+}
+
+fn split(node: Node, at: usize) -> (Node, Node) {
+    match node {
+        Node::Leaf(mut content) => {
+            let right = content.split_off(at);
+            (Node::Leaf(content), Node::Leaf(right))
+        },
+        Node::Branch { left, right, .. } => {
+            let left_len = node_len(&left);
+
+            if at < left_len {
+                let (ll, lr) = split(*left, at);
+                (ll, join(lr, *right))
+            } else {
+                let (rl, rr) = split(*right, at - left_len);
+                (join(*left, rl), rr)
+            }
+        },
+    }
+}
+
+fn build(bytes: &[u8]) -> Node {
+    if bytes.len() <= MAX_LEAF {
+        return Node::Leaf(Vec::from(bytes));
+    }
+
+    let mid = bytes.len() / 2;
+    branch(build(&bytes[..mid]), build(&bytes[mid..]))
+}
+
+fn insert_in_leaf(node: &mut Node, at: usize, bytes: &[u8]) -> bool {
+    match node {
+        Node::Leaf(content) => {
+            if content.len() + bytes.len() > MAX_LEAF {
+                return false;
+            }
+
+            content.splice(at..at, bytes.iter().copied());
+            true
+        },
+        Node::Branch { left, right, len, lines, .. } => {
+            let left_len = node_len(left);
+            let inserted = if at <= left_len {
+                insert_in_leaf(left, at, bytes)
+            } else {
+                insert_in_leaf(right, at - left_len, bytes)
+            };
+
+            if inserted {
+                *len += bytes.len();
+                *lines += bytes.iter().filter(|c| **c == b'\n').count();
+            }
+
+            inserted
+        },
+    }
+}
+
+fn delete_in_leaf(node: &mut Node, start: usize, end: usize) -> bool {
+    match node {
+        Node::Leaf(content) => {
+            content.drain(start..end);
+            true
+        },
+        Node::Branch { left, right, len, lines, .. } => {
+            let left_len = node_len(left);
+            let (child, child_start, child_end) = if end <= left_len {
+                (left, start, end)
+            } else if start >= left_len {
+                (right, start - left_len, end - left_len)
+            } else {
+                return false;
+            };
+
+            let removed_lines = count_lines(child, child_start, child_end);
+            if !delete_in_leaf(child, child_start, child_end) {
+                return false;
+            }
+
+            *len -= end - start;
+            *lines -= removed_lines;
+            true
+        },
+    }
+}
+
+fn count_lines(node: &Node, start: usize, end: usize) -> usize {
+    match node {
+        Node::Leaf(content) => content[start..end].iter().filter(|c| **c == b'\n').count(),
+        Node::Branch { left, right, .. } => {
+            let left_len = node_len(left);
+
+            if start == 0 && end == node_len(node) {
+                node_lines(node)
+            } else if end <= left_len {
+                count_lines(left, start, end)
+            } else if start >= left_len {
+                count_lines(right, start - left_len, end - left_len)
+            } else {
+                count_lines(left, start, left_len) + count_lines(right, 0, end - left_len)
+            }
+        },
+    }
+}
+
+fn newline_offset(node: &Node, n: usize) -> usize {
+    match node {
+        Node::Leaf(content) => {
+            let mut count = 0;
+
+            for (i, c) in content.iter().enumerate() {
+                if *c == b'\n' {
+                    count += 1;
+
+                    if count == n {
+                        return i;
+                    }
+                }
+            }
+
+            content.len()
+        },
+        Node::Branch { left, right, .. } => {
+            let left_lines = node_lines(left);
+
+            if n <= left_lines {
+                newline_offset(left, n)
+            } else {
+                node_len(left) + newline_offset(right, n - left_lines)
+            }
+        },
+    }
+}
+
+fn collect(node: &Node, start: usize, end: usize, out: &mut Vec<u8>) {
+    if start >= end {
+        return;
+    }
+
+    match node {
+        Node::Leaf(content) => out.extend_from_slice(&content[start..end]),
+        Node::Branch { left, right, .. } => {
+            let left_len = node_len(left);
+
+            if start < left_len {
+                collect(left, start, std::cmp::min(end, left_len), out);
+            }
+
+            if end > left_len {
+                collect(right, start.saturating_sub(left_len), end - left_len, out);
+            }
+        },
+    }
+}
+
+fn byte(node: &Node, at: usize) -> u8 {
+    match node {
+        Node::Leaf(content) => content[at],
+        Node::Branch { left, right, .. } => {
+            let left_len = node_len(left);
+
+            if at < left_len {
+                byte(left, at)
+            } else {
+                byte(right, at - left_len)
+            }
+        },
+    }
+}
+
+pub fn empty() -> Rope {
+    Rope {
+        root: Node::Leaf(Vec::new()),
+    }
+}
+
+pub fn from_bytes(bytes: &[u8]) -> Rope {
+    Rope {
+        root: build(bytes),
+    }
+}
+
+pub fn len(rope: &Rope) -> usize {
+    node_len(&rope.root)
+}
+
+pub fn line_count(rope: &Rope) -> usize {
+    node_lines(&rope.root) + 1
+}
+
+pub fn line_start(rope: &Rope, line: usize) -> usize {
+    if line == 0 {
+        0
+    } else {
+        newline_offset(&rope.root, line) + 1
+    }
+}
+
+pub fn line_end(rope: &Rope, line: usize) -> usize {
+    newline_offset(&rope.root, line + 1)
+}
+
+pub fn line_len(rope: &Rope, line: usize) -> usize {
+    line_end(rope, line) - line_start(rope, line)
+}
+
+pub fn line(rope: &Rope, line: usize) -> Vec<u8> {
+    slice(rope, line_start(rope, line), line_end(rope, line))
+}
+
+pub fn line_of_offset(rope: &Rope, offset: usize) -> usize {
+    count_lines(&rope.root, 0, offset)
+}
+
+pub fn byte_at(rope: &Rope, offset: usize) -> u8 {
+    byte(&rope.root, offset)
+}
+
+pub fn slice(rope: &Rope, start: usize, end: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(end - start);
+    collect(&rope.root, start, end, &mut out);
+
+    out
+}
+
+pub fn to_bytes(rope: &Rope) -> Vec<u8> {
+    slice(rope, 0, len(rope))
+}
+
+pub fn insert(rope: &mut Rope, at: usize, bytes: &[u8]) {
+    if bytes.is_empty() || insert_in_leaf(&mut rope.root, at, bytes) {
+        return;
+    }
+
+    let root = std::mem::replace(&mut rope.root, Node::Leaf(Vec::new()));
+    let (left, right) = split(root, at);
+    rope.root = join(join(left, build(bytes)), right);
+}
+
+pub fn delete(rope: &mut Rope, start: usize, end: usize) {
+    if start >= end || delete_in_leaf(&mut rope.root, start, end) {
+        return;
+    }
+
+    let root = std::mem::replace(&mut rope.root, Node::Leaf(Vec::new()));
+    let (left, rest) = split(root, start);
+    let (_, right) = split(rest, end - start);
+    rope.root = join(left, right);
+}
@@ -0,0 +1,225 @@
+use super::grapheme;
+use super::rope::{self, Rope};
+
+pub struct Isearch {
+    pub query: Vec<u8>,
+    pub forward: bool,
+    pub case_fold: bool,
+    pub origin: usize,
+    pub current: Option<[usize; 2]>,
+    pub failing: bool,
+    pub wrapped: bool,
+}
+
+pub fn start(origin: usize, forward: bool) -> Isearch {
+    Isearch {
+        query: Vec::new(),
+        forward,
+        case_fold: true,
+        origin,
+        current: None,
+        failing: false,
+        wrapped: false,
+    }
+}
+
+fn same_char(a: char, b: char, case_fold: bool) -> bool {
+    a == b || (case_fold && a.to_lowercase().eq(b.to_lowercase()))
+}
+
+pub fn match_at(haystack: &[u8], at: usize, query: &[u8], case_fold: bool) -> Option<usize> {
+    let mut i = at;
+    let mut j = 0;
+
+    while j < query.len() {
+        if i >= haystack.len() {
+            return None;
+        }
+
+        let (a, a_len) = grapheme::decode(haystack, i);
+        let (b, b_len) = grapheme::decode(query, j);
+
+        if !same_char(a, b, case_fold) {
+            return None;
+        }
+
+        i += a_len;
+        j += b_len;
+    }
+
+    Some(i)
+}
+
+fn is_char_start(haystack: &[u8], at: usize) -> bool {
+    at >= haystack.len() || haystack[at] & 0xc0 != 0x80
+}
+
+pub fn find_forward(haystack: &[u8], query: &[u8], from: usize, case_fold: bool) -> Option<[usize; 2]> {
+    if query.is_empty() {
+        return None;
+    }
+
+    for start in from..haystack.len() {
+        if !is_char_start(haystack, start) {
+            continue;
+        }
+
+        if let Some(end) = match_at(haystack, start, query, case_fold) {
+            return Some([start, end]);
+        }
+    }
+
+    None
+}
+
+pub fn find_backward(haystack: &[u8], query: &[u8], before: usize, case_fold: bool) -> Option<[usize; 2]> {
+    if query.is_empty() {
+        return None;
+    }
+
+    for start in (0..std::cmp::min(before, haystack.len())).rev() {
+        if !is_char_start(haystack, start) {
+            continue;
+        }
+
+        if let Some(end) = match_at(haystack, start, query, case_fold) {
+            return Some([start, end]);
+        }
+    }
+
+    None
+}
+
+pub fn find_all(haystack: &[u8], query: &[u8], case_fold: bool) -> Vec<[usize; 2]> {
+    let mut matches = Vec::new();
+    let mut from = 0;
+
+    while let Some([start, end]) = find_forward(haystack, query, from, case_fold) {
+        matches.push([start, end]);
+        from = end;
+    }
+
+    matches
+}
+
+// The searches below go a line at a time outward from `from`, so a step
+// costs the distance to the match rather than the size of the text. Matches
+// don't span lines, the same as the highlighted ones.
+fn find_forward_in(text: &Rope, query: &[u8], from: usize, case_fold: bool) -> Option<[usize; 2]> {
+    if query.is_empty() || from > rope::len(text) {
+        return None;
+    }
+
+    for line in rope::line_of_offset(text, from)..rope::line_count(text) {
+        let start = rope::line_start(text, line);
+        let found = find_forward(&rope::line(text, line), query, from.saturating_sub(start), case_fold);
+
+        if let Some([match_start, match_end]) = found {
+            return Some([start + match_start, start + match_end]);
+        }
+    }
+
+    None
+}
+
+fn find_backward_in(text: &Rope, query: &[u8], before: usize, case_fold: bool) -> Option<[usize; 2]> {
+    if query.is_empty() {
+        return None;
+    }
+
+    let before = std::cmp::min(before, rope::len(text));
+    for line in (0..=rope::line_of_offset(text, before)).rev() {
+        let start = rope::line_start(text, line);
+        let found = find_backward(&rope::line(text, line), query, before - start, case_fold);
+
+        if let Some([match_start, match_end]) = found {
+            return Some([start + match_start, start + match_end]);
+        }
+    }
+
+    None
+}
+
+fn find(isearch: &mut Isearch, text: &Rope, from: usize) {
+    let found = if isearch.forward {
+        find_forward_in(text, &isearch.query, from, isearch.case_fold)
+    } else {
+        find_backward_in(text, &isearch.query, from, isearch.case_fold)
+    };
+
+    isearch.failing = found.is_none() && !isearch.query.is_empty();
+
+    if found.is_some() || isearch.query.is_empty() {
+        isearch.current = found;
+    }
+}
+
+pub fn update(isearch: &mut Isearch, text: &Rope) {
+    let from = match isearch.current {
+        Some([start, _]) if isearch.forward => start,
+        Some([start, _]) => start + 1,
+        None => isearch.origin,
+    };
+
+    find(isearch, text, from);
+}
+
+pub fn repeat(isearch: &mut Isearch, text: &Rope, forward: bool) {
+    if isearch.forward != forward {
+        isearch.forward = forward;
+        isearch.failing = false;
+    }
+
+    let from = if isearch.failing {
+        isearch.wrapped = true;
+
+        if forward {
+            0
+        } else {
+            rope::len(text)
+        }
+    } else {
+        match isearch.current {
+            Some([_, end]) if forward => end,
+            Some([start, _]) => start,
+            None => isearch.origin,
+        }
+    };
+
+    find(isearch, text, from);
+}
+
+pub fn point(isearch: &Isearch) -> usize {
+    match isearch.current {
+        Some([_, end]) if isearch.forward => end,
+        Some([start, _]) => start,
+        None => isearch.origin,
+    }
+}
+
+pub fn prompt(isearch: &Isearch) -> Vec<u8> {
+    let mut prompt = Vec::new();
+
+    if isearch.failing {
+        prompt.extend_from_slice(b"Failing ");
+    }
+
+    if isearch.wrapped {
+        prompt.extend_from_slice(b"Wrapped ");
+    }
+
+    prompt.extend_from_slice(b"I-search");
+
+    if !isearch.forward {
+        prompt.extend_from_slice(b" backward");
+    }
+
+    if !isearch.case_fold {
+        prompt.extend_from_slice(b" [case]");
+    }
+
+    prompt.extend_from_slice(b": ");
+    prompt.extend_from_slice(&isearch.query);
+
+    prompt
+}
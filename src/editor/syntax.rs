@@ -0,0 +1,445 @@
+use super::rope;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Language {
+    Plain,
+    Rust,
+    C,
+    Markdown,
+    // Indented by its rules, but not highlighted yet.
+    Python,
+}
+
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Kind {
+    Text,
+    Keyword,
+    Type,
+    Function,
+    String,
+    Number,
+    Comment,
+    Macro,
+    Heading,
+    Emphasis,
+    Code,
+    Link,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum State {
+    Normal,
+    BlockComment(u32),
+    String,
+    RawString(u8),
+    Preprocessor,
+    Fenced,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub kind: Kind,
+}
+
+pub struct Highlighter {
+    pub language: Language,
+    states: Vec<State>,
+    spans: Vec<Vec<Span>>,
+    dirty: Vec<bool>,
+}
+
+const RUST_KEYWORDS: &[&[u8]] = &[
+    b"as", b"async", b"await", b"break", b"const", b"continue", b"crate", b"dyn", b"else", b"enum", b"extern",
+    b"false", b"fn", b"for", b"if", b"impl", b"in", b"let", b"loop", b"match", b"mod", b"move", b"mut", b"pub",
+    b"ref", b"return", b"self", b"Self", b"static", b"struct", b"super", b"trait", b"true", b"type", b"unsafe",
+    b"use", b"where", b"while",
+];
+
+const RUST_TYPES: &[&[u8]] = &[
+    b"bool", b"char", b"str", b"u8", b"u16", b"u32", b"u64", b"u128", b"usize", b"i8", b"i16", b"i32", b"i64",
+    b"i128", b"isize", b"f32", b"f64",
+];
+
+const C_KEYWORDS: &[&[u8]] = &[
+    b"auto", b"break", b"case", b"const", b"continue", b"default", b"do", b"else", b"enum", b"extern", b"for",
+    b"goto", b"if", b"inline", b"register", b"restrict", b"return", b"sizeof", b"static", b"struct", b"switch",
+    b"typedef", b"union", b"volatile", b"while", b"NULL", b"true", b"false",
+];
+
+const C_TYPES: &[&[u8]] = &[
+    b"char", b"double", b"float", b"int", b"long", b"short", b"signed", b"unsigned", b"void", b"bool",
+    b"size_t", b"ssize_t", b"uint8_t", b"uint16_t", b"uint32_t", b"uint64_t", b"int8_t", b"int16_t", b"int32_t",
+    b"int64_t",
+];
+
+pub fn language_from_file_name(file_name: &[u8]) -> Language {
+    let extension = file_name.rsplit(|c| *c == b'.').next().unwrap_or(&[]);
+
+    if !file_name.contains(&b'.') {
+        return Language::Plain;
+    }
+
+    match extension {
+        b"rs" => Language::Rust,
+        b"c" | b"h" => Language::C,
+        b"md" | b"markdown" => Language::Markdown,
+        b"py" => Language::Python,
+        _ => Language::Plain,
+    }
+}
+
+pub fn new(language: Language, line_count: usize) -> Highlighter {
+    Highlighter {
+        language,
+        states: vec![State::Normal; line_count],
+        spans: vec![Vec::new(); line_count],
+        dirty: vec![true; line_count],
+    }
+}
+
+pub fn edited(highlighter: &mut Highlighter, line: usize, removed_lines: usize, inserted_lines: usize) {
+    let line = std::cmp::min(line, highlighter.dirty.len().saturating_sub(1));
+    let removed = std::cmp::min(removed_lines, highlighter.dirty.len().saturating_sub(line + 1));
+    let state = highlighter.states.get(line).copied().unwrap_or(State::Normal);
+
+    highlighter.states.splice(line + 1..line + 1 + removed, std::iter::repeat_n(state, inserted_lines));
+    highlighter.spans.splice(line + 1..line + 1 + removed, std::iter::repeat_n(Vec::new(), inserted_lines));
+    highlighter.dirty.splice(line + 1..line + 1 + removed, std::iter::repeat_n(true, inserted_lines));
+
+    if let Some(dirty) = highlighter.dirty.get_mut(line) {
+        *dirty = true;
+    }
+}
+
+pub fn update(highlighter: &mut Highlighter, text: &rope::Rope) -> usize {
+    let line_count = rope::line_count(text);
+
+    if highlighter.dirty.len() != line_count {
+        *highlighter = new(highlighter.language, line_count);
+    }
+
+    let mut highlighted = 0;
+    let mut y = if let Some(y) = highlighter.dirty.iter().position(|dirty| *dirty) {
+        y
+    } else {
+        return 0;
+    };
+
+    let mut state = highlighter.states[y];
+
+    while y < line_count {
+        if !highlighter.dirty[y] && highlighter.states[y] == state {
+            y = if let Some(next) = highlighter.dirty[y..].iter().position(|dirty| *dirty) {
+                y + next
+            } else {
+                break;
+            };
+
+            state = highlighter.states[y];
+            continue;
+        }
+
+        let (spans, end) = highlight_line(highlighter.language, &rope::line(text, y), state);
+        highlighter.states[y] = state;
+        highlighter.spans[y] = spans;
+        highlighter.dirty[y] = false;
+        highlighted += 1;
+
+        state = end;
+        y += 1;
+    }
+
+    highlighted
+}
+
+pub fn spans(highlighter: &Highlighter, line: usize) -> &[Span] {
+    highlighter.spans.get(line).map(|spans| spans.as_slice()).unwrap_or(&[])
+}
+
+pub fn kind_at(spans: &[Span], at: usize) -> Kind {
+    spans.iter().find(|span| span.start <= at && at < span.end).map(|span| span.kind).unwrap_or(Kind::Text)
+}
+
+pub fn highlight_line(language: Language, line: &[u8], state: State) -> (Vec<Span>, State) {
+    match language {
+        Language::Plain | Language::Python => (Vec::new(), State::Normal),
+        Language::Rust | Language::C => highlight_code(language, line, state),
+        Language::Markdown => highlight_markdown(line, state),
+    }
+}
+
+fn push(spans: &mut Vec<Span>, start: usize, end: usize, kind: Kind) {
+    if start < end {
+        spans.push(Span { start, end, kind });
+    }
+}
+
+fn is_ident(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_' || c >= 0x80
+}
+
+fn scan_string(line: &[u8], mut i: usize) -> (usize, bool) {
+    while i < line.len() {
+        match line[i] {
+            b'\\' => i += 2,
+            b'"' => return (i + 1, true),
+            _ => i += 1,
+        }
+    }
+
+    (line.len(), false)
+}
+
+fn scan_raw_string(line: &[u8], mut i: usize, hashes: u8) -> (usize, bool) {
+    while i < line.len() {
+        if line[i] == b'"' && line[i + 1..].iter().take(hashes as usize).filter(|c| **c == b'#').count() == hashes as usize {
+            return (i + 1 + hashes as usize, true);
+        }
+
+        i += 1;
+    }
+
+    (line.len(), false)
+}
+
+fn scan_block_comment(line: &[u8], mut i: usize, mut depth: u32, nested: bool) -> (usize, u32) {
+    while i < line.len() {
+        if line[i..].starts_with(b"*/") {
+            depth -= 1;
+            i += 2;
+
+            if depth == 0 {
+                break;
+            }
+        } else if nested && line[i..].starts_with(b"/*") {
+            depth += 1;
+            i += 2;
+        } else {
+            i += 1;
+        }
+    }
+
+    (i, depth)
+}
+
+fn highlight_code(language: Language, line: &[u8], state: State) -> (Vec<Span>, State) {
+    let rust = language == Language::Rust;
+    let (keywords, types) = if rust { (RUST_KEYWORDS, RUST_TYPES) } else { (C_KEYWORDS, C_TYPES) };
+
+    let mut spans = Vec::new();
+    let mut state = state;
+    let mut i = 0;
+
+    match state {
+        State::BlockComment(depth) => {
+            let (end, depth) = scan_block_comment(line, 0, depth, rust);
+            push(&mut spans, 0, end, Kind::Comment);
+            state = if depth == 0 { State::Normal } else { State::BlockComment(depth) };
+            i = end;
+        },
+        State::String => {
+            let (end, closed) = scan_string(line, 0);
+            push(&mut spans, 0, end, Kind::String);
+            state = if closed { State::Normal } else { State::String };
+            i = end;
+        },
+        State::RawString(hashes) => {
+            let (end, closed) = scan_raw_string(line, 0, hashes);
+            push(&mut spans, 0, end, Kind::String);
+            state = if closed { State::Normal } else { State::RawString(hashes) };
+            i = end;
+        },
+        State::Preprocessor => {
+            push(&mut spans, 0, line.len(), Kind::Macro);
+            let next = if line.ends_with(b"\\") { State::Preprocessor } else { State::Normal };
+            return (spans, next);
+        },
+        State::Normal | State::Fenced => state = State::Normal,
+    }
+
+    while i < line.len() && state == State::Normal {
+        let c = line[i];
+        let start = i;
+
+        if c.is_ascii_whitespace() {
+            i += 1;
+        } else if line[i..].starts_with(b"//") {
+            push(&mut spans, i, line.len(), Kind::Comment);
+            i = line.len();
+        } else if line[i..].starts_with(b"/*") {
+            let (end, depth) = scan_block_comment(line, i + 2, 1, rust);
+            push(&mut spans, start, end, Kind::Comment);
+            state = if depth == 0 { State::Normal } else { State::BlockComment(depth) };
+            i = end;
+        } else if c == b'"' || (rust && c == b'b' && line.get(i + 1) == Some(&b'"')) {
+            let (end, closed) = scan_string(line, i + if c == b'b' { 2 } else { 1 });
+            push(&mut spans, start, end, Kind::String);
+            state = if closed { State::Normal } else { State::String };
+            i = end;
+        } else if rust && (c == b'r' || (c == b'b' && line.get(i + 1) == Some(&b'r'))) && {
+            let prefix = if c == b'b' { 2 } else { 1 };
+            let hashes = line[i + prefix..].iter().take_while(|c| **c == b'#').count();
+            line.get(i + prefix + hashes) == Some(&b'"')
+        } {
+            let prefix = if c == b'b' { 2 } else { 1 };
+            let hashes = line[i + prefix..].iter().take_while(|c| **c == b'#').count();
+            let (end, closed) = scan_raw_string(line, i + prefix + hashes + 1, hashes as u8);
+            push(&mut spans, start, end, Kind::String);
+            state = if closed { State::Normal } else { State::RawString(hashes as u8) };
+            i = end;
+        } else if c == b'\'' {
+            let end = if line.get(i + 1) == Some(&b'\\') {
+                line.get(i + 3..).and_then(|rest| rest.iter().position(|c| *c == b'\'')).map(|end| i + end + 4)
+            } else if let Some(next) = line.get(i + 1) {
+                let len = grapheme_len(*next);
+                if line.get(i + 1 + len) == Some(&b'\'') { Some(i + len + 2) } else { None }
+            } else {
+                None
+            };
+
+            if let Some(end) = end.or(if rust { None } else { Some(line.len()) }) {
+                push(&mut spans, start, end, Kind::String);
+                i = end;
+            } else {
+                i += 1;
+                while i < line.len() && is_ident(line[i]) {
+                    i += 1;
+                }
+
+                push(&mut spans, start, i, Kind::Type);
+            }
+        } else if !rust && c == b'#' && line[..i].iter().all(|c| c.is_ascii_whitespace()) {
+            push(&mut spans, start, line.len(), Kind::Macro);
+            let next = if line.ends_with(b"\\") { State::Preprocessor } else { State::Normal };
+            return (spans, next);
+        } else if rust && c == b'#' && matches!(line.get(i + 1), Some(b'[') | Some(b'!')) {
+            let end = line[i..].iter().position(|c| *c == b']').map(|end| i + end + 1).unwrap_or(line.len());
+            push(&mut spans, start, end, Kind::Macro);
+            i = end;
+        } else if c.is_ascii_digit() {
+            while i < line.len() && (is_ident(line[i]) || (line[i] == b'.' && line.get(i + 1).map(|c| c.is_ascii_digit()).unwrap_or(false))) {
+                i += 1;
+            }
+
+            push(&mut spans, start, i, Kind::Number);
+        } else if is_ident(c) {
+            while i < line.len() && is_ident(line[i]) {
+                i += 1;
+            }
+
+            let word = &line[start..i];
+            let kind = if keywords.contains(&word) {
+                Kind::Keyword
+            } else if types.contains(&word) || (rust && c.is_ascii_uppercase()) {
+                Kind::Type
+            } else if rust && line.get(i) == Some(&b'!') && line.get(i + 1) != Some(&b'=') {
+                i += 1;
+                Kind::Macro
+            } else if line[i..].iter().find(|c| !c.is_ascii_whitespace()) == Some(&b'(') {
+                Kind::Function
+            } else {
+                Kind::Text
+            };
+
+            if kind != Kind::Text {
+                push(&mut spans, start, i, kind);
+            }
+        } else {
+            i += grapheme_len(c);
+        }
+    }
+
+    (spans, state)
+}
+
+fn grapheme_len(c: u8) -> usize {
+    if c < 0x80 {
+        1
+    } else if c & 0xe0 == 0xc0 {
+        2
+    } else if c & 0xf0 == 0xe0 {
+        3
+    } else {
+        4
+    }
+}
+
+fn highlight_markdown(line: &[u8], state: State) -> (Vec<Span>, State) {
+    let mut spans = Vec::new();
+    let indent = line.iter().take_while(|c| **c == b' ').count();
+    let trimmed = &line[indent..];
+
+    if state == State::Fenced {
+        push(&mut spans, 0, line.len(), Kind::Code);
+        let next = if trimmed.starts_with(b"```") { State::Normal } else { State::Fenced };
+        return (spans, next);
+    }
+
+    if trimmed.starts_with(b"```") {
+        push(&mut spans, 0, line.len(), Kind::Code);
+        return (spans, State::Fenced);
+    }
+
+    let hashes = trimmed.iter().take_while(|c| **c == b'#').count();
+    if (1..=6).contains(&hashes) && matches!(trimmed.get(hashes), None | Some(b' ')) {
+        push(&mut spans, 0, line.len(), Kind::Heading);
+        return (spans, State::Normal);
+    }
+
+    if trimmed.starts_with(b">") {
+        push(&mut spans, 0, line.len(), Kind::Comment);
+        return (spans, State::Normal);
+    }
+
+    let mut i = indent;
+    let digits = trimmed.iter().take_while(|c| c.is_ascii_digit()).count();
+
+    if matches!(trimmed, [b'-' | b'*' | b'+', b' ', ..]) {
+        push(&mut spans, i, i + 1, Kind::Keyword);
+        i += 2;
+    } else if digits > 0 && trimmed[digits..].starts_with(b". ") {
+        push(&mut spans, i, i + digits + 1, Kind::Keyword);
+        i += digits + 2;
+    }
+
+    while i < line.len() {
+        let c = line[i];
+
+        if c == b'`' {
+            let end = line[i + 1..].iter().position(|c| *c == b'`').map(|end| i + end + 2).unwrap_or(line.len());
+            push(&mut spans, i, end, Kind::Code);
+            i = end;
+        } else if c == b'*' || c == b'_' {
+            let width = if line.get(i + 1) == Some(&c) { 2 } else { 1 };
+            let delimiter = &line[i..i + width];
+            let close = line[i + width..].windows(width).position(|w| w == delimiter);
+
+            match close {
+                Some(close) if close > 0 => {
+                    let end = i + width + close + width;
+                    push(&mut spans, i, end, Kind::Emphasis);
+                    i = end;
+                },
+                _ => i += width,
+            }
+        } else if c == b'[' {
+            let close = line[i..].iter().position(|c| *c == b']').map(|close| i + close);
+            let end = close.filter(|close| line.get(close + 1) == Some(&b'(')).and_then(|close| line[close..].iter().position(|c| *c == b')').map(|end| close + end + 1));
+
+            match end {
+                Some(end) => {
+                    push(&mut spans, i, end, Kind::Link);
+                    i = end;
+                },
+                None => i += 1,
+            }
+        } else {
+            i += 1;
+        }
+    }
+
+    (spans, State::Normal)
+}
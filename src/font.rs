@@ -15,34 +15,49 @@ pub struct GlyphMetrics {
     pub y_offset: u32,
 }
 
+struct Shelf {
+    y: u32,
+    free: Vec<[u32; 2]>,
+}
+
+struct Bitmap {
+    data: Vec<u8>,
+    width: u32,
+    height: u32,
+    left: i32,
+    top: i32,
+    scaled: bool,
+}
+
 pub struct TrueTypeFont {
     pub texture_atlas: Vec<u8>,
     pub width: u32,
     pub height: u32,
     pub metrics: Vec<GlyphMetrics>,
+    pub glyph_indices: std::collections::HashMap<char, u32>,
+    pub dirty_regions: Vec<[u32; 4]>,
+    pub dirty_glyphs: Vec<u32>,
     pub line_height: u32,
     pub x_ratio: f32,
     pub scale: f32,
-}
 
-fn add_bitmap_to_atlas(
-    x_offset: usize,
-    y_offset: usize,
-    bitmap: &[u8],
-    texture_width: usize,
-    width: usize,
-    height: usize,
-    atlas: &mut [u8]
-) {
-    for j in 0..height {
-        let line_offset = (j + y_offset) * texture_width;
-        for i in 0..width {
-            atlas[x_offset + i + line_offset] = bitmap[i + j * width];
-        }
-    }
+    faces: Vec<freetype::Face>,
+    library: freetype::Library,
+    size: u8,
+    ascender: i32,
+    advance: u32,
+    shelves: Vec<Shelf>,
+    slot_chars: Vec<Option<char>>,
+    free_slots: Vec<u32>,
+    last_used: Vec<u64>,
+    frame: u64,
 }
 
 const PADDING: u32 = 3;
+const ATLAS_COLUMNS: u32 = 32;
+const ATLAS_ROWS: u32 = 32;
+const MAX_CELLS_PER_GLYPH: u32 = 2;
+const REPLACEMENT_CHAR: char = '\u{fffd}';
 
 fn get_monospaced_advance(face: &freetype::Face) -> u32 {
     face.load_char(b'a' as usize, freetype::face::LoadFlag::RENDER).unwrap();
@@ -51,87 +66,319 @@ fn get_monospaced_advance(face: &freetype::Face) -> u32 {
     advance.x as u32 >> 6
 }
 
-pub fn init(path: &str, code_points: &[u8], size: u8) -> Result<TrueTypeFont, ParseError> {
-    let lib = freetype::Library::init().unwrap();
-    let face = lib.new_face(path, 0).unwrap();
+pub fn init(path: &str, code_points: &[char], size: u8) -> Result<TrueTypeFont, ParseError> {
+    let library = freetype::Library::init().map_err(|_| ParseError::LibTrueTypeNotFound)?;
+    let face = library.new_face(path, 0).map_err(|_| ParseError::FailToParse)?;
 
-    face.set_char_size(size as isize * 72, 0, 72, 72).unwrap();
+    face.set_char_size(size as isize * 72, 0, 72, 72).map_err(|_| ParseError::InvalidValue)?;
 
     let scale = size as f32 / face.em_size() as f32;
     let ascender = (face.ascender() as f32 * scale).round() as i32;
-    let max_advance = get_monospaced_advance(&face);
+    let advance = get_monospaced_advance(&face);
+    let line_height = (scale * face.height() as f32) as u32;
 
-    let (glyphs_per_row, line_count) = {
-        let len = code_points.len() as f32;
-        let height: f32 = len.sqrt().floor();
-        ((len as f32 / height).ceil() as u32, height as u32)
+    let width = ATLAS_COLUMNS * (advance + PADDING) + PADDING;
+    let height = ATLAS_ROWS * (line_height + PADDING) + PADDING;
+
+    let shelves = (0..ATLAS_ROWS).map(|row| Shelf {
+        y: PADDING + row * (line_height + PADDING),
+        free: vec![[PADDING, width - PADDING]],
+    }).collect();
+
+    let mut font = TrueTypeFont {
+        texture_atlas: vec![0; (width * height) as usize],
+        width,
+        height,
+        metrics: Vec::new(),
+        glyph_indices: std::collections::HashMap::with_capacity(code_points.len()),
+        dirty_regions: Vec::new(),
+        dirty_glyphs: Vec::new(),
+        line_height,
+        x_ratio: advance as f32 / line_height as f32,
+        scale,
+        faces: vec![face],
+        library,
+        size,
+        ascender,
+        advance,
+        shelves,
+        slot_chars: Vec::new(),
+        free_slots: Vec::new(),
+        last_used: Vec::new(),
+        frame: 0,
     };
 
-    let line_height = (scale * face.height() as f32) as u32;
-    let texture_width = (glyphs_per_row + PADDING) * max_advance;
-    let texture_height = (line_height + PADDING) * line_count;
+    glyph(&mut font, REPLACEMENT_CHAR);
+    for code_point in code_points.iter() {
+        glyph(&mut font, *code_point);
+    }
 
-    let mut metrics = Vec::new();
-    let mut texture: Vec<u8> = vec![0; (texture_width * texture_height) as usize];
+    Ok(font)
+}
 
-    let mut x_offset = PADDING;
-    let mut y_offset = PADDING;
-    let mut i = 0;
+pub fn add_fallback(font: &mut TrueTypeFont, path: &str) -> Result<(), ParseError> {
+    let face = font.library.new_face(path, 0).map_err(|_| ParseError::FailToParse)?;
 
-    for code_point in code_points.iter() {
-        face.load_char(*code_point as usize, freetype::face::LoadFlag::RENDER).unwrap();
-
-        let glyph = face.glyph();
-        let bitmap = glyph.bitmap();
-        let buffer = bitmap.buffer();
-        let width = bitmap.width();
-        let height = bitmap.rows();
-        let metric = glyph.metrics();
-        let advance = max_advance;
-
-        let left_bearing = (metric.horiBearingX as f32 * scale).round() as u32;
-        let topmost = glyph.bitmap_top();
-        let top_offset = ascender - topmost;
-
-        add_bitmap_to_atlas(
-            (left_bearing + x_offset) as usize,
-            (top_offset + y_offset as i32) as usize,
-            &buffer,
-            texture_width as usize,
-            width as usize,
-            height as usize,
-            &mut texture,
-        );
-
-        metrics.push(GlyphMetrics {
-            width: advance,
-            x_offset,
-            y_offset,
-        });
+    if face.set_char_size(font.size as isize * 72, 0, 72, 72).is_err() {
+        let _ = face.set_pixel_sizes(0, font.line_height);
+    }
+
+    font.faces.push(face);
+
+    Ok(())
+}
+
+pub fn capacity(font: &TrueTypeFont) -> usize {
+    (font.width / (font.advance + PADDING) * ATLAS_ROWS) as usize
+}
 
-        x_offset += advance + PADDING;
+pub fn begin_frame(font: &mut TrueTypeFont) {
+    font.frame += 1;
+}
 
-        i += 1;
-        if i == glyphs_per_row {
-            i = 0;
+pub fn glyph(font: &mut TrueTypeFont, c: char) -> u32 {
+    if let Some(slot) = font.glyph_indices.get(&c) {
+        font.last_used[*slot as usize] = font.frame;
+        return *slot;
+    }
+
+    if let Some(slot) = rasterize(font, c) {
+        return slot;
+    }
 
-            y_offset += line_height + PADDING;
-            x_offset = 0;
+    match font.glyph_indices.get(&REPLACEMENT_CHAR).or(font.glyph_indices.get(&'?')) {
+        Some(slot) => *slot,
+        None => 0,
+    }
+}
+
+pub fn take_dirty(font: &mut TrueTypeFont) -> (Vec<[u32; 4]>, Vec<u32>) {
+    (std::mem::take(&mut font.dirty_regions), std::mem::take(&mut font.dirty_glyphs))
+}
+
+fn load_bitmap(font: &TrueTypeFont, c: char) -> Option<Bitmap> {
+    let face = font.faces.iter().find(|face| face.get_char_index(c as usize).is_some()).unwrap_or(&font.faces[0]);
+    face.load_char(c as usize, freetype::face::LoadFlag::RENDER | freetype::face::LoadFlag::COLOR).ok()?;
+
+    let glyph = face.glyph();
+    let bitmap = glyph.bitmap();
+    let buffer = bitmap.buffer();
+    let width = bitmap.width() as u32;
+    let height = bitmap.rows() as u32;
+    let pitch = bitmap.pitch().unsigned_abs() as usize;
+    let mut data = vec![0; (width * height) as usize];
+
+    for j in 0..height as usize {
+        for i in 0..width as usize {
+            data[i + j * width as usize] = match bitmap.pixel_mode() {
+                Ok(freetype::bitmap::PixelMode::Mono) => if buffer[j * pitch + i / 8] & (0x80 >> (i % 8)) != 0 { 255 } else { 0 },
+                Ok(freetype::bitmap::PixelMode::Bgra) => buffer[j * pitch + i * 4 + 3],
+                _ => buffer[j * pitch + i],
+            };
         }
     }
 
-    let x_ratio = max_advance as f32 / line_height as f32;
+    let max_width = font.advance * MAX_CELLS_PER_GLYPH;
+    if width <= max_width && height <= font.line_height {
+        return Some(Bitmap {
+            data,
+            width,
+            height,
+            left: glyph.bitmap_left(),
+            top: glyph.bitmap_top(),
+            scaled: false,
+        });
+    }
+
+    let factor = f32::min(max_width as f32 / width as f32, font.line_height as f32 / height as f32);
+    let scaled_width = std::cmp::max(1, (width as f32 * factor) as u32);
+    let scaled_height = std::cmp::max(1, (height as f32 * factor) as u32);
+    let mut scaled = vec![0; (scaled_width * scaled_height) as usize];
+
+    for j in 0..scaled_height {
+        for i in 0..scaled_width {
+            let x0 = (i as f32 / factor) as u32;
+            let y0 = (j as f32 / factor) as u32;
+            let x1 = std::cmp::min(width, ((i + 1) as f32 / factor).ceil() as u32);
+            let y1 = std::cmp::min(height, ((j + 1) as f32 / factor).ceil() as u32);
+
+            let mut sum = 0;
+            let mut count = 0;
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    sum += data[(x + y * width) as usize] as u32;
+                    count += 1;
+                }
+            }
+
+            scaled[(i + j * scaled_width) as usize] = (sum / std::cmp::max(count, 1)) as u8;
+        }
+    }
 
-    Ok(TrueTypeFont {
-        texture_atlas: texture,
-        width: texture_width,
-        height: texture_height,
-        metrics,
-        line_height,
-        x_ratio,
-        scale
+    Some(Bitmap {
+        data: scaled,
+        width: scaled_width,
+        height: scaled_height,
+        left: 0,
+        top: 0,
+        scaled: true,
     })
 }
+
+fn rasterize(font: &mut TrueTypeFont, c: char) -> Option<u32> {
+    let bitmap = load_bitmap(font, c)?;
+    let width = (bitmap.width as i32 + std::cmp::max(bitmap.left, 0)).clamp(font.advance as i32, (font.advance * MAX_CELLS_PER_GLYPH) as i32) as u32;
+
+    let [x, y] = loop {
+        if let Some(position) = allocate(font, width) {
+            break position;
+        }
+
+        if !evict(font) {
+            return None;
+        }
+    };
+
+    let (x_offset, y_offset) = if bitmap.scaled {
+        (x as i32 + (width - bitmap.width) as i32 / 2, y as i32 + (font.line_height - bitmap.height) as i32 / 2)
+    } else {
+        (x as i32 + std::cmp::max(bitmap.left, 0), y as i32 + font.ascender - bitmap.top)
+    };
+
+    add_bitmap_to_atlas(
+        x_offset,
+        y_offset,
+        [x, y, width, font.line_height],
+        &bitmap.data,
+        font.width as usize,
+        [bitmap.width as usize, bitmap.height as usize],
+        &mut font.texture_atlas,
+    );
+
+    let metric = GlyphMetrics {
+        width,
+        x_offset: x,
+        y_offset: y,
+    };
+
+    let slot = if let Some(slot) = font.free_slots.pop() {
+        font.metrics[slot as usize] = metric;
+        font.slot_chars[slot as usize] = Some(c);
+        font.last_used[slot as usize] = font.frame;
+        slot
+    } else {
+        font.metrics.push(metric);
+        font.slot_chars.push(Some(c));
+        font.last_used.push(font.frame);
+        font.metrics.len() as u32 - 1
+    };
+
+    font.glyph_indices.insert(c, slot);
+    font.dirty_regions.push([x, y, width, font.line_height]);
+    font.dirty_glyphs.push(slot);
+
+    Some(slot)
+}
+
+fn allocate(font: &mut TrueTypeFont, width: u32) -> Option<[u32; 2]> {
+    for shelf in font.shelves.iter_mut() {
+        for i in 0..shelf.free.len() {
+            let [start, end] = shelf.free[i];
+
+            if end - start >= width + PADDING {
+                shelf.free[i][0] += width + PADDING;
+
+                if shelf.free[i][0] >= shelf.free[i][1] {
+                    shelf.free.remove(i);
+                }
+
+                return Some([start, shelf.y]);
+            }
+        }
+    }
+
+    None
+}
+
+fn evict(font: &mut TrueTypeFont) -> bool {
+    let mut oldest: Option<usize> = None;
+
+    for (slot, c) in font.slot_chars.iter().enumerate() {
+        if c.is_none() || font.last_used[slot] >= font.frame || *c == Some(REPLACEMENT_CHAR) {
+            continue;
+        }
+
+        if oldest.map(|o| font.last_used[slot] < font.last_used[o]).unwrap_or(true) {
+            oldest = Some(slot);
+        }
+    }
+
+    let slot = if let Some(slot) = oldest {
+        slot
+    } else {
+        return false;
+    };
+
+    let metric = &font.metrics[slot];
+    let [x, y, width] = [metric.x_offset, metric.y_offset, metric.width];
+
+    for j in y..y + font.line_height {
+        let line_offset = (j * font.width) as usize;
+        font.texture_atlas[line_offset + x as usize..line_offset + (x + width) as usize].fill(0);
+    }
+
+    let shelf = font.shelves.iter_mut().find(|shelf| shelf.y == y).unwrap();
+    let index = shelf.free.iter().position(|span| span[0] > x).unwrap_or(shelf.free.len());
+    shelf.free.insert(index, [x, x + width + PADDING]);
+
+    let mut i = 0;
+    while i + 1 < shelf.free.len() {
+        if shelf.free[i][1] >= shelf.free[i + 1][0] {
+            shelf.free[i][1] = std::cmp::max(shelf.free[i][1], shelf.free[i + 1][1]);
+            shelf.free.remove(i + 1);
+        } else {
+            i += 1;
+        }
+    }
+
+    if let Some(c) = font.slot_chars[slot].take() {
+        font.glyph_indices.remove(&c);
+    }
+
+    font.free_slots.push(slot as u32);
+    font.dirty_regions.push([x, y, width, font.line_height]);
+
+    true
+}
+
+fn add_bitmap_to_atlas(
+    x_offset: i32,
+    y_offset: i32,
+    cell: [u32; 4],
+    bitmap: &[u8],
+    texture_width: usize,
+    size: [usize; 2],
+    atlas: &mut [u8]
+) {
+    let [width, height] = size;
+
+    for j in 0..height {
+        let y = y_offset + j as i32;
+        if y < cell[1] as i32 || y >= (cell[1] + cell[3]) as i32 {
+            continue;
+        }
+
+        let line_offset = y as usize * texture_width;
+        for i in 0..width {
+            let x = x_offset + i as i32;
+            if x < cell[0] as i32 || x >= (cell[0] + cell[2]) as i32 {
+                continue;
+            }
+
+            atlas[x as usize + line_offset] = bitmap[i + j * width];
+        }
+    }
+}
 // use std::io::{ Seek, Read, BufReader};
 // use crate::binding::dl;
 
@@ -1,5 +1,6 @@
 pub mod binding;
 pub mod renderer;
+pub mod editor;
 pub mod font;
 
 #[cfg(test)]
@@ -8,7 +9,1917 @@ mod font_test {
 
     #[test]
     fn init_font() -> Result<(), font::ParseError> {
-        font::init("assets/fonts/font.ttf", &(32..127).collect::<Vec<u8>>(), 100)?;
+        font::init("assets/fonts/font.ttf", &(' '..='~').collect::<Vec<char>>(), 100)?;
         Ok(())
     }
+
+    #[test]
+    fn glyph_cache_evicts_least_recently_used() -> Result<(), font::ParseError> {
+        let mut font = font::init("assets/fonts/font.ttf", &['a'], 20)?;
+        let capacity = font::capacity(&font);
+        let a = font::glyph(&mut font, 'a');
+
+        for i in 0..capacity as u32 * 2 {
+            font::begin_frame(&mut font);
+            assert_eq!(font::glyph(&mut font, 'a'), a);
+            font::glyph(&mut font, char::from_u32(0x4e00 + i).unwrap());
+        }
+
+        assert!(font.metrics.len() <= capacity);
+        assert!(font.glyph_indices.contains_key(&'a'));
+        assert!(!font.glyph_indices.contains_key(&'\u{4e00}'));
+        assert!(font.glyph_indices.contains_key(&char::from_u32(0x4e00 + capacity as u32 * 2 - 1).unwrap()));
+
+        let (regions, glyphs) = font::take_dirty(&mut font);
+        assert!(!regions.is_empty() && !glyphs.is_empty());
+        assert!(regions.iter().all(|[x, y, width, height]| x + width <= font.width && y + height <= font.height));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod rope_test {
+    use super::editor::rope;
+
+    fn next(seed: &mut u64) -> u64 {
+        *seed ^= *seed << 13;
+        *seed ^= *seed >> 7;
+        *seed ^= *seed << 17;
+        *seed
+    }
+
+    fn naive_lines(bytes: &[u8]) -> Vec<&[u8]> {
+        bytes.split(|c| *c == b'\n').collect()
+    }
+
+    fn check(rope: &rope::Rope, model: &[u8]) {
+        assert_eq!(rope::len(rope), model.len());
+        assert_eq!(rope::to_bytes(rope), model);
+
+        let lines = naive_lines(model);
+        assert_eq!(rope::line_count(rope), lines.len());
+
+        let mut start = 0;
+        for (i, line) in lines.iter().enumerate() {
+            assert_eq!(rope::line_start(rope, i), start);
+            assert_eq!(rope::line_len(rope, i), line.len());
+            assert_eq!(&rope::line(rope, i)[..], *line);
+            assert_eq!(rope::line_of_offset(rope, start), i);
+            start += line.len() + 1;
+        }
+    }
+
+    #[test]
+    fn random_edits_match_naive_model() {
+        for round in 0..20 {
+            let mut seed = 0x9E37_79B9_7F4A_7C15 ^ round;
+            let mut model: Vec<u8> = Vec::new();
+            let mut rope = rope::empty();
+
+            for _ in 0..400 {
+                let op = next(&mut seed) % 3;
+                let at = (next(&mut seed) as usize) % (model.len() + 1);
+
+                if op < 2 {
+                    let len = if next(&mut seed) % 10 == 0 { 3000 } else { (next(&mut seed) % 8) as usize };
+                    let bytes: Vec<u8> = (0..len).map(|_| if next(&mut seed) % 6 == 0 { b'\n' } else { b'a' + (next(&mut seed) % 26) as u8 }).collect();
+
+                    rope::insert(&mut rope, at, &bytes);
+                    model.splice(at..at, bytes);
+                } else {
+                    let end = std::cmp::min(model.len(), at + (next(&mut seed) % 2000) as usize);
+
+                    rope::delete(&mut rope, at, end);
+                    model.drain(at..end);
+                }
+            }
+
+            check(&rope, &model);
+        }
+    }
+
+    #[test]
+    fn from_bytes_indexes_lines() {
+        let content = b"fn main() {\n    println!();\n}\n\nend".repeat(500);
+        let rope = rope::from_bytes(&content);
+
+        check(&rope, &content);
+    }
+}
+
+#[cfg(test)]
+mod history_test {
+    use super::editor::buffer::{Cursor, Position};
+    use super::editor::history;
+    use super::editor::rope;
+
+    fn cursor(x: u32, y: u32) -> Vec<Cursor> {
+        vec![Cursor { position: Position { x, y }, selection: None }]
+    }
+
+    fn type_char(text: &mut rope::Rope, history: &mut history::History, x: u32, c: u8) {
+        history::begin(history, history::EditKind::Typing, &cursor(x, 0));
+        rope::insert(text, x as usize, &[c]);
+        history::record(history, history::Change::Insert { offset: x as usize, content: vec![c] });
+        history::commit(history, &cursor(x + 1, 0));
+    }
+
+    #[test]
+    fn consecutive_typing_undoes_as_one_step() {
+        let mut text = rope::empty();
+        let mut history = history::empty();
+
+        for (i, c) in b"hello".iter().enumerate() {
+            type_char(&mut text, &mut history, i as u32, *c);
+        }
+
+        let cursors = history::undo(&mut text, &mut history).unwrap();
+        assert_eq!(rope::to_bytes(&text), b"");
+        assert!(cursors[0].position == Position { x: 0, y: 0 });
+        assert!(history::undo(&mut text, &mut history).is_none());
+
+        let cursors = history::redo(&mut text, &mut history).unwrap();
+        assert_eq!(rope::to_bytes(&text), b"hello");
+        assert!(cursors[0].position == Position { x: 5, y: 0 });
+    }
+
+    #[test]
+    fn group_undoes_every_cursor_change() {
+        let mut text = rope::from_bytes(b"abc\nabc");
+        let mut history = history::empty();
+        let cursors = vec![
+            Cursor { position: Position { x: 3, y: 0 }, selection: None },
+            Cursor { position: Position { x: 3, y: 1 }, selection: None },
+        ];
+
+        history::begin(&mut history, history::EditKind::Other, &cursors);
+        for offset in [7, 3] {
+            let content = rope::slice(&text, offset - 1, offset);
+            rope::delete(&mut text, offset - 1, offset);
+            history::record(&mut history, history::Change::Delete { offset: offset - 1, content });
+        }
+        history::commit(&mut history, &cursors);
+
+        assert_eq!(rope::to_bytes(&text), b"ab\nab");
+        history::undo(&mut text, &mut history).unwrap();
+        assert_eq!(rope::to_bytes(&text), b"abc\nabc");
+    }
+
+    #[test]
+    fn cursor_jump_breaks_typing_group() {
+        let mut text = rope::empty();
+        let mut history = history::empty();
+
+        type_char(&mut text, &mut history, 0, b'a');
+        type_char(&mut text, &mut history, 1, b'b');
+        type_char(&mut text, &mut history, 0, b'c');
+
+        history::undo(&mut text, &mut history).unwrap();
+        assert_eq!(rope::to_bytes(&text), b"ab");
+    }
+}
+
+#[cfg(test)]
+mod utf8_test {
+    use super::editor::buffer;
+    use super::editor::grapheme;
+
+    #[test]
+    fn clusters_group_combining_marks_and_emoji() {
+        let line = "e\u{301}a🇧🇷x👩\u{200d}💻!".as_bytes();
+        let mut boundaries = vec![0];
+
+        while *boundaries.last().unwrap() < line.len() {
+            boundaries.push(grapheme::next_boundary(line, *boundaries.last().unwrap()));
+        }
+
+        let clusters: Vec<&str> = boundaries.windows(2).map(|w| std::str::from_utf8(&line[w[0]..w[1]]).unwrap()).collect();
+        assert_eq!(clusters, vec!["e\u{301}", "a", "🇧🇷", "x", "👩\u{200d}💻", "!"]);
+
+        for w in boundaries.windows(2) {
+            assert_eq!(grapheme::prev_boundary(line, w[1]), w[0]);
+        }
+
+        // The flag is two columns wide.
+        assert_eq!(grapheme::column_of(line, boundaries[3], 4), 4);
+        assert_eq!(grapheme::offset_of(line, 4, 4), boundaries[3]);
+
+        let tabbed = b"\tab\tc";
+        assert_eq!([0, 1, 3, 4].map(|at| grapheme::column_of(tabbed, at, 4)), [0, 4, 6, 8]);
+        assert_eq!([0, 3, 4, 5, 7].map(|column| grapheme::offset_of(tabbed, column, 4)), [0, 0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn wide_characters_take_two_columns() {
+        let line = "a日b\t한".as_bytes();
+        assert_eq!([0, 1, 4, 5, 6].map(|at| grapheme::column_of(line, at, 4)), [0, 1, 3, 4, 8]);
+        assert_eq!([0, 1, 2, 3, 4, 7, 8, 9].map(|column| grapheme::offset_of(line, column, 4)), [0, 1, 1, 4, 5, 5, 6, 6]);
+        assert_eq!(['a', 'é', '日', 'Ａ', '🦀'].map(grapheme::char_width), [1, 1, 2, 2, 2]);
+
+        let mut buffer = buffer::empty_buffer();
+        buffer.text = super::editor::rope::from_bytes("日x\n".as_bytes());
+        let unique_chars = buffer::unique_chars_from_text(80, 20, &buffer.text, &mut buffer.syntax, &buffer.mode_line);
+        assert_eq!(unique_chars.positions[&'x'][0][..2], [2, 0]);
+    }
+
+    #[test]
+    fn invalid_bytes_decode_to_replacement() {
+        assert_eq!(grapheme::decode(&[0xff, b'a'], 0), ('\u{fffd}', 1));
+        assert_eq!(grapheme::decode(&[0xe2, 0x82], 0), ('\u{fffd}', 1));
+        assert_eq!(grapheme::decode("ç".as_bytes(), 0), ('ç', 2));
+    }
+
+    #[test]
+    fn non_ascii_file_round_trips() {
+        let content = "fn olá() {\n    let ñ = \"日本語 — ünïcödé\";\n}\n";
+        let path = std::env::temp_dir().join("engine_utf8_round_trip.rs");
+        std::fs::write(&path, content).unwrap();
+
+        let mut buffer = buffer::buffer_from_file(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(buffer::buffer_content(&buffer), Ok(content.as_bytes().to_vec()));
+        let unique_chars = buffer::unique_chars_from_text(80, 20, &buffer.text, &mut buffer.syntax, &buffer.mode_line);
+        assert!(unique_chars.positions.get(&'日').map(|p| p.len()) == Some(1));
+    }
+}
+
+#[cfg(test)]
+mod kill_ring_test {
+    use super::editor::{buffer, kill_ring, rope};
+
+    #[test]
+    fn consecutive_kills_append_and_yank_pop_rotates() {
+        let mut ring = kill_ring::empty();
+        assert_eq!(kill_ring::current(&ring), None);
+
+        kill_ring::push(&mut ring, b"first".to_vec());
+        kill_ring::push(&mut ring, b"second".to_vec());
+        kill_ring::append(&mut ring, b" line\n");
+
+        assert_eq!(kill_ring::current(&ring), Some(&b"second line\n"[..]));
+        assert_eq!(kill_ring::rotate(&mut ring), Some(&b"first"[..]));
+        assert_eq!(kill_ring::rotate(&mut ring), Some(&b"second line\n"[..]));
+    }
+
+    #[test]
+    fn region_is_ordered_between_mark_and_point() {
+        let mut buffer = buffer::empty_buffer();
+        buffer.text = rope::from_bytes(b"one\ntwo\nthree");
+
+        assert_eq!(buffer::region(&buffer, 0), None);
+
+        buffer.cursors[0].position = buffer::Position { x: 1, y: 2 };
+        buffer.cursors[0].selection = Some(buffer::Position { x: 2, y: 0 });
+
+        let [start, end] = buffer::region(&buffer, 0).unwrap();
+        assert_eq!(rope::slice(&buffer.text, start, end), b"e\ntwo\nt");
+    }
+}
+
+#[cfg(test)]
+mod search_test {
+    use super::editor::{rope, search};
+
+    const TEXT: &[u8] = "let Foo = foo(); // fóo FOO\nfoo".as_bytes();
+
+    #[test]
+    fn case_folding_toggle() {
+        assert_eq!(search::find_all(TEXT, b"foo", true).len(), 4);
+        assert_eq!(search::find_all(TEXT, b"foo", false), vec![[10, 13], [29, 32]]);
+        assert_eq!(search::find_all(TEXT, "FÓO".as_bytes(), true), vec![[20, 24]]);
+    }
+
+    #[test]
+    fn typing_extends_match_in_place() {
+        let text = rope::from_bytes(TEXT);
+        let mut isearch = search::start(0, true);
+
+        for c in b"fo" {
+            isearch.query.push(*c);
+            search::update(&mut isearch, &text);
+        }
+
+        assert_eq!(isearch.current, Some([4, 6]));
+
+        isearch.query.push(b'o');
+        search::update(&mut isearch, &text);
+        assert_eq!(isearch.current, Some([4, 7]));
+        assert_eq!(search::point(&isearch), 7);
+    }
+
+    #[test]
+    fn repeat_steps_fails_and_wraps() {
+        let text = rope::from_bytes(TEXT);
+        let mut isearch = search::start(0, true);
+        isearch.case_fold = false;
+        isearch.query = b"foo".to_vec();
+        search::update(&mut isearch, &text);
+        assert_eq!(isearch.current, Some([10, 13]));
+
+        search::repeat(&mut isearch, &text, true);
+        assert_eq!(isearch.current, Some([29, 32]));
+
+        search::repeat(&mut isearch, &text, true);
+        assert!(isearch.failing);
+        assert_eq!(isearch.current, Some([29, 32]));
+        assert!(search::prompt(&isearch).starts_with(b"Failing I-search"));
+
+        search::repeat(&mut isearch, &text, true);
+        assert!(!isearch.failing && isearch.wrapped);
+        assert_eq!(isearch.current, Some([10, 13]));
+
+        search::repeat(&mut isearch, &text, false);
+        assert!(isearch.failing);
+
+        search::repeat(&mut isearch, &text, false);
+        assert_eq!(isearch.current, Some([29, 32]));
+        assert_eq!(search::point(&isearch), 29);
+    }
+}
+
+#[cfg(test)]
+mod replace_test {
+    use super::editor::{regex, replace};
+
+    fn apply(input: &[u8], argument: &[u8]) -> Vec<u8> {
+        let replace = replace::parse_arguments(argument).unwrap();
+        let mut output = input.to_vec();
+
+        for ([start, end], content) in replace::replace_all(&replace, input, 0).into_iter().rev() {
+            output.splice(start..end, content);
+        }
+
+        output
+    }
+
+    #[test]
+    fn regex_features() {
+        let regex = regex::parse(br"^(\w+)\s*=\s*([0-9]+|true|false);$", false).unwrap();
+        let input = b"x = 1;\nflag=true;\nbad = ;";
+        let all = regex::find_all(&regex, input);
+
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[1][1], Some([7, 11]));
+        assert_eq!(all[1][2], Some([12, 16]));
+
+        assert_eq!(regex::find_at(&regex::parse(b"a.*?b", false).unwrap(), b"xaxbxb", 0).unwrap()[0], Some([1, 4]));
+        assert_eq!(regex::find_at(&regex::parse(b"(ab){2,}", false).unwrap(), b"ab abababx", 0).unwrap()[0], Some([3, 9]));
+        assert_eq!(regex::find_at(&regex::parse(br"\bcat\b", false).unwrap(), b"concat cat", 0).unwrap()[0], Some([7, 10]));
+        assert_eq!(regex::find_at(&regex::parse(b"[^a-c]+", true).unwrap(), b"ABCdef", 0).unwrap()[0], Some([3, 6]));
+        assert!(regex::parse(b"(a", false).is_err());
+        assert!(regex::parse(b"*a", false).is_err());
+        assert!(regex::parse(b"[ab", false).is_err());
+        assert!(regex::parse(b"a{3,1}", false).is_err());
+    }
+
+    #[test]
+    fn braces_that_repeat_nothing_are_literal() {
+        assert!(regex::parse(b"fn main() {", false).is_ok());
+        let regex = regex::parse(br"main\(\) {", false).unwrap();
+        assert_eq!(regex::find_at(&regex, b"pub fn main() {}", 0).unwrap()[0], Some([7, 15]));
+        assert_eq!(regex::find_at(&regex::parse(b"a{2", false).unwrap(), b"aa{2", 0).unwrap()[0], Some([1, 4]));
+        assert_eq!(regex::find_at(&regex::parse(b"x{1,}{", false).unwrap(), b"xx{", 0).unwrap()[0], Some([0, 3]));
+    }
+
+    #[test]
+    fn long_repetitions_backtrack_without_recursing() {
+        let input = vec![b'a'; 200_000];
+        assert_eq!(regex::find_at(&regex::parse(b"(a|b)*", false).unwrap(), &input, 0).unwrap()[1], Some([199_999, 200_000]));
+        assert_eq!(regex::find_at(&regex::parse(b"(a|b)*?$", false).unwrap(), &input, 0).unwrap()[0], Some([0, 200_000]));
+        assert_eq!(regex::find_at(&regex::parse(b"(a*)*b", false).unwrap(), b"aaab", 0).unwrap()[0], Some([0, 4]));
+    }
+
+    #[test]
+    fn capture_group_substitution() {
+        assert_eq!(apply(b"foo(a, b)\nfoo(c, d)", br"/foo\((\w+), (\w+)\)/bar(\2, \1)/"), b"bar(b, a)\nbar(d, c)");
+        assert_eq!(apply(b"a.b.c", b"/./-/"), b"-----");
+        assert_eq!(apply(b"a.b.c", b"/./-/l"), b"a-b-c");
+        assert_eq!(apply(b"Cat cat", b"|cat|[\\&]|i"), b"[Cat] [cat]");
+        assert_eq!(apply(b"x/y", br"/\//\\/"), b"x\\y");
+        assert!(replace::parse_arguments(b"/a/b/z").is_err());
+        assert!(replace::parse_arguments(b"/a").is_err());
+    }
+
+    #[test]
+    fn query_steps_through_matches() {
+        let input = b"one two one two one";
+        let mut query = replace::query(replace::parse_arguments(b"/one/1/").unwrap(), 0);
+
+        assert!(replace::find_next(&mut query, input));
+        assert_eq!(query.current.as_ref().unwrap()[0], Some([0, 3]));
+
+        replace::skip(&mut query);
+        assert!(replace::find_next(&mut query, input));
+        assert_eq!(query.current.as_ref().unwrap()[0], Some([8, 11]));
+
+        query.next = 19;
+        assert!(!replace::find_next(&mut query, input));
+    }
+}
+
+#[cfg(test)]
+mod syntax_test {
+    use super::editor::{rope, syntax};
+    use super::editor::syntax::{Kind, Language, State};
+
+    fn kinds(language: Language, line: &[u8], state: State) -> (Vec<(Vec<u8>, Kind)>, State) {
+        let (spans, state) = syntax::highlight_line(language, line, state);
+        (spans.iter().map(|span| (line[span.start..span.end].to_vec(), span.kind)).collect(), state)
+    }
+
+    #[test]
+    fn tokenizers_assign_kinds() {
+        let (spans, state) = kinds(Language::Rust, b"pub fn main() -> u32 { println!(\"{}\", 'a'); 42 } /* open", State::Normal);
+        assert_eq!(spans, vec![
+            (b"pub".to_vec(), Kind::Keyword),
+            (b"fn".to_vec(), Kind::Keyword),
+            (b"main".to_vec(), Kind::Function),
+            (b"u32".to_vec(), Kind::Type),
+            (b"println!".to_vec(), Kind::Macro),
+            (b"\"{}\"".to_vec(), Kind::String),
+            (b"'a'".to_vec(), Kind::String),
+            (b"42".to_vec(), Kind::Number),
+            (b"/* open".to_vec(), Kind::Comment),
+        ]);
+        assert_eq!(state, State::BlockComment(1));
+
+        let (spans, state) = kinds(Language::Rust, b"/* nested */ still */ fn x<'a>()", State::BlockComment(1));
+        assert_eq!(spans[0], (b"/* nested */ still */".to_vec(), Kind::Comment));
+        assert_eq!(spans[2], (b"'a".to_vec(), Kind::Type));
+        assert_eq!(state, State::Normal);
+
+        let deep = b"/*".repeat(300);
+        assert_eq!(kinds(Language::Rust, &deep, State::Normal).1, State::BlockComment(300));
+        assert_eq!(kinds(Language::Rust, &b"*/".repeat(300), State::BlockComment(300)).1, State::Normal);
+
+        let (spans, state) = kinds(Language::Rust, b"let s = r#\"raw", State::Normal);
+        assert_eq!(spans.last().unwrap(), &(b"r#\"raw".to_vec(), Kind::String));
+        assert_eq!(state, State::RawString(1));
+
+        let (spans, state) = kinds(Language::C, b"#define MAX(a, b) \\", State::Normal);
+        assert_eq!(spans, vec![(b"#define MAX(a, b) \\".to_vec(), Kind::Macro)]);
+        assert_eq!(state, State::Preprocessor);
+
+        let (spans, _) = kinds(Language::C, b"static int count = 0x1f; // total", State::Normal);
+        assert_eq!(spans.iter().map(|(_, kind)| *kind).collect::<Vec<_>>(), vec![Kind::Keyword, Kind::Type, Kind::Number, Kind::Comment]);
+
+        let (spans, _) = kinds(Language::Markdown, b"- a *word* with `code` and [link](url)", State::Normal);
+        assert_eq!(spans, vec![
+            (b"-".to_vec(), Kind::Keyword),
+            (b"*word*".to_vec(), Kind::Emphasis),
+            (b"`code`".to_vec(), Kind::Code),
+            (b"[link](url)".to_vec(), Kind::Link),
+        ]);
+        assert_eq!(kinds(Language::Markdown, b"```rust", State::Normal).1, State::Fenced);
+        assert_eq!(kinds(Language::Markdown, b"## Title", State::Normal).0, vec![(b"## Title".to_vec(), Kind::Heading)]);
+    }
+
+    #[test]
+    fn only_dirty_lines_are_rehighlighted() {
+        let mut text = rope::from_bytes(&b"let x = 1;\n".repeat(100));
+        let mut highlighter = syntax::new(syntax::language_from_file_name(b"src/main.rs"), rope::line_count(&text));
+
+        assert_eq!(syntax::update(&mut highlighter, &text), 101);
+        assert_eq!(syntax::update(&mut highlighter, &text), 0);
+
+        let start = rope::line_start(&text, 50);
+        rope::insert(&mut text, start, b"fn ");
+        syntax::edited(&mut highlighter, 50, 0, 0);
+        assert_eq!(syntax::update(&mut highlighter, &text), 1);
+        assert_eq!(syntax::spans(&highlighter, 50)[0].kind, Kind::Keyword);
+
+        let start = rope::line_start(&text, 10);
+        rope::insert(&mut text, start, b"/*\n");
+        syntax::edited(&mut highlighter, 10, 0, 1);
+        assert_eq!(syntax::update(&mut highlighter, &text), 92);
+        assert_eq!(syntax::spans(&highlighter, 60)[0].kind, Kind::Comment);
+
+        let start = rope::line_start(&text, 20);
+        rope::insert(&mut text, start, b"*/");
+        syntax::edited(&mut highlighter, 20, 0, 0);
+        assert_eq!(syntax::update(&mut highlighter, &text), 82);
+        assert_eq!(syntax::spans(&highlighter, 60)[0].kind, Kind::Keyword);
+    }
+}
+
+#[cfg(test)]
+mod shader_test {
+    // Just enough SPIR-V to see what the compiled shaders read and write.
+    const MAGIC: u32 = 0x0723_0203;
+    const OP_TYPE_INT: u32 = 21;
+    const OP_TYPE_FLOAT: u32 = 22;
+    const OP_TYPE_VECTOR: u32 = 23;
+    const OP_TYPE_POINTER: u32 = 32;
+    const OP_VARIABLE: u32 = 59;
+    const OP_DECORATE: u32 = 71;
+    const LOCATION: u32 = 30;
+    const BINDING: u32 = 33;
+    const DESCRIPTOR_SET: u32 = 34;
+    const INPUT: u32 = 1;
+    const OUTPUT: u32 = 3;
+    const PUSH_CONSTANT: u32 = 9;
+
+    fn instructions(path: &str) -> Vec<(u32, Vec<u32>)> {
+        let bytes = std::fs::read(path).unwrap();
+        let words = bytes.chunks(4).map(|word| u32::from_le_bytes(word.try_into().unwrap())).collect::<Vec<u32>>();
+        assert_eq!(words[0], MAGIC);
+
+        let mut instructions = Vec::new();
+        let mut i = 5;
+
+        while i < words.len() {
+            let len = (words[i] >> 16) as usize;
+            instructions.push((words[i] & 0xffff, words[i + 1..i + len].to_vec()));
+            i += len;
+        }
+
+        instructions
+    }
+
+    // Names a type the way GLSL does, looking through pointers.
+    fn type_name(instructions: &[(u32, Vec<u32>)], id: u32) -> String {
+        let (op, operands) = instructions.iter().find(|(op, operands)| *op != OP_DECORATE && operands.first() == Some(&id)).unwrap();
+
+        match *op {
+            OP_TYPE_INT if operands[2] == 0 => "uint".to_string(),
+            OP_TYPE_INT => "int".to_string(),
+            OP_TYPE_FLOAT => "float".to_string(),
+            OP_TYPE_VECTOR => format!("{}vec{}", type_name(instructions, operands[1]).trim_end_matches("float").trim_end_matches("int"), operands[2]),
+            OP_TYPE_POINTER => type_name(instructions, operands[2]),
+            op => panic!("unexpected op {}", op),
+        }
+    }
+
+    fn decorated(instructions: &[(u32, Vec<u32>)], id: u32, decoration: u32) -> Option<u32> {
+        instructions.iter().find_map(|(op, operands)| (*op == OP_DECORATE && operands[0] == id && operands[1] == decoration).then(|| operands[2]))
+    }
+
+    fn variables(instructions: &[(u32, Vec<u32>)]) -> impl Iterator<Item = (u32, u32, u32)> + '_ {
+        instructions.iter().filter(|(op, _)| *op == OP_VARIABLE).map(|(_, operands)| (operands[0], operands[1], operands[2]))
+    }
+
+    // The type at each location of the interface in `storage_class`.
+    fn locations(instructions: &[(u32, Vec<u32>)], storage_class: u32) -> Vec<(u32, String)> {
+        let mut locations = variables(instructions)
+            .filter(|(_, _, class)| *class == storage_class)
+            .filter_map(|(pointer, id, _)| Some((decorated(instructions, id, LOCATION)?, type_name(instructions, pointer))))
+            .collect::<Vec<_>>();
+
+        locations.sort();
+        locations
+    }
+
+    fn bindings(instructions: &[(u32, Vec<u32>)]) -> Vec<(u32, u32)> {
+        let mut bindings = variables(instructions)
+            .filter_map(|(_, id, _)| Some((decorated(instructions, id, DESCRIPTOR_SET)?, decorated(instructions, id, BINDING)?)))
+            .collect::<Vec<_>>();
+
+        bindings.sort();
+        bindings
+    }
+
+    // The binaries are committed rather than built, so check they still take
+    // what the pipeline gives them: each instance's position, glyph, color and
+    // width as attributes, the glyph coordinates in a storage buffer, and no
+    // push constants.
+    #[test]
+    fn compiled_shaders_match_the_pipeline() {
+        let vert = instructions("assets/shader/vert.spv");
+        let frag = instructions("assets/shader/frag.spv");
+        let names = |locations: &[(u32, &str)]| locations.iter().map(|(location, name)| (*location, name.to_string())).collect::<Vec<_>>();
+
+        assert_eq!(locations(&vert, INPUT), names(&[(0, "vec2"), (1, "uint"), (2, "vec4"), (3, "float")]));
+        assert_eq!(locations(&vert, OUTPUT), names(&[(0, "vec4"), (1, "vec2")]));
+        assert_eq!(locations(&frag, INPUT), names(&[(0, "vec4"), (1, "vec2")]));
+        assert_eq!(locations(&frag, OUTPUT), names(&[(0, "vec4")]));
+
+        assert_eq!(bindings(&vert), [(0, 0), (0, 1)]);
+        assert_eq!(bindings(&frag), [(1, 0)]);
+        assert!(variables(&vert).chain(variables(&frag)).all(|(_, _, class)| class != PUSH_CONSTANT));
+    }
+}
+
+#[cfg(test)]
+mod layout_test {
+    use super::editor::layout::{self, Direction, Rect};
+
+    #[test]
+    fn splits_divide_the_focused_window() {
+        let mut layout = layout::single();
+        assert_eq!(layout::split(&mut layout, Direction::Horizontal), 1);
+
+        let (windows, separators) = layout::arrange(&layout, [80, 25]);
+        assert_eq!(windows, vec![
+            (0, Rect { x: 0, y: 0, width: 80, height: 13 }),
+            (1, Rect { x: 0, y: 13, width: 80, height: 12 }),
+        ]);
+        assert!(separators.is_empty());
+
+        assert_eq!(layout::split(&mut layout, Direction::Vertical), 2);
+
+        let (windows, separators) = layout::arrange(&layout, [80, 25]);
+        assert_eq!(windows, vec![
+            (0, Rect { x: 0, y: 0, width: 39, height: 13 }),
+            (2, Rect { x: 40, y: 0, width: 40, height: 13 }),
+            (1, Rect { x: 0, y: 13, width: 80, height: 12 }),
+        ]);
+        assert_eq!(separators, vec![Rect { x: 39, y: 0, width: 1, height: 13 }]);
+    }
+
+    #[test]
+    fn focus_cycles_in_screen_order() {
+        let mut layout = layout::single();
+        layout::split(&mut layout, Direction::Vertical);
+        layout::split(&mut layout, Direction::Horizontal);
+
+        assert_eq!(layout::leaves(&layout), vec![0, 2, 1]);
+        assert_eq!(layout::next_focus(&mut layout), 2);
+        assert_eq!(layout::next_focus(&mut layout), 1);
+        assert_eq!(layout::next_focus(&mut layout), 0);
+
+        assert!(!layout::can_split(Rect { x: 0, y: 0, width: 8, height: 3 }, Direction::Vertical));
+        assert!(!layout::can_split(Rect { x: 0, y: 0, width: 8, height: 3 }, Direction::Horizontal));
+    }
+}
+
+#[cfg(test)]
+mod pointer_test {
+    use super::editor::{buffer, grapheme, rope};
+    use super::renderer::wayland::pointer;
+
+    #[test]
+    fn clicks_on_the_same_cell_count_up_and_cycle() {
+        let mut state = pointer::new();
+
+        assert_eq!(pointer::click(&mut state, 1000, [3, 4]), 1);
+        assert_eq!(pointer::click(&mut state, 1200, [3, 4]), 2);
+        assert_eq!(pointer::click(&mut state, 1400, [3, 4]), 3);
+        assert_eq!(pointer::click(&mut state, 1500, [3, 4]), 1);
+        assert_eq!(pointer::click(&mut state, 1600, [4, 4]), 1);
+        assert_eq!(pointer::click(&mut state, 2600, [4, 4]), 1);
+
+        assert_eq!(pointer::cell_at([25.0, 41.0], [10.0, 20.0]), [2, 2]);
+    }
+
+    #[test]
+    fn continuous_scroll_accumulates_and_discrete_steps_win() {
+        let mut state = pointer::new();
+        let cell = [8.0, 16.0];
+
+        pointer::axis(&mut state, pointer::AXIS_VERTICAL, 10.0);
+        assert_eq!(pointer::frame(&mut state, cell), [0, 0]);
+        pointer::axis(&mut state, pointer::AXIS_VERTICAL, 10.0);
+        assert_eq!(pointer::frame(&mut state, cell), [1, 0]);
+        pointer::axis(&mut state, pointer::AXIS_HORIZONTAL, -17.0);
+        assert_eq!(pointer::frame(&mut state, cell), [0, -2]);
+
+        pointer::axis(&mut state, pointer::AXIS_VERTICAL, 15.0);
+        pointer::axis_discrete(&mut state, pointer::AXIS_VERTICAL, -1);
+        assert_eq!(pointer::frame(&mut state, cell), [-pointer::LINES_PER_STEP, 0]);
+        assert_eq!(pointer::frame(&mut state, cell), [0, 0]);
+    }
+
+    #[test]
+    fn word_bounds_group_clusters_by_class() {
+        let line = "let olá_1 = a.b;".as_bytes();
+
+        assert_eq!(grapheme::word_bounds(line, 5), [4, 10]);
+        assert_eq!(grapheme::word_bounds(line, 3), [3, 4]);
+        assert_eq!(grapheme::word_bounds(line, 14), [14, 15]);
+        assert_eq!(grapheme::word_bounds(line, line.len()), [16, 17]);
+        assert_eq!(grapheme::word_bounds(b"", 0), [0, 0]);
+    }
+
+    #[test]
+    fn scrolling_drags_cursors_into_view() {
+        let mut buffer = buffer::empty_buffer();
+        buffer.text = rope::from_bytes(&b"line\n".repeat(50));
+        buffer.cursors[0].position = buffer::Position { x: 2, y: 1 };
+
+        buffer::scroll_view(&buffer.text, buffer.indent.tab_width, &mut buffer.offset, &mut buffer.cursors, [10, 0], [80, 5]);
+        assert_eq!((buffer.offset.y, buffer.cursors[0].position.clone()), (10, buffer::Position { x: 2, y: 10 }));
+        assert_eq!(buffer::position_at_cell(&buffer, [30, 2]), buffer::Position { x: 4, y: 12 });
+
+        buffer::scroll_view(&buffer.text, buffer.indent.tab_width, &mut buffer.offset, &mut buffer.cursors, [-100, 0], [80, 5]);
+        assert_eq!((buffer.offset.y, buffer.cursors[0].position.y), (0, 4));
+    }
+}
+
+#[cfg(test)]
+mod clipboard_test {
+    use super::renderer::wayland::clipboard::{self, Clipboard};
+    use std::os::fd::IntoRawFd;
+
+    fn handle(id: usize) -> *mut std::ffi::c_void {
+        id as *mut std::ffi::c_void
+    }
+
+    // Stands in for the compositor: cancels the previous owner's source and
+    // announces the new selection to every client as a fresh offer.
+    fn set_selection(clients: &mut [Clipboard], owner: usize, selection: usize, source: usize, content: &[u8]) {
+        clipboard::publish(&mut clients[owner], selection, handle(source), content.to_vec());
+
+        for (i, client) in clients.iter_mut().enumerate() {
+            if i != owner {
+                let previous = client.sources[selection];
+                clipboard::cancelled(client, previous);
+            }
+
+            let offer = handle(source * 100 + i);
+            clipboard::introduce(client, offer);
+            clipboard::add_mime_type(client, offer, b"text/html");
+            for mime_type in clipboard::MIME_TYPES {
+                clipboard::add_mime_type(client, offer, mime_type);
+            }
+
+            clipboard::select(client, selection, offer);
+        }
+    }
+
+    // Connects a receive request to whichever client owns the selection.
+    fn paste(clients: &[Clipboard], reader: usize, selection: usize) -> Option<Vec<u8>> {
+        let offer = clients[reader].offers[selection].as_ref()?;
+        assert_eq!(clipboard::preferred_mime_type(offer), Some(&b"text/plain;charset=utf-8"[..]));
+
+        let owner = clients.iter().find(|client| clipboard::owns(client, selection))?;
+        let content = clipboard::content_of(owner, owner.sources[selection])?;
+        let (pipe, writer) = std::io::pipe().ok()?;
+
+        clipboard::send(writer.into_raw_fd(), content.to_vec());
+        clipboard::receive(pipe, clipboard::RECEIVE_TIMEOUT)
+    }
+
+    #[test]
+    fn selections_transfer_between_clients() {
+        let mut clients = [clipboard::new(), clipboard::new()];
+
+        set_selection(&mut clients, 0, clipboard::CLIPBOARD, 1, "héllo\n".as_bytes());
+        assert!(clipboard::owns(&clients[0], clipboard::CLIPBOARD));
+        assert_eq!(paste(&clients, 1, clipboard::CLIPBOARD), Some("héllo\n".as_bytes().to_vec()));
+
+        set_selection(&mut clients, 1, clipboard::CLIPBOARD, 2, b"world");
+        assert!(!clipboard::owns(&clients[0], clipboard::CLIPBOARD));
+        assert_eq!(paste(&clients, 0, clipboard::CLIPBOARD), Some(b"world".to_vec()));
+
+        set_selection(&mut clients, 0, clipboard::PRIMARY, 3, b"selected");
+        assert_eq!(paste(&clients, 1, clipboard::PRIMARY), Some(b"selected".to_vec()));
+        assert_eq!(paste(&clients, 0, clipboard::CLIPBOARD), Some(b"world".to_vec()));
+
+        let large = vec![b'x'; 1 << 20];
+        set_selection(&mut clients, 1, clipboard::CLIPBOARD, 4, &large);
+        assert_eq!(paste(&clients, 0, clipboard::CLIPBOARD), Some(large));
+    }
+
+    #[test]
+    fn offers_are_replaced_and_mime_types_ranked() {
+        let mut client = clipboard::new();
+
+        clipboard::introduce(&mut client, handle(1));
+        clipboard::add_mime_type(&mut client, handle(1), b"image/png");
+        assert_eq!(clipboard::select(&mut client, clipboard::CLIPBOARD, handle(1)), None);
+        assert_eq!(clipboard::preferred_mime_type(client.offers[0].as_ref().unwrap()), None);
+
+        clipboard::introduce(&mut client, handle(2));
+        clipboard::add_mime_type(&mut client, handle(2), b"STRING");
+        clipboard::add_mime_type(&mut client, handle(2), b"text/plain");
+        assert_eq!(clipboard::select(&mut client, clipboard::CLIPBOARD, handle(2)), Some(handle(1)));
+        assert_eq!(clipboard::preferred_mime_type(client.offers[0].as_ref().unwrap()), Some(&b"text/plain"[..]));
+
+        clipboard::introduce(&mut client, handle(3));
+        assert!(clipboard::discard(&mut client, handle(3)));
+        assert_eq!(clipboard::select(&mut client, clipboard::CLIPBOARD, std::ptr::null_mut()), Some(handle(2)));
+        assert!(client.offers[0].is_none());
+    }
+
+    #[test]
+    fn unresponsive_sources_time_out() {
+        let (pipe, writer) = std::io::pipe().unwrap();
+        let start = std::time::Instant::now();
+
+        assert_eq!(clipboard::receive(pipe, std::time::Duration::from_millis(50)), None);
+        assert!(start.elapsed() < clipboard::RECEIVE_TIMEOUT);
+        drop(writer);
+    }
+}
+
+#[cfg(test)]
+mod keymap_test {
+    use super::renderer::wayland::keymap::{self, Keymap};
+    use std::os::fd::IntoRawFd;
+
+    const SHIFT: u32 = 0x01;
+    const LOCK: u32 = 0x02;
+    const CONTROL: u32 = 0x04;
+    const MOD5: u32 = 0x80;
+
+    const KEY_1: u32 = 2;
+    const KEY_2: u32 = 3;
+    const KEY_TAB: u32 = 15;
+    const KEY_Q: u32 = 16;
+    const KEY_ENTER: u32 = 28;
+    const KEY_A: u32 = 30;
+    const KEY_SEMICOLON: u32 = 39;
+    const KEY_SPACE: u32 = 57;
+
+    // Hands the fixture over the way the compositor does: in a file of its
+    // own, with the size counting a terminating nul.
+    fn compile(name: &str) -> Keymap {
+        let xkb = keymap::load().expect("libxkbcommon is needed to run the keymap tests");
+        let mut text = std::fs::read(format!("assets/keymaps/{}.xkb", name)).unwrap();
+        text.push(0);
+
+        let path = std::env::temp_dir().join(format!("engine-keymap-{}-{}", name, std::process::id()));
+        std::fs::write(&path, &text).unwrap();
+        let fd = std::fs::File::open(&path).unwrap().into_raw_fd();
+        std::fs::remove_file(&path).unwrap();
+
+        keymap::from_fd(&xkb, fd, text.len() as u32).unwrap()
+    }
+
+    fn text(keymap: &mut Keymap, mods: u32, group: u32, code: u32) -> String {
+        keymap::update_mask(keymap, mods, 0, 0, group);
+        keymap::key_text(keymap, code)
+    }
+
+    #[test]
+    fn us_levels_and_lock() {
+        let mut keymap = compile("us");
+
+        assert_eq!(text(&mut keymap, 0, 0, KEY_A), "a");
+        assert_eq!(text(&mut keymap, SHIFT, 0, KEY_A), "A");
+        assert_eq!(text(&mut keymap, LOCK, 0, KEY_A), "A");
+        assert_eq!(text(&mut keymap, SHIFT, 0, KEY_1), "!");
+        assert_eq!(text(&mut keymap, LOCK, 0, KEY_1), "1");
+        assert_eq!(text(&mut keymap, SHIFT, 0, KEY_SEMICOLON), ":");
+        assert_eq!(text(&mut keymap, 0, 0, KEY_SPACE), " ");
+        assert_eq!(text(&mut keymap, 0, 0, KEY_TAB), "\t");
+        assert_eq!(text(&mut keymap, 0, 0, KEY_ENTER), "\r");
+
+        keymap::update_mask(&mut keymap, CONTROL, 0, 0, 0);
+        assert_eq!(keymap::key_sym(&keymap, KEY_A), 0x61);
+        assert_eq!(keymap::sym_char(&keymap, keymap::key_sym(&keymap, KEY_A)), Some('a'));
+        assert_eq!(keymap::key_sym(&keymap, KEY_ENTER), 0xff0d);
+        assert_eq!(keymap::key_sym(&keymap, 200), 0);
+
+        keymap::destroy(&keymap);
+    }
+
+    #[test]
+    fn azerty_positions_and_third_level() {
+        let mut keymap = compile("fr");
+
+        assert_eq!(text(&mut keymap, 0, 0, KEY_Q), "a");
+        assert_eq!(text(&mut keymap, 0, 0, KEY_A), "q");
+        assert_eq!(text(&mut keymap, 0, 0, KEY_SEMICOLON), "m");
+        assert_eq!(text(&mut keymap, 0, 0, KEY_1), "&");
+        assert_eq!(text(&mut keymap, SHIFT, 0, KEY_1), "1");
+        assert_eq!(text(&mut keymap, 0, 0, KEY_2), "é");
+        assert_eq!(text(&mut keymap, MOD5, 0, KEY_2), "~");
+        assert_eq!(text(&mut keymap, SHIFT | MOD5, 0, KEY_2), "É");
+        assert_eq!(text(&mut keymap, MOD5, 0, KEY_1), "¹");
+
+        keymap::destroy(&keymap);
+    }
+
+    #[test]
+    fn second_group_falls_back_to_latin_base() {
+        let mut keymap = compile("us_ru");
+
+        assert_eq!(text(&mut keymap, 0, 0, KEY_A), "a");
+        assert_eq!(text(&mut keymap, 0, 1, KEY_A), "ф");
+        assert_eq!(text(&mut keymap, SHIFT, 1, KEY_A), "Ф");
+        assert_eq!(text(&mut keymap, SHIFT, 1, KEY_2), "\"");
+
+        keymap::update_mask(&mut keymap, CONTROL, 0, 0, 1);
+        assert_eq!(keymap::sym_char(&keymap, keymap::key_sym(&keymap, KEY_Q)), Some('й'));
+        assert_eq!(keymap::sym_char(&keymap, keymap::base_sym(&keymap, KEY_Q)), Some('q'));
+
+        keymap::destroy(&keymap);
+    }
+
+    #[test]
+    fn broken_keymaps_are_rejected() {
+        let xkb = keymap::load().unwrap();
+
+        assert!(keymap::new(&xkb, b"xkb_keymap { xkb_symbols { key <AC01> { [ a ] }; };").is_none());
+        assert!(keymap::new(&xkb, b"").is_none());
+    }
+}
+
+#[cfg(test)]
+mod key_test {
+    use super::editor::key::{self, Key};
+    use super::renderer::wayland::keymap;
+
+    #[test]
+    fn keysyms_and_scancodes_agree() {
+        let pairs = [
+            (0xff1b, 1, Key::Escape),
+            (0xff0d, 28, Key::Enter),
+            (0xff08, 14, Key::Backspace),
+            (0xff52, 103, Key::Up),
+            (0xff54, 108, Key::Down),
+            (0xff51, 105, Key::Left),
+            (0xff53, 106, Key::Right),
+            (0xff50, 102, Key::Home),
+            (0xff57, 107, Key::End),
+            (0xff55, 104, Key::PageUp),
+            (0xff56, 109, Key::PageDown),
+            (0xff63, 110, Key::Insert),
+            (0xffff, 111, Key::Delete),
+            (0xffbe, 59, Key::Function(1)),
+            (0xffc7, 68, Key::Function(10)),
+            (0xffc8, 87, Key::Function(11)),
+            (0xffc9, 88, Key::Function(12)),
+        ];
+
+        for (sym, code, expected) in pairs {
+            assert_eq!(keymap::special_key_from_sym(sym), Some(expected));
+            assert_eq!(keymap::special_key_from_code(code), Some(expected));
+        }
+
+        assert_eq!(keymap::special_key_from_sym(0x61), None);
+        assert_eq!(keymap::special_key_from_code(30), None);
+        assert_eq!(key::name(Key::Function(12)), "<f12>");
+    }
+
+    #[test]
+    fn keypad_navigates_without_num_lock() {
+        assert_eq!(keymap::special_key_from_sym(0xff8d), Some(Key::Enter));
+        assert_eq!(keymap::special_key_from_sym(0xff95), Some(Key::Home));
+        assert_eq!(keymap::special_key_from_sym(0xff9b), Some(Key::PageDown));
+        assert_eq!(keymap::special_key_from_sym(0xff9f), Some(Key::Delete));
+        assert_eq!(keymap::special_key_from_sym(0xffb7), None);
+
+        assert_eq!(keymap::special_key_from_code(96), Some(Key::Enter));
+        assert_eq!(keymap::special_key_from_code(72), Some(Key::Up));
+        assert_eq!(keymap::special_key_from_code(83), Some(Key::Delete));
+
+        assert!(key::is_motion(Key::PageUp));
+        assert!(!key::is_motion(Key::Delete));
+    }
+}
+
+#[cfg(test)]
+mod bindings_test {
+    use super::editor::{self, bindings::{self, Chord, ConfigError, ConfigErrorKind, Lookup, Press}, key::Key};
+
+    const COMMANDS: [&str; 4] = ["next-line", "save-buffer", "split-window-below", "page-down"];
+
+    fn keys(text: &str) -> Vec<Chord> {
+        text.split(' ').map(|chord| bindings::parse_chord(chord).unwrap()).collect()
+    }
+
+    #[test]
+    fn chords_read_and_print_back() {
+        for text in ["C-x", "M-v", "C-M-%", "S-<up>", "C-SPC", "TAB", "<f12>", "C--", "M-<", "é", "C-<insert>"] {
+            let chord = bindings::parse_chord(text).unwrap();
+            assert_eq!(bindings::chord_name(&chord), text);
+        }
+
+        assert_eq!(bindings::parse_chord("C-<up>").unwrap().press, Press::Key(Key::Up));
+        assert_eq!(bindings::parse_chord("<prior>").unwrap().press, Press::Key(Key::PageUp));
+        assert_eq!(bindings::parse_chord("SPC").unwrap().press, Press::Char(' '));
+
+        for text in ["", "C-", "ab", "S-a", "<f13>", "<nope>", "X-a"] {
+            assert_eq!(bindings::parse_chord(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn prefixes_and_overrides() {
+        let mut config = bindings::new();
+        let text = "[edit]\nC-n next-line\nC-x C-s save-buffer\nC-x 2 split-window-below\n\n[command]\nC-n page-down\n";
+        assert_eq!(bindings::parse(&mut config, text, &COMMANDS), vec![]);
+
+        assert_eq!(bindings::lookup(&config.edit, &keys("C-n")), Lookup::Command(0));
+        assert_eq!(bindings::lookup(&config.command, &keys("C-n")), Lookup::Command(3));
+        assert_eq!(bindings::lookup(&config.edit, &keys("C-x")), Lookup::Prefix);
+        assert_eq!(bindings::lookup(&config.edit, &keys("C-x C-s")), Lookup::Command(1));
+        assert_eq!(bindings::lookup(&config.edit, &keys("C-x 2")), Lookup::Command(2));
+        assert_eq!(bindings::lookup(&config.edit, &keys("C-x 3")), Lookup::Undefined);
+        assert_eq!(bindings::lookup(&config.edit, &keys("C-n C-n")), Lookup::Undefined);
+        assert_eq!(bindings::lookup(&config.command, &keys("C-x")), Lookup::Undefined);
+
+        let text = "# user overrides\n[edit]\nC-n page-down\nC-x C-s unbind\n<next> next-line\n";
+        assert_eq!(bindings::parse(&mut config, text, &COMMANDS), vec![]);
+
+        assert_eq!(bindings::lookup(&config.edit, &keys("C-n")), Lookup::Command(3));
+        assert_eq!(bindings::lookup(&config.edit, &keys("C-x C-s")), Lookup::Undefined);
+        assert_eq!(bindings::lookup(&config.edit, &keys("C-x 2")), Lookup::Command(2));
+        assert_eq!(bindings::lookup(&config.edit, &keys("<next>")), Lookup::Command(0));
+    }
+
+    #[test]
+    fn errors_carry_line_numbers() {
+        let mut config = bindings::new();
+        let text = "C-n next-line\n[edit]\nC-n\nC-q frobnicate\nC-x C-s save-buffer\nC-x-q page-down\nC-x C-s C-c next-line\n[view]\nC-n next-line\n[edit]\nC-v page-down\n";
+        let errors = bindings::parse(&mut config, text, &COMMANDS);
+
+        let kinds = [
+            (1, ConfigErrorKind::NoSection),
+            (3, ConfigErrorKind::MissingCommand),
+            (4, ConfigErrorKind::UnknownCommand(String::from("frobnicate"))),
+            (6, ConfigErrorKind::BadChord(String::from("C-x-q"))),
+            (7, ConfigErrorKind::NotPrefix(String::from("C-x C-s"))),
+            (8, ConfigErrorKind::UnknownSection(String::from("view"))),
+            (9, ConfigErrorKind::NoSection),
+        ];
+        let expected: Vec<ConfigError> = kinds.into_iter().map(|(line, kind)| ConfigError { line, kind }).collect();
+        assert_eq!(errors, expected);
+
+        assert_eq!(bindings::error_message(&errors[2]), "4: unknown command frobnicate");
+        assert_eq!(bindings::lookup(&config.edit, &keys("C-x C-s")), Lookup::Command(1));
+        assert_eq!(bindings::lookup(&config.edit, &keys("C-v")), Lookup::Command(3));
+    }
+
+    #[test]
+    fn defaults_parse_cleanly() {
+        let mut config = bindings::new();
+        let names = editor::command_names();
+        assert_eq!(bindings::parse(&mut config, editor::DEFAULT_BINDINGS, &names), vec![]);
+
+        let save = names.iter().position(|name| *name == "save-buffer").unwrap();
+        assert_eq!(bindings::lookup(&config.edit, &keys("C-x C-s")), Lookup::Command(save));
+        assert_eq!(bindings::lookup(&config.command, &keys("C-x C-s")), Lookup::Undefined);
+    }
+}
+
+#[cfg(test)]
+mod test_input {
+    use super::editor::{self, bindings, Editor, InputEvent, KeyEvent, Request};
+
+    // Plays the frontend: handles the event and answers reads as if no other
+    // program had touched the selections. Returns the selections set.
+    pub fn send(editor: &mut Editor, event: InputEvent) -> Vec<(usize, Vec<u8>)> {
+        editor::handle_event(editor, event);
+        answer(editor, None)
+    }
+
+    pub fn answer(editor: &mut Editor, content: Option<&[u8]>) -> Vec<(usize, Vec<u8>)> {
+        let mut set = Vec::new();
+
+        while !editor.requests.is_empty() {
+            for request in std::mem::take(&mut editor.requests) {
+                match request {
+                    Request::SetSelection(selection, content) => set.push((selection, content)),
+                    Request::ReadSelection(selection) => {
+                        let content = content.map(Vec::from);
+                        editor::handle_event(editor, InputEvent::Selection { selection, content });
+                    },
+                    Request::Quit => {},
+                }
+            }
+        }
+
+        set
+    }
+
+    // Presses keys written the way the bindings are, like "C-x 2".
+    pub fn keys(editor: &mut Editor, sequence: &str) -> Vec<(usize, Vec<u8>)> {
+        let mut set = Vec::new();
+
+        for word in sequence.split(' ') {
+            let chord = bindings::parse_chord(word).unwrap();
+            let shift = chord.shift || matches!(chord.press, bindings::Press::Char(c) if c.is_uppercase());
+            let event = KeyEvent { press: chord.press, control: chord.control, alt: chord.alt, shift };
+
+            set.extend(send(editor, InputEvent::Key(event)));
+            send(editor, InputEvent::KeyRelease);
+        }
+
+        set
+    }
+
+    pub fn type_text(editor: &mut Editor, text: &str) {
+        for c in text.chars() {
+            let event = KeyEvent { press: bindings::Press::Char(c), control: false, alt: false, shift: c.is_uppercase() };
+            send(editor, InputEvent::Key(event));
+        }
+    }
+}
+
+#[cfg(test)]
+mod editor_test {
+    use super::editor::{self, buffer, bindings, complete, rope, syntax, Editor, InputEvent, KeyEvent, Request};
+    use super::test_input::{answer, keys, send, type_text};
+
+    fn editor(text: &str) -> Editor {
+        let mut buffer = buffer::empty_buffer();
+        buffer.text = rope::from_bytes(text.as_bytes());
+        buffer.syntax = syntax::new(syntax::Language::Plain, rope::line_count(&buffer.text));
+
+        editor::new(buffer, 40, 12)
+    }
+
+    fn text(editor: &Editor) -> String {
+        String::from_utf8(rope::to_bytes(&editor.buffers[editor.main_buffer_index as usize].text)).unwrap()
+    }
+
+    fn cursor(editor: &Editor) -> [u32; 2] {
+        let buffer = &editor.buffers[editor.main_buffer_index as usize];
+        let position = &buffer.cursors[buffer.main_cursor_index as usize].position;
+
+        [position.x, position.y]
+    }
+
+    #[test]
+    fn typing_and_motions_edit_the_buffer() {
+        let mut editor = editor("");
+
+        type_text(&mut editor, "Helo");
+        keys(&mut editor, "C-b");
+        type_text(&mut editor, "l");
+        keys(&mut editor, "C-e <return>");
+        type_text(&mut editor, "wörld");
+        keys(&mut editor, "<left> <backspace> C-a C-d");
+
+        assert_eq!(text(&editor), "Hello\nörd");
+        assert_eq!(cursor(&editor), [0, 1]);
+        assert!(editor.changed);
+
+        keys(&mut editor, "C-/ C-/");
+        assert_eq!(text(&editor), "Hello\nwörld");
+    }
+
+    #[test]
+    fn paging_a_buffer_shorter_than_the_window() {
+        let mut editor = editor("one\ntwo\nthree");
+
+        keys(&mut editor, "C-f <next>");
+        assert_eq!(cursor(&editor), [0, 2]);
+        assert_eq!(editor.buffers[0].offset.y, 0);
+
+        keys(&mut editor, "<prior>");
+        assert_eq!(cursor(&editor), [0, 0]);
+
+        keys(&mut editor, "C-v");
+        assert_eq!(cursor(&editor), [0, 2]);
+    }
+
+    #[test]
+    fn paging_keeps_a_line_of_overlap() {
+        let lines: Vec<String> = (0..30).map(|i| i.to_string()).collect();
+        let mut editor = editor(&lines.join("\n"));
+        // Twelve rows leave eleven for text above the mode line.
+        let rows = 11;
+
+        keys(&mut editor, "<next>");
+        assert_eq!(editor.buffers[0].offset.y, rows - 1);
+        assert_eq!(cursor(&editor), [0, rows - 1]);
+
+        keys(&mut editor, "<next> <next> <next>");
+        assert_eq!(editor.buffers[0].offset.y, 30 - rows);
+        assert_eq!(cursor(&editor), [0, 29]);
+
+        keys(&mut editor, "<prior> <prior> <prior> <prior>");
+        assert_eq!(editor.buffers[0].offset.y, 0);
+        assert_eq!(cursor(&editor), [0, 0]);
+    }
+
+    #[test]
+    fn tabs_reach_the_next_stop() {
+        let mut editor = editor("ab\n\tcd\nxyz");
+
+        keys(&mut editor, "C-f TAB");
+        assert_eq!(text(&editor), "a   b\n\tcd\nxyz");
+        assert_eq!(cursor(&editor), [4, 0]);
+
+        // The tab spans columns 0 to 3, so moving down lands on `c`.
+        keys(&mut editor, "C-n");
+        assert_eq!(cursor(&editor), [1, 1]);
+        assert!(editor.buffers[0].mode_line.right.ends_with(b"c:4  [+]"));
+        let c = &editor.windows[0].unique_chars.positions[&'c'];
+        assert_eq!((c[0][0], c[0][1]), (4, 1));
+        assert!(!editor.windows[0].unique_chars.positions.contains_key(&'\t'));
+
+        keys(&mut editor, "C-n");
+        assert_eq!(cursor(&editor), [3, 2]);
+
+        keys(&mut editor, "M-x");
+        type_text(&mut editor, "indent tabs");
+        keys(&mut editor, "<return> C-a TAB <return>");
+        assert_eq!(text(&editor), "a   b\n\tcd\n\t\n\txyz");
+
+        keys(&mut editor, "M-x");
+        type_text(&mut editor, "tab-width 8");
+        keys(&mut editor, "<return>");
+        assert!(editor.buffers[0].mode_line.right.ends_with(b"c:8  [+]"));
+    }
+
+    #[test]
+    fn indentation_follows_the_language() {
+        let mut editor = editor("");
+        editor.buffers[0].syntax = syntax::new(syntax::Language::Rust, 1);
+
+        type_text(&mut editor, "fn main() {");
+        keys(&mut editor, "<return>");
+        type_text(&mut editor, "let s = \"{\"; // (");
+        keys(&mut editor, "<return>");
+        type_text(&mut editor, "if x {");
+        keys(&mut editor, "<return>");
+        type_text(&mut editor, "}");
+        keys(&mut editor, "<return>");
+        type_text(&mut editor, "}");
+        assert_eq!(text(&editor), "fn main() {\n    let s = \"{\"; // (\n    if x {\n    }\n}");
+        assert_eq!(cursor(&editor), [1, 4]);
+    }
+
+    #[test]
+    fn python_indents_after_a_colon() {
+        let mut editor = editor("def f(x):\n");
+        editor.buffers[0].syntax = syntax::new(syntax::Language::Python, 2);
+        keys(&mut editor, "C-e <return>");
+        type_text(&mut editor, "return x");
+        keys(&mut editor, "<return>");
+        assert_eq!(text(&editor), "def f(x):\n    return x\n    \n");
+    }
+
+    #[test]
+    fn python_strings_hold_no_brackets_or_colons() {
+        for line in ["x = \"(:\"", "y = '[' # (", "z = \"\\\"{\""] {
+            let mut editor = editor(&format!("{}\n", line));
+            editor.buffers[0].syntax = syntax::new(syntax::Language::Python, 2);
+            keys(&mut editor, "C-e <return>");
+            assert_eq!(text(&editor), format!("{}\n\n", line), "{}", line);
+        }
+    }
+
+    #[test]
+    fn indent_region_reindents_the_lines_it_touches() {
+        let mut editor = editor("fn f() {\nlet a = [\n1,\n   \n];\n      }\nrest");
+        editor.buffers[0].syntax = syntax::new(syntax::Language::Rust, 7);
+
+        keys(&mut editor, "C-SPC C-n C-n C-n C-n C-n C-e C-M-\\");
+        assert_eq!(text(&editor), "fn f() {\n    let a = [\n        1,\n\n    ];\n}\nrest");
+        assert_eq!(cursor(&editor), [1, 5]);
+
+        // Without a region, only the cursor's line.
+        keys(&mut editor, "C-p C-p C-p C-p");
+        type_text(&mut editor, "  ");
+        keys(&mut editor, "C-M-\\");
+        assert_eq!(text(&editor), "fn f() {\n    let a = [\n        1,\n\n    ];\n}\nrest");
+        assert_eq!(cursor(&editor), [4, 1]);
+    }
+
+    #[test]
+    fn fuzzy_completion_ranks_word_starts_first() {
+        let candidates = ["main.rs", "mirror", "mod.rs", "rope.rs"].map(|c| c.as_bytes().to_vec()).to_vec();
+        let ranked = complete::rank(b"MR", candidates, 0);
+        assert_eq!(ranked, ["mod.rs", "main.rs", "mirror"].map(|c| c.as_bytes().to_vec()));
+
+        assert_eq!(complete::score(b"", b"anything"), Some(0));
+        assert_eq!(complete::score(b"ba", b"abc"), None);
+    }
+
+    #[test]
+    fn tab_completes_paths_buffers_and_commands() {
+        let directory = std::env::temp_dir().join(format!("engine_complete_test_{}", std::process::id()));
+        std::fs::create_dir_all(directory.join("nested")).unwrap();
+        std::fs::write(directory.join("alpha.txt"), "alpha\n").unwrap();
+        std::fs::write(directory.join("beta.txt"), "beta\n").unwrap();
+        std::fs::write(directory.join(".hidden"), "").unwrap();
+
+        let mut editor = editor("");
+        keys(&mut editor, "M-x");
+        type_text(&mut editor, &format!("e {}/alp", directory.display()));
+        keys(&mut editor, "TAB <return>");
+        assert_eq!(editor.buffers.len(), 2);
+        assert_eq!(text(&editor), "alpha");
+
+        // Several candidates open the box, and stepping puts each in turn on
+        // the command line.
+        keys(&mut editor, "M-x");
+        type_text(&mut editor, &format!("e {}/", directory.display()));
+        keys(&mut editor, "TAB");
+        let rows: std::collections::BTreeSet<u8> = editor.windows[0].unique_chars.positions[&'/'].iter().map(|cell| cell[1]).filter(|row| *row < 11).collect();
+        assert_eq!(rows.into_iter().collect::<Vec<u8>>(), [8, 9, 10]);
+        assert!(editor.windows[0].highlights.is_empty());
+
+        // Ties go to the shorter candidate: nested/, beta.txt, alpha.txt.
+
+        keys(&mut editor, "C-n C-n C-n C-p");
+        assert_eq!(editor.windows[0].highlights.len(), 40);
+        assert!(editor.windows[0].highlights.iter().all(|cell| cell[1] == 9));
+        keys(&mut editor, "<return>");
+        assert_eq!(text(&editor), "beta");
+
+        // Typing closes the box.
+        keys(&mut editor, "M-x");
+        type_text(&mut editor, "b ");
+        keys(&mut editor, "TAB");
+        assert!(editor.windows[0].unique_chars.positions.get(&'.').is_some_and(|cells| !cells.is_empty()));
+        type_text(&mut editor, "x");
+        assert!(editor.windows[0].unique_chars.positions.get(&'.').is_none_or(|cells| cells.is_empty()));
+        keys(&mut editor, "C-g");
+
+        keys(&mut editor, "M-x");
+        type_text(&mut editor, "splitbelow");
+        keys(&mut editor, "TAB <return>");
+        assert_eq!(editor.windows.len(), 2);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn unsaved_buffers_are_marked_and_guarded() {
+        let path = std::env::temp_dir().join(format!("engine_modified_test_{}.txt", std::process::id()));
+        std::fs::write(&path, "saved\n").unwrap();
+
+        let mut editor = editor::new(buffer::buffer_from_file(path.to_str().unwrap()).unwrap(), 40, 12);
+        type_text(&mut editor, "x");
+        assert!(editor.buffers[0].modified);
+        assert!(editor.buffers[0].mode_line.right.ends_with(b"  [+]"));
+
+        keys(&mut editor, "C-x C-s");
+        assert!(!editor.buffers[0].modified);
+        assert!(editor.buffers[0].mode_line.right.ends_with(b"c:1"));
+
+        // Undoing past the save changes the text again.
+        keys(&mut editor, "C-/");
+        assert!(editor.buffers[0].modified);
+        keys(&mut editor, "C-?");
+        assert!(!editor.buffers[0].modified);
+
+        // An edit after undoing past the save drops the saved text for good.
+        keys(&mut editor, "C-/");
+        type_text(&mut editor, "y");
+        keys(&mut editor, "C-/");
+        assert!(editor.buffers[0].modified);
+
+        keys(&mut editor, "M-x");
+        type_text(&mut editor, "k");
+        keys(&mut editor, "<return>");
+        assert_eq!(editor.buffers.len(), 1);
+
+        keys(&mut editor, "M-x");
+        type_text(&mut editor, "k!");
+        keys(&mut editor, "<return>");
+        assert_eq!(editor.buffers.len(), 1);
+        assert!(editor.buffers[0].file_name.is_none());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn undoing_back_to_the_loaded_text_is_clean() {
+        let mut editor = editor("abc");
+        type_text(&mut editor, "xy");
+        keys(&mut editor, "C-/");
+        assert!(!editor.buffers[0].modified);
+        assert!(!editor.buffers[0].mode_line.right.ends_with(b"[+]"));
+
+        keys(&mut editor, "C-?");
+        assert!(editor.buffers[0].modified);
+
+        // Nothing left to undo leaves the buffer as it was.
+        keys(&mut editor, "C-/ C-/");
+        assert_eq!(text(&editor), "abc");
+        assert!(!editor.buffers[0].modified);
+    }
+
+    #[test]
+    fn the_buffer_list_switches_and_kills() {
+        let mut editor = editor("scratch");
+        type_text(&mut editor, "!");
+
+        keys(&mut editor, "M-x");
+        type_text(&mut editor, "e Cargo.toml");
+        keys(&mut editor, "<return>");
+        keys(&mut editor, "M-x");
+        type_text(&mut editor, "e build.rs");
+        keys(&mut editor, "<return>");
+        assert_eq!(editor.buffers.len(), 3);
+
+        keys(&mut editor, "C-x C-b");
+        assert_eq!(text(&editor), " * maconha\n   Cargo.toml\n   build.rs\n   *Buffers*");
+
+        // Letters don't type into the list.
+        keys(&mut editor, "C-p x <return>");
+        assert_eq!(editor.main_buffer_index, 1);
+        assert!(text(&editor).starts_with("[package]"));
+
+        keys(&mut editor, "C-x C-b C-n k");
+        assert_eq!(editor.buffers.len(), 3);
+        assert_eq!(text(&editor), " * maconha\n   Cargo.toml\n   *Buffers*");
+
+        // Unsaved buffers stay until killed with `:k!`.
+        keys(&mut editor, "C-p C-p k <return> C-x k");
+        assert_eq!(editor.buffers.len(), 3);
+        assert_eq!(text(&editor), "!scratch");
+
+        keys(&mut editor, "M-x");
+        type_text(&mut editor, "k!");
+        keys(&mut editor, "<return>");
+        assert_eq!(editor.buffers.len(), 2);
+        assert!(text(&editor).starts_with("[package]"));
+
+        keys(&mut editor, "M-x");
+        type_text(&mut editor, "b missing.rs");
+        keys(&mut editor, "<return>");
+        assert!(text(&editor).starts_with("[package]"));
+        assert_eq!(editor.buffers[editor.main_buffer_index as usize].mode_line.middle, b"No buffer named missing.rs");
+    }
+
+    #[test]
+    fn quitting_with_unsaved_edits_asks_again() {
+        let mut editor = editor("");
+
+        editor::handle_event(&mut editor, InputEvent::Close);
+        assert!(matches!(editor.requests[..], [Request::Quit]));
+        editor.requests.clear();
+
+        type_text(&mut editor, "edit");
+        editor::handle_event(&mut editor, InputEvent::Close);
+        assert!(editor.requests.is_empty());
+        assert!(editor::buffer::unsaved_buffers(&editor) == [b"maconha".to_vec()]);
+
+        editor::handle_event(&mut editor, InputEvent::Close);
+        assert!(matches!(editor.requests[..], [Request::Quit]));
+        editor.requests.clear();
+
+        // Anything in between asks again.
+        keys(&mut editor, "C-f");
+        editor::handle_event(&mut editor, InputEvent::Close);
+        keys(&mut editor, "C-f");
+        editor::handle_event(&mut editor, InputEvent::Close);
+        assert!(editor.requests.is_empty());
+    }
+
+    #[test]
+    fn held_keys_repeat_until_released() {
+        let mut editor = editor("abcdef");
+
+        send(&mut editor, InputEvent::Key(KeyEvent { press: bindings::Press::Char('d'), control: true, alt: false, shift: false }));
+        assert!(editor::repeating(&editor));
+        editor::repeat(&mut editor);
+        editor::repeat(&mut editor);
+        assert_eq!(text(&editor), "def");
+
+        send(&mut editor, InputEvent::KeyRelease);
+        assert!(!editor::repeating(&editor));
+        editor::repeat(&mut editor);
+        assert_eq!(text(&editor), "def");
+
+        keys(&mut editor, "C-SPC");
+        assert!(!editor::repeating(&editor));
+    }
+
+    #[test]
+    fn kills_reach_the_clipboard_and_yank_back() {
+        let mut editor = editor("one\ntwo\nthree");
+
+        assert_eq!(keys(&mut editor, "C-k"), vec![(editor::CLIPBOARD, b"one".to_vec())]);
+        assert_eq!(keys(&mut editor, "C-k"), vec![(editor::CLIPBOARD, b"one\n".to_vec())]);
+        keys(&mut editor, "C-n C-y");
+        assert_eq!(text(&editor), "two\none\nthree");
+
+        keys(&mut editor, "C-p");
+        editor::handle_event(&mut editor, InputEvent::Key(KeyEvent { press: bindings::Press::Char('y'), control: true, alt: false, shift: false }));
+        answer(&mut editor, Some(b"pasted "));
+        assert_eq!(text(&editor), "two\npasted one\nthree");
+
+        keys(&mut editor, "M-y");
+        assert_eq!(text(&editor), "two\none\none\nthree");
+    }
+
+    #[test]
+    fn shift_motions_select_and_prefix_keys_wait() {
+        let mut editor = editor("select me");
+
+        keys(&mut editor, "S-<right> S-<right> C-F");
+        assert_eq!(keys(&mut editor, "M-w"), vec![(editor::CLIPBOARD, b"sel".to_vec())]);
+
+        keys(&mut editor, "<right> C-x");
+        assert_eq!(editor.windows.len(), 1);
+        keys(&mut editor, "3");
+        assert_eq!(editor.windows.len(), 2);
+        assert_eq!(editor.windows[0].rect.width + editor.windows[1].rect.width + 1, 40);
+
+        keys(&mut editor, "C-x C-z");
+        type_text(&mut editor, "!");
+        assert_eq!(text(&editor), "sele!ct me");
+    }
+
+    #[test]
+    fn searches_and_the_command_line_take_keys() {
+        let mut editor = editor("alpha\nbeta\ngamma beta");
+
+        keys(&mut editor, "C-s");
+        type_text(&mut editor, "beta");
+        keys(&mut editor, "C-s <return>");
+        assert_eq!(cursor(&editor), [10, 2]);
+
+        keys(&mut editor, "M-x");
+        type_text(&mut editor, "replace /beta/delta/");
+        keys(&mut editor, "<return>");
+        assert_eq!(text(&editor), "alpha\ndelta\ngamma delta");
+
+        keys(&mut editor, "M-x");
+        type_text(&mut editor, "discarded");
+        keys(&mut editor, "C-g");
+        type_text(&mut editor, "x");
+        assert_eq!(text(&editor), "alpha\ndelta\ngamma deltax");
+    }
+
+    #[test]
+    fn pointer_selects_and_pastes_primary() {
+        let mut editor = editor("first line\nsecond line");
+
+        send(&mut editor, InputEvent::Press { cell: [6, 0], clicks: 1 });
+        send(&mut editor, InputEvent::Drag { cell: [2, 1] });
+        assert_eq!(send(&mut editor, InputEvent::Release), vec![(editor::PRIMARY, b"line\nse".to_vec())]);
+
+        send(&mut editor, InputEvent::Press { cell: [8, 1], clicks: 1 });
+        send(&mut editor, InputEvent::Press { cell: [8, 1], clicks: 2 });
+        assert_eq!(send(&mut editor, InputEvent::Release), vec![(editor::PRIMARY, b"line".to_vec())]);
+
+        send(&mut editor, InputEvent::Paste { cell: [0, 0] });
+        assert_eq!(text(&editor), "linefirst line\nsecond line");
+
+        send(&mut editor, InputEvent::Scroll { cell: [0, 0], lines: [1, 0] });
+        assert_eq!(editor.buffers[0].offset.y, 1);
+    }
+
+    #[test]
+    fn resizing_lays_windows_out_again() {
+        let mut editor = editor("text");
+
+        keys(&mut editor, "C-x 2");
+        send(&mut editor, InputEvent::Resize { columns: 20, rows: 30 });
+
+        assert_eq!(editor.windows.iter().map(|window| window.rect.height).sum::<u32>() + editor.separators.len() as u32, 30);
+        assert!(editor.windows.iter().all(|window| window.rect.width == 20));
+    }
+}
+
+#[cfg(test)]
+mod frame_test {
+    use super::editor::buffer::UniqueChars;
+    use super::renderer::{self, vulkan, Rect};
+
+    #[test]
+    fn runs_break_at_blanks_gaps_and_colors() {
+        let mut positions = std::collections::HashMap::new();
+        positions.insert('a', vec![[0, 0, 0], [1, 0, 0], [3, 0, 0], [0, 1, 0]]);
+        positions.insert('b', vec![[2, 0, 1], [5, 0, 1]]);
+        positions.insert(' ', vec![[4, 0, 0]]);
+
+        let runs = renderer::text_runs(&UniqueChars { positions }, [10, 20]);
+        let runs = runs.iter().map(|run| (run.cell, run.text.iter().collect::<String>())).collect::<Vec<_>>();
+
+        assert_eq!(runs, vec![
+            ([10, 20], "aa".to_string()),
+            ([12, 20], "b".to_string()),
+            ([13, 20], "a".to_string()),
+            ([15, 20], "b".to_string()),
+            ([10, 21], "a".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn separators_get_an_unclipped_layer() {
+        let separator = Rect { x: 5, y: 0, width: 1, height: 4 };
+        let frame = renderer::frame(&[], &[separator]);
+
+        assert_eq!(frame.layers.len(), 1);
+        assert!(frame.layers[0].clip.is_none());
+        assert_eq!(frame.layers[0].fills[0].rect, separator);
+    }
+
+    #[test]
+    fn wide_glyphs_cover_two_cells() {
+        let runs = [renderer::TextRun { cell: [1, 0], text: vec!['a', '日'], color: renderer::TEXT_COLORS[0] }];
+        let glyph_indices = [('a', 0), ('日', 1)].into_iter().collect();
+        let empty = vulkan::GlyphInstance { position: [0.0; 2], glyph: 0, color: [0.0; 4], width: 1.0 };
+        let mut instances = [empty; 2];
+
+        assert_eq!(vulkan::text_instances(&mut instances, &glyph_indices, &runs), 2);
+        assert_eq!(instances.map(|instance| (instance.position[0], instance.width)), [(3.0, 1.0), (6.0, 2.0)]);
+    }
+}
+
+#[cfg(test)]
+mod software_test {
+    use super::editor::{self, buffer, Editor, InputEvent};
+    use super::test_input::{keys, type_text};
+    use super::renderer::{self, software, Renderer};
+    use super::font;
+
+    // Wide enough that each half of a split shows its whole mode line.
+    const SIZE: [u32; 2] = [960, 240];
+
+    // Sized the way the Wayland frontend sizes its grid.
+    fn setup() -> (Editor, software::Software) {
+        let font = font::init("assets/fonts/font.ttf", &(' '..='~').collect::<Vec<char>>(), 50).unwrap();
+        let buffer = buffer::buffer_from_file("assets/golden/sample.rs").unwrap();
+
+        let rows = (1.0 / font.scale) as u32;
+        let columns = (1.0 / (font.scale * font.x_ratio * SIZE[1] as f32 / SIZE[0] as f32)) as u32;
+        let mut editor = editor::new(buffer, columns, rows);
+        editor::handle_event(&mut editor, InputEvent::Resize { columns, rows });
+
+        (editor, software::new(font))
+    }
+
+    // Set ENGINE_UPDATE_GOLDEN to rewrite the references after an intended
+    // change. A mismatch leaves the frame in the temp dir to look at.
+    fn check(name: &str, editor: &Editor, software: &mut software::Software) {
+        let frame = renderer::frame(&editor.windows, &editor.separators);
+        software.invalidate();
+        software.draw(&frame, SIZE).unwrap();
+
+        let png = software::to_png(&software.image);
+        let path = format!("assets/golden/{}.png", name);
+
+        if std::env::var_os("ENGINE_UPDATE_GOLDEN").is_some() {
+            std::fs::write(&path, &png).unwrap();
+            return;
+        }
+
+        if std::fs::read(&path).ok().as_ref() != Some(&png) {
+            let actual = std::env::temp_dir().join(format!("{}.png", name));
+            std::fs::write(&actual, &png).unwrap();
+            panic!("{} differs from {}", actual.display(), path);
+        }
+    }
+
+    #[test]
+    fn ppm_keeps_the_color_channels() {
+        let image = software::Image { width: 2, height: 1, pixels: vec![1, 2, 3, 255, 4, 5, 6, 255] };
+
+        assert_eq!(software::to_ppm(&image), b"P6\n2 1\n255\n\x01\x02\x03\x04\x05\x06");
+    }
+
+    #[test]
+    fn scrolled_view() {
+        let (mut editor, mut software) = setup();
+
+        editor::handle_event(&mut editor, InputEvent::Scroll { cell: [0, 0], lines: [6, 0] });
+        check("scrolled", &editor, &mut software);
+    }
+
+    #[test]
+    fn mode_line_with_split_selection_and_matches() {
+        let (mut editor, mut software) = setup();
+
+        keys(&mut editor, "C-x 3 C-s");
+        type_text(&mut editor, "count");
+        keys(&mut editor, "<return> C-SPC C-n C-n");
+        check("split", &editor, &mut software);
+    }
+
+    #[test]
+    fn command_mode() {
+        let (mut editor, mut software) = setup();
+
+        keys(&mut editor, "M-x");
+        type_text(&mut editor, "replace /a/b/");
+        check("command", &editor, &mut software);
+    }
+}
+
+#[cfg(test)]
+mod tty_test {
+    use super::editor::{bindings::Press, key::Key, KeyEvent};
+    use super::renderer::{self, Rect};
+    use super::renderer::tty::{self, input::{self, Input}, screen};
+
+    fn key(press: Press, control: bool, alt: bool, shift: bool) -> Input {
+        Input::Key(KeyEvent { press, control, alt, shift })
+    }
+
+    #[test]
+    fn control_alt_and_escape_sequences() {
+        let bytes = b"a\x01\x1bx\x1b[1;5C\x1b[3~\x1bOP\x1b[Z\r\x7f\x1b\x1b";
+        let (inputs, used) = input::parse(bytes, true);
+
+        assert_eq!(used, bytes.len());
+        assert_eq!(inputs, vec![
+            key(Press::Char('a'), false, false, false),
+            key(Press::Char('a'), true, false, false),
+            key(Press::Char('x'), false, true, false),
+            key(Press::Key(Key::Right), true, false, false),
+            key(Press::Key(Key::Delete), false, false, false),
+            key(Press::Key(Key::Function(1)), false, false, false),
+            key(Press::Char('\t'), false, false, true),
+            key(Press::Key(Key::Enter), false, false, false),
+            key(Press::Key(Key::Backspace), false, false, false),
+            key(Press::Key(Key::Escape), false, false, false),
+            key(Press::Key(Key::Escape), false, false, false),
+        ]);
+    }
+
+    #[test]
+    fn split_escape_sequences_wait_for_the_rest() {
+        for bytes in [&b"a\x1b"[..], b"a\x1b[", b"a\x1b[1;5", b"a\x1bO", b"a\x1b\xc3"] {
+            let (inputs, used) = input::parse(bytes, false);
+            assert_eq!(inputs, vec![key(Press::Char('a'), false, false, false)], "{:?}", bytes);
+            assert_eq!(used, 1, "{:?}", bytes);
+        }
+
+        let (inputs, used) = input::parse(b"\x1b[1;5C", false);
+        assert_eq!(inputs, vec![key(Press::Key(Key::Right), true, false, false)]);
+        assert_eq!(used, 6);
+
+        let (inputs, used) = input::parse(b"\x1b[", true);
+        assert_eq!(inputs, vec![key(Press::Key(Key::Escape), false, false, false), key(Press::Char('['), false, false, false)]);
+        assert_eq!(used, 2);
+    }
+
+    #[test]
+    fn split_characters_wait_and_mouse_reports_parse() {
+        let (inputs, used) = input::parse("Aé".as_bytes().split_last().unwrap().1, false);
+        assert_eq!(inputs, vec![key(Press::Char('A'), false, false, true)]);
+        assert_eq!(used, 1);
+
+        let (inputs, _) = input::parse(b"\x1b[<0;5;2M\x1b[<32;6;2M\x1b[<0;6;2m\x1b[<65;1;1M", false);
+        assert_eq!(inputs, vec![
+            Input::Mouse { button: 0, cell: [4, 1], released: false },
+            Input::Mouse { button: 32, cell: [5, 1], released: false },
+            Input::Mouse { button: 0, cell: [5, 1], released: true },
+            Input::Mouse { button: 65, cell: [0, 0], released: false },
+        ]);
+    }
+
+    #[test]
+    fn frames_lay_out_and_only_changes_are_written() {
+        let mut frame = renderer::frame(&[], &[Rect { x: 2, y: 0, width: 1, height: 2 }]);
+        frame.layers[0].text.push(renderer::TextRun { cell: [0, 1], text: vec!['h', 'i'], color: renderer::TEXT_COLORS[0] });
+
+        let old = screen::cells(&frame, [4, 2]);
+        assert_eq!(old[5].text, "i");
+        assert_eq!(old[2].background, old[6].background);
+        assert_ne!(old[2].background, old[3].background);
+
+        frame.layers[0].text[0].text[1] = 'o';
+        let new = screen::cells(&frame, [4, 2]);
+        assert_eq!(screen::diff(&old, &new, 4), b"\x1b[2;2H\x1b[38;2;255;255;255;48;2;0;0;0mo");
+        assert!(screen::diff(&new, &new, 4).is_empty());
+
+        // The terminal draws both halves of a wide character from one write.
+        frame.layers[0].text[0].text = vec!['日'];
+        let wide = screen::cells(&frame, [4, 2]);
+        assert_eq!(screen::diff(&new, &wide, 4), "\x1b[2;1H\x1b[38;2;255;255;255;48;2;0;0;0m日".as_bytes());
+    }
+
+    #[test]
+    fn selections_encode_for_osc_52() {
+        assert_eq!(tty::base64(b""), "");
+        assert_eq!(tty::base64(b"f"), "Zg==");
+        assert_eq!(tty::base64(b"fo"), "Zm8=");
+        assert_eq!(tty::base64(b"foobar"), "Zm9vYmFy");
+    }
+}
+
+#[cfg(test)]
+mod file_test {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::editor::{self, buffer, file, Editor};
+    use super::test_input::{keys, type_text};
+
+    fn temporary(name: &str) -> std::path::PathBuf {
+        let directory = std::env::temp_dir().join(format!("engine_file_test_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        directory.join(name)
+    }
+
+    fn message(editor: &Editor) -> String {
+        String::from_utf8_lossy(&editor.buffers[editor.main_buffer_index as usize].mode_line.middle).into_owned()
+    }
+
+    #[test]
+    fn line_endings_and_final_newline_round_trip() {
+        for content in ["a\r\nb\r\n", "a\r\nb", "a\nb", "a\nb\n", "a\rb\r", "", "\n"] {
+            let (text, format) = file::decode(content.as_bytes());
+            assert_eq!(file::encode(&text, &format), Ok(content.as_bytes().to_vec()), "{:?}", content);
+        }
+
+        assert_eq!(file::decode(b"a\r\nb").1.line_ending, file::LineEnding::CrLf);
+        assert!(!file::decode(b"a\r\nb").1.final_newline);
+
+        let (text, format) = file::decode(b"a\r\nb\nc\r\nd\r");
+        assert_eq!(text, b"a\nb\nc\nd");
+        assert_eq!(file::encode(&text, &format), Ok(b"a\r\nb\r\nc\r\nd\r\n".to_vec()));
+    }
+
+    #[test]
+    fn encodings_are_detected_and_round_trip() {
+        let cases: [(&[u8], &str); 6] = [
+            (b"\xef\xbb\xbfol\xc3\xa1\n", "utf-8-bom LF"),
+            (b"\xff\xfeo\x00l\x00\xe1\x00\r\x00\n\x00", "utf-16le CRLF"),
+            (b"\xfe\xff\x00o\x00l\x00\xe1\x00\n", "utf-16be LF"),
+            (b"o\x00l\x00\xe1\x00", "utf-16le LF"),
+            (b"ol\xe1\r", "latin-1 CR"),
+            (b"ol\xc3\xa1", "utf-8 LF"),
+        ];
+
+        for (content, label) in cases {
+            let (text, format) = file::decode(content);
+            assert_eq!(text, "olá".as_bytes(), "{}", label);
+            assert_eq!(file::label(&format), label);
+            assert_eq!(file::encode(&text, &format), Ok(content.to_vec()), "{}", label);
+        }
+
+        let latin1 = file::Format { encoding: file::Encoding::Latin1, ..file::default_format() };
+        assert_eq!(file::encode("λ".as_bytes(), &latin1), Err('λ'));
+    }
+
+    #[test]
+    fn formats_show_in_the_mode_line_and_convert() {
+        let path = temporary("latin1.txt");
+        std::fs::write(&path, b"caf\xe9\n").unwrap();
+
+        let buffer = buffer::buffer_from_file(path.to_str().unwrap()).unwrap();
+        assert!(buffer.mode_line.right.starts_with(b"latin-1 LF  "));
+        let mut editor = editor::new(buffer, 40, 12);
+
+        keys(&mut editor, "M-x");
+        type_text(&mut editor, "line-ending crlf");
+        keys(&mut editor, "<return>");
+        assert!(editor.buffers[0].mode_line.right.starts_with(b"latin-1 CRLF  "));
+
+        keys(&mut editor, "C-x C-s");
+        assert_eq!(std::fs::read(&path).unwrap(), b"caf\xe9\r\n");
+
+        type_text(&mut editor, "λ");
+        keys(&mut editor, "C-x C-s");
+        assert!(message(&editor).ends_with("latin-1 CRLF can't hold 'λ'"));
+
+        keys(&mut editor, "M-x");
+        type_text(&mut editor, "encoding utf-16le");
+        keys(&mut editor, "<return>");
+        keys(&mut editor, "C-x C-s");
+        assert_eq!(std::fs::read(&path).unwrap(), b"\xff\xfe\xbb\x03c\x00a\x00f\x00\xe9\x00\r\x00\n\x00");
+
+        keys(&mut editor, "M-x");
+        type_text(&mut editor, "encoding latin-1");
+        keys(&mut editor, "<return>");
+        assert_eq!(message(&editor), "latin-1 can't hold 'λ'");
+        assert_eq!(editor.buffers[0].format.encoding, file::Encoding::Utf16Le);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn saving_keeps_permissions_and_replaces_atomically() {
+        let path = temporary("script.sh");
+        std::fs::write(&path, "#!/bin/sh\r\necho hi\r\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o750)).unwrap();
+
+        let buffer = buffer::buffer_from_file(path.to_str().unwrap()).unwrap();
+        let mut editor = editor::new(buffer, 40, 12);
+        type_text(&mut editor, "# ");
+        keys(&mut editor, "C-x C-s");
+
+        assert_eq!(message(&editor), format!("Wrote {}", path.display()));
+        assert_eq!(std::fs::read(&path).unwrap(), b"# #!/bin/sh\r\necho hi\r\n");
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o750);
+
+        let leftovers = std::fs::read_dir(path.parent().unwrap()).unwrap().filter(|entry| entry.as_ref().unwrap().file_name().to_string_lossy().ends_with(".save")).count();
+        assert_eq!(leftovers, 0);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn changes_on_disk_stop_the_first_save() {
+        let path = temporary("shared.txt");
+        std::fs::write(&path, "mine\n").unwrap();
+
+        let buffer = buffer::buffer_from_file(path.to_str().unwrap()).unwrap();
+        let mut editor = editor::new(buffer, 40, 12);
+        std::fs::write(&path, "theirs, and longer\n").unwrap();
+
+        keys(&mut editor, "C-x C-s");
+        assert!(message(&editor).ends_with("changed on disk; save again to overwrite"));
+        assert_eq!(std::fs::read(&path).unwrap(), b"theirs, and longer\n");
+
+        keys(&mut editor, "C-x C-s");
+        assert_eq!(std::fs::read(&path).unwrap(), b"mine\n");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn unnamed_buffers_ask_for_a_name_and_errors_show() {
+        let path = temporary("new.txt");
+        let _ = std::fs::remove_file(&path);
+
+        let mut editor = editor::new(buffer::empty_buffer(), 40, 12);
+        type_text(&mut editor, "hello");
+        // The prompt comes with `w ` typed.
+        keys(&mut editor, "C-x C-s");
+        type_text(&mut editor, path.to_str().unwrap());
+        keys(&mut editor, "<return>");
+        assert_eq!(std::fs::read(&path).unwrap(), b"hello\n");
+        assert_eq!(editor.buffers[0].file_name.as_deref(), Some(path.to_str().unwrap().as_bytes()));
+        std::fs::remove_file(&path).unwrap();
+
+        let missing = temporary("missing").join("new.txt");
+        keys(&mut editor, "M-x");
+        type_text(&mut editor, &format!("w {}", missing.display()));
+        keys(&mut editor, "<return>");
+        assert!(message(&editor).starts_with(&format!("Could not save {}: ", missing.display())));
+    }
 }
@@ -1,52 +1,83 @@
-use engine::renderer::wayland;
-use engine::renderer::vulkan;
+use engine::renderer::{self, tty, wayland, vulkan, Renderer};
+use engine::editor::buffer;
 use engine::font;
 
+const FALLBACK_FONTS: [&str; 3] = [
+    "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/noto/NotoColorEmoji.ttf",
+];
+
+// engine [--tty] [file]
 pub fn main() {
+    let mut use_tty = false;
+    let mut path = None;
+
+    for argument in std::env::args().skip(1) {
+        match argument.as_str() {
+            "--tty" | "-t" => use_tty = true,
+            _ => path = Some(argument),
+        }
+    }
+
+    let buffer = path.and_then(|path| buffer::buffer_from_file(&path)).unwrap_or_else(buffer::empty_buffer);
+
+    if use_tty {
+        run_tty(buffer);
+    } else {
+        run_wayland(buffer);
+    }
+}
+
+fn run_wayland(buffer: buffer::Buffer) {
     let default_width = 1920;
     let default_height = 1080;
-    let char_set = (32..127).collect::<Vec<u8>>();
-    let font = font::init("assets/fonts/vic.ttf", &char_set, 30).unwrap();
-
-    let mut window = wayland::init("Engine name", default_width, default_height, font.scale, font.x_ratio).unwrap();
-    let instance = vulkan::instance(&window.extensions).unwrap();
-    let surface = vulkan::surface(&instance, window.display, window.surface).unwrap();
-    let device = vulkan::device(&instance, surface).unwrap();
-    let graphics_pipeline = vulkan::graphics_pipeline(&device, &instance, window.width, window.height).unwrap();
-
-    let mut swapchain = vulkan::swapchain(
-        &device,
-        &graphics_pipeline,
-        &font,
-        char_set.len(),
-        window.width,
-        window.height
-    ).unwrap();
+    let char_set: Vec<char> = (' '..='~').chain('\u{a0}'..='\u{17f}').chain('\u{2010}'..='\u{2027}').chain(['\u{fffd}']).collect();
+    let mut font = font::init("assets/fonts/vic.ttf", &char_set, 30).unwrap();
+    for path in FALLBACK_FONTS.iter() {
+        let _ = font::add_fallback(&mut font, path);
+    }
+
+    let mut window = wayland::init("Engine name", default_width, default_height, font.scale, font.x_ratio, buffer).unwrap();
+    let mut vulkan = vulkan::new(&window.extensions, window.display, window.surface, font, [window.width, window.height]).unwrap();
+    let mut frame = renderer::frame(&window.editor.windows, &window.editor.separators);
 
     while window.running {
-        if let Err(_) = vulkan::draw_frame(
-            &device,
-            &mut swapchain,
-            &graphics_pipeline,
-            &window.buffers[window.main_buffer_index as usize],
-            window.width,
-            window.height
-        ) {
+        if let Err(_) = vulkan.draw(&frame, [window.width, window.height]) {
             break;
         }
 
-        if window.changed {
-            vulkan::set_change(&mut swapchain);
+        if window.editor.changed {
+            frame = renderer::frame(&window.editor.windows, &window.editor.separators);
+            vulkan.invalidate();
             wayland::set_unchanged(&mut window);
         }
 
         wayland::update(&mut window);
     }
 
-    vulkan::shutdown_swapchain(&device, &swapchain);
-    vulkan::shutdown_graphics_pipeline(&device, &graphics_pipeline);
-    vulkan::shutdown_device(&device);
-    vulkan::shutdown_surface(&instance, surface);
-    vulkan::shutdown_instance(&instance);
+    vulkan::shutdown(&vulkan);
     wayland::shutdown(&window);
 }
+
+fn run_tty(buffer: buffer::Buffer) {
+    let mut terminal = tty::init(buffer).unwrap();
+    let mut screen = tty::screen::new();
+    let mut frame = renderer::frame(&terminal.editor.windows, &terminal.editor.separators);
+
+    while terminal.running {
+        if let Err(_) = screen.draw(&frame, terminal.size) {
+            break;
+        }
+
+        if terminal.editor.changed {
+            frame = renderer::frame(&terminal.editor.windows, &terminal.editor.separators);
+            screen.invalidate();
+            tty::set_unchanged(&mut terminal);
+        }
+
+        tty::update(&mut terminal);
+    }
+
+    tty::shutdown(&terminal);
+}
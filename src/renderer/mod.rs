@@ -1,2 +1,161 @@
 pub mod vulkan;
 pub mod wayland;
+pub mod software;
+pub mod tty;
+
+use crate::editor::buffer::{UniqueChars, Window};
+pub use crate::editor::layout::Rect;
+
+// Shared by every backend. Text colors are indexed by `syntax::Kind`.
+pub const TEXT_COLORS: [[f32; 4]; 12] = [
+    [1.0, 1.0, 1.0, 1.0],
+    [0.78, 0.47, 0.87, 1.0],
+    [0.9, 0.75, 0.48, 1.0],
+    [0.38, 0.69, 0.94, 1.0],
+    [0.6, 0.76, 0.47, 1.0],
+    [0.82, 0.6, 0.4, 1.0],
+    [0.5, 0.55, 0.6, 1.0],
+    [0.34, 0.71, 0.76, 1.0],
+    [0.88, 0.42, 0.46, 1.0],
+    [0.9, 0.75, 0.48, 1.0],
+    [0.6, 0.76, 0.47, 1.0],
+    [0.38, 0.69, 0.94, 1.0],
+];
+pub const CURSOR_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+pub const HIGHLIGHT_COLOR: [f32; 4] = [0.25, 0.35, 0.55, 1.0];
+pub const MATCH_COLOR: [f32; 4] = [0.55, 0.4, 0.1, 1.0];
+pub const SEPARATOR_COLOR: [f32; 4] = [0.3, 0.3, 0.3, 1.0];
+pub const CLEAR_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+// A frame, described in grid cells so that any backend can draw it. Fills of
+// every layer go first, then text, then cursors on top.
+pub struct Frame {
+    pub layers: Vec<Layer>,
+}
+
+// Everything drawn in a layer is clipped to its rect, or only to the surface
+// when there is none.
+pub struct Layer {
+    pub clip: Option<Rect>,
+    pub fills: Vec<Fill>,
+    pub text: Vec<TextRun>,
+    pub cursors: Vec<Fill>,
+}
+
+pub struct Fill {
+    pub rect: Rect,
+    pub color: [f32; 4],
+}
+
+// Characters in consecutive cells of one row, sharing a color.
+pub struct TextRun {
+    pub cell: [u32; 2],
+    pub text: Vec<char>,
+    pub color: [f32; 4],
+}
+
+// Colors are linear, as the GPU blends them; this is what they look like on
+// an sRGB display.
+pub fn srgb(linear: f32) -> u8 {
+    let linear = linear.clamp(0.0, 1.0);
+    let encoded = if linear <= 0.0031308 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    };
+
+    (encoded * 255.0).round() as u8
+}
+
+pub trait Renderer {
+    type Error;
+
+    // The next `draw` shows a different frame than the last one.
+    fn invalidate(&mut self);
+
+    // Shows the frame, if it was invalidated or the size changed since. The
+    // size is in the backend's own units: pixels, or cells for a terminal.
+    fn draw(&mut self, frame: &Frame, size: [u32; 2]) -> Result<(), Self::Error>;
+}
+
+// One layer per window, clipped to it, and one for the separators between.
+pub fn frame(windows: &[Window], separators: &[Rect]) -> Frame {
+    let mut layers = Vec::with_capacity(windows.len() + 1);
+
+    for window in windows {
+        let Rect { x, y, .. } = window.rect;
+        let cells = |cells: &[[u8; 2]]| cells.iter().map(|cell| [x + cell[0] as u32, y + cell[1] as u32]).collect::<Vec<_>>();
+
+        let mut fills = row_fills(&cells(&window.matches), MATCH_COLOR);
+        fills.extend(row_fills(&cells(&window.highlights), HIGHLIGHT_COLOR));
+
+        let cursors = window.cursor_cells.iter().map(|cell| [x + cell[0], y + cell[1]]).collect::<Vec<_>>();
+
+        layers.push(Layer {
+            clip: Some(window.rect),
+            fills,
+            text: text_runs(&window.unique_chars, [x, y]),
+            cursors: row_fills(&cursors, CURSOR_COLOR),
+        });
+    }
+
+    layers.push(Layer {
+        clip: None,
+        fills: separators.iter().map(|rect| Fill { rect: *rect, color: SEPARATOR_COLOR }).collect(),
+        text: Vec::new(),
+        cursors: Vec::new(),
+    });
+
+    Frame { layers }
+}
+
+// Merges cells that follow each other on a row, keeping the order they come in.
+fn row_fills(cells: &[[u32; 2]], color: [f32; 4]) -> Vec<Fill> {
+    let mut fills: Vec<Fill> = Vec::new();
+
+    for [x, y] in cells.iter().copied() {
+        if let Some(last) = fills.last_mut() {
+            if last.rect.y == y && last.rect.x + last.rect.width == x {
+                last.rect.width += 1;
+                continue;
+            }
+        }
+
+        fills.push(Fill { rect: Rect { x, y, width: 1, height: 1 }, color });
+    }
+
+    fills
+}
+
+// Whitespace isn't drawn, so it ends a run.
+pub fn text_runs(characters: &UniqueChars, origin: [u32; 2]) -> Vec<TextRun> {
+    let mut cells = Vec::new();
+
+    for (c, offset) in characters.positions.iter() {
+        if c.is_whitespace() {
+            continue;
+        }
+
+        for pos in offset.iter() {
+            cells.push((pos[1], pos[0], *c, pos[2]));
+        }
+    }
+
+    cells.sort_unstable_by_key(|(y, x, _, _)| (*y, *x));
+
+    let mut runs: Vec<TextRun> = Vec::new();
+    let mut end = [u32::MAX; 3];
+
+    for (y, x, c, kind) in cells {
+        let cell = [origin[0] + x as u32, origin[1] + y as u32];
+
+        match runs.last_mut() {
+            Some(run) if end == [cell[0], cell[1], kind as u32] => run.text.push(c),
+            _ => runs.push(TextRun { cell, text: vec![c], color: TEXT_COLORS[kind as usize] }),
+        }
+
+        end = [cell[0] + 1, cell[1], kind as u32];
+    }
+
+    runs
+}
@@ -0,0 +1,381 @@
+use crate::editor::grapheme;
+use crate::font::{self, TrueTypeFont};
+use super::{srgb, Frame, Layer, Rect, Renderer, CLEAR_COLOR};
+
+// A headless reference for the Vulkan renderer. It draws the same instances
+// in the same order, with the same cell geometry, scissors, bilinear atlas
+// sampling and alpha blending, so its output is what the GPU should show.
+
+// Rows of sRGB encoded RGBA pixels.
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+pub struct Software {
+    pub font: TrueTypeFont,
+    pub image: Image,
+    changed: bool,
+}
+
+// Blending happens on linear colors, like on the sRGB swapchain; they are
+// only encoded once the frame is done.
+struct Target {
+    width: u32,
+    height: u32,
+    cell: [f32; 2],
+    colors: Vec<[f32; 4]>,
+}
+
+// A pixel rect, as a scissor is.
+#[derive(Clone, Copy)]
+struct Clip {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+pub fn new(font: TrueTypeFont) -> Software {
+    Software {
+        font,
+        image: Image { width: 0, height: 0, pixels: Vec::new() },
+        changed: true,
+    }
+}
+
+impl Renderer for Software {
+    type Error = std::convert::Infallible;
+
+    fn invalidate(&mut self) {
+        self.changed = true;
+    }
+
+    fn draw(&mut self, frame: &Frame, size: [u32; 2]) -> Result<(), Self::Error> {
+        if self.changed || [self.image.width, self.image.height] != size {
+            self.image = draw_frame(&mut self.font, frame, size);
+            self.changed = false;
+        }
+
+        Ok(())
+    }
+}
+
+pub fn draw_frame(font: &mut TrueTypeFont, frame: &Frame, size: [u32; 2]) -> Image {
+    let [width, height] = size;
+    let mut target = Target {
+        width,
+        height,
+        cell: [font.scale * font.x_ratio * height as f32, font.scale * height as f32],
+        colors: vec![CLEAR_COLOR; (width * height) as usize],
+    };
+
+    let slots = update_glyph_atlas(font, frame);
+
+    for layer in frame.layers.iter() {
+        let clip = layer_clip(&target, layer);
+        for fill in layer.fills.iter() {
+            fill_rect(&mut target, fill.rect, fill.color, clip);
+        }
+    }
+
+    for layer in frame.layers.iter() {
+        let clip = layer_clip(&target, layer);
+        for run in layer.text.iter() {
+            for (i, c) in run.text.iter().enumerate() {
+                if let Some(glyph) = slots.get(c) {
+                    draw_glyph(&mut target, font, [run.cell[0] + i as u32, run.cell[1]], grapheme::char_width(*c), *glyph, run.color, clip);
+                }
+            }
+        }
+    }
+
+    for layer in frame.layers.iter() {
+        let clip = layer_clip(&target, layer);
+        for fill in layer.cursors.iter() {
+            fill_rect(&mut target, fill.rect, fill.color, clip);
+        }
+    }
+
+    Image {
+        width,
+        height,
+        pixels: target.colors.iter().flat_map(|color| [srgb(color[0]), srgb(color[1]), srgb(color[2]), srgb(color[3])]).collect(),
+    }
+}
+
+// Same as `update_glyph_atlas` in the Vulkan renderer.
+fn update_glyph_atlas(font: &mut TrueTypeFont, frame: &Frame) -> std::collections::HashMap<char, u32> {
+    font::begin_frame(font);
+
+    let mut slots = std::collections::HashMap::new();
+    for run in frame.layers.iter().flat_map(|layer| layer.text.iter()) {
+        for c in run.text.iter() {
+            slots.entry(*c).or_insert_with(|| font::glyph(font, *c));
+        }
+    }
+
+    font::take_dirty(font);
+    slots
+}
+
+fn layer_clip(target: &Target, layer: &Layer) -> Clip {
+    match layer.clip {
+        Some(rect) => window_scissor(target, rect),
+        None => Clip { x: 0, y: 0, width: target.width, height: target.height },
+    }
+}
+
+// Same as `window_scissor` in the Vulkan renderer.
+fn window_scissor(target: &Target, rect: Rect) -> Clip {
+    let x = ((rect.x as f32 * target.cell[0]) as u32).min(target.width);
+    let y = ((rect.y as f32 * target.cell[1]) as u32).min(target.height);
+    let width = ((rect.width as f32 * target.cell[0]).ceil() as u32).min(target.width - x);
+    let height = ((rect.height as f32 * target.cell[1]).ceil() as u32).min(target.height - y);
+
+    Clip { x, y, width, height }
+}
+
+// The pixels whose centers fall inside a cell, as the rasterizer picks them,
+// clipped to the scissor.
+fn cell_pixels(target: &Target, cell: [u32; 2], clip: Clip) -> ([u32; 2], [u32; 2]) {
+    let span = |index: u32, size: f32, start: u32, end: u32| {
+        let first = (index as f32 * size - 0.5).ceil().max(start as f32) as u32;
+        let last = ((index + 1) as f32 * size - 0.5).ceil().min(end as f32) as u32;
+
+        [first, last.max(first)]
+    };
+
+    (
+        span(cell[0], target.cell[0], clip.x, clip.x + clip.width),
+        span(cell[1], target.cell[1], clip.y, clip.y + clip.height),
+    )
+}
+
+fn blend(target: &mut Target, x: u32, y: u32, color: [f32; 4]) {
+    let destination = &mut target.colors[(y * target.width + x) as usize];
+    let alpha = color[3];
+
+    for channel in 0..4 {
+        destination[channel] = color[channel] * alpha + destination[channel] * (1.0 - alpha);
+    }
+}
+
+// The solid glyph samples the all-white cursor texture. A rect is drawn as
+// one instance per cell, so its edges land where theirs do.
+fn fill_rect(target: &mut Target, rect: Rect, color: [f32; 4], clip: Clip) {
+    for cell_y in rect.y..rect.y + rect.height {
+        for cell_x in rect.x..rect.x + rect.width {
+            let ([x0, x1], [y0, y1]) = cell_pixels(target, [cell_x, cell_y], clip);
+
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    blend(target, x, y, color);
+                }
+            }
+        }
+    }
+}
+
+// Maps the `width` cells from `cell` onto the glyph's atlas rect, as the
+// glyph vertices do.
+fn draw_glyph(target: &mut Target, font: &TrueTypeFont, cell: [u32; 2], width: u32, glyph: u32, color: [f32; 4], clip: Clip) {
+    let metric = &font.metrics[glyph as usize];
+    let ([x0, _], [y0, y1]) = cell_pixels(target, cell, clip);
+    let ([_, x1], _) = cell_pixels(target, [cell[0] + width - 1, cell[1]], clip);
+    let origin = [cell[0] as f32 * target.cell[0], cell[1] as f32 * target.cell[1]];
+    let step = [metric.width as f32 / (target.cell[0] * width as f32), font.line_height as f32 / target.cell[1]];
+
+    for y in y0..y1 {
+        let v = metric.y_offset as f32 + (y as f32 + 0.5 - origin[1]) * step[1];
+
+        for x in x0..x1 {
+            let u = metric.x_offset as f32 + (x as f32 + 0.5 - origin[0]) * step[0];
+            let coverage = sample(font, u, v);
+
+            blend(target, x, y, [color[0], color[1], color[2], color[3] * coverage]);
+        }
+    }
+}
+
+// Bilinear filtering with a transparent border, at texel coordinates.
+fn sample(font: &TrueTypeFont, u: f32, v: f32) -> f32 {
+    let texel = |x: i64, y: i64| {
+        if x < 0 || y < 0 || x >= font.width as i64 || y >= font.height as i64 {
+            0.0
+        } else {
+            font.texture_atlas[(y * font.width as i64 + x) as usize] as f32 / 255.0
+        }
+    };
+
+    let u = u - 0.5;
+    let v = v - 0.5;
+    let x = u.floor();
+    let y = v.floor();
+    let [fx, fy] = [u - x, v - y];
+    let [x, y] = [x as i64, y as i64];
+
+    let top = texel(x, y) * (1.0 - fx) + texel(x + 1, y) * fx;
+    let bottom = texel(x, y + 1) * (1.0 - fx) + texel(x + 1, y + 1) * fx;
+
+    top * (1.0 - fy) + bottom * fy
+}
+
+// Binary PPM, dropping alpha.
+pub fn to_ppm(image: &Image) -> Vec<u8> {
+    let mut bytes = format!("P6\n{} {}\n255\n", image.width, image.height).into_bytes();
+
+    for pixel in image.pixels.chunks(4) {
+        bytes.extend_from_slice(&pixel[..3]);
+    }
+
+    bytes
+}
+
+// Truecolor with alpha, unfiltered scanlines in a fixed-Huffman deflate
+// stream. The output only depends on the pixels, so images can be compared
+// as bytes.
+pub fn to_png(image: &Image) -> Vec<u8> {
+    let stride = image.width as usize * 4;
+    let mut raw = Vec::with_capacity((stride + 1) * image.height as usize);
+    for row in image.pixels.chunks(stride.max(1)) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&image.width.to_be_bytes());
+    header.extend_from_slice(&image.height.to_be_bytes());
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut bytes = b"\x89PNG\r\n\x1a\n".to_vec();
+    png_chunk(&mut bytes, b"IHDR", &header);
+    png_chunk(&mut bytes, b"IDAT", &zlib(&raw, [4, stride + 1]));
+    png_chunk(&mut bytes, b"IEND", &[]);
+
+    bytes
+}
+
+fn png_chunk(bytes: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    bytes.extend_from_slice(&(data.len() as u32).to_be_bytes());
+
+    let start = bytes.len();
+    bytes.extend_from_slice(kind);
+    bytes.extend_from_slice(data);
+
+    let crc = crc32(&bytes[start..]);
+    bytes.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffffffffu32;
+
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { 0xedb88320 ^ (crc >> 1) } else { crc >> 1 };
+        }
+    }
+
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let [mut a, mut b] = [1u32, 0u32];
+
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+
+    (b << 16) | a
+}
+
+struct Bits {
+    bytes: Vec<u8>,
+    value: u32,
+    count: u32,
+}
+
+fn put_bits(bits: &mut Bits, value: u32, count: u32) {
+    bits.value |= value << bits.count;
+    bits.count += count;
+
+    while bits.count >= 8 {
+        bits.bytes.push(bits.value as u8);
+        bits.value >>= 8;
+        bits.count -= 8;
+    }
+}
+
+// Huffman codes go out most significant bit first.
+fn put_code(bits: &mut Bits, code: u32, count: u32) {
+    put_bits(bits, code.reverse_bits() >> (32 - count), count);
+}
+
+fn put_literal(bits: &mut Bits, symbol: u32) {
+    match symbol {
+        0..=143 => put_code(bits, 0x30 + symbol, 8),
+        144..=255 => put_code(bits, 0x190 + symbol - 144, 9),
+        256..=279 => put_code(bits, symbol - 256, 7),
+        _ => put_code(bits, 0xc0 + symbol - 280, 8),
+    }
+}
+
+const LENGTH_BASES: [u32; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u32; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASES: [u32; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DISTANCE_EXTRA: [u32; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+
+fn put_match(bits: &mut Bits, length: u32, distance: u32) {
+    let code = LENGTH_BASES.iter().rposition(|base| *base <= length).unwrap();
+    put_literal(bits, 257 + code as u32);
+    put_bits(bits, length - LENGTH_BASES[code], LENGTH_EXTRA[code]);
+
+    let code = DISTANCE_BASES.iter().rposition(|base| *base <= distance).unwrap();
+    put_code(bits, code as u32, 5);
+    put_bits(bits, distance - DISTANCE_BASES[code], DISTANCE_EXTRA[code]);
+}
+
+// Only looks for repeats at the given distances, which for an image are the
+// previous pixel and the previous row. Good enough for mostly flat frames.
+fn zlib(data: &[u8], distances: [usize; 2]) -> Vec<u8> {
+    let mut bits = Bits { bytes: vec![0x78, 0x01], value: 0, count: 0 };
+    put_bits(&mut bits, 1, 1);
+    put_bits(&mut bits, 1, 2);
+
+    let mut i = 0;
+    while i < data.len() {
+        let mut best = [0, 0];
+
+        for distance in distances {
+            if distance == 0 || distance > i || distance > 32768 {
+                continue;
+            }
+
+            let length = (0..258.min(data.len() - i)).take_while(|k| data[i + k] == data[i + k - distance]).count();
+            if length > best[0] {
+                best = [length, distance];
+            }
+        }
+
+        if best[0] >= 3 {
+            put_match(&mut bits, best[0] as u32, best[1] as u32);
+            i += best[0];
+        } else {
+            put_literal(&mut bits, data[i] as u32);
+            i += 1;
+        }
+    }
+
+    put_literal(&mut bits, 256);
+
+    let mut bytes = bits.bytes;
+    if bits.count > 0 {
+        bytes.push(bits.value as u8);
+    }
+
+    bytes.extend_from_slice(&adler32(data).to_be_bytes());
+    bytes
+}
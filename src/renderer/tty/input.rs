@@ -0,0 +1,176 @@
+use crate::editor::{bindings::Press, key::Key, KeyEvent};
+
+const ESCAPE: u8 = 0x1b;
+
+// SGR mouse reports: the low bits name the button, the rest are flags.
+pub const BUTTON_LEFT: u32 = 0;
+pub const BUTTON_MIDDLE: u32 = 1;
+pub const MODIFIER_BITS: u32 = 4 | 8 | 16;
+pub const MOTION_BIT: u32 = 32;
+pub const WHEEL_BIT: u32 = 64;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Input {
+    Key(KeyEvent),
+    // A mouse report, with the cell counted from zero.
+    Mouse { button: u32, cell: [u32; 2], released: bool },
+}
+
+// Reads what the terminal has sent so far. An escape sequence or character
+// cut short is left for the next read; returns how many bytes were used.
+// Escape is also how sequences start, so a lone escape only counts as the
+// key once `timed_out` says nothing followed it for a while.
+pub fn parse(bytes: &[u8], timed_out: bool) -> (Vec<Input>, usize) {
+    let mut inputs = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match next(&bytes[i..]) {
+            Some((input, len)) => {
+                inputs.extend(input);
+                i += len;
+            },
+            None if timed_out && bytes[i] == ESCAPE => {
+                inputs.extend(key(Press::Key(Key::Escape), false, false, false));
+                i += 1;
+            },
+            None => break,
+        }
+    }
+
+    (inputs, i)
+}
+
+fn key(press: Press, control: bool, alt: bool, shift: bool) -> Option<Input> {
+    Some(Input::Key(KeyEvent { press, control, alt, shift }))
+}
+
+// The input at the start of `bytes` and its length, None when its bytes
+// haven't all arrived yet.
+fn next(bytes: &[u8]) -> Option<(Option<Input>, usize)> {
+    if bytes[0] != ESCAPE {
+        return byte_key(bytes);
+    }
+
+    match bytes.get(1) {
+        None => None,
+        Some(&ESCAPE) => Some((key(Press::Key(Key::Escape), false, false, false), 1)),
+        Some(b'[') => csi(bytes),
+        Some(b'O') => bytes.get(2).map(|b| (ss3(*b), 3)),
+        Some(_) => {
+            let (input, len) = byte_key(&bytes[1..])?;
+            let input = match input {
+                Some(Input::Key(event)) => key(event.press, event.control, true, event.shift),
+                input => input,
+            };
+
+            Some((input, len + 1))
+        },
+    }
+}
+
+// Keys that arrive as a single character, with control folded into the
+// C0 range the way terminals do it.
+fn byte_key(bytes: &[u8]) -> Option<(Option<Input>, usize)> {
+    let input = match bytes[0] {
+        0x00 => key(Press::Char(' '), true, false, false),
+        b'\t' => key(Press::Char('\t'), false, false, false),
+        b'\r' | b'\n' => key(Press::Key(Key::Enter), false, false, false),
+        0x08 | 0x7f => key(Press::Key(Key::Backspace), false, false, false),
+        b @ 0x01..=0x1a => key(Press::Char((b'a' + b - 1) as char), true, false, false),
+        b @ 0x1c..=0x1f => key(Press::Char(['\\', ']', '^', '/'][(b - 0x1c) as usize]), true, false, false),
+        _ => {
+            let len = match bytes[0] {
+                0xf0..=0xf7 => 4,
+                0xe0..=0xef => 3,
+                0xc0..=0xdf => 2,
+                _ => 1,
+            };
+
+            if bytes.len() < len {
+                return None;
+            }
+
+            let c = std::str::from_utf8(&bytes[..len]).ok().and_then(|s| s.chars().next()).unwrap_or('\u{fffd}');
+            return Some((key(Press::Char(c), false, false, c.is_uppercase()), len));
+        },
+    };
+
+    Some((input, 1))
+}
+
+// ESC O, which keypads in application mode and some F1-F4 send.
+fn ss3(b: u8) -> Option<Input> {
+    let press = match b {
+        b'A' => Key::Up,
+        b'B' => Key::Down,
+        b'C' => Key::Right,
+        b'D' => Key::Left,
+        b'H' => Key::Home,
+        b'F' => Key::End,
+        b'P'..=b'S' => Key::Function(b - b'P' + 1),
+        b'M' => Key::Enter,
+        _ => return None,
+    };
+
+    key(Press::Key(press), false, false, false)
+}
+
+// ESC [, parameters, and a final byte. Modifiers come as a second parameter,
+// one more than a mask of shift, alt and control.
+fn csi(bytes: &[u8]) -> Option<(Option<Input>, usize)> {
+    let end = bytes[2..].iter().position(|b| (0x40..=0x7e).contains(b))?;
+
+    let len = end + 3;
+    let parameters = &bytes[2..len - 1];
+    let last = bytes[len - 1];
+
+    if let Some(parameters) = parameters.strip_prefix(b"<") {
+        return Some((mouse(parameters, last), len));
+    }
+
+    let numbers = parameters.split(|b| *b == b';').map(|n| std::str::from_utf8(n).ok().and_then(|n| n.parse().ok()).unwrap_or(1)).collect::<Vec<u32>>();
+    let mask = numbers.get(1).copied().unwrap_or(1).saturating_sub(1);
+    let [shift, alt, control] = [mask & 1 != 0, mask & (2 | 8) != 0, mask & 4 != 0];
+
+    let press = match last {
+        b'A' => Press::Key(Key::Up),
+        b'B' => Press::Key(Key::Down),
+        b'C' => Press::Key(Key::Right),
+        b'D' => Press::Key(Key::Left),
+        b'H' => Press::Key(Key::Home),
+        b'F' => Press::Key(Key::End),
+        b'P'..=b'S' => Press::Key(Key::Function(last - b'P' + 1)),
+        b'Z' => return Some((key(Press::Char('\t'), false, false, true), len)),
+        b'~' => match numbers.first().copied().unwrap_or(0) {
+            1 | 7 => Press::Key(Key::Home),
+            2 => Press::Key(Key::Insert),
+            3 => Press::Key(Key::Delete),
+            4 | 8 => Press::Key(Key::End),
+            5 => Press::Key(Key::PageUp),
+            6 => Press::Key(Key::PageDown),
+            n @ 11..=15 => Press::Key(Key::Function(n as u8 - 10)),
+            n @ 17..=21 => Press::Key(Key::Function(n as u8 - 11)),
+            n @ 23..=24 => Press::Key(Key::Function(n as u8 - 12)),
+            _ => return Some((None, len)),
+        },
+        _ => return Some((None, len)),
+    };
+
+    Some((key(press, control, alt, shift), len))
+}
+
+// ESC [ < button ; column ; row, ending in M for presses and motion and in
+// m for releases.
+fn mouse(parameters: &[u8], last: u8) -> Option<Input> {
+    let numbers = std::str::from_utf8(parameters).ok()?.split(';').map(|n| n.parse().ok()).collect::<Option<Vec<u32>>>()?;
+    let [button, x, y] = numbers[..] else {
+        return None;
+    };
+
+    Some(Input::Mouse {
+        button,
+        cell: [x.saturating_sub(1), y.saturating_sub(1)],
+        released: last == b'm',
+    })
+}
@@ -0,0 +1,233 @@
+use std::io::Write;
+
+use crate::binding::termios;
+use crate::editor::{self, buffer, InputEvent, Request};
+
+pub mod input;
+pub mod screen;
+
+const READ_SIZE: usize = 4096;
+// How long `update` waits for input, which paces the main loop.
+const POLL_TIMEOUT_MS: i32 = 17;
+// How long an escape waits for the rest of a sequence before it's the key.
+const ESCAPE_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(50);
+const DOUBLE_CLICK: std::time::Duration = std::time::Duration::from_millis(400);
+const LINES_PER_STEP: i32 = 3;
+
+// The alternate screen, a hidden cursor and SGR mouse reports with motion
+// while a button is down; and the way back.
+const ENTER: &[u8] = b"\x1b[?1049h\x1b[?25l\x1b[?1002h\x1b[?1006h";
+const LEAVE: &[u8] = b"\x1b[?1006l\x1b[?1002l\x1b[0m\x1b[?25h\x1b[?1049l";
+
+#[derive(Debug)]
+pub enum TtyError {
+    NotATerminal,
+    Attributes,
+}
+
+pub struct Core {
+    pub running: bool,
+    pub editor: editor::Editor,
+    // Columns and rows.
+    pub size: [u32; 2],
+
+    original: termios::termios,
+    pending: Vec<u8>,
+    last_read: std::time::Instant,
+    pressed: bool,
+    clicks: u32,
+    last_click: Option<(std::time::Instant, [u32; 2])>,
+}
+
+fn window_size() -> [u32; 2] {
+    let mut size = termios::winsize::default();
+    unsafe { termios::ioctl(termios::STDOUT_FILENO, termios::TIOCGWINSZ, &mut size as *mut termios::winsize) };
+
+    [(size.ws_col as u32).max(1), (size.ws_row as u32).max(2)]
+}
+
+fn write(bytes: &[u8]) {
+    let mut stdout = std::io::stdout().lock();
+    let _ = stdout.write_all(bytes);
+    let _ = stdout.flush();
+}
+
+// Puts the terminal in raw mode and takes over the screen. Bindings load
+// first so their errors print to the shell, and a panic hook gives the
+// terminal back before the panic message is written.
+pub fn init(buffer: buffer::Buffer) -> Result<Core, TtyError> {
+    if unsafe { termios::isatty(termios::STDIN_FILENO) } == 0 {
+        return Err(TtyError::NotATerminal);
+    }
+
+    let mut original = std::mem::MaybeUninit::<termios::termios>::uninit();
+    if unsafe { termios::tcgetattr(termios::STDIN_FILENO, original.as_mut_ptr()) } != 0 {
+        return Err(TtyError::Attributes);
+    }
+
+    let original = unsafe { original.assume_init() };
+    let size = window_size();
+    let mut core = Core {
+        running: true,
+        editor: editor::new(buffer, size[0], size[1]),
+        size,
+        original,
+        pending: Vec::new(),
+        last_read: std::time::Instant::now(),
+        pressed: false,
+        clicks: 0,
+        last_click: None,
+    };
+
+    editor::load_user_bindings(&mut core.editor);
+
+    let mut raw = original;
+    unsafe { termios::cfmakeraw(&mut raw) };
+
+    if unsafe { termios::tcsetattr(termios::STDIN_FILENO, termios::TCSAFLUSH, &raw) } != 0 {
+        return Err(TtyError::Attributes);
+    }
+
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore(&original);
+        hook(info);
+    }));
+
+    write(ENTER);
+
+    // What a compositor's first configure does for the Wayland frontend.
+    dispatch(&mut core, InputEvent::Resize { columns: size[0], rows: size[1] });
+
+    Ok(core)
+}
+
+pub fn set_unchanged(core: &mut Core) {
+    core.editor.changed = false;
+}
+
+// Waits a little for input and hands whatever came to the editor. The
+// terminal sends nothing on resize without a signal handler, so the size is
+// checked every time instead.
+pub fn update(core: &mut Core) {
+    let size = window_size();
+    if size != core.size {
+        core.size = size;
+        dispatch(core, InputEvent::Resize { columns: size[0], rows: size[1] });
+    }
+
+    let mut fd = termios::pollfd { fd: termios::STDIN_FILENO, events: termios::POLLIN, revents: 0 };
+    if unsafe { termios::poll(&mut fd, 1, POLL_TIMEOUT_MS) } > 0 {
+        let mut bytes = [0u8; READ_SIZE];
+        let len = unsafe { termios::read(termios::STDIN_FILENO, bytes.as_mut_ptr().cast(), bytes.len()) };
+
+        if len > 0 {
+            core.pending.extend_from_slice(&bytes[..len as usize]);
+            core.last_read = std::time::Instant::now();
+        }
+    }
+
+    let (inputs, used) = input::parse(&core.pending, core.last_read.elapsed() >= ESCAPE_TIMEOUT);
+    core.pending.drain(..used);
+
+    for input in inputs {
+        match input {
+            // Terminals repeat keys themselves and never report releases.
+            input::Input::Key(event) => {
+                dispatch(core, InputEvent::Key(event));
+                dispatch(core, InputEvent::KeyRelease);
+            },
+            input::Input::Mouse { button, cell, released } => {
+                if let Some(event) = mouse_event(core, button, cell, released) {
+                    dispatch(core, event);
+                }
+            },
+        }
+    }
+}
+
+fn mouse_event(core: &mut Core, button: u32, cell: [u32; 2], released: bool) -> Option<InputEvent> {
+    // Wheel buttons 4 to 7 are up, down, left and right.
+    if button & input::WHEEL_BIT != 0 {
+        let step = if button & 1 == 0 { -LINES_PER_STEP } else { LINES_PER_STEP };
+        let lines = if button & 2 == 0 { [step, 0] } else { [0, step] };
+        return Some(InputEvent::Scroll { cell, lines });
+    }
+
+    match button & !(input::MOTION_BIT | input::MODIFIER_BITS) {
+        input::BUTTON_LEFT if released => {
+            core.pressed = false;
+            Some(InputEvent::Release)
+        },
+        input::BUTTON_LEFT if button & input::MOTION_BIT != 0 => {
+            Some(InputEvent::Drag { cell }).filter(|_| core.pressed)
+        },
+        input::BUTTON_LEFT => {
+            let now = std::time::Instant::now();
+            core.clicks = match core.last_click {
+                Some((last, last_cell)) if last_cell == cell && now - last <= DOUBLE_CLICK => core.clicks % 3 + 1,
+                _ => 1,
+            };
+
+            core.last_click = Some((now, cell));
+            core.pressed = true;
+            Some(InputEvent::Press { cell, clicks: core.clicks })
+        },
+        input::BUTTON_MIDDLE if !released && button & input::MOTION_BIT == 0 => Some(InputEvent::Paste { cell }),
+        _ => None,
+    }
+}
+
+fn dispatch(core: &mut Core, event: InputEvent) {
+    editor::handle_event(&mut core.editor, event);
+    serve_requests(core);
+}
+
+// Selections go out through OSC 52, which works over SSH, but terminals
+// rarely let us read them back; the editor's own copy stands in.
+fn serve_requests(core: &mut Core) {
+    while !core.editor.requests.is_empty() {
+        for request in std::mem::take(&mut core.editor.requests) {
+            match request {
+                Request::SetSelection(selection, content) => {
+                    let target = if selection == editor::PRIMARY { 'p' } else { 'c' };
+                    write(format!("\x1b]52;{};{}\x07", target, base64(&content)).as_bytes());
+                },
+                Request::ReadSelection(selection) => {
+                    editor::handle_event(&mut core.editor, InputEvent::Selection { selection, content: None });
+                },
+                Request::Quit => core.running = false,
+            }
+        }
+    }
+}
+
+pub fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                text.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+
+    text
+}
+
+fn restore(original: &termios::termios) {
+    write(LEAVE);
+    unsafe { termios::tcsetattr(termios::STDIN_FILENO, termios::TCSAFLUSH, original) };
+}
+
+pub fn shutdown(core: &Core) {
+    // Drops the hook from `init`, which would restore the terminal twice.
+    let _ = std::panic::take_hook();
+    restore(&core.original);
+}
@@ -0,0 +1,172 @@
+use std::io::Write;
+
+use crate::editor::grapheme;
+use crate::renderer::{srgb, Frame, Rect, Renderer, TEXT_COLORS, CLEAR_COLOR, CURSOR_COLOR};
+
+// What one terminal cell shows. Empty text is a blank; combining marks
+// share the cell with the character they follow.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Cell {
+    pub text: String,
+    pub foreground: [u8; 3],
+    pub background: [u8; 3],
+}
+
+// Draws frames on the terminal, writing only the cells that changed since
+// the last one.
+pub struct Screen {
+    cells: Vec<Cell>,
+    size: [u32; 2],
+    changed: bool,
+}
+
+pub fn new() -> Screen {
+    Screen {
+        cells: Vec::new(),
+        size: [0, 0],
+        changed: true,
+    }
+}
+
+impl Renderer for Screen {
+    type Error = std::io::Error;
+
+    fn invalidate(&mut self) {
+        self.changed = true;
+    }
+
+    fn draw(&mut self, frame: &Frame, size: [u32; 2]) -> Result<(), std::io::Error> {
+        if !self.changed && self.size == size {
+            return Ok(());
+        }
+
+        let cells = cells(frame, size);
+        let mut bytes = Vec::new();
+
+        // A resized terminal keeps whatever it reflowed, so start over.
+        if self.size != size {
+            bytes.extend_from_slice(b"\x1b[0m\x1b[2J");
+            self.cells.clear();
+        }
+
+        bytes.extend(diff(&self.cells, &cells, size[0]));
+
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(&bytes)?;
+        stdout.flush()?;
+
+        self.cells = cells;
+        self.size = size;
+        self.changed = false;
+        Ok(())
+    }
+}
+
+fn rgb(color: [f32; 4]) -> [u8; 3] {
+    [srgb(color[0]), srgb(color[1]), srgb(color[2])]
+}
+
+// Lays the frame out on a grid of `size` columns and rows. Cursors show the
+// text under them in the background color.
+pub fn cells(frame: &Frame, size: [u32; 2]) -> Vec<Cell> {
+    let [columns, rows] = size;
+    let blank = Cell { text: String::new(), foreground: rgb(TEXT_COLORS[0]), background: rgb(CLEAR_COLOR) };
+    let mut cells = vec![blank; (columns * rows) as usize];
+
+    let screen = Rect { x: 0, y: 0, width: columns, height: rows };
+    let index = |clip: Option<Rect>, x: u32, y: u32| {
+        let clip = clip.unwrap_or(screen);
+        let inside = |rect: Rect| x >= rect.x && y >= rect.y && x < rect.x + rect.width && y < rect.y + rect.height;
+
+        if inside(clip) && inside(screen) {
+            Some((y * columns + x) as usize)
+        } else {
+            None
+        }
+    };
+
+    let cells_of = |rect: Rect| (rect.y..rect.y + rect.height).flat_map(move |y| (rect.x..rect.x + rect.width).map(move |x| (x, y)));
+
+    for layer in frame.layers.iter() {
+        for fill in layer.fills.iter() {
+            for (x, y) in cells_of(fill.rect) {
+                if let Some(i) = index(layer.clip, x, y) {
+                    cells[i].background = rgb(fill.color);
+                }
+            }
+        }
+    }
+
+    for layer in frame.layers.iter() {
+        for run in layer.text.iter() {
+            for (offset, c) in run.text.iter().enumerate() {
+                if let Some(i) = index(layer.clip, run.cell[0] + offset as u32, run.cell[1]) {
+                    cells[i].text.push(*c);
+                    cells[i].foreground = rgb(run.color);
+                }
+            }
+        }
+    }
+
+    for layer in frame.layers.iter() {
+        for fill in layer.cursors.iter() {
+            for (x, y) in cells_of(fill.rect) {
+                if let Some(i) = index(layer.clip, x, y) {
+                    cells[i].foreground = rgb(CLEAR_COLOR);
+                    cells[i].background = rgb(CURSOR_COLOR);
+                }
+            }
+        }
+    }
+
+    cells
+}
+
+// Escape codes that turn `old` into `new`, with cells missing from `old`
+// always written. The cursor is only moved when the next changed cell isn't
+// where the last write left it, and colors only set when they change. Wide
+// characters move the cursor by an amount only the terminal knows, so
+// anything but ASCII is followed by an explicit move.
+pub fn diff(old: &[Cell], new: &[Cell], columns: u32) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut position = None;
+    let mut colors: Option<([u8; 3], [u8; 3])> = None;
+
+    for (i, cell) in new.iter().enumerate() {
+        if old.get(i) == Some(cell) {
+            continue;
+        }
+
+        let [x, y] = [i as u32 % columns, i as u32 / columns];
+
+        // The right half of a wide character, which writing would erase.
+        if x > 0 && new[i - 1].text.chars().next().is_some_and(|c| grapheme::char_width(c) == 2) {
+            continue;
+        }
+
+        if position != Some([x, y]) {
+            bytes.extend(format!("\x1b[{};{}H", y + 1, x + 1).into_bytes());
+        }
+
+        if colors != Some((cell.foreground, cell.background)) {
+            let [fr, fg, fb] = cell.foreground;
+            let [br, bg, bb] = cell.background;
+            bytes.extend(format!("\x1b[38;2;{};{};{};48;2;{};{};{}m", fr, fg, fb, br, bg, bb).into_bytes());
+            colors = Some((cell.foreground, cell.background));
+        }
+
+        if cell.text.is_empty() {
+            bytes.push(b' ');
+        } else {
+            bytes.extend_from_slice(cell.text.as_bytes());
+        }
+
+        position = if cell.text.is_ascii() && x + 1 < columns {
+            Some([x + 1, y])
+        } else {
+            None
+        };
+    }
+
+    bytes
+}
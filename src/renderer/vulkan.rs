@@ -4,9 +4,19 @@ use crate::binding::dl;
 use crate::binding::vulkan;
 use crate::binding::wayland;
 
+use crate::editor::grapheme;
+use crate::font;
 use crate::font::TrueTypeFont;
-use crate::renderer::wayland::buffer::UniqueChars;
-use crate::renderer::wayland::buffer::Buffer as WindowBuffer;
+use super::{Fill, Frame, Rect, Renderer, TextRun, CLEAR_COLOR};
+
+// The instance buffer is split in three regions, each large enough for
+// every cell of the largest grid the editor lays out.
+const INSTANCE_REGION: usize = 256 * 256;
+const TEXT_INSTANCES: usize = 0;
+const HIGHLIGHT_INSTANCES: usize = INSTANCE_REGION;
+const CURSOR_INSTANCES: usize = 2 * INSTANCE_REGION;
+const INSTANCE_CAPACITY: usize = 3 * INSTANCE_REGION;
+
 
 macro_rules! instance_function {
     ($proc:ident, $instance:ident, $name:ident) => {
@@ -157,7 +167,6 @@ pub struct Device {
     vkCmdCopyBuffer: vulkan::vkCmdCopyBuffer,
     vkCmdBindIndexBuffer: vulkan::vkCmdBindIndexBuffer,
     vkCmdDrawIndexed: vulkan::vkCmdDrawIndexed,
-    vkCmdPushConstants: vulkan::vkCmdPushConstants,
     vkCmdExecuteCommands: vulkan::vkCmdExecuteCommands,
 }
 
@@ -176,7 +185,7 @@ pub struct GraphicsPipeline {
 
 struct CommandBuffer {
     handle: *mut vulkan::CommandBuffer,
-    secondary: [*mut vulkan::CommandBuffer; 2],
+    secondary: [*mut vulkan::CommandBuffer; 3],
     is_text_updated: bool,
 }
 
@@ -190,13 +199,17 @@ pub struct Swapchain {
     texture_image: Image,
     texture_sampler: *mut vulkan::Sampler,
     texture_descriptor_set: *mut vulkan::DescriptorSet,
+    texture_width: u32,
+    texture_height: u32,
 
     cursor_texture_image: Image,
     cursor_texture_sampler: *mut vulkan::Sampler,
     cursor_texture_descriptor_set: *mut vulkan::DescriptorSet,
 
     uniform_descriptor_set: *mut vulkan::DescriptorSet,
-    global_uniform_map: Vec<f32>,
+    // Buffers stay mapped for as long as the swapchain lives; these point
+    // into them.
+    global_uniform_map: *mut f32,
 
     framebuffers: Vec<*mut vulkan::Framebuffer>,
     command_pool: *mut vulkan::CommandPool,
@@ -204,14 +217,38 @@ pub struct Swapchain {
 
     global_uniform_buffer: Buffer,
     vertex_buffer: Buffer,
+    vertex_map: *mut [f32; 2],
+    vertex_len: usize,
     index_buffer: Buffer,
-    cursor_vertex_buffer: Buffer,
+    instance_buffer: Buffer,
+    instance_map: *mut GlyphInstance,
+    solid_glyph: u32,
 
     image_available: *mut vulkan::Semaphore,
     render_finished: *mut vulkan::Semaphore,
     in_flight: *mut vulkan::Fence,
 }
 
+// Everything it takes to draw frames on a Wayland surface.
+pub struct Vulkan {
+    instance: Instance,
+    surface: *mut vulkan::SurfaceKHR,
+    device: Device,
+    graphics_pipeline: GraphicsPipeline,
+    swapchain: Swapchain,
+    font: TrueTypeFont,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct GlyphInstance {
+    pub position: [f32; 2],
+    pub glyph: u32,
+    pub color: [f32; 4],
+    // In cells, two for wide characters.
+    pub width: f32,
+}
+
 struct Buffer {
     handle: *mut vulkan::Buffer,
     memory: *mut vulkan::DeviceMemory,
@@ -463,7 +500,6 @@ pub fn device(dispatch: &Instance, surface: *mut vulkan::SurfaceKHR) -> Result<D
         vkCmdCopyBuffer: device_function!(vkGetDeviceProcAddr, device, PFN_vkCmdCopyBuffer)?,
         vkCmdBindIndexBuffer: device_function!(vkGetDeviceProcAddr, device, PFN_vkCmdBindIndexBuffer)?,
         vkCmdDrawIndexed: device_function!(vkGetDeviceProcAddr, device, PFN_vkCmdDrawIndexed)?,
-        vkCmdPushConstants: device_function!(vkGetDeviceProcAddr, device, PFN_vkCmdPushConstants)?,
         vkCmdExecuteCommands: device_function!(vkGetDeviceProcAddr, device, PFN_vkCmdExecuteCommands)?,
     })
 }
@@ -608,27 +644,47 @@ pub fn graphics_pipeline(device: &Device, instance: &Instance, width: u32, heigh
         pDynamicStates: dynamic_states.as_ptr() as *const u32,
     };
 
-    let vertex_binding_description = vulkan::VertexInputBindingDescription {
+    let instance_binding_description = vulkan::VertexInputBindingDescription {
         binding: 0,
-        stride: std::mem::size_of::<f32>() as u32 * 2,
-        inputRate: vulkan::VERTEX_INPUT_RATE,
+        stride: std::mem::size_of::<GlyphInstance>() as u32,
+        inputRate: vulkan::VERTEX_INPUT_RATE_INSTANCE,
     };
 
-    let texture_coords_attribute_description = vulkan::VertexInputAttributeDescription {
-        binding: 0,
-        location: 0,
-        format: vulkan::R32G32_SFLOAT,
-        offset: 0,
-    };
+    let instance_attribute_descriptions = [
+        vulkan::VertexInputAttributeDescription {
+            binding: 0,
+            location: 0,
+            format: vulkan::R32G32_SFLOAT,
+            offset: 0,
+        },
+        vulkan::VertexInputAttributeDescription {
+            binding: 0,
+            location: 1,
+            format: vulkan::R32_UINT,
+            offset: std::mem::size_of::<[f32; 2]>() as u32,
+        },
+        vulkan::VertexInputAttributeDescription {
+            binding: 0,
+            location: 2,
+            format: vulkan::R32G32B32A32_SFLOAT,
+            offset: std::mem::size_of::<[f32; 2]>() as u32 + std::mem::size_of::<u32>() as u32,
+        },
+        vulkan::VertexInputAttributeDescription {
+            binding: 0,
+            location: 3,
+            format: vulkan::R32_SFLOAT,
+            offset: std::mem::size_of::<[f32; 2]>() as u32 + std::mem::size_of::<u32>() as u32 + std::mem::size_of::<[f32; 4]>() as u32,
+        },
+    ];
 
     let vertex_input_state_info = vulkan::PipelineVertexInputStateCreateInfo {
         sType: vulkan::STRUCTURE_TYPE_PIPELINE_VERTEX_INPUT_STATE_CREATE_INFO,
         pNext: std::ptr::null(),
         flags: 0,
         vertexBindingDescriptionCount: 1,
-        pVertexBindingDescriptions: &vertex_binding_description as *const vulkan::VertexInputBindingDescription,
-        vertexAttributeDescriptionCount: 1,
-        pVertexAttributeDescriptions: [texture_coords_attribute_description].as_ptr() as *const vulkan::VertexInputAttributeDescription,
+        pVertexBindingDescriptions: &instance_binding_description as *const vulkan::VertexInputBindingDescription,
+        vertexAttributeDescriptionCount: instance_attribute_descriptions.len() as u32,
+        pVertexAttributeDescriptions: instance_attribute_descriptions.as_ptr(),
     };
 
     let input_assembly_state_info = vulkan::PipelineInputAssemblyStateCreateInfo {
@@ -734,20 +790,29 @@ pub fn graphics_pipeline(device: &Device, instance: &Instance, width: u32, heigh
         depthBoundsTestEnable: vulkan::FALSE,
     };
 
-    let global_binding = vulkan::DescriptorSetLayoutBinding {
-        binding: 0,
-        stageFlags: vulkan::SHADER_STAGE_VERTEX_BIT,
-        descriptorType: vulkan::DESCRIPTOR_TYPE_UNIFORM_BUFFER,
-        descriptorCount: 1,
-        pImmutableSamplers: std::ptr::null(),
-    };
+    let global_bindings = [
+        vulkan::DescriptorSetLayoutBinding {
+            binding: 0,
+            stageFlags: vulkan::SHADER_STAGE_VERTEX_BIT,
+            descriptorType: vulkan::DESCRIPTOR_TYPE_UNIFORM_BUFFER,
+            descriptorCount: 1,
+            pImmutableSamplers: std::ptr::null(),
+        },
+        vulkan::DescriptorSetLayoutBinding {
+            binding: 1,
+            stageFlags: vulkan::SHADER_STAGE_VERTEX_BIT,
+            descriptorType: vulkan::DESCRIPTOR_TYPE_STORAGE_BUFFER,
+            descriptorCount: 1,
+            pImmutableSamplers: std::ptr::null(),
+        },
+    ];
 
     let global_layout_info = vulkan::DescriptorSetLayoutCreateInfo {
         sType: vulkan::STRUCTURE_TYPE_DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
         flags: 0,
         pNext: std::ptr::null(),
-        bindingCount: 1,
-        pBindings: &global_binding as *const vulkan::DescriptorSetLayoutBinding,
+        bindingCount: global_bindings.len() as u32,
+        pBindings: global_bindings.as_ptr(),
     };
 
     let mut global_descriptor_set_layout: *mut vulkan::DescriptorSetLayout = std::ptr::null_mut();
@@ -776,18 +841,12 @@ pub fn graphics_pipeline(device: &Device, instance: &Instance, width: u32, heigh
         return Err(LoadError::GraphicsPipelineFail);
     }
 
-    let push_constant = vulkan::PushConstantRange {
-        stageFlags: vulkan::SHADER_STAGE_VERTEX_BIT,
-        offset: 0,
-        size: std::mem::size_of::<f32>() as u32 * 2,
-    };
-
     let layout_info = vulkan::PipelineLayoutCreateInfo {
         sType: vulkan::STRUCTURE_TYPE_PIPELINE_LAYOUT_CREATE_INFO,
         pNext: std::ptr::null(),
         flags: 0,
-        pushConstantRangeCount: 1,
-        pPushConstantRanges: &push_constant as *const vulkan::PushConstantRange,
+        pushConstantRangeCount: 0,
+        pPushConstantRanges: std::ptr::null(),
         setLayoutCount: 2,
         pSetLayouts: [global_descriptor_set_layout, texture_descriptor_set_layout].as_ptr(),
     };
@@ -797,17 +856,23 @@ pub fn graphics_pipeline(device: &Device, instance: &Instance, width: u32, heigh
         return Err(LoadError::GraphicsPipelineFail);
     }
 
-    let global_pool_size = vulkan::DescriptorPoolSize {
-        type_: vulkan::DESCRIPTOR_TYPE_UNIFORM_BUFFER,
-        descriptorCount: 16,
-    };
+    let global_pool_sizes = [
+        vulkan::DescriptorPoolSize {
+            type_: vulkan::DESCRIPTOR_TYPE_UNIFORM_BUFFER,
+            descriptorCount: 16,
+        },
+        vulkan::DescriptorPoolSize {
+            type_: vulkan::DESCRIPTOR_TYPE_STORAGE_BUFFER,
+            descriptorCount: 16,
+        },
+    ];
 
     let global_pool_info = vulkan::DescriptorPoolCreateInfo {
         sType: vulkan::STRUCTURE_TYPE_DESCRIPTOR_POOL_CREATE_INFO,
         flags: 0,
         pNext: std::ptr::null(),
-        poolSizeCount: 1,
-        pPoolSizes: &global_pool_size,
+        poolSizeCount: global_pool_sizes.len() as u32,
+        pPoolSizes: global_pool_sizes.as_ptr(),
         maxSets: 16,
     };
 
@@ -1290,7 +1355,6 @@ pub fn swapchain(
     device: &Device,
     graphics_pipeline: &GraphicsPipeline,
     font: &TrueTypeFont,
-    chars_len: usize,
     width: u32,
     height: u32,
 ) -> Result<Swapchain, LoadError> {
@@ -1420,9 +1484,9 @@ pub fn swapchain(
     ];
 
     let global_uniform_buffer = buffer::<f32>(device, vulkan::BUFFER_USAGE_UNIFORM_BUFFER_BIT, vulkan::MEMORY_PROPERTY_HOST_VISIBLE_BIT | vulkan::MEMORY_PROPERTY_HOST_COHERENT_BIT, global_uniform.len())?;
-    unsafe { (device.vkMapMemory)(device.handle, global_uniform_buffer.memory, 0, global_uniform.len() as u64, 0, std::mem::transmute::<&mut *mut f32, *mut *mut std::ffi::c_void>(&mut global_uniform_dst)) };
+    unsafe { (device.vkMapMemory)(device.handle, global_uniform_buffer.memory, 0, std::mem::size_of_val(&global_uniform) as u64, 0, std::mem::transmute::<&mut *mut f32, *mut *mut std::ffi::c_void>(&mut global_uniform_dst)) };
     unsafe { std::ptr::copy(global_uniform.as_ptr(), global_uniform_dst, global_uniform.len()) };
-    let global_uniform_map = unsafe { Vec::from_raw_parts(global_uniform_dst, global_uniform.len(), 0) };
+    let global_uniform_map = global_uniform_dst;
 
     let uniform_descriptor_set = allocate_descriptor_set(device, graphics_pipeline.global_descriptor_pool, graphics_pipeline.global_descriptor_set_layout);
 
@@ -1447,61 +1511,51 @@ pub fn swapchain(
 
     unsafe { (device.vkUpdateDescriptorSets)(device.handle, 1, &global_uniform_write_descriptor_set as *const vulkan::WriteDescriptorSet, 0, std::ptr::null()) };
 
-    let vertex_staging_buffer = buffer::<[f32; 2]>(device, vulkan::BUFFER_USAGE_TRANSFER_SRC_BIT, vulkan::MEMORY_PROPERTY_HOST_VISIBLE_BIT | vulkan::MEMORY_PROPERTY_HOST_COHERENT_BIT, 4 * chars_len)?;
+    let glyph_capacity = font::capacity(font);
+    let solid_glyph = glyph_capacity as u32;
+    let vertex_buffer = buffer::<[f32; 2]>(device, vulkan::BUFFER_USAGE_STORAGE_BUFFER_BIT, vulkan::MEMORY_PROPERTY_HOST_VISIBLE_BIT | vulkan::MEMORY_PROPERTY_HOST_COHERENT_BIT, 4 * (glyph_capacity + 1))?;
     let mut vertex_data: *mut [f32; 2] = std::ptr::null_mut();
+    unsafe { (device.vkMapMemory)(device.handle, vertex_buffer.memory, 0, ((glyph_capacity + 1) * 4 * std::mem::size_of::<[f32; 2]>()) as u64, 0, std::mem::transmute::<&mut *mut [f32; 2], *mut *mut std::ffi::c_void>(&mut vertex_data)) };
 
-    unsafe { (device.vkMapMemory)(device.handle, vertex_staging_buffer.memory, 0, (chars_len * 4 * std::mem::size_of::<[f32; 2]>()) as u64, 0, std::mem::transmute::<&mut *mut [f32; 2], *mut *mut std::ffi::c_void>(&mut vertex_data)) };
-    let vertex_buffer = buffer::<[f32; 2]>(device, vulkan::BUFFER_USAGE_TRANSFER_DST_BIT | vulkan::BUFFER_USAGE_VERTEX_BUFFER_BIT, vulkan::MEMORY_PROPERTY_HOST_VISIBLE_BIT | vulkan::MEMORY_PROPERTY_HOST_COHERENT_BIT, 4 * chars_len)?;
-    let mut instance_vertex_buffer = unsafe { Vec::from_raw_parts(vertex_data, chars_len * 4, 0) };
-    let vertex_command_buffer = begin_command_buffer(device, command_pool);
-
-    let height = font.line_height as f32 / font.height as f32;
-    for (i, metric) in font.metrics.iter().enumerate() {
-        let width = metric.width as f32 / font.width as f32;
-        let x_offset = metric.x_offset as f32 / font.width as f32;
-        let y_offset = metric.y_offset as f32 / font.height as f32;
-
-        let index: usize = i as usize * 4;
-
-        instance_vertex_buffer[index..(index + 4)].copy_from_slice(&[
-            [x_offset, y_offset],
-            [x_offset + width, y_offset],
-            [x_offset, y_offset + height],
-            [x_offset + width, y_offset + height],
-        ]);
-
-        let vertex_copy_info = vulkan::BufferCopy {
-            srcOffset: (4 * i as usize * std::mem::size_of::<[f32; 2]>()) as u64,
-            dstOffset: (4 * i as usize * std::mem::size_of::<[f32; 2]>()) as u64,
-            size: (4 * std::mem::size_of::<[f32; 2]>()) as u64,
-        };
-
-        unsafe { (device.vkCmdCopyBuffer)(vertex_command_buffer, vertex_staging_buffer.handle, vertex_buffer.handle, 1, &vertex_copy_info as *const vulkan::BufferCopy) };
+    let vertex_len = (glyph_capacity + 1) * 4;
+    let vertex_map = unsafe { std::slice::from_raw_parts_mut(vertex_data, vertex_len) };
+    for i in 0..font.metrics.len() {
+        update_glyph_vertices(vertex_map, font, i as u32);
     }
 
-    // let vertex_staging_buffer = buffer::<[f32; 2]>(device, vulkan::BUFFER_USAGE_TRANSFER_SRC_BIT, vulkan::MEMORY_PROPERTY_HOST_VISIBLE_BIT | vulkan::MEMORY_PROPERTY_HOST_COHERENT_BIT, 4)?;
-    // let mut vertex_data: *mut [f32; 2] = std::ptr::null_mut();
-    // unsafe { (device.vkMapMemory)(device.handle, vertex_staging_buffer.memory, 0, (4 * std::mem::size_of::<[f32; 2]>()) as u64, 0, std::mem::transmute::<&mut *mut [f32; 2], *mut *mut std::ffi::c_void>(&mut vertex_data)) };
+    vertex_map[solid_glyph as usize * 4..].copy_from_slice(&[
+        [0.0, 0.0],
+        [1.0, 0.0],
+        [0.0, 1.0],
+        [1.0, 1.0],
+    ]);
 
-    // let vertex_buffer = buffer::<[f32; 2]>(device, vulkan::BUFFER_USAGE_TRANSFER_DST_BIT | vulkan::BUFFER_USAGE_VERTEX_BUFFER_BIT, vulkan::MEMORY_PROPERTY_HOST_VISIBLE_BIT | vulkan::MEMORY_PROPERTY_HOST_COHERENT_BIT, 4)?;
-    // let mut instance_vertex_buffer = unsafe { Vec::from_raw_parts(vertex_data, 4, 0) };
+    let glyph_descriptor_info = vulkan::DescriptorBufferInfo {
+        buffer: vertex_buffer.handle,
+        offset: 0,
+        range: ((glyph_capacity + 1) * 4 * std::mem::size_of::<[f32; 2]>()) as u64,
+    };
+
+    let glyph_write_descriptor_set = vulkan::WriteDescriptorSet {
+        sType: vulkan::STRUCTURE_TYPE_WRITE_DESCRIPTOR_SET,
+        pNext: std::ptr::null(),
+        dstSet: uniform_descriptor_set,
+        dstBinding: 1,
+        dstArrayElement: 0,
+        descriptorCount: 1,
+        descriptorType: vulkan::DESCRIPTOR_TYPE_STORAGE_BUFFER,
+        pImageInfo: std::ptr::null(),
+        pBufferInfo: &glyph_descriptor_info as *const vulkan::DescriptorBufferInfo,
+        pTexelBufferView: std::ptr::null(),
+    };
 
-    // instance_vertex_buffer[0..4].copy_from_slice(&[
-    //     [0.0, 1.0],
-    //     [1.0, 1.0],
-    //     [0.0, 0.0],
-    //     [1.0, 0.0],
-    // ]);
-    // let vertex_copy_info = vulkan::BufferCopy {
-    //     srcOffset: 0,
-    //     dstOffset: 0,
-    //     size: (4 * std::mem::size_of::<[f32; 2]>()) as u64,
-    // };
-    // let vertex_command_buffer = begin_command_buffer(device, command_pool);
+    unsafe { (device.vkUpdateDescriptorSets)(device.handle, 1, &glyph_write_descriptor_set as *const vulkan::WriteDescriptorSet, 0, std::ptr::null()) };
 
-    // unsafe { (device.vkCmdCopyBuffer)(vertex_command_buffer, vertex_staging_buffer.handle, vertex_buffer.handle, 1, &vertex_copy_info as *const vulkan::BufferCopy) };
+    let instance_buffer = buffer::<GlyphInstance>(device, vulkan::BUFFER_USAGE_VERTEX_BUFFER_BIT, vulkan::MEMORY_PROPERTY_HOST_VISIBLE_BIT | vulkan::MEMORY_PROPERTY_HOST_COHERENT_BIT, INSTANCE_CAPACITY)?;
+    let mut instance_data: *mut GlyphInstance = std::ptr::null_mut();
+    unsafe { (device.vkMapMemory)(device.handle, instance_buffer.memory, 0, (INSTANCE_CAPACITY * std::mem::size_of::<GlyphInstance>()) as u64, 0, std::mem::transmute::<&mut *mut GlyphInstance, *mut *mut std::ffi::c_void>(&mut instance_data)) };
 
-    unsafe { (device.vkUnmapMemory)(device.handle, vertex_staging_buffer.memory) };
+    let vertex_command_buffer = begin_command_buffer(device, command_pool);
 
     let indices: [u16; 6] = [
         0, 1, 2, 1, 3, 2
@@ -1522,38 +1576,10 @@ pub fn swapchain(
 
     unsafe { (device.vkCmdCopyBuffer)(vertex_command_buffer, index_staging_buffer.handle, index_buffer.handle, 1, &index_copy_info as *const vulkan::BufferCopy) };
 
-    let cursor_texture_coords: [[f32; 2]; 4] = [
-        [0.0, 0.0],
-        [1.0, 0.0],
-        [0.0, 1.0],
-        [1.0, 1.0],
-    ];
-
-    let cursor_vertex_staging_buffer = buffer::<[f32; 2]>(device, vulkan::BUFFER_USAGE_TRANSFER_SRC_BIT, vulkan::MEMORY_PROPERTY_HOST_VISIBLE_BIT | vulkan::MEMORY_PROPERTY_HOST_COHERENT_BIT, cursor_texture_coords.len())?;
-    let mut cursor_vertex_data: *mut [f32; 2] = std::ptr::null_mut();
-
-    unsafe { (device.vkMapMemory)(device.handle, cursor_vertex_staging_buffer.memory, 0, (cursor_texture_coords.len() * std::mem::size_of::<[f32; 2]>()) as u64, 0, std::mem::transmute::<&mut *mut [f32; 2], *mut *mut std::ffi::c_void>(&mut cursor_vertex_data)) };
-
-
-    unsafe { std::ptr::copy(cursor_texture_coords.as_ptr(), cursor_vertex_data, cursor_texture_coords.len()) };
-    unsafe { (device.vkUnmapMemory)(device.handle, cursor_vertex_staging_buffer.memory) };
-
-    let cursor_vertex_buffer = buffer::<[f32; 2]>(device, vulkan::BUFFER_USAGE_TRANSFER_DST_BIT | vulkan::BUFFER_USAGE_VERTEX_BUFFER_BIT, vulkan::MEMORY_PROPERTY_HOST_VISIBLE_BIT | vulkan::MEMORY_PROPERTY_HOST_COHERENT_BIT, cursor_texture_coords.len())?;
-    let cursor_vertex_copy_info = vulkan::BufferCopy {
-        srcOffset: 0,
-        dstOffset: 0,
-        size: (cursor_texture_coords.len() * std::mem::size_of::<[f32; 2]>()) as u64,
-    };
-
-    unsafe { (device.vkCmdCopyBuffer)(vertex_command_buffer, cursor_vertex_staging_buffer.handle, cursor_vertex_buffer.handle, 1, &cursor_vertex_copy_info as *const vulkan::BufferCopy) };
     end_command_buffer(device, command_pool, vertex_command_buffer);
 
-    unsafe { (device.vkFreeMemory)(device.handle, vertex_staging_buffer.memory, std::ptr::null()) };
-    unsafe { (device.vkDestroyBuffer)(device.handle, vertex_staging_buffer.handle, std::ptr::null()) };
     unsafe { (device.vkFreeMemory)(device.handle, index_staging_buffer.memory, std::ptr::null()) };
     unsafe { (device.vkDestroyBuffer)(device.handle, index_staging_buffer.handle, std::ptr::null()) };
-    unsafe { (device.vkFreeMemory)(device.handle, cursor_vertex_staging_buffer.memory, std::ptr::null()) };
-    unsafe { (device.vkDestroyBuffer)(device.handle, cursor_vertex_staging_buffer.handle, std::ptr::null()) };
 
     let texture_image = create_image(
         device,
@@ -1601,10 +1627,10 @@ pub fn swapchain(
         pNext: std::ptr::null(),
         commandPool: command_pool,
         level: vulkan::COMMAND_BUFFER_LEVEL_SECONDARY,
-        commandBufferCount: count * 2,
+        commandBufferCount: count * 3,
     };
 
-    let mut secondary_command_buffers: Vec<[*mut vulkan::CommandBuffer; 2]> = Vec::with_capacity(count as usize);
+    let mut secondary_command_buffers: Vec<[*mut vulkan::CommandBuffer; 3]> = Vec::with_capacity(count as usize);
     unsafe { (device.vkAllocateCommandBuffers)(device.handle, &secondary_command_buffer_info as *const vulkan::CommandBufferAllocateInfo, secondary_command_buffers.as_mut_ptr() as *mut *mut vulkan::CommandBuffer) };
     unsafe { secondary_command_buffers.set_len(count as usize) };
 
@@ -1612,7 +1638,7 @@ pub fn swapchain(
     for i in 0..count {
         command_buffers.push(CommandBuffer {
             handle: primary_command_buffers[i as usize],
-            secondary: [secondary_command_buffers[i as usize][0], secondary_command_buffers[i as usize][1]],
+            secondary: secondary_command_buffers[i as usize],
             is_text_updated: false,
         });
     }
@@ -1626,6 +1652,8 @@ pub fn swapchain(
         texture_image,
         texture_sampler,
         texture_descriptor_set,
+        texture_width: font.width,
+        texture_height: font.height,
 
         cursor_texture_image,
         cursor_texture_sampler,
@@ -1639,8 +1667,12 @@ pub fn swapchain(
         command_pool,
         command_buffers,
         vertex_buffer,
-        cursor_vertex_buffer,
+        vertex_map: vertex_data,
+        vertex_len,
         index_buffer,
+        instance_buffer,
+        instance_map: instance_data,
+        solid_glyph,
 
         render_finished,
         image_available,
@@ -1648,6 +1680,168 @@ pub fn swapchain(
     })
 }
 
+fn vertex_map(swapchain: &mut Swapchain) -> &mut [[f32; 2]] {
+    unsafe { std::slice::from_raw_parts_mut(swapchain.vertex_map, swapchain.vertex_len) }
+}
+
+fn instance_map(swapchain: &mut Swapchain) -> &mut [GlyphInstance] {
+    unsafe { std::slice::from_raw_parts_mut(swapchain.instance_map, INSTANCE_CAPACITY) }
+}
+
+fn update_glyph_vertices(vertex_map: &mut [[f32; 2]], font: &TrueTypeFont, glyph: u32) {
+    let metric = &font.metrics[glyph as usize];
+    let width = metric.width as f32 / font.width as f32;
+    let height = font.line_height as f32 / font.height as f32;
+    let x_offset = metric.x_offset as f32 / font.width as f32;
+    let y_offset = metric.y_offset as f32 / font.height as f32;
+
+    let index = glyph as usize * 4;
+    vertex_map[index..(index + 4)].copy_from_slice(&[
+        [x_offset, y_offset],
+        [x_offset + width, y_offset],
+        [x_offset, y_offset + height],
+        [x_offset + width, y_offset + height],
+    ]);
+}
+
+fn update_image_regions(
+    device: &Device,
+    command_pool: *mut vulkan::CommandPool,
+    image: *mut vulkan::Image,
+    data: &[u8],
+    width: u32,
+    regions: &[[u32; 4]],
+) -> Result<(), LoadError> {
+    let len: usize = regions.iter().map(|region| (region[2] * region[3]) as usize).sum();
+    if len == 0 {
+        return Ok(());
+    }
+
+    let buffer = buffer::<u8>(device, vulkan::BUFFER_USAGE_TRANSFER_SRC_BIT, vulkan::MEMORY_PROPERTY_HOST_VISIBLE_BIT | vulkan::MEMORY_PROPERTY_HOST_COHERENT_BIT, len)?;
+
+    let mut dst: *mut u8 = std::ptr::null_mut();
+    unsafe { (device.vkMapMemory)(device.handle, buffer.memory, 0, len as u64, 0, std::mem::transmute::<&mut *mut u8, *mut *mut std::ffi::c_void>(&mut dst)) };
+    let staging = unsafe { std::slice::from_raw_parts_mut(dst, len) };
+
+    let mut copies = Vec::with_capacity(regions.len());
+    let mut offset = 0;
+    for [x, y, region_width, region_height] in regions.iter() {
+        for j in 0..*region_height {
+            let start = ((y + j) * width + x) as usize;
+            staging[offset + (j * region_width) as usize..offset + ((j + 1) * region_width) as usize].copy_from_slice(&data[start..start + *region_width as usize]);
+        }
+
+        copies.push(vulkan::BufferImageCopy {
+            bufferOffset: offset as u64,
+            bufferRowLength: 0,
+            bufferImageHeight: 0,
+            imageSubresource: vulkan::ImageSubresourceLayers {
+                aspectMask: vulkan::IMAGE_ASPECT_COLOR_BIT,
+                mipLevel: 0,
+                baseArrayLayer: 0,
+                layerCount: 1,
+            },
+            imageOffset: vulkan::Offset3D {
+                x: *x as i32,
+                y: *y as i32,
+                z: 0,
+            },
+            imageExtent: vulkan::Extent3D {
+                width: *region_width,
+                height: *region_height,
+                depth: 1,
+            },
+        });
+
+        offset += (region_width * region_height) as usize;
+    }
+
+    unsafe { (device.vkUnmapMemory)(device.handle, buffer.memory) };
+
+    let barrier = vulkan::ImageMemoryBarrier {
+        sType: vulkan::STRUCTURE_TYPE_IMAGE_MEMORY_BARRIER,
+        pNext: std::ptr::null(),
+        oldLayout: vulkan::IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL,
+        newLayout: vulkan::IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL,
+        srcQueueFamilyIndex: vulkan::QUEUE_FAMILY_IGNORED,
+        dstQueueFamilyIndex: vulkan::QUEUE_FAMILY_IGNORED,
+        image,
+        subresourceRange: vulkan::ImageSubresourceRange {
+            aspectMask: vulkan::IMAGE_ASPECT_COLOR_BIT,
+            baseMipLevel: 0,
+            levelCount: 1,
+            baseArrayLayer: 0,
+            layerCount: 1,
+        },
+        srcAccessMask: vulkan::ACCESS_SHADER_READ_BIT,
+        dstAccessMask: vulkan::ACCESS_TRANSFER_WRITE_BIT,
+    };
+
+    let second_barrier = vulkan::ImageMemoryBarrier {
+        sType: vulkan::STRUCTURE_TYPE_IMAGE_MEMORY_BARRIER,
+        pNext: std::ptr::null(),
+        oldLayout: vulkan::IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL,
+        newLayout: vulkan::IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL,
+        srcQueueFamilyIndex: vulkan::QUEUE_FAMILY_IGNORED,
+        dstQueueFamilyIndex: vulkan::QUEUE_FAMILY_IGNORED,
+        image,
+        subresourceRange: vulkan::ImageSubresourceRange {
+            aspectMask: vulkan::IMAGE_ASPECT_COLOR_BIT,
+            baseMipLevel: 0,
+            levelCount: 1,
+            baseArrayLayer: 0,
+            layerCount: 1,
+        },
+        srcAccessMask: vulkan::ACCESS_TRANSFER_WRITE_BIT,
+        dstAccessMask: vulkan::ACCESS_SHADER_READ_BIT,
+    };
+
+    let command_buffer = begin_command_buffer(device, command_pool);
+    unsafe { (device.vkCmdPipelineBarrier)(command_buffer, vulkan::PIPELINE_STAGE_FRAGMENT_SHADER_BIT, vulkan::PIPELINE_STAGE_TRANSFER_BIT, 0, 0, std::ptr::null(), 0, std::ptr::null(), 1, &barrier) };
+    unsafe { (device.vkCmdCopyBufferToImage)(command_buffer, buffer.handle, image, vulkan::IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL, copies.len() as u32, copies.as_ptr()) };
+    unsafe { (device.vkCmdPipelineBarrier)(command_buffer, vulkan::PIPELINE_STAGE_TRANSFER_BIT, vulkan::PIPELINE_STAGE_FRAGMENT_SHADER_BIT, 0, 0, std::ptr::null(), 0, std::ptr::null(), 1, &second_barrier) };
+    end_command_buffer(device, command_pool, command_buffer);
+
+    unsafe { (device.vkFreeMemory)(device.handle, buffer.memory, std::ptr::null()) };
+    unsafe { (device.vkDestroyBuffer)(device.handle, buffer.handle, std::ptr::null()) };
+
+    Ok(())
+}
+
+// Returns the slot each character of the frame is drawn with, which is the
+// replacement glyph's for those that couldn't be rasterized.
+fn update_glyph_atlas(device: &Device, swapchain: &mut Swapchain, font: &mut TrueTypeFont, frame: &Frame) -> Result<std::collections::HashMap<char, u32>, LoadError> {
+    font::begin_frame(font);
+
+    let mut slots = std::collections::HashMap::new();
+    for run in frame.layers.iter().flat_map(|layer| layer.text.iter()) {
+        for c in run.text.iter() {
+            slots.entry(*c).or_insert_with(|| font::glyph(font, *c));
+        }
+    }
+
+    let (regions, glyphs) = font::take_dirty(font);
+    if regions.is_empty() && glyphs.is_empty() {
+        return Ok(slots);
+    }
+
+    if font.width != swapchain.texture_width || font.height != swapchain.texture_height {
+        return Err(LoadError::ImageFail);
+    }
+
+    for glyph in glyphs {
+        update_glyph_vertices(vertex_map(swapchain), font, glyph);
+    }
+
+    update_image_regions(device, swapchain.command_pool, swapchain.texture_image.handle, &font.texture_atlas, font.width, &regions)?;
+
+    for command_buffer in swapchain.command_buffers.iter_mut() {
+        command_buffer.is_text_updated = false;
+    }
+
+    Ok(slots)
+}
+
 pub fn set_change(swapchain: &mut Swapchain) {
     swapchain.has_changed = true;
 
@@ -1786,7 +1980,7 @@ pub fn recreate_swapchain(device: &Device, swapchain: &mut Swapchain, graphics_p
         );
     }
 
-    swapchain.global_uniform_map[0] = height as f32 / width as f32;
+    unsafe { swapchain.global_uniform_map.write(height as f32 / width as f32) };
 
     unsafe { (device.vkDestroySemaphore)(device.handle, swapchain.image_available, std::ptr::null()) };
     let semaphore_info = vulkan::SemaphoreCreateInfo {
@@ -1802,72 +1996,200 @@ pub fn recreate_swapchain(device: &Device, swapchain: &mut Swapchain, graphics_p
     Ok(())
 }
 
-fn record_text_secondary_command_buffer(
+pub fn text_instances(instances: &mut [GlyphInstance], glyph_indices: &std::collections::HashMap<char, u32>, runs: &[TextRun]) -> usize {
+    let mut count = 0;
+
+    for run in runs {
+        for (i, c) in run.text.iter().enumerate() {
+            let glyph = if let Some(glyph) = glyph_indices.get(c) {
+                *glyph
+            } else {
+                continue;
+            };
+
+            if count == instances.len() {
+                return count;
+            }
+
+            // Centered on the cells it covers.
+            let width = grapheme::char_width(*c) as f32;
+            instances[count] = GlyphInstance {
+                position: [(run.cell[0] + i as u32) as f32 * 2.0 + width, run.cell[1] as f32 * 2.0 + 1.0],
+                glyph,
+                color: run.color,
+                width,
+            };
+
+            count += 1;
+        }
+    }
+
+    count
+}
+
+fn fill_instances(instances: &mut [GlyphInstance], fills: &[Fill], glyph: u32) -> usize {
+    let cells = fills.iter().flat_map(|fill| {
+        let Rect { x, y, width, height } = fill.rect;
+        (y..y + height).flat_map(move |y| (x..x + width).map(move |x| ([x, y], fill.color)))
+    });
+
+    let mut count = 0;
+
+    for (instance, ([x, y], color)) in instances.iter_mut().zip(cells) {
+        *instance = GlyphInstance {
+            position: [x as f32 * 2.0 + 1.0, y as f32 * 2.0 + 1.0],
+            glyph,
+            color,
+            width: 1.0,
+        };
+
+        count += 1;
+    }
+
+    count
+}
+
+// Converts a rect of grid cells into pixels, clamped to the swapchain extent.
+// Without one, the whole extent.
+fn layer_scissor(swapchain: &Swapchain, clip: Option<Rect>) -> vulkan::Rect2D {
+    let rect = if let Some(rect) = clip {
+        rect
+    } else {
+        return vulkan::Rect2D {
+            offset: vulkan::Offset2D {
+                x: 0,
+                y: 0,
+            },
+            extent: vulkan::Extent2D {
+                width: swapchain.extent.width,
+                height: swapchain.extent.height,
+            },
+        };
+    };
+
+    let [_, scale, _, _, x_ratio] = unsafe { swapchain.global_uniform_map.cast::<[f32; 5]>().read() };
+    let height = swapchain.extent.height as f32;
+    let cell = [scale * x_ratio * height, scale * height];
+
+    let x = ((rect.x as f32 * cell[0]) as u32).min(swapchain.extent.width);
+    let y = ((rect.y as f32 * cell[1]) as u32).min(swapchain.extent.height);
+    let width = ((rect.width as f32 * cell[0]).ceil() as u32).min(swapchain.extent.width - x);
+    let height = ((rect.height as f32 * cell[1]).ceil() as u32).min(swapchain.extent.height - y);
+
+    vulkan::Rect2D {
+        offset: vulkan::Offset2D {
+            x: x as i32,
+            y: y as i32,
+        },
+        extent: vulkan::Extent2D {
+            width,
+            height,
+        },
+    }
+}
+
+fn record_instances(
     device: &Device,
     command_buffer: *mut vulkan::CommandBuffer,
-    vertex_buffer: *mut vulkan::Buffer,
+    instance_buffer: *mut vulkan::Buffer,
     index_buffer: *mut vulkan::Buffer,
-    uniform_descriptor_set: *mut vulkan::DescriptorSet,
-    texture_descriptor_set: *mut vulkan::DescriptorSet,
+    descriptor_sets: [*mut vulkan::DescriptorSet; 2],
     graphics_pipeline: &GraphicsPipeline,
-    characters: &UniqueChars
+    instances: [usize; 2],
 ) {
+    let [first, count] = instances;
+    if count == 0 {
+        return;
+    }
+
     unsafe { (device.vkCmdBindPipeline)(command_buffer, vulkan::PIPELINE_BIND_POINT_GRAPHICS, graphics_pipeline.handle) };
-    unsafe { (device.vkCmdBindDescriptorSets)(command_buffer, vulkan::PIPELINE_BIND_POINT_GRAPHICS, graphics_pipeline.layout, 0, 2, [uniform_descriptor_set, texture_descriptor_set].as_ptr() as *const *mut vulkan::DescriptorSet, 0, std::ptr::null()) };
-    unsafe { (device.vkCmdBindIndexBuffer)(command_buffer, index_buffer as *mut vulkan::Buffer, 0, vulkan::INDEX_TYPE_UINT16) };
+    unsafe { (device.vkCmdBindDescriptorSets)(command_buffer, vulkan::PIPELINE_BIND_POINT_GRAPHICS, graphics_pipeline.layout, 0, 2, descriptor_sets.as_ptr(), 0, std::ptr::null()) };
+    unsafe { (device.vkCmdBindIndexBuffer)(command_buffer, index_buffer, 0, vulkan::INDEX_TYPE_UINT16) };
+    unsafe { (device.vkCmdBindVertexBuffers)(command_buffer, 0, 1, &instance_buffer as *const *mut vulkan::Buffer, [0].as_ptr()) };
+    unsafe { (device.vkCmdDrawIndexed)(command_buffer, 6, count as u32, 0, 0, first as u32) };
+}
 
-    for (i, offset) in characters.positions.iter().enumerate() {
-        if offset.len() == 0 || i == 0 {
-            continue;
-        }
+fn record_text_secondary_command_buffer(
+    device: &Device,
+    command_buffer: *mut vulkan::CommandBuffer,
+    swapchain: &mut Swapchain,
+    graphics_pipeline: &GraphicsPipeline,
+    glyph_indices: &std::collections::HashMap<char, u32>,
+    frame: &Frame
+) {
+    let descriptor_sets = [swapchain.uniform_descriptor_set, swapchain.texture_descriptor_set];
+    let mut first = TEXT_INSTANCES;
 
-        unsafe { (device.vkCmdBindVertexBuffers)(command_buffer, 0, 1, &vertex_buffer as *const *mut vulkan::Buffer, [(std::mem::size_of::<[f32; 2]>() * i) as u64 * 4].as_ptr()) };
+    for layer in frame.layers.iter() {
+        let count = text_instances(&mut instance_map(swapchain)[first..TEXT_INSTANCES + INSTANCE_REGION], glyph_indices, &layer.text);
 
-        for pos in offset.iter() {
-            unsafe { (device.vkCmdPushConstants)(command_buffer, graphics_pipeline.layout, vulkan::SHADER_STAGE_VERTEX_BIT, 0, std::mem::size_of::<f32>() as u32 * 2, std::mem::transmute::<*const f32, *const std::ffi::c_void>([pos[0] as f32 * 2.0 + 1.0, pos[1] as f32 * 2.0 + 1.0].as_ptr())) };
-            unsafe { (device.vkCmdDrawIndexed)(command_buffer, 6, 1, 0, 0, 0) };
-        }
+        let scissor = layer_scissor(swapchain, layer.clip);
+        unsafe { (device.vkCmdSetScissor)(command_buffer, 0, 1, &scissor as *const vulkan::Rect2D) };
+
+        record_instances(device, command_buffer, swapchain.instance_buffer.handle, swapchain.index_buffer.handle, descriptor_sets, graphics_pipeline, [first, count]);
+        first += count;
+    }
+}
+
+fn record_highlight_secondary_command_buffer(
+    device: &Device,
+    command_buffer: *mut vulkan::CommandBuffer,
+    swapchain: &mut Swapchain,
+    graphics_pipeline: &GraphicsPipeline,
+    frame: &Frame
+) {
+    let descriptor_sets = [swapchain.uniform_descriptor_set, swapchain.cursor_texture_descriptor_set];
+    let mut first = HIGHLIGHT_INSTANCES;
+    let solid_glyph = swapchain.solid_glyph;
+
+    for layer in frame.layers.iter() {
+        let count = fill_instances(&mut instance_map(swapchain)[first..HIGHLIGHT_INSTANCES + INSTANCE_REGION], &layer.fills, solid_glyph);
+
+        let scissor = layer_scissor(swapchain, layer.clip);
+        unsafe { (device.vkCmdSetScissor)(command_buffer, 0, 1, &scissor as *const vulkan::Rect2D) };
+
+        record_instances(device, command_buffer, swapchain.instance_buffer.handle, swapchain.index_buffer.handle, descriptor_sets, graphics_pipeline, [first, count]);
+        first += count;
     }
 }
 
 fn record_cursor_secondary_command_buffer(
     device: &Device,
     command_buffer: *mut vulkan::CommandBuffer,
-    vertex_buffer: *mut vulkan::Buffer,
-    index_buffer: *mut vulkan::Buffer,
-    uniform_descriptor_set: *mut vulkan::DescriptorSet,
-    texture_descriptor_set: *mut vulkan::DescriptorSet,
+    swapchain: &mut Swapchain,
     graphics_pipeline: &GraphicsPipeline,
-    buffer: &WindowBuffer
+    frame: &Frame
 ) {
-    unsafe { (device.vkCmdBindPipeline)(command_buffer, vulkan::PIPELINE_BIND_POINT_GRAPHICS, graphics_pipeline.handle) };
-    unsafe { (device.vkCmdBindDescriptorSets)(command_buffer, vulkan::PIPELINE_BIND_POINT_GRAPHICS, graphics_pipeline.layout, 0, 2, [uniform_descriptor_set, texture_descriptor_set].as_ptr() as *const *mut vulkan::DescriptorSet, 0, std::ptr::null()) };
-    unsafe { (device.vkCmdBindIndexBuffer)(command_buffer, index_buffer as *mut vulkan::Buffer, 0, vulkan::INDEX_TYPE_UINT16) };
-    unsafe { (device.vkCmdBindVertexBuffers)(command_buffer, 0, 1, &vertex_buffer as *const *mut vulkan::Buffer, [0].as_ptr()) };
+    let descriptor_sets = [swapchain.uniform_descriptor_set, swapchain.cursor_texture_descriptor_set];
+    let mut first = CURSOR_INSTANCES;
+    let solid_glyph = swapchain.solid_glyph;
 
-    for cursor in buffer.cursors.iter() {
-        let xpos = cursor.position.x - buffer.offset.x;
-        let ypos = cursor.position.y - buffer.offset.y;
+    for layer in frame.layers.iter() {
+        let count = fill_instances(&mut instance_map(swapchain)[first..CURSOR_INSTANCES + INSTANCE_REGION], &layer.cursors, solid_glyph);
 
-        unsafe { (device.vkCmdPushConstants)(command_buffer, graphics_pipeline.layout, vulkan::SHADER_STAGE_VERTEX_BIT, 0, std::mem::size_of::<f32>() as u32 * 2, std::mem::transmute::<*const f32, *const std::ffi::c_void>([xpos as f32 * 2.0 + 1.0, ypos as f32 * 2.0 + 1.0].as_ptr())) };
-        unsafe { (device.vkCmdDrawIndexed)(command_buffer, 6, 1, 0, 0, 0) };
+        let scissor = layer_scissor(swapchain, layer.clip);
+        unsafe { (device.vkCmdSetScissor)(command_buffer, 0, 1, &scissor as *const vulkan::Rect2D) };
+
+        record_instances(device, command_buffer, swapchain.instance_buffer.handle, swapchain.index_buffer.handle, descriptor_sets, graphics_pipeline, [first, count]);
+        first += count;
     }
 }
 
 fn record_command_buffer(
     device: &Device,
-    command_buffer: &mut CommandBuffer,
-    framebuffer: *mut vulkan::Framebuffer,
-    vertex_buffer: *mut vulkan::Buffer,
-    cursor_vertex_buffer: *mut vulkan::Buffer,
-    index_buffer: *mut vulkan::Buffer,
-    uniform_descriptor_set: *mut vulkan::DescriptorSet,
-    texture_descriptor_set: *mut vulkan::DescriptorSet,
-    cursor_texture_descriptor_set: *mut vulkan::DescriptorSet,
-    extent: &vulkan::Extent2D,
+    swapchain: &mut Swapchain,
+    image_index: usize,
     graphics_pipeline: &GraphicsPipeline,
-    buffer: &WindowBuffer,
+    glyph_indices: &std::collections::HashMap<char, u32>,
+    frame: &Frame,
 ) {
+    let CommandBuffer { handle, secondary, is_text_updated } = swapchain.command_buffers[image_index];
+    let framebuffer = swapchain.framebuffers[image_index];
+    let extent = vulkan::Extent2D {
+        width: swapchain.extent.width,
+        height: swapchain.extent.height,
+    };
+
     let begin_info = vulkan::CommandBufferBeginInfo {
         sType: vulkan::STRUCTURE_TYPE_COMMAND_BUFFER_BEGIN_INFO,
         pNext: std::ptr::null(),
@@ -1875,12 +2197,12 @@ fn record_command_buffer(
         pInheritanceInfo: std::ptr::null(),
     };
 
-    unsafe { (device.vkBeginCommandBuffer)(command_buffer.handle, &begin_info as *const vulkan::CommandBufferBeginInfo) };
+    unsafe { (device.vkBeginCommandBuffer)(handle, &begin_info as *const vulkan::CommandBufferBeginInfo) };
 
     let clear_values = [
         vulkan::ClearValue {
             color: vulkan::ClearColorValue {
-                float32: [0.0, 0.0, 0.0, 1.0],
+                float32: CLEAR_COLOR,
             },
         },
     ];
@@ -1904,7 +2226,7 @@ fn record_command_buffer(
         pClearValues: clear_values.as_ptr() as *const vulkan::ClearValue
     };
 
-    unsafe { (device.vkCmdBeginRenderPass)(command_buffer.handle, &render_pass_info as *const vulkan::RenderPassBeginInfo, vulkan::SUBPASS_CONTENTS_SECONDARY_COMMAND_BUFFERS) };
+    unsafe { (device.vkCmdBeginRenderPass)(handle, &render_pass_info as *const vulkan::RenderPassBeginInfo, vulkan::SUBPASS_CONTENTS_SECONDARY_COMMAND_BUFFERS) };
 
     let viewport = vulkan::Viewport {
         x: 0.0,
@@ -1915,17 +2237,6 @@ fn record_command_buffer(
         maxDepth: 1.0,
     };
 
-    let scissor = vulkan::Rect2D {
-        offset: vulkan::Offset2D {
-            x: 0,
-            y: 0,
-        },
-        extent: vulkan::Extent2D {
-            width: extent.width,
-            height: extent.height,
-        }
-    };
-
     let inheritance_info = vulkan::CommandBufferInheritanceInfo {
         sType: vulkan::STRUCTURE_TYPE_COMMAND_BUFFER_INHERITANCE_INFO,
         renderPass: graphics_pipeline.render_pass,
@@ -1944,48 +2255,54 @@ fn record_command_buffer(
         pInheritanceInfo: &inheritance_info as *const vulkan::CommandBufferInheritanceInfo,
     };
 
-    if !command_buffer.is_text_updated {
-        unsafe { (device.vkBeginCommandBuffer)(command_buffer.secondary[0], &secondary_command_buffer_begin_info as *const vulkan::CommandBufferBeginInfo) };
-        unsafe { (device.vkCmdSetViewport)(command_buffer.secondary[0], 0, 1, &viewport as *const vulkan::Viewport) };
-        unsafe { (device.vkCmdSetScissor)(command_buffer.secondary[0], 0, 1, &scissor as *const vulkan::Rect2D) };
+    if !is_text_updated {
+        unsafe { (device.vkBeginCommandBuffer)(secondary[0], &secondary_command_buffer_begin_info as *const vulkan::CommandBufferBeginInfo) };
+        unsafe { (device.vkCmdSetViewport)(secondary[0], 0, 1, &viewport as *const vulkan::Viewport) };
 
         record_text_secondary_command_buffer(
             device,
-            command_buffer.secondary[0],
-            vertex_buffer,
-            index_buffer,
-            uniform_descriptor_set,
-            texture_descriptor_set,
+            secondary[0],
+            swapchain,
             graphics_pipeline,
-            &buffer.unique_chars
+            glyph_indices,
+            frame
         );
 
-        unsafe { (device.vkEndCommandBuffer)(command_buffer.secondary[0]) };
+        unsafe { (device.vkEndCommandBuffer)(secondary[0]) };
+
+        unsafe { (device.vkBeginCommandBuffer)(secondary[2], &secondary_command_buffer_begin_info as *const vulkan::CommandBufferBeginInfo) };
+        unsafe { (device.vkCmdSetViewport)(secondary[2], 0, 1, &viewport as *const vulkan::Viewport) };
 
-        command_buffer.is_text_updated = true;
+        record_highlight_secondary_command_buffer(
+            device,
+            secondary[2],
+            swapchain,
+            graphics_pipeline,
+            frame
+        );
+
+        unsafe { (device.vkEndCommandBuffer)(secondary[2]) };
+
+        swapchain.command_buffers[image_index].is_text_updated = true;
     }
 
-    unsafe { (device.vkBeginCommandBuffer)(command_buffer.secondary[1], &secondary_command_buffer_begin_info as *const vulkan::CommandBufferBeginInfo) };
-    unsafe { (device.vkCmdSetViewport)(command_buffer.secondary[1], 0, 1, &viewport as *const vulkan::Viewport) };
-    unsafe { (device.vkCmdSetScissor)(command_buffer.secondary[1], 0, 1, &scissor as *const vulkan::Rect2D) };
+    unsafe { (device.vkBeginCommandBuffer)(secondary[1], &secondary_command_buffer_begin_info as *const vulkan::CommandBufferBeginInfo) };
+    unsafe { (device.vkCmdSetViewport)(secondary[1], 0, 1, &viewport as *const vulkan::Viewport) };
 
     record_cursor_secondary_command_buffer(
         device,
-        command_buffer.secondary[1],
-        cursor_vertex_buffer,
-        index_buffer,
-        uniform_descriptor_set,
-        cursor_texture_descriptor_set,
-        // texture_descriptor_set,
+        secondary[1],
+        swapchain,
         graphics_pipeline,
-        buffer,
+        frame
     );
 
-    unsafe { (device.vkEndCommandBuffer)(command_buffer.secondary[1]) };
+    unsafe { (device.vkEndCommandBuffer)(secondary[1]) };
 
-    unsafe { (device.vkCmdExecuteCommands)(command_buffer.handle, 2, command_buffer.secondary.as_ptr() as *const *mut vulkan::CommandBuffer) };
-    unsafe { (device.vkCmdEndRenderPass)(command_buffer.handle) };
-    unsafe { (device.vkEndCommandBuffer)(command_buffer.handle) };
+    let secondary = [secondary[2], secondary[0], secondary[1]];
+    unsafe { (device.vkCmdExecuteCommands)(handle, secondary.len() as u32, secondary.as_ptr() as *const *mut vulkan::CommandBuffer) };
+    unsafe { (device.vkCmdEndRenderPass)(handle) };
+    unsafe { (device.vkEndCommandBuffer)(handle) };
 }
 
 fn acquire_next_image(device: &Device, swapchain: &Swapchain) -> Result<u32, LoadError> {
@@ -2005,10 +2322,11 @@ pub fn draw_frame(
     device: &Device,
     swapchain: &mut Swapchain,
     graphics_pipeline: &GraphicsPipeline,
-    buffer: &WindowBuffer,
-    width: u32,
-    height: u32
+    font: &mut TrueTypeFont,
+    frame: &Frame,
+    size: [u32; 2]
 ) -> Result<(), LoadError> {
+    let [width, height] = size;
     std::thread::sleep(std::time::Duration::from_millis(17));
 
     if swapchain.has_changed {
@@ -2030,19 +2348,15 @@ pub fn draw_frame(
         image_index = acquire_next_image(device, swapchain)?;
     }
 
+    let slots = update_glyph_atlas(device, swapchain, font, frame)?;
+
     record_command_buffer(
         device,
-        &mut swapchain.command_buffers[image_index as usize],
-        swapchain.framebuffers[image_index as usize],
-        swapchain.vertex_buffer.handle,
-        swapchain.cursor_vertex_buffer.handle,
-        swapchain.index_buffer.handle,
-        swapchain.uniform_descriptor_set,
-        swapchain.texture_descriptor_set,
-        swapchain.cursor_texture_descriptor_set,
-        &swapchain.extent,
+        swapchain,
+        image_index as usize,
         graphics_pipeline,
-        buffer,
+        &slots,
+        frame,
     );
 
     unsafe { (device.vkResetFences)(device.handle, 1, &swapchain.in_flight as *const *mut vulkan::Fence) };
@@ -2078,6 +2392,50 @@ pub fn draw_frame(
     Ok(())
 }
 
+pub fn new(
+    extensions: &[*const std::ffi::c_char],
+    display: *mut wayland::wl_display,
+    wayland_surface: *mut wayland::wl_surface,
+    font: TrueTypeFont,
+    size: [u32; 2]
+) -> Result<Vulkan, LoadError> {
+    let [width, height] = size;
+    let instance = instance(extensions)?;
+    let surface = surface(&instance, display, wayland_surface)?;
+    let device = device(&instance, surface)?;
+    let graphics_pipeline = graphics_pipeline(&device, &instance, width, height)?;
+    let swapchain = swapchain(&device, &graphics_pipeline, &font, width, height)?;
+
+    Ok(Vulkan {
+        instance,
+        surface,
+        device,
+        graphics_pipeline,
+        swapchain,
+        font,
+    })
+}
+
+impl Renderer for Vulkan {
+    type Error = LoadError;
+
+    fn invalidate(&mut self) {
+        set_change(&mut self.swapchain);
+    }
+
+    fn draw(&mut self, frame: &Frame, size: [u32; 2]) -> Result<(), LoadError> {
+        draw_frame(&self.device, &mut self.swapchain, &self.graphics_pipeline, &mut self.font, frame, size)
+    }
+}
+
+pub fn shutdown(vulkan: &Vulkan) {
+    shutdown_swapchain(&vulkan.device, &vulkan.swapchain);
+    shutdown_graphics_pipeline(&vulkan.device, &vulkan.graphics_pipeline);
+    shutdown_device(&vulkan.device);
+    shutdown_surface(&vulkan.instance, vulkan.surface);
+    shutdown_instance(&vulkan.instance);
+}
+
 pub fn shutdown_swapchain(device: &Device, swapchain: &Swapchain) {
     unsafe { (device.vkWaitForFences)(device.handle, 1, &swapchain.in_flight as *const *mut vulkan::Fence, vulkan::TRUE, 0xFFFFFF) };
     unsafe {
@@ -2092,13 +2450,13 @@ pub fn shutdown_swapchain(device: &Device, swapchain: &Swapchain) {
         }
 
         (device.vkFreeMemory)(device.handle, swapchain.vertex_buffer.memory, null);
-        (device.vkFreeMemory)(device.handle, swapchain.cursor_vertex_buffer.memory, null);
+        (device.vkFreeMemory)(device.handle, swapchain.instance_buffer.memory, null);
         (device.vkFreeMemory)(device.handle, swapchain.index_buffer.memory, null);
         (device.vkFreeMemory)(device.handle, swapchain.texture_image.memory, null);
         (device.vkFreeMemory)(device.handle, swapchain.cursor_texture_image.memory, null);
         (device.vkFreeMemory)(device.handle, swapchain.global_uniform_buffer.memory, null);
         (device.vkDestroyBuffer)(device.handle, swapchain.vertex_buffer.handle, null);
-        (device.vkDestroyBuffer)(device.handle, swapchain.cursor_vertex_buffer.handle, null);
+        (device.vkDestroyBuffer)(device.handle, swapchain.instance_buffer.handle, null);
         (device.vkDestroyBuffer)(device.handle, swapchain.index_buffer.handle, null);
         (device.vkDestroyBuffer)(device.handle, swapchain.global_uniform_buffer.handle, null);
         (device.vkDestroySemaphore)(device.handle, swapchain.render_finished, null);
@@ -0,0 +1,118 @@
+use std::io::Read;
+use std::io::Write;
+use std::os::fd::FromRawFd;
+
+pub use crate::editor::{CLIPBOARD, PRIMARY};
+
+// In order of preference when reading another client's selection.
+pub const MIME_TYPES: [&[u8]; 4] = [b"text/plain;charset=utf-8", b"UTF8_STRING", b"text/plain", b"STRING"];
+pub const RECEIVE_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(500);
+
+pub struct Offer {
+    pub handle: *mut std::ffi::c_void,
+    pub mime_types: Vec<Vec<u8>>,
+}
+
+// Selection state for the regular clipboard and the primary selection,
+// indexed by `CLIPBOARD` and `PRIMARY`. Offers are other clients' data (or
+// our own, echoed back by the compositor); sources are what we published.
+pub struct Clipboard {
+    pending: Vec<Offer>,
+    pub offers: [Option<Offer>; 2],
+    pub sources: [*mut std::ffi::c_void; 2],
+    pub contents: [Vec<u8>; 2],
+}
+
+pub fn new() -> Clipboard {
+    Clipboard {
+        pending: Vec::new(),
+        offers: [None, None],
+        sources: [std::ptr::null_mut(), std::ptr::null_mut()],
+        contents: [Vec::new(), Vec::new()],
+    }
+}
+
+pub fn introduce(clipboard: &mut Clipboard, handle: *mut std::ffi::c_void) {
+    clipboard.pending.push(Offer {
+        handle,
+        mime_types: Vec::new(),
+    });
+}
+
+pub fn add_mime_type(clipboard: &mut Clipboard, handle: *mut std::ffi::c_void, mime_type: &[u8]) {
+    let current = clipboard.offers.iter_mut().flatten();
+
+    if let Some(offer) = clipboard.pending.iter_mut().chain(current).find(|offer| offer.handle == handle) {
+        offer.mime_types.push(Vec::from(mime_type));
+    }
+}
+
+// Forgets a pending offer that will not become a selection, such as one
+// introduced for drag and drop.
+pub fn discard(clipboard: &mut Clipboard, handle: *mut std::ffi::c_void) -> bool {
+    let len = clipboard.pending.len();
+    clipboard.pending.retain(|offer| offer.handle != handle);
+
+    len != clipboard.pending.len()
+}
+
+// Makes a pending offer (or nothing, for a null handle) the current
+// selection and returns the replaced offer, which the caller must destroy.
+pub fn select(clipboard: &mut Clipboard, selection: usize, handle: *mut std::ffi::c_void) -> Option<*mut std::ffi::c_void> {
+    let offer = clipboard.pending.iter().position(|offer| offer.handle == handle).map(|i| clipboard.pending.remove(i));
+    let previous = std::mem::replace(&mut clipboard.offers[selection], offer);
+
+    previous.map(|offer| offer.handle).filter(|previous| *previous != handle)
+}
+
+pub fn preferred_mime_type(offer: &Offer) -> Option<&[u8]> {
+    MIME_TYPES.iter().copied().find(|mime_type| offer.mime_types.iter().any(|offered| offered == mime_type))
+}
+
+pub fn owns(clipboard: &Clipboard, selection: usize) -> bool {
+    !clipboard.sources[selection].is_null()
+}
+
+// Records a newly published source and returns the one it replaces.
+pub fn publish(clipboard: &mut Clipboard, selection: usize, source: *mut std::ffi::c_void, content: Vec<u8>) -> *mut std::ffi::c_void {
+    clipboard.contents[selection] = content;
+    std::mem::replace(&mut clipboard.sources[selection], source)
+}
+
+pub fn content_of(clipboard: &Clipboard, source: *mut std::ffi::c_void) -> Option<&[u8]> {
+    clipboard.sources.iter().position(|s| *s == source && !source.is_null()).map(|i| clipboard.contents[i].as_slice())
+}
+
+pub fn cancelled(clipboard: &mut Clipboard, source: *mut std::ffi::c_void) -> bool {
+    if let Some(i) = clipboard.sources.iter().position(|s| *s == source && !source.is_null()) {
+        clipboard.sources[i] = std::ptr::null_mut();
+        clipboard.contents[i].clear();
+        return true;
+    }
+
+    false
+}
+
+// Writes our content to a requesting client. The write happens on its own
+// thread so that a slow reader can't stall the event loop, and the fd is
+// closed once everything is written.
+pub fn send(fd: i32, content: Vec<u8>) {
+    let mut file = unsafe { std::fs::File::from_raw_fd(fd) };
+
+    std::thread::spawn(move || {
+        let _ = file.write_all(&content);
+    });
+}
+
+// Reads another client's content until it closes its end of the pipe, giving
+// up after `timeout` so that an unresponsive source can't hang the editor.
+pub fn receive(mut reader: std::io::PipeReader, timeout: std::time::Duration) -> Option<Vec<u8>> {
+    let (sender, receiver) = std::sync::mpsc::channel();
+
+    std::thread::spawn(move || {
+        let mut content = Vec::new();
+        let _ = sender.send(reader.read_to_end(&mut content).map(|_| content));
+    });
+
+    receiver.recv_timeout(timeout).ok()?.ok()
+}
@@ -0,0 +1,207 @@
+use crate::binding::dl;
+use crate::binding::xkb;
+use crate::editor::key::Key;
+
+// Evdev scancodes from wl_keyboard.key are offset by 8 in XKB keycodes.
+const EVDEV_OFFSET: u32 = 8;
+const TEXT_CAPACITY: usize = 64;
+
+macro_rules! xkb_function {
+    ($library:ident, $name:ident) => {
+        unsafe {
+            let string = std::ffi::CString::new(&stringify!($name)[4..]).unwrap();
+            let pointer = dl::dlsym($library, string.as_ptr());
+            let func = std::mem::transmute::<*const std::ffi::c_void, xkb::$name>(pointer);
+
+            func?
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Xkb {
+    context_new: xkb::xkb_context_new,
+    context_unref: xkb::xkb_context_unref,
+    keymap_new_from_buffer: xkb::xkb_keymap_new_from_buffer,
+    keymap_unref: xkb::xkb_keymap_unref,
+    keymap_key_get_syms_by_level: xkb::xkb_keymap_key_get_syms_by_level,
+    state_new: xkb::xkb_state_new,
+    state_unref: xkb::xkb_state_unref,
+    state_update_mask: xkb::xkb_state_update_mask,
+    state_key_get_one_sym: xkb::xkb_state_key_get_one_sym,
+    state_key_get_utf8: xkb::xkb_state_key_get_utf8,
+    keysym_to_utf32: xkb::xkb_keysym_to_utf32,
+}
+
+// A compiled keymap together with the modifier and group state the
+// compositor last reported for it.
+pub struct Keymap {
+    xkb: Xkb,
+    context: *mut xkb::xkb_context,
+    keymap: *mut xkb::xkb_keymap,
+    state: *mut xkb::xkb_state,
+}
+
+// Loads libxkbcommon at runtime, so that the editor still starts (with the
+// built-in US layout) on systems that don't have it.
+pub fn load() -> Option<Xkb> {
+    let library = dl::load_library("libxkbcommon.so.0").ok()?;
+
+    Some(Xkb {
+        context_new: xkb_function!(library, PFN_xkb_context_new),
+        context_unref: xkb_function!(library, PFN_xkb_context_unref),
+        keymap_new_from_buffer: xkb_function!(library, PFN_xkb_keymap_new_from_buffer),
+        keymap_unref: xkb_function!(library, PFN_xkb_keymap_unref),
+        keymap_key_get_syms_by_level: xkb_function!(library, PFN_xkb_keymap_key_get_syms_by_level),
+        state_new: xkb_function!(library, PFN_xkb_state_new),
+        state_unref: xkb_function!(library, PFN_xkb_state_unref),
+        state_update_mask: xkb_function!(library, PFN_xkb_state_update_mask),
+        state_key_get_one_sym: xkb_function!(library, PFN_xkb_state_key_get_one_sym),
+        state_key_get_utf8: xkb_function!(library, PFN_xkb_state_key_get_utf8),
+        keysym_to_utf32: xkb_function!(library, PFN_xkb_keysym_to_utf32),
+    })
+}
+
+// Compiles a keymap in the XKB text format, as sent by the compositor.
+pub fn new(xkb: &Xkb, text: &[u8]) -> Option<Keymap> {
+    unsafe {
+        let context = (xkb.context_new)(xkb::XKB_CONTEXT_NO_FLAGS);
+        if context.is_null() {
+            return None;
+        }
+
+        let keymap = (xkb.keymap_new_from_buffer)(
+            context,
+            text.as_ptr().cast(),
+            text.len(),
+            xkb::XKB_KEYMAP_FORMAT_TEXT_V1,
+            xkb::XKB_KEYMAP_COMPILE_NO_FLAGS,
+        );
+        if keymap.is_null() {
+            (xkb.context_unref)(context);
+            return None;
+        }
+
+        let state = (xkb.state_new)(keymap);
+        if state.is_null() {
+            (xkb.keymap_unref)(keymap);
+            (xkb.context_unref)(context);
+            return None;
+        }
+
+        Some(Keymap { xkb: *xkb, context, keymap, state })
+    }
+}
+
+// Reads the keymap the compositor shares through `fd`. The file may be
+// sealed or shared with other clients, so it is read in place without
+// touching its offset; the fd is closed afterwards. The size counts a
+// terminating nul, which older libxkbcommon rejects as part of a buffer.
+pub fn from_fd(xkb: &Xkb, fd: i32, size: u32) -> Option<Keymap> {
+    use std::os::fd::FromRawFd;
+    use std::os::unix::fs::FileExt;
+
+    let file = unsafe { std::fs::File::from_raw_fd(fd) };
+    let mut text = vec![0; size as usize];
+    file.read_exact_at(&mut text, 0).ok()?;
+
+    new(xkb, text.strip_suffix(&[0]).unwrap_or(&text))
+}
+
+pub fn destroy(keymap: &Keymap) {
+    unsafe {
+        (keymap.xkb.state_unref)(keymap.state);
+        (keymap.xkb.keymap_unref)(keymap.keymap);
+        (keymap.xkb.context_unref)(keymap.context);
+    }
+}
+
+pub fn update_mask(keymap: &mut Keymap, depressed: u32, latched: u32, locked: u32, group: u32) {
+    unsafe {
+        (keymap.xkb.state_update_mask)(keymap.state, depressed, latched, locked, 0, 0, group);
+    }
+}
+
+// The keysym a key produces at the current level and group.
+pub fn key_sym(keymap: &Keymap, code: u32) -> u32 {
+    unsafe { (keymap.xkb.state_key_get_one_sym)(keymap.state, code + EVDEV_OFFSET) }
+}
+
+// The keysym on the base level of the first group, which is where the
+// letters of a Latin layout sit even when another group is active.
+pub fn base_sym(keymap: &Keymap, code: u32) -> u32 {
+    let mut syms: *const u32 = std::ptr::null();
+
+    unsafe {
+        let count = (keymap.xkb.keymap_key_get_syms_by_level)(keymap.keymap, code + EVDEV_OFFSET, 0, 0, &mut syms);
+        if count == 1 {
+            *syms
+        } else {
+            xkb::XKB_KEY_NoSymbol
+        }
+    }
+}
+
+pub fn sym_char(keymap: &Keymap, sym: u32) -> Option<char> {
+    let value = unsafe { (keymap.xkb.keysym_to_utf32)(sym) };
+    char::from_u32(value).filter(|c| *c != '\0')
+}
+
+// The text a key types with the current modifiers, or an empty string for
+// keys that don't type anything.
+pub fn key_text(keymap: &Keymap, code: u32) -> String {
+    let mut buffer = [0u8; TEXT_CAPACITY];
+
+    let len = unsafe {
+        (keymap.xkb.state_key_get_utf8)(keymap.state, code + EVDEV_OFFSET, buffer.as_mut_ptr().cast(), buffer.len())
+    };
+
+    let len = (len.max(0) as usize).min(buffer.len() - 1);
+    String::from_utf8_lossy(&buffer[..len]).into_owned()
+}
+
+// Keysyms from the compositor's keymap for keys that don't type text. The
+// keypad digits and operators are looked up as text while Num Lock is on and
+// come here as the navigation keys printed under them while it is off.
+pub fn special_key_from_sym(sym: u32) -> Option<Key> {
+    match sym {
+        0xff1b => Some(Key::Escape),
+        0xff0d | 0xff8d => Some(Key::Enter),
+        0xff08 => Some(Key::Backspace),
+        0xff52 | 0xff97 => Some(Key::Up),
+        0xff54 | 0xff99 => Some(Key::Down),
+        0xff51 | 0xff96 => Some(Key::Left),
+        0xff53 | 0xff98 => Some(Key::Right),
+        0xff50 | 0xff95 => Some(Key::Home),
+        0xff57 | 0xff9c => Some(Key::End),
+        0xff55 | 0xff9a => Some(Key::PageUp),
+        0xff56 | 0xff9b => Some(Key::PageDown),
+        0xff63 | 0xff9e => Some(Key::Insert),
+        0xffff | 0xff9f => Some(Key::Delete),
+        0xffbe..=0xffc9 => Some(Key::Function((sym - 0xffbe + 1) as u8)),
+        _ => None,
+    }
+}
+
+// Evdev scancodes, for when there is no keymap. Num Lock state isn't known
+// then, so the keypad always navigates.
+pub fn special_key_from_code(code: u8) -> Option<Key> {
+    match code {
+        1 => Some(Key::Escape),
+        28 | 96 => Some(Key::Enter),
+        14 => Some(Key::Backspace),
+        103 | 72 => Some(Key::Up),
+        108 | 80 => Some(Key::Down),
+        105 | 75 => Some(Key::Left),
+        106 | 77 => Some(Key::Right),
+        102 | 71 => Some(Key::Home),
+        107 | 79 => Some(Key::End),
+        104 | 73 => Some(Key::PageUp),
+        109 | 81 => Some(Key::PageDown),
+        110 | 82 => Some(Key::Insert),
+        111 | 83 => Some(Key::Delete),
+        59..=68 => Some(Key::Function(code - 58)),
+        87 | 88 => Some(Key::Function(code - 76)),
+        _ => None,
+    }
+}
@@ -1,5 +1,8 @@
 use crate::binding::wayland;
-pub mod buffer;
+use crate::editor::{self, buffer, key::Key, bindings::Press, InputEvent, KeyEvent, Request};
+pub mod pointer;
+pub mod clipboard;
+pub mod keymap;
 
 pub struct Core {
     pub display: *mut wayland::wl_display,
@@ -10,43 +13,49 @@ pub struct Core {
     pub width: u32,
     pub height: u32,
 
-    pub changed: bool,
-    pub buffers: Vec<buffer::Buffer>,
-    pub main_buffer_index: u32,
-
-    completion_lines: Vec<buffer::Line>,
-
-    command_mode: bool,
-    command: Vec<u8>,
+    pub editor: editor::Editor,
+    clipboard: clipboard::Clipboard,
 
     scale: f32,
     x_ratio: f32,
     window_ratio: f32,
 
-    chars_per_row: u32,
-    chars_per_coloumn: u32,
-
     key_delay: std::time::Duration,
     key_rate: std::time::Duration,
 
-    last_inserted_char: u8,
-    last_function: Option<fn(&mut Core)>,
     last_fetch_rate: std::time::Instant,
     last_fetch_delay: std::time::Instant,
     alt_modifier: bool,
     shift_modifier: bool,
     control_modifier: bool,
+    shift_held: bool,
+    pointer_state: pointer::Pointer,
+    serial: u32,
+    xkb: Option<keymap::Xkb>,
+    keymap: Option<keymap::Keymap>,
 
     registry: *mut wayland::wl_registry,
     compositor: *mut wayland::wl_compositor,
     seat: *mut wayland::wl_seat,
     keyboard: *mut wayland::wl_keyboard,
+    pointer: *mut wayland::wl_pointer,
+    data_device_manager: *mut wayland::wl_data_device_manager,
+    data_device: *mut wayland::wl_data_device,
+    primary_selection_manager: *mut wayland::zwp_primary_selection_device_manager_v1,
+    primary_selection_device: *mut wayland::zwp_primary_selection_device_v1,
     xdg_shell: *mut wayland::xdg_wm_base,
     xdg_surface: *mut wayland::xdg_surface,
     xdg_toplevel: *mut wayland::xdg_toplevel,
 
     seat_listener: wayland::wl_seat_listener,
     keyboard_listener: wayland::wl_keyboard_listener,
+    pointer_listener: wayland::wl_pointer_listener,
+    data_device_listener: wayland::wl_data_device_listener,
+    data_offer_listener: wayland::wl_data_offer_listener,
+    data_source_listener: wayland::wl_data_source_listener,
+    primary_selection_device_listener: wayland::zwp_primary_selection_device_v1_listener,
+    primary_selection_offer_listener: wayland::zwp_primary_selection_offer_v1_listener,
+    primary_selection_source_listener: wayland::zwp_primary_selection_source_v1_listener,
     registry_listener: wayland::wl_registry_listener,
     shell_listener: wayland::xdg_wm_base_listener,
     shell_surface_listener: wayland::xdg_surface_listener,
@@ -60,7 +69,7 @@ pub enum WaylandError {
 }
 
 pub fn set_unchanged(core: &mut Core) {
-    core.changed = false;
+    core.editor.changed = false;
 }
 
 pub fn update(core: &mut Core) {
@@ -69,421 +78,364 @@ pub fn update(core: &mut Core) {
         wayland::wl_display_roundtrip(core.display);
     };
 
-    if let Some(f) = core.last_function {
+    if editor::repeating(&core.editor) {
         if core.last_fetch_delay.elapsed() >= core.key_delay {
             if core.last_fetch_rate.elapsed() >= core.key_rate {
-                f(core);
+                editor::repeat(&mut core.editor);
+                serve_requests(core);
                 core.last_fetch_rate = std::time::Instant::now();
             }
         }
     }
 }
 
-fn page_down(core: &mut Core) {
-    let buffer = &mut core.buffers[core.main_buffer_index as usize];
-    let position = &mut buffer.cursors[buffer.main_cursor_index as usize].position;
-    let len = buffer.lines.len() as u32;
+fn dispatch(core: &mut Core, event: InputEvent) {
+    editor::handle_event(&mut core.editor, event);
+    serve_requests(core);
+}
 
-    if buffer.offset.y + core.chars_per_row - 1 >= len {
-        buffer.offset.y = len - core.chars_per_coloumn;
-    } else {
-        buffer.offset.y += core.chars_per_coloumn - 1;
-    }
+// Carries out what the editor asked for while handling an event. Selections
+// we still own are left for the editor to take from its own copy; reading
+// them through the compositor would have us write to ourselves.
+fn serve_requests(core: &mut Core) {
+    while !core.editor.requests.is_empty() {
+        for request in std::mem::take(&mut core.editor.requests) {
+            match request {
+                Request::SetSelection(selection, content) => publish_selection(core, selection, content),
+                Request::ReadSelection(selection) => {
+                    let content = if clipboard::owns(&core.clipboard, selection) {
+                        None
+                    } else {
+                        Some(receive_selection(core, selection).unwrap_or_default())
+                    };
 
-    if position.y < buffer.offset.y {
-        position.y = buffer.offset.y;
+                    editor::handle_event(&mut core.editor, InputEvent::Selection { selection, content });
+                },
+                Request::Quit => core.running = false,
+            }
+        }
     }
-
-    position.x = 0;
-    buffer::update_mode_line_right(core);
-    buffer::update_chars(core);
-    core.changed = true;
 }
 
-fn page_up(core: &mut Core) {
-    let buffer = &mut core.buffers[core.main_buffer_index as usize];
-    let position = &mut buffer.cursors[buffer.main_cursor_index as usize].position;
+const SHIFT_BIT: u8 = 0x01;
+const CAPSLOCK_BIT: u8 = 0x02;
+const CONTROL_BIT: u8 = 0x04;
+const ALT_BIT: u8 = 0x08;
 
-    if buffer.offset.y as i32 - core.chars_per_coloumn as i32 - 1 < 0 {
-        buffer.offset.y = 0;
-    } else {
-        buffer.offset.y -= core.chars_per_coloumn - 1;
-    }
+const KEYMAP_FORMAT_XKB_V1: u32 = 1;
 
-    if position.y > buffer.offset.y + core.chars_per_coloumn - 1 {
-        position.y = core.chars_per_coloumn + buffer.offset.y - 1;
-    }
+// Compiles the compositor's keymap. Without libxkbcommon, or for a keymap
+// that fails to compile, keys keep going through the built-in US layout.
+unsafe extern "C" fn keymap(data: *mut std::ffi::c_void, _: *mut wayland::wl_keyboard, format: u32, fd: i32, size: u32) {
+    let core = &mut *(data as *mut Core);
 
-    position.x = 0;
-    buffer::update_mode_line_right(core);
-    buffer::update_chars(core);
-    core.changed = true;
+    match core.xkb.as_ref() {
+        Some(xkb) if format == KEYMAP_FORMAT_XKB_V1 => {
+            let new = keymap::from_fd(xkb, fd, size);
+            if let Some(previous) = std::mem::replace(&mut core.keymap, new) {
+                keymap::destroy(&previous);
+            }
+        },
+        _ => drop(<std::fs::File as std::os::fd::FromRawFd>::from_raw_fd(fd)),
+    }
 }
 
-fn prev_line(core: &mut Core) {
-    let buffer = &mut core.buffers[core.main_buffer_index as usize];
-    for i in 0..buffer.cursors.len() {
-        buffer::prev_line(core, i);
+unsafe extern "C" fn enter(_: *mut std::ffi::c_void, _: *mut wayland::wl_keyboard, _: u32, _: *mut wayland::wl_surface, _: *mut wayland::wl_array) {}
+unsafe extern "C" fn leave(_: *mut std::ffi::c_void, _: *mut wayland::wl_keyboard, _: u32, _: *mut wayland::wl_surface,) {}
+unsafe extern "C" fn modifiers(data: *mut std::ffi::c_void, _: *mut wayland::wl_keyboard, _: u32, depressed: u32, latched: u32, locked: u32, group: u32) {
+    let core = &mut *(data as *mut Core);
+    let pressed = depressed as u8 | locked as u8;
+
+    if let Some(keymap) = core.keymap.as_mut() {
+        keymap::update_mask(keymap, depressed, latched, locked, group);
     }
 
-    buffer::update_mode_line_right(core);
-    buffer::check_offset(core);
-    buffer::update_chars(core);
+    core.control_modifier = pressed & CONTROL_BIT > 0;
+    core.shift_modifier = pressed & (SHIFT_BIT | CAPSLOCK_BIT) > 0;
+    core.shift_held = depressed as u8 & SHIFT_BIT > 0;
+    core.alt_modifier = pressed & ALT_BIT > 0;
+}
 
-    core.changed = true;
+unsafe extern "C" fn repeat_info(data: *mut std::ffi::c_void, _: *mut wayland::wl_keyboard, rate: i32, delay: i32) {
+    let core = &mut *(data as *mut Core);
+    core.key_delay = std::time::Duration::from_millis(delay as u64);
+    core.key_rate = std::time::Duration::from_millis(rate as u64);
 }
 
-fn next_line(core: &mut Core) {
-    let buffer = &mut core.buffers[core.main_buffer_index as usize];
-    for i in 0..buffer.cursors.len() {
-        buffer::next_line(core, i);
-    }
+unsafe extern "C" fn key(data: *mut std::ffi::c_void, _: *mut wayland::wl_keyboard, serial: u32, _: u32, id: u32, state: u32) {
+    let core = std::mem::transmute::<*mut std::ffi::c_void, &mut Core>(data);
+    core.serial = serial;
+    core.last_fetch_delay = std::time::Instant::now();
 
-    buffer::update_mode_line_right(core);
-    buffer::check_offset(core);
-    buffer::update_chars(core);
+    let event = match key_event(core, id as u8) {
+        Some(event) if state == 1 => InputEvent::Key(event),
+        _ => InputEvent::KeyRelease,
+    };
 
-    core.changed = true;
+    dispatch(core, event);
 }
 
-fn prev_char(core: &mut Core) {
-    let buffer = &mut core.buffers[core.main_buffer_index as usize];
-    for i in 0..buffer.cursors.len() {
-        buffer::prev_char(core, i);
-    }
+// Version 5 is the first with pointer frames and discrete axis steps.
+const SEAT_VERSION: u32 = 5;
+
+fn cell_size(core: &Core) -> [f64; 2] {
+    let height = core.height as f64 * core.scale as f64;
 
-    buffer::update_mode_line_right(core);
-    buffer::check_offset(core);
-    buffer::update_chars(core);
+    [height * core.x_ratio as f64, height]
+}
 
-    core.changed = true;
+fn pointer_cell(core: &Core) -> [u32; 2] {
+    pointer::cell_at(core.pointer_state.position, cell_size(core))
 }
 
-fn next_char(core: &mut Core) {
-    let buffer = &mut core.buffers[core.main_buffer_index as usize];
-    for i in 0..buffer.cursors.len() {
-        buffer::next_char(core, i);
+fn pointer_scroll(core: &mut Core) {
+    let size = cell_size(core);
+    let lines = pointer::frame(&mut core.pointer_state, size);
+    if lines != [0, 0] {
+        let cell = pointer_cell(core);
+        dispatch(core, InputEvent::Scroll { cell, lines });
     }
+}
 
-    buffer::update_mode_line_right(core);
-    buffer::check_offset(core);
-    buffer::update_chars(core);
+unsafe extern "C" fn pointer_enter(data: *mut std::ffi::c_void, _: *mut wayland::wl_pointer, _: u32, _: *mut wayland::wl_surface, x: i32, y: i32) {
+    let core = &mut *(data as *mut Core);
+    core.pointer_state.position = [pointer::fixed_to_f64(x), pointer::fixed_to_f64(y)];
+}
 
-    core.changed = true;
+unsafe extern "C" fn pointer_leave(data: *mut std::ffi::c_void, _: *mut wayland::wl_pointer, _: u32, _: *mut wayland::wl_surface) {
+    let core = &mut *(data as *mut Core);
+    core.pointer_state.pressed = false;
 }
 
-fn end_of_line(core: &mut Core) {
-    let buffer = &mut core.buffers[core.main_buffer_index as usize];
-    for i in 0..buffer.cursors.len() {
-        buffer::end_of_line(core, i);
+unsafe extern "C" fn pointer_motion(data: *mut std::ffi::c_void, _: *mut wayland::wl_pointer, _: u32, x: i32, y: i32) {
+    let core = &mut *(data as *mut Core);
+    core.pointer_state.position = [pointer::fixed_to_f64(x), pointer::fixed_to_f64(y)];
+
+    if core.pointer_state.pressed {
+        let cell = pointer_cell(core);
+        dispatch(core, InputEvent::Drag { cell });
     }
+}
 
-    buffer::update_mode_line_right(core);
-    buffer::check_offset(core);
-    buffer::update_chars(core);
+unsafe extern "C" fn pointer_button(data: *mut std::ffi::c_void, _: *mut wayland::wl_pointer, serial: u32, time: u32, button: u32, state: u32) {
+    let core = &mut *(data as *mut Core);
+    core.serial = serial;
 
-    core.changed = true;
-}
+    let pressed = state == wayland::WL_POINTER_BUTTON_STATE_PRESSED;
+    let cell = pointer_cell(core);
 
-fn start_of_line(core: &mut Core) {
-    let buffer = &mut core.buffers[core.main_buffer_index as usize];
-    for i in 0..buffer.cursors.len() {
-        buffer::start_of_line(core, i);
+    if button == pointer::BUTTON_MIDDLE && pressed {
+        dispatch(core, InputEvent::Paste { cell });
+    } else if button == pointer::BUTTON_LEFT && pressed {
+        core.pointer_state.pressed = true;
+        let clicks = pointer::click(&mut core.pointer_state, time, cell);
+        dispatch(core, InputEvent::Press { cell, clicks });
+    } else if button == pointer::BUTTON_LEFT {
+        core.pointer_state.pressed = false;
+        dispatch(core, InputEvent::Release);
     }
+}
 
-    buffer::update_mode_line_right(core);
-    buffer::check_offset(core);
-    buffer::update_chars(core);
+unsafe extern "C" fn pointer_axis(data: *mut std::ffi::c_void, wl_pointer: *mut wayland::wl_pointer, _: u32, axis: u32, value: i32) {
+    let core = &mut *(data as *mut Core);
+    pointer::axis(&mut core.pointer_state, axis as usize & 1, pointer::fixed_to_f64(value));
 
-    core.changed = true;
+    if wayland::wl_proxy_get_version(wl_pointer as *mut wayland::wl_proxy) < wayland::WL_POINTER_FRAME_SINCE_VERSION {
+        pointer_scroll(core);
+    }
 }
 
-fn delete_char_at(core: &mut Core) {
-    let buffer = &mut core.buffers[core.main_buffer_index as usize];
-    for i in 0..buffer.cursors.len() {
-        buffer::delete_char_at(core, i);
-    }
+unsafe extern "C" fn pointer_frame(data: *mut std::ffi::c_void, _: *mut wayland::wl_pointer) {
+    let core = &mut *(data as *mut Core);
+    pointer_scroll(core);
+}
 
-    buffer::check_offset(core);
-    buffer::update_chars(core);
+unsafe extern "C" fn pointer_axis_source(_: *mut std::ffi::c_void, _: *mut wayland::wl_pointer, _: u32) {}
+unsafe extern "C" fn pointer_axis_stop(_: *mut std::ffi::c_void, _: *mut wayland::wl_pointer, _: u32, _: u32) {}
 
-    core.changed = true;
+unsafe extern "C" fn pointer_axis_discrete(data: *mut std::ffi::c_void, _: *mut wayland::wl_pointer, axis: u32, discrete: i32) {
+    let core = &mut *(data as *mut Core);
+    pointer::axis_discrete(&mut core.pointer_state, axis as usize & 1, discrete);
 }
 
-fn delete_to_line_end(core: &mut Core) {
-    let buffer = &mut core.buffers[core.main_buffer_index as usize];
-    for i in 0..buffer.cursors.len() {
-        buffer::delete_to_line_end(core, i);
-    }
+// Version 3 is the last one; newer managers only add drag and drop actions.
+const DATA_DEVICE_MANAGER_VERSION: u32 = 3;
 
-    buffer::check_offset(core);
-    buffer::update_chars(core);
+fn publish_selection(core: &mut Core, selection: usize, content: Vec<u8>) {
+    let data = core as *mut Core as *mut std::ffi::c_void;
 
-    core.changed = true;
-}
+    let source = if selection == clipboard::CLIPBOARD {
+        if core.data_device.is_null() {
+            return;
+        }
 
-fn insert_char_at_current_position(core: &mut Core) {
-    let buffer = &mut core.buffers[core.main_buffer_index as usize];
-    for i in 0..buffer.cursors.len() {
-        buffer::insert_char_at(core, i);
-    }
+        let source = unsafe { wayland::wl_proxy_marshal_flags(core.data_device_manager as *mut wayland::wl_proxy, wayland::WL_DATA_DEVICE_MANAGER_CREATE_DATA_SOURCE, &wayland::wl_data_source_interface, wayland::wl_proxy_get_version(core.data_device_manager as *mut wayland::wl_proxy), 0, std::ptr::null::<std::ffi::c_void>()) };
+        unsafe { wayland::wl_proxy_add_listener(source, std::mem::transmute::<*mut wayland::wl_data_source_listener, *mut Option<unsafe extern "C" fn()>>(&mut core.data_source_listener), data) };
 
-    buffer::update_mode_line_right(core);
-    buffer::check_offset(core);
-    buffer::update_chars(core);
+        for mime_type in clipboard::MIME_TYPES {
+            let mime_type = std::ffi::CString::new(mime_type).unwrap();
+            unsafe { wayland::wl_proxy_marshal_flags(source, wayland::WL_DATA_SOURCE_OFFER, std::ptr::null(), wayland::wl_proxy_get_version(source), 0, mime_type.as_ptr()) };
+        }
 
-    core.changed = true;
-}
+        unsafe { wayland::wl_proxy_marshal_flags(core.data_device as *mut wayland::wl_proxy, wayland::WL_DATA_DEVICE_SET_SELECTION, std::ptr::null(), wayland::wl_proxy_get_version(core.data_device as *mut wayland::wl_proxy), 0, source, core.serial) };
+        source
+    } else {
+        if core.primary_selection_device.is_null() {
+            return;
+        }
 
-fn delete_to_command_end(core: &mut Core) {
-    if core.buffers[core.main_buffer_index as usize].cursors[1].position.x < core.command.len() as u32 + 1 {
-        core.command.drain(core.buffers[core.main_buffer_index as usize].cursors[1].position.x as usize - 1..);
-        buffer::update_chars(core);
-        core.changed = true;
-    }
-}
+        let source = unsafe { wayland::wl_proxy_marshal_flags(core.primary_selection_manager as *mut wayland::wl_proxy, wayland::ZWP_PRIMARY_SELECTION_DEVICE_MANAGER_V1_CREATE_SOURCE, std::ptr::addr_of!(wayland::zwp_primary_selection_source_v1_interface), 1, 0, std::ptr::null::<std::ffi::c_void>()) };
+        unsafe { wayland::wl_proxy_add_listener(source, std::mem::transmute::<*mut wayland::zwp_primary_selection_source_v1_listener, *mut Option<unsafe extern "C" fn()>>(&mut core.primary_selection_source_listener), data) };
 
-fn insert_new_line(core: &mut Core) {
-    let buffer = &mut core.buffers[core.main_buffer_index as usize];
-    for i in 0..buffer.cursors.len() {
-        buffer::insert_new_line(core, i);
-    }
+        for mime_type in clipboard::MIME_TYPES {
+            let mime_type = std::ffi::CString::new(mime_type).unwrap();
+            unsafe { wayland::wl_proxy_marshal_flags(source, wayland::ZWP_PRIMARY_SELECTION_SOURCE_V1_OFFER, std::ptr::null(), 1, 0, mime_type.as_ptr()) };
+        }
 
-    buffer::update_mode_line_right(core);
-    buffer::check_offset(core);
-    buffer::update_chars(core);
+        unsafe { wayland::wl_proxy_marshal_flags(core.primary_selection_device as *mut wayland::wl_proxy, wayland::ZWP_PRIMARY_SELECTION_DEVICE_V1_SET_SELECTION, std::ptr::null(), 1, 0, source, core.serial) };
+        source
+    };
 
-    core.changed = true;
+    let previous = clipboard::publish(&mut core.clipboard, selection, source as *mut std::ffi::c_void, content);
+    destroy_source(selection, previous);
 }
 
-fn delete_prev_char(core: &mut Core) {
-    let buffer = &mut core.buffers[core.main_buffer_index as usize];
-    for i in 0..buffer.cursors.len() {
-        buffer::delete_prev_char(core, i);
+fn destroy_source(selection: usize, source: *mut std::ffi::c_void) {
+    if source.is_null() {
+        return;
     }
 
-    buffer::update_mode_line_right(core);
-    buffer::check_offset(core);
-    buffer::update_chars(core);
+    let opcode = if selection == clipboard::CLIPBOARD {
+        wayland::WL_DATA_SOURCE_DESTROY
+    } else {
+        wayland::ZWP_PRIMARY_SELECTION_SOURCE_V1_DESTROY
+    };
 
-    core.changed = true;
+    unsafe { wayland::wl_proxy_marshal_flags(source as *mut wayland::wl_proxy, opcode, std::ptr::null(), wayland::wl_proxy_get_version(source as *mut wayland::wl_proxy), wayland::WL_MARSHAL_FLAG_DESTROY) };
 }
 
-fn insert_command_char(core: &mut Core) {
-    let c = core.last_inserted_char;
-    if c == b'\t' {
-        let completions: Vec<Vec<u8>> = core.buffers.iter().map(|b| b.file_name.clone().unwrap()).collect();
-        buffer::show_completion_box(core, completions);
+fn destroy_offer(selection: usize, offer: *mut std::ffi::c_void) {
+    let opcode = if selection == clipboard::CLIPBOARD {
+        wayland::WL_DATA_OFFER_DESTROY
     } else {
-        core.command.insert(core.buffers[core.main_buffer_index as usize].cursors[1].position.x as usize - 1, c);
-        core.buffers[core.main_buffer_index as usize].cursors[1].position.x += 1;
-        buffer::update_chars(core);
-    }
+        wayland::ZWP_PRIMARY_SELECTION_OFFER_V1_DESTROY
+    };
 
-    core.changed = true;
+    unsafe { wayland::wl_proxy_marshal_flags(offer as *mut wayland::wl_proxy, opcode, std::ptr::null(), wayland::wl_proxy_get_version(offer as *mut wayland::wl_proxy), wayland::WL_MARSHAL_FLAG_DESTROY) };
 }
 
-fn delete_prev_command_char(core: &mut Core) {
-    if let Some(_) = core.command.pop() {
-        core.buffers[core.main_buffer_index as usize].cursors[1].position.x -= 1;
-        buffer::update_chars(core);
-        core.changed = true;
-    }
-}
+// Asks the owner of the current selection to write it into a pipe and waits
+// for the result. Our own selections must not come through here, since we
+// would be blocked reading while we are the one who has to write.
+fn receive_selection(core: &mut Core, selection: usize) -> Option<Vec<u8>> {
+    use std::os::fd::AsRawFd;
+
+    let offer = core.clipboard.offers[selection].as_ref()?;
+    let mime_type = std::ffi::CString::new(clipboard::preferred_mime_type(offer)?).ok()?;
+    let (reader, writer) = std::io::pipe().ok()?;
 
-fn prev_command_char(core: &mut Core) {
-    if core.buffers[core.main_buffer_index as usize].cursors[1].position.x > 0 {
-        core.buffers[core.main_buffer_index as usize].cursors[1].position.x -= 1;
-        buffer::update_chars(core);
-        core.changed = true;
+    let opcode = if selection == clipboard::CLIPBOARD {
+        wayland::WL_DATA_OFFER_RECEIVE
+    } else {
+        wayland::ZWP_PRIMARY_SELECTION_OFFER_V1_RECEIVE
     };
+
+    let handle = offer.handle as *mut wayland::wl_proxy;
+    unsafe { wayland::wl_proxy_marshal_flags(handle, opcode, std::ptr::null(), wayland::wl_proxy_get_version(handle), 0, mime_type.as_ptr(), writer.as_raw_fd()) };
+    drop(writer);
+    unsafe { wayland::wl_display_flush(core.display) };
+
+    clipboard::receive(reader, clipboard::RECEIVE_TIMEOUT)
 }
 
-fn next_command_char(core: &mut Core) {
-    if core.buffers[core.main_buffer_index as usize].cursors[1].position.x < core.command.len() as u32 + 1 {
-        core.buffers[core.main_buffer_index as usize].cursors[1].position.x += 1;
-        buffer::update_chars(core);
-        core.changed = true;
-    }
+unsafe extern "C" fn data_device_data_offer(data: *mut std::ffi::c_void, _: *mut wayland::wl_data_device, id: *mut wayland::wl_data_offer) {
+    let core = &mut *(data as *mut Core);
+
+    clipboard::introduce(&mut core.clipboard, id as *mut std::ffi::c_void);
+    wayland::wl_proxy_add_listener(id as *mut wayland::wl_proxy, std::mem::transmute::<*mut wayland::wl_data_offer_listener, *mut Option<unsafe extern "C" fn()>>(&mut core.data_offer_listener), data);
 }
 
-fn end_of_command_line(core: &mut Core) {
-    if core.buffers[core.main_buffer_index as usize].cursors[1].position.x < core.command.len() as u32 + 1 {
-        core.buffers[core.main_buffer_index as usize].cursors[1].position.x = core.command.len() as u32 + 1;
-        buffer::update_chars(core);
-        core.changed = true;
+unsafe extern "C" fn data_device_enter(data: *mut std::ffi::c_void, _: *mut wayland::wl_data_device, _: u32, _: *mut wayland::wl_surface, _: i32, _: i32, id: *mut wayland::wl_data_offer) {
+    let core = &mut *(data as *mut Core);
+
+    if !id.is_null() && clipboard::discard(&mut core.clipboard, id as *mut std::ffi::c_void) {
+        destroy_offer(clipboard::CLIPBOARD, id as *mut std::ffi::c_void);
     }
 }
 
-fn start_of_command_line(core: &mut Core) {
-    if core.buffers[core.main_buffer_index as usize].cursors[1].position.x > 1 {
-        core.buffers[core.main_buffer_index as usize].cursors[1].position.x = 1;
-        buffer::update_chars(core);
-        core.changed = true;
+unsafe extern "C" fn data_device_leave(_: *mut std::ffi::c_void, _: *mut wayland::wl_data_device) {}
+unsafe extern "C" fn data_device_motion(_: *mut std::ffi::c_void, _: *mut wayland::wl_data_device, _: u32, _: i32, _: i32) {}
+unsafe extern "C" fn data_device_drop(_: *mut std::ffi::c_void, _: *mut wayland::wl_data_device) {}
+
+unsafe extern "C" fn data_device_selection(data: *mut std::ffi::c_void, _: *mut wayland::wl_data_device, id: *mut wayland::wl_data_offer) {
+    let core = &mut *(data as *mut Core);
+
+    if let Some(previous) = clipboard::select(&mut core.clipboard, clipboard::CLIPBOARD, id as *mut std::ffi::c_void) {
+        destroy_offer(clipboard::CLIPBOARD, previous);
     }
 }
 
-fn delete_command_char_at(core: &mut Core) {
-    if core.command.len() > 0 {
-        core.command.remove(core.buffers[core.main_buffer_index as usize].cursors[1].position.x as usize - 1);
-        buffer::update_chars(core);
-        core.changed = true;
-    }
+unsafe extern "C" fn data_offer_offer(data: *mut std::ffi::c_void, offer: *mut wayland::wl_data_offer, mime_type: *const std::ffi::c_char) {
+    let core = &mut *(data as *mut Core);
+    clipboard::add_mime_type(&mut core.clipboard, offer as *mut std::ffi::c_void, std::ffi::CStr::from_ptr(mime_type).to_bytes());
 }
 
-fn active_command_mode(core: &mut Core) {
-    core.command_mode = true;
-    core.buffers[core.main_buffer_index as usize].cursors.resize(
-        1,
-        buffer::Cursor {
-            selection: None,
-            position: buffer::Position {
-                x: 0,
-                y: 0,
-            }
-        }
-    );
-
-    let offset = core.buffers[core.main_buffer_index as usize].offset.y;
-    core.buffers[core.main_buffer_index as usize].cursors.push(
-        buffer::Cursor {
-            selection: None,
-            position: buffer::Position {
-                x: 1,
-                y: core.chars_per_coloumn + offset,
-            }
-        }
-    );
+unsafe extern "C" fn data_offer_source_actions(_: *mut std::ffi::c_void, _: *mut wayland::wl_data_offer, _: u32) {}
+unsafe extern "C" fn data_offer_action(_: *mut std::ffi::c_void, _: *mut wayland::wl_data_offer, _: u32) {}
 
-    buffer::update_chars(core);
-    core.changed = true;
-}
+unsafe extern "C" fn data_source_target(_: *mut std::ffi::c_void, _: *mut wayland::wl_data_source, _: *const std::ffi::c_char) {}
 
-fn deactive_command_mode(core: &mut Core) {
-    core.command_mode = false;
-    core.buffers[core.main_buffer_index as usize].cursors.pop();
-}
+unsafe extern "C" fn data_source_send(data: *mut std::ffi::c_void, source: *mut wayland::wl_data_source, _: *const std::ffi::c_char, fd: i32) {
+    let core = &mut *(data as *mut Core);
+    let content = clipboard::content_of(&core.clipboard, source as *mut std::ffi::c_void).map(Vec::from).unwrap_or_default();
 
-fn execute_command(core: &mut Core) {
-    buffer::execute_command(core);
-    buffer::update_chars(core);
-    core.changed = true;
+    clipboard::send(fd, content);
 }
 
-fn save_buffer(core: &mut Core) {
-    buffer::save_buffer(core);
+unsafe extern "C" fn data_source_cancelled(data: *mut std::ffi::c_void, source: *mut wayland::wl_data_source) {
+    let core = &mut *(data as *mut Core);
+
+    if clipboard::cancelled(&mut core.clipboard, source as *mut std::ffi::c_void) {
+        destroy_source(clipboard::CLIPBOARD, source as *mut std::ffi::c_void);
+    }
 }
 
-const SHIFT_BIT: u8 = 0x01;
-const CAPSLOCK_BIT: u8 = 0x02;
-const CONTROL_BIT: u8 = 0x04;
-const ALT_BIT: u8 = 0x08;
+unsafe extern "C" fn data_source_dnd_drop_performed(_: *mut std::ffi::c_void, _: *mut wayland::wl_data_source) {}
+unsafe extern "C" fn data_source_dnd_finished(_: *mut std::ffi::c_void, _: *mut wayland::wl_data_source) {}
+unsafe extern "C" fn data_source_action(_: *mut std::ffi::c_void, _: *mut wayland::wl_data_source, _: u32) {}
 
-unsafe extern "C" fn keymap(_: *mut std::ffi::c_void, _: *mut wayland::wl_keyboard, _: u32, _: i32, _: u32) {}
-unsafe extern "C" fn enter(_: *mut std::ffi::c_void, _: *mut wayland::wl_keyboard, _: u32, _: *mut wayland::wl_surface, _: *mut wayland::wl_array) {}
-unsafe extern "C" fn leave(_: *mut std::ffi::c_void, _: *mut wayland::wl_keyboard, _: u32, _: *mut wayland::wl_surface,) {}
-unsafe extern "C" fn modifiers(data: *mut std::ffi::c_void, _: *mut wayland::wl_keyboard, _: u32, depressed: u32, _: u32, locked: u32, _: u32) {
-    let core = std::mem::transmute::<*mut std::ffi::c_void, &mut Core>(data);
-    let pressed = depressed as u8 | locked as u8;
+unsafe extern "C" fn primary_selection_data_offer(data: *mut std::ffi::c_void, _: *mut wayland::zwp_primary_selection_device_v1, offer: *mut wayland::zwp_primary_selection_offer_v1) {
+    let core = &mut *(data as *mut Core);
 
-    core.control_modifier = pressed & CONTROL_BIT > 0;
-    core.shift_modifier = pressed & (SHIFT_BIT | CAPSLOCK_BIT) > 0;
-    core.alt_modifier = pressed & ALT_BIT > 0;
+    clipboard::introduce(&mut core.clipboard, offer as *mut std::ffi::c_void);
+    wayland::wl_proxy_add_listener(offer as *mut wayland::wl_proxy, std::mem::transmute::<*mut wayland::zwp_primary_selection_offer_v1_listener, *mut Option<unsafe extern "C" fn()>>(&mut core.primary_selection_offer_listener), data);
 }
 
-unsafe extern "C" fn repeat_info(data: *mut std::ffi::c_void, _: *mut wayland::wl_keyboard, rate: i32, delay: i32) {
-    let core = std::mem::transmute::<*mut std::ffi::c_void, &mut Core>(data);
-    core.key_delay = std::time::Duration::from_millis(delay as u64);
-    core.key_rate = std::time::Duration::from_millis(rate as u64);
+unsafe extern "C" fn primary_selection_selection(data: *mut std::ffi::c_void, _: *mut wayland::zwp_primary_selection_device_v1, id: *mut wayland::zwp_primary_selection_offer_v1) {
+    let core = &mut *(data as *mut Core);
+
+    if let Some(previous) = clipboard::select(&mut core.clipboard, clipboard::PRIMARY, id as *mut std::ffi::c_void) {
+        destroy_offer(clipboard::PRIMARY, previous);
+    }
 }
 
-unsafe extern "C" fn key(data: *mut std::ffi::c_void, _: *mut wayland::wl_keyboard, _: u32, _: u32, id: u32, state: u32) {
-    let core = std::mem::transmute::<*mut std::ffi::c_void, &mut Core>(data);
-    let code = id as u8;
+unsafe extern "C" fn primary_selection_offer(data: *mut std::ffi::c_void, offer: *mut wayland::zwp_primary_selection_offer_v1, mime_type: *const std::ffi::c_char) {
+    let core = &mut *(data as *mut Core);
+    clipboard::add_mime_type(&mut core.clipboard, offer as *mut std::ffi::c_void, std::ffi::CStr::from_ptr(mime_type).to_bytes());
+}
 
-    core.last_fetch_delay = std::time::Instant::now();
-    core.last_function = None;
-
-    if state == 1 {
-        if let Ok(b) = try_ascci(code) {
-            let c = if core.shift_modifier {
-                b[1]
-            } else {
-                b[0]
-            };
-
-            if core.control_modifier {
-                if core.command_mode {
-                    match c {
-                        b'b' => core.last_function = Some(prev_command_char),
-                        b'f' => core.last_function = Some(next_command_char),
-                        b'e' => core.last_function = Some(end_of_command_line),
-                        b'a' => core.last_function = Some(start_of_command_line),
-                        b'd' => core.last_function = Some(delete_command_char_at),
-                        b'k' => core.last_function = Some(delete_to_command_end),
-                        _ => {},
-                    }
-                } else {
-                    match c {
-                        b'p' => core.last_function = Some(prev_line),
-                        b'n' => core.last_function = Some(next_line),
-                        b'b' => core.last_function = Some(prev_char),
-                        b'f' => core.last_function = Some(next_char),
-                        b'e' => core.last_function = Some(end_of_line),
-                        b'a' => core.last_function = Some(start_of_line),
-                        b'd' => core.last_function = Some(delete_char_at),
-                        b'k' => core.last_function = Some(delete_to_line_end),
-                        b's' => core.last_function = Some(save_buffer),
-                        b'v' => core.last_function = Some(page_down),
-                        _ => {},
-                    }
-                }
-
-            } else if core.alt_modifier {
-                if core.command_mode {
-                    return;
-                }
-
-                match c {
-                    // b'f' => core.last_function = Some(next_word),
-                    b'v' => core.last_function = Some(page_up),
-                    b'x' => core.last_function = Some(active_command_mode),
-                    _ => {},
-                }
-            } else {
-                core.last_inserted_char = c;
-                if core.command_mode {
-                    core.last_function = Some(insert_command_char);
-                } else {
-                    core.last_function = Some(insert_char_at_current_position);
-                }
-            }
-        } else if code == ENTER {
-            if core.command_mode {
-                deactive_command_mode(core);
-                execute_command(core);
-                return;
-            }
+unsafe extern "C" fn primary_selection_send(data: *mut std::ffi::c_void, source: *mut wayland::zwp_primary_selection_source_v1, _: *const std::ffi::c_char, fd: i32) {
+    let core = &mut *(data as *mut Core);
+    let content = clipboard::content_of(&core.clipboard, source as *mut std::ffi::c_void).map(Vec::from).unwrap_or_default();
 
-            core.last_function = Some(insert_new_line);
-        } else if code == BACKSPACE {
-            if core.command_mode {
-                core.last_function = Some(delete_prev_command_char);
-            } else {
-                core.last_function = Some(delete_prev_char);
-            }
-        } else if code == ESC {
-            if core.command_mode {
-                deactive_command_mode(core);
-                core.command.clear();
-                buffer::update_chars(core);
-                core.changed = true;
-            }
-        }
+    clipboard::send(fd, content);
+}
 
-        if let Some(f) = core.last_function {
-            f(core)
-        }
+unsafe extern "C" fn primary_selection_cancelled(data: *mut std::ffi::c_void, source: *mut wayland::zwp_primary_selection_source_v1) {
+    let core = &mut *(data as *mut Core);
+
+    if clipboard::cancelled(&mut core.clipboard, source as *mut std::ffi::c_void) {
+        destroy_source(clipboard::PRIMARY, source as *mut std::ffi::c_void);
     }
 }
 
@@ -491,10 +443,15 @@ unsafe extern "C" fn seat_name(_: *mut std::ffi::c_void, _: *mut wayland::wl_sea
 unsafe extern "C" fn capabilities(data: *mut std::ffi::c_void, seat: *mut wayland::wl_seat, capability: u32) {
     let mut core = std::mem::transmute::<*mut std::ffi::c_void, &mut Core>(data);
 
-    if capability != 0 && wayland::WL_SEAT_CAPABILITY_KEYBOARD != 0 {
+    if capability & wayland::WL_SEAT_CAPABILITY_KEYBOARD != 0 && core.keyboard.is_null() {
         core.keyboard = wayland::wl_proxy_marshal_flags(seat as *mut wayland::wl_proxy, wayland::WL_SEAT_GET_KEYBOARD, &wayland::wl_keyboard_interface, wayland::wl_proxy_get_version(seat as *mut wayland::wl_proxy), 0, std::ptr::null::<std::ffi::c_void>()) as *mut wayland::wl_keyboard;
         wayland::wl_proxy_add_listener(core.keyboard as *mut wayland::wl_proxy, std::mem::transmute::<*mut wayland::wl_keyboard_listener, *mut Option<unsafe extern "C" fn()>>(&mut core.keyboard_listener), std::mem::transmute::<&mut Core, *mut std::ffi::c_void>(&mut core));
     }
+
+    if capability & wayland::WL_SEAT_CAPABILITY_POINTER != 0 && core.pointer.is_null() {
+        core.pointer = wayland::wl_proxy_marshal_flags(seat as *mut wayland::wl_proxy, wayland::WL_SEAT_GET_POINTER, &wayland::wl_pointer_interface, wayland::wl_proxy_get_version(seat as *mut wayland::wl_proxy), 0, std::ptr::null::<std::ffi::c_void>()) as *mut wayland::wl_pointer;
+        wayland::wl_proxy_add_listener(core.pointer as *mut wayland::wl_proxy, std::mem::transmute::<*mut wayland::wl_pointer_listener, *mut Option<unsafe extern "C" fn()>>(&mut core.pointer_listener), data);
+    }
 }
 
 unsafe extern "C" fn shell_ping(_: *mut std::ffi::c_void, s: *mut wayland::xdg_wm_base, serial: u32) {
@@ -505,9 +462,10 @@ unsafe extern "C" fn shell_surface_configure(_: *mut std::ffi::c_void, shell_sur
     wayland::wl_proxy_marshal_flags(shell_surface as *mut wayland::wl_proxy, wayland::XDG_SURFACE_ACK_CONFIGURE, std::ptr::null(), wayland::wl_proxy_get_version(shell_surface as *mut wayland::wl_proxy), 0, serial);
 }
 
+// The editor decides, since unsaved edits make it ask first.
 unsafe extern "C" fn toplevel_close(data: *mut std::ffi::c_void, _: *mut wayland::xdg_toplevel) {
     let core = std::mem::transmute::<*mut std::ffi::c_void, &mut Core>(data);
-    core.running = false;
+    dispatch(core, InputEvent::Close);
 }
 
 unsafe extern "C" fn toplevel_configure(data: *mut std::ffi::c_void, _: *mut wayland::xdg_toplevel, width: i32, height: i32, _: *mut wayland::wl_array) {
@@ -516,13 +474,12 @@ unsafe extern "C" fn toplevel_configure(data: *mut std::ffi::c_void, _: *mut way
     if width > 0 && height > 0 {
         core.width = width as u32;
         core.height = height as u32;
-        core.changed = true;
 
         core.window_ratio = core.height as f32 / core.width as f32;
-        core.chars_per_coloumn = (1.0 / core.scale) as u32 - 1;
-        core.chars_per_row = (1.0 / (core.scale * core.x_ratio * core.window_ratio)) as u32;
+        let rows = (1.0 / core.scale) as u32;
+        let columns = (1.0 / (core.scale * core.x_ratio * core.window_ratio)) as u32;
 
-        buffer::update_chars(core);
+        dispatch(core, InputEvent::Resize { columns, rows });
     }
 }
 
@@ -530,7 +487,7 @@ unsafe extern "C" fn toplevel_configure_bounds(_: *mut std::ffi::c_void, _: *mut
 unsafe extern "C" fn toplevel_wm_capabilities(_: *mut std::ffi::c_void, _: *mut wayland::xdg_toplevel, _: *mut wayland::wl_array) {}
 unsafe extern "C" fn remove_listener(_: *mut std::ffi::c_void, _: *mut wayland::wl_registry, _: u32) {}
 
-unsafe extern "C" fn global_listener(data: *mut std::ffi::c_void, wl_registry: *mut wayland::wl_registry, name: u32, interface: *const std::ffi::c_char, _: u32) {
+unsafe extern "C" fn global_listener(data: *mut std::ffi::c_void, wl_registry: *mut wayland::wl_registry, name: u32, interface: *const std::ffi::c_char, version: u32) {
     let core = std::mem::transmute::<*mut std::ffi::c_void, &mut Core>(data);
     let interface_name = std::ffi::CStr::from_ptr(interface);
 
@@ -563,13 +520,38 @@ unsafe extern "C" fn global_listener(data: *mut std::ffi::c_void, wl_registry: *
             wl_registry as *mut wayland::wl_proxy,
             wayland::WL_REGISTRY_BIND,
             &wayland::wl_seat_interface,
-            1,
+            std::cmp::min(version, SEAT_VERSION),
             0,
             name,
             wayland::wl_seat_interface.name,
-            4,
+            std::cmp::min(version, SEAT_VERSION),
             std::ptr::null::<std::ffi::c_void>(),
         ) as *mut wayland::wl_seat;
+    } else if interface_name == std::ffi::CStr::from_ptr(wayland::wl_data_device_manager_interface.name) {
+        core.data_device_manager = wayland::wl_proxy_marshal_flags(
+            wl_registry as *mut wayland::wl_proxy,
+            wayland::WL_REGISTRY_BIND,
+            &wayland::wl_data_device_manager_interface,
+            std::cmp::min(version, DATA_DEVICE_MANAGER_VERSION),
+            0,
+            name,
+            wayland::wl_data_device_manager_interface.name,
+            std::cmp::min(version, DATA_DEVICE_MANAGER_VERSION),
+            std::ptr::null::<std::ffi::c_void>(),
+        ) as *mut wayland::wl_data_device_manager;
+    } else if interface_name == std::ffi::CStr::from_ptr(wayland::zwp_primary_selection_device_manager_v1_interface.name) {
+        let interface = std::ptr::addr_of!(wayland::zwp_primary_selection_device_manager_v1_interface);
+        core.primary_selection_manager = wayland::wl_proxy_marshal_flags(
+            wl_registry as *mut wayland::wl_proxy,
+            wayland::WL_REGISTRY_BIND,
+            interface,
+            1,
+            0,
+            name,
+            (*interface).name,
+            1,
+            std::ptr::null::<std::ffi::c_void>(),
+        ) as *mut wayland::zwp_primary_selection_device_manager_v1;
     }
 }
 
@@ -579,10 +561,11 @@ pub fn init(
     height: u32,
     scale: f32,
     x_ratio: f32,
+    buffer: buffer::Buffer,
 ) -> Result<Box<Core>, WaylandError> {
     let window_ratio = height as f32 / width as f32;
-    let chars_per_coloumn = (1.0 / scale) as u32 - 1;
-    let chars_per_row = (1.0 / (scale * x_ratio * window_ratio)) as u32;
+    let rows = (1.0 / scale) as u32;
+    let columns = (1.0 / (scale * x_ratio * window_ratio)) as u32;
 
     let mut core = Box::new(Core {
         display: std::ptr::null_mut(),
@@ -591,6 +574,11 @@ pub fn init(
         compositor: std::ptr::null_mut(),
         seat: std::ptr::null_mut(),
         keyboard: std::ptr::null_mut(),
+        pointer: std::ptr::null_mut(),
+        data_device_manager: std::ptr::null_mut(),
+        data_device: std::ptr::null_mut(),
+        primary_selection_manager: std::ptr::null_mut(),
+        primary_selection_device: std::ptr::null_mut(),
         xdg_shell: std::ptr::null_mut(),
         xdg_surface: std::ptr::null_mut(),
         xdg_toplevel: std::ptr::null_mut(),
@@ -604,23 +592,20 @@ pub fn init(
         x_ratio,
         window_ratio,
         running: true,
-        changed: false,
-        command_mode: false,
-        command: Vec::new(),
-        buffers: vec![buffer::buffer_from_file(chars_per_row, chars_per_coloumn, "src/renderer/wayland.rs").unwrap()],
-        completion_lines: Vec::new(),
-        main_buffer_index: 0,
-        chars_per_row,
-        chars_per_coloumn,
+        editor: editor::new(buffer, columns, rows),
+        clipboard: clipboard::new(),
         key_rate: std::time::Duration::from_millis(20),
         key_delay: std::time::Duration::from_millis(200),
-        last_function: None,
-        last_inserted_char: b' ',
         last_fetch_delay: std::time::Instant::now(),
         last_fetch_rate: std::time::Instant::now(),
         alt_modifier: false,
         shift_modifier: false,
         control_modifier: false,
+        shift_held: false,
+        pointer_state: pointer::new(),
+        serial: 0,
+        xkb: keymap::load(),
+        keymap: None,
         registry_listener: wayland::wl_registry_listener {
             global: Some(global_listener),
             global_remove: Some(remove_listener),
@@ -637,6 +622,49 @@ pub fn init(
             modifiers: Some(modifiers),
             repeat_info: Some(repeat_info),
         },
+        pointer_listener: wayland::wl_pointer_listener {
+            enter: Some(pointer_enter),
+            leave: Some(pointer_leave),
+            motion: Some(pointer_motion),
+            button: Some(pointer_button),
+            axis: Some(pointer_axis),
+            frame: Some(pointer_frame),
+            axis_source: Some(pointer_axis_source),
+            axis_stop: Some(pointer_axis_stop),
+            axis_discrete: Some(pointer_axis_discrete),
+        },
+        data_device_listener: wayland::wl_data_device_listener {
+            data_offer: Some(data_device_data_offer),
+            enter: Some(data_device_enter),
+            leave: Some(data_device_leave),
+            motion: Some(data_device_motion),
+            drop: Some(data_device_drop),
+            selection: Some(data_device_selection),
+        },
+        data_offer_listener: wayland::wl_data_offer_listener {
+            offer: Some(data_offer_offer),
+            source_actions: Some(data_offer_source_actions),
+            action: Some(data_offer_action),
+        },
+        data_source_listener: wayland::wl_data_source_listener {
+            target: Some(data_source_target),
+            send: Some(data_source_send),
+            cancelled: Some(data_source_cancelled),
+            dnd_drop_performed: Some(data_source_dnd_drop_performed),
+            dnd_finished: Some(data_source_dnd_finished),
+            action: Some(data_source_action),
+        },
+        primary_selection_device_listener: wayland::zwp_primary_selection_device_v1_listener {
+            data_offer: Some(primary_selection_data_offer),
+            selection: Some(primary_selection_selection),
+        },
+        primary_selection_offer_listener: wayland::zwp_primary_selection_offer_v1_listener {
+            offer: Some(primary_selection_offer),
+        },
+        primary_selection_source_listener: wayland::zwp_primary_selection_source_v1_listener {
+            send: Some(primary_selection_send),
+            cancelled: Some(primary_selection_cancelled),
+        },
         shell_listener: wayland::xdg_wm_base_listener {
             ping: Some(shell_ping)
         },
@@ -682,17 +710,50 @@ pub fn init(
         return Err(WaylandError::CouldNotAddListener);
     }
 
+    if !core.data_device_manager.is_null() {
+        core.data_device = unsafe { wayland::wl_proxy_marshal_flags(core.data_device_manager as *mut wayland::wl_proxy, wayland::WL_DATA_DEVICE_MANAGER_GET_DATA_DEVICE, &wayland::wl_data_device_interface, wayland::wl_proxy_get_version(core.data_device_manager as *mut wayland::wl_proxy), 0, std::ptr::null::<std::ffi::c_void>(), core.seat) } as *mut wayland::wl_data_device;
+        if 0 != unsafe { wayland::wl_proxy_add_listener(core.data_device as *mut wayland::wl_proxy, std::mem::transmute::<*mut wayland::wl_data_device_listener, *mut Option<unsafe extern "C" fn()>>(&mut core.data_device_listener), data) } {
+            return Err(WaylandError::CouldNotAddListener);
+        }
+    }
+
+    if !core.primary_selection_manager.is_null() {
+        core.primary_selection_device = unsafe { wayland::wl_proxy_marshal_flags(core.primary_selection_manager as *mut wayland::wl_proxy, wayland::ZWP_PRIMARY_SELECTION_DEVICE_MANAGER_V1_GET_DEVICE, std::ptr::addr_of!(wayland::zwp_primary_selection_device_v1_interface), 1, 0, std::ptr::null::<std::ffi::c_void>(), core.seat) } as *mut wayland::zwp_primary_selection_device_v1;
+        if 0 != unsafe { wayland::wl_proxy_add_listener(core.primary_selection_device as *mut wayland::wl_proxy, std::mem::transmute::<*mut wayland::zwp_primary_selection_device_v1_listener, *mut Option<unsafe extern "C" fn()>>(&mut core.primary_selection_device_listener), data) } {
+            return Err(WaylandError::CouldNotAddListener);
+        }
+    }
+
     let title = std::ffi::CString::new(name).unwrap();
     unsafe { wayland::wl_proxy_marshal_flags(core.xdg_toplevel as *mut wayland::wl_proxy, wayland::XDG_TOPLEVEL_SET_TITLE, std::ptr::null(), wayland::wl_proxy_get_version(core.xdg_toplevel as *mut wayland::wl_proxy), 0, title.as_ptr()) };
     unsafe { wayland::wl_proxy_marshal_flags(core.xdg_toplevel as *mut wayland::wl_proxy, wayland::XDG_TOPLEVEL_SET_APP_ID, std::ptr::null(), wayland::wl_proxy_get_version(core.xdg_toplevel as *mut wayland::wl_proxy), 0, title.as_ptr()) };
     unsafe { wayland::wl_proxy_marshal_flags(core.surface as *mut wayland::wl_proxy, wayland::WL_SURFACE_COMMIT, std::ptr::null(), wayland::wl_proxy_get_version(core.surface as *mut wayland::wl_proxy), 0) };
     unsafe { wayland::wl_display_roundtrip(core.display) };
 
+    editor::load_user_bindings(&mut core.editor);
+
     Ok(core)
 }
 
 pub fn shutdown(core: &Core) {
+    if let Some(keymap) = core.keymap.as_ref() {
+        keymap::destroy(keymap);
+    }
+
     unsafe {
+        if !core.data_device.is_null() {
+            wayland::wl_proxy_marshal_flags(core.data_device as *mut wayland::wl_proxy, wayland::WL_DATA_DEVICE_RELEASE, std::ptr::null(), wayland::wl_proxy_get_version(core.data_device as *mut wayland::wl_proxy), wayland::WL_MARSHAL_FLAG_DESTROY);
+        }
+
+        if !core.primary_selection_device.is_null() {
+            wayland::wl_proxy_marshal_flags(core.primary_selection_device as *mut wayland::wl_proxy, wayland::ZWP_PRIMARY_SELECTION_DEVICE_V1_DESTROY, std::ptr::null(), 1, wayland::WL_MARSHAL_FLAG_DESTROY);
+            wayland::wl_proxy_marshal_flags(core.primary_selection_manager as *mut wayland::wl_proxy, wayland::ZWP_PRIMARY_SELECTION_DEVICE_MANAGER_V1_DESTROY, std::ptr::null(), 1, wayland::WL_MARSHAL_FLAG_DESTROY);
+        }
+
+        if !core.pointer.is_null() {
+            wayland::wl_proxy_marshal_flags(core.pointer as *mut wayland::wl_proxy, wayland::WL_POINTER_RELEASE, std::ptr::null(), wayland::wl_proxy_get_version(core.pointer as *mut wayland::wl_proxy), wayland::WL_MARSHAL_FLAG_DESTROY);
+        }
+
         wayland::wl_proxy_marshal_flags(core.seat as *mut wayland::wl_proxy, wayland::WL_SEAT_RELEASE, std::ptr::null(), wayland::wl_proxy_get_version(core.seat as *mut wayland::wl_proxy), wayland::WL_MARSHAL_FLAG_DESTROY);
         wayland::wl_proxy_marshal_flags(core.xdg_toplevel as *mut wayland::wl_proxy, wayland::XDG_TOPLEVEL_DESTROY, std::ptr::null(), wayland::wl_proxy_get_version(core.xdg_toplevel as *mut wayland::wl_proxy), wayland::WL_MARSHAL_FLAG_DESTROY);
         wayland::wl_proxy_marshal_flags(core.xdg_surface as *mut wayland::wl_proxy, wayland::XDG_SURFACE_DESTROY, std::ptr::null(), wayland::wl_proxy_get_version(core.xdg_surface as *mut wayland::wl_proxy), wayland::WL_MARSHAL_FLAG_DESTROY);
@@ -704,9 +765,57 @@ pub fn shutdown(core: &Core) {
     };
 }
 
-const ENTER: u8 = 28;
-const BACKSPACE: u8 = 14;
-const ESC: u8 = 1;
+fn special_key(core: &Core, code: u8) -> Option<Key> {
+    match core.keymap.as_ref() {
+        Some(keymap) => keymap::special_key_from_sym(keymap::key_sym(keymap, code as u32)),
+        None => keymap::special_key_from_code(code),
+    }
+}
+
+// What the editor gets for a key going down, or None for keys like the
+// modifiers that are only seen through their effect on others.
+fn key_event(core: &Core, code: u8) -> Option<KeyEvent> {
+    let press = match key_char(core, code) {
+        Some(c) => Press::Char(c),
+        None => Press::Key(special_key(core, code)?),
+    };
+
+    Some(KeyEvent {
+        press,
+        control: core.control_modifier,
+        alt: core.alt_modifier,
+        shift: core.shift_held,
+    })
+}
+
+// The character a key stands for. Bindings go by the keysym, falling back to
+// the first group's base level when a non-Latin group is active; typed text
+// comes from the keymap with every modifier applied.
+fn key_char(core: &Core, code: u8) -> Option<char> {
+    let Some(keymap) = core.keymap.as_ref() else {
+        let b = try_ascci(code).ok()?;
+        let c = if core.shift_modifier {
+            b[1]
+        } else {
+            b[0]
+        };
+
+        return Some(c as char);
+    };
+
+    let c = if core.control_modifier || core.alt_modifier {
+        let c = keymap::sym_char(keymap, keymap::key_sym(keymap, code as u32))?;
+        if c.is_ascii() {
+            c
+        } else {
+            keymap::sym_char(keymap, keymap::base_sym(keymap, code as u32))?
+        }
+    } else {
+        keymap::key_text(keymap, code as u32).chars().next()?
+    };
+
+    Some(c).filter(|c| !c.is_control() || *c == '\t')
+}
 
 fn try_ascci(u: u8) -> Result<[u8; 2], WaylandError> {
     match u {
@@ -0,0 +1,83 @@
+pub const BUTTON_LEFT: u32 = 0x110;
+pub const BUTTON_MIDDLE: u32 = 0x112;
+pub const DOUBLE_CLICK_MS: u32 = 400;
+pub const LINES_PER_STEP: i32 = 3;
+
+pub const AXIS_VERTICAL: usize = 0;
+pub const AXIS_HORIZONTAL: usize = 1;
+
+pub struct Pointer {
+    pub position: [f64; 2],
+    pub pressed: bool,
+    pub clicks: u32,
+    last_click: Option<(u32, [u32; 2])>,
+    continuous: [f64; 2],
+    discrete: [i32; 2],
+    remainder: [f64; 2],
+}
+
+pub fn new() -> Pointer {
+    Pointer {
+        position: [0.0, 0.0],
+        pressed: false,
+        clicks: 0,
+        last_click: None,
+        continuous: [0.0, 0.0],
+        discrete: [0, 0],
+        remainder: [0.0, 0.0],
+    }
+}
+
+pub fn fixed_to_f64(value: i32) -> f64 {
+    value as f64 / 256.0
+}
+
+pub fn cell_at(position: [f64; 2], cell: [f64; 2]) -> [u32; 2] {
+    [
+        (position[0].max(0.0) / cell[0]) as u32,
+        (position[1].max(0.0) / cell[1]) as u32,
+    ]
+}
+
+// Counts presses on the same cell within `DOUBLE_CLICK_MS` of each other,
+// cycling single, double and triple clicks.
+pub fn click(pointer: &mut Pointer, time: u32, cell: [u32; 2]) -> u32 {
+    pointer.clicks = match pointer.last_click {
+        Some((last, last_cell)) if last_cell == cell && time.wrapping_sub(last) <= DOUBLE_CLICK_MS => pointer.clicks % 3 + 1,
+        _ => 1,
+    };
+
+    pointer.last_click = Some((time, cell));
+    pointer.clicks
+}
+
+pub fn axis(pointer: &mut Pointer, axis: usize, value: f64) {
+    pointer.continuous[axis] += value;
+}
+
+pub fn axis_discrete(pointer: &mut Pointer, axis: usize, steps: i32) {
+    pointer.discrete[axis] += steps;
+}
+
+// Ends a pointer frame and returns how many lines and columns to scroll.
+// Wheel clicks move a fixed number of lines; touchpad motion is divided by
+// the cell size and whatever doesn't make a whole cell carries over.
+pub fn frame(pointer: &mut Pointer, cell: [f64; 2]) -> [i32; 2] {
+    let mut scroll = [0, 0];
+
+    for (i, size) in [cell[1], cell[0]].into_iter().enumerate() {
+        if pointer.discrete[i] != 0 {
+            scroll[i] = pointer.discrete[i] * LINES_PER_STEP;
+            pointer.remainder[i] = 0.0;
+        } else {
+            pointer.remainder[i] += pointer.continuous[i] / size;
+            scroll[i] = pointer.remainder[i].trunc() as i32;
+            pointer.remainder[i] -= scroll[i] as f64;
+        }
+
+        pointer.continuous[i] = 0.0;
+        pointer.discrete[i] = 0;
+    }
+
+    scroll
+}